@@ -0,0 +1,67 @@
+//! A minimal client of the live tee. It connects to the socket configured in the `[tee]` section
+//! of a project and prints the level of every received block, e.g.:
+//!
+//! ```sh
+//! cargo run --example tee_client -- /run/schlaflosigkeit/tee.sock
+//! ```
+
+use std::collections::HashMap;
+use std::env;
+use std::os::unix::net::UnixStream;
+
+use schlaflosigkeit::tee::TeeBlock;
+
+fn main() {
+    let socket_path = match env::args().nth(1) {
+        Some(socket_path) => socket_path,
+        None => {
+            eprintln!("Usage: tee_client <socket>");
+            return;
+        }
+    };
+    let mut stream = match UnixStream::connect(&socket_path) {
+        Ok(stream) => stream,
+        Err(error) => {
+            eprintln!("Could not connect to {}: {}", socket_path, error);
+            return;
+        }
+    };
+
+    // the sequence numbers of an input have gaps if the client did not keep up
+    let mut next_sequences: HashMap<String, u64> = HashMap::new();
+    loop {
+        let block = match TeeBlock::read_from(&mut stream) {
+            Ok(block) => block,
+            Err(error) => {
+                eprintln!("The connection to the tee was closed: {}", error);
+                return;
+            }
+        };
+        if let Some(next_sequence) = next_sequences.get(&block.input) {
+            if block.sequence > *next_sequence {
+                println!(
+                    "{}: missed {} blocks",
+                    block.input,
+                    block.sequence - next_sequence
+                );
+            }
+        }
+        next_sequences.insert(block.input.clone(), block.sequence + 1);
+
+        let peak = block
+            .samples
+            .iter()
+            .map(|sample| i32::from(*sample).abs())
+            .max()
+            .unwrap_or(0);
+        println!(
+            "{}: block {} with {} frames ({} channels, {} Hz), peak {:.3}",
+            block.input,
+            block.sequence,
+            block.samples.len() / usize::from(block.channels.max(1)),
+            block.channels,
+            block.samples_per_second,
+            peak as f32 / 32768.0
+        );
+    }
+}
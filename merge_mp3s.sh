@@ -1,2 +1,15 @@
 #!/bin/bash
-find . -maxdepth 1 -iname '*.mp3' -print0 | sort -Vz | xargs -0 mp3wrap ../tmp.mp3
+# older recordings were made with 48 kHz while newer ones use 44.1 kHz. mp3wrap can only join files with the same
+# sample rate, so a folder with mixed sample rates is resampled to 44.1 kHz with ffmpeg while merging.
+sample_rates=$(find . -maxdepth 1 -iname '*.mp3' -exec ffprobe -v error -select_streams a:0 -show_entries stream=sample_rate -of csv=p=0 {} \; 2>/dev/null | sort -u | wc -l)
+if [ "$sample_rates" -le 1 ]; then
+	find . -maxdepth 1 -iname '*.mp3' -print0 | sort -Vz | xargs -0 mp3wrap ../tmp.mp3
+	exit $?
+fi
+
+file_list=$(mktemp)
+trap 'rm -f "$file_list"' EXIT
+find . -maxdepth 1 -iname '*.mp3' -print0 | sort -Vz | while IFS= read -r -d '' file; do
+	printf "file '%s'\n" "$(realpath "$file" | sed "s/'/'\\\\''/g")" >>"$file_list"
+done
+ffmpeg -v error -f concat -safe 0 -i "$file_list" -af aresample=44100 -ar 44100 -codec:a libmp3lame -q:a 4 ../tmp.mp3
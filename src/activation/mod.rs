@@ -0,0 +1,227 @@
+use core::fmt;
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Defines if the recorder captures continuously or only while something can be heard.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivationMode {
+    /// Record the whole segments.
+    #[default]
+    Off,
+
+    /// Only record while the input level exceeds the threshold (requires the cpal backend).
+    Level,
+}
+
+impl fmt::Display for ActivationMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ActivationMode::Off => write!(f, "off"),
+            ActivationMode::Level => write!(f, "level"),
+        }
+    }
+}
+
+/// The configuration for the sound-activated recording, which produces a file per event instead
+/// of a file per segment.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ActivationConfiguration {
+    #[serde(default = "ActivationConfiguration::default_mode")]
+    pub mode: ActivationMode,
+
+    /// The RMS energy (between 0.0 and 1.0) the input has to exceed for starting an event.
+    #[serde(default = "ActivationConfiguration::default_threshold")]
+    pub threshold: f32,
+
+    /// The time the input has to stay above the threshold before an event is started.
+    #[serde(default = "ActivationConfiguration::default_minimum_duration_in_milliseconds")]
+    pub minimum_duration_in_milliseconds: u32,
+
+    /// The time the input has to stay below the threshold before an event is finished.
+    #[serde(default = "ActivationConfiguration::default_hold_in_seconds")]
+    pub hold_in_seconds: u32,
+
+    /// The time before the start of an event which is kept in memory and written to the file of
+    /// the event, so its onset is not cut off.
+    #[serde(default = "ActivationConfiguration::default_pre_roll_in_seconds")]
+    pub pre_roll_in_seconds: u32,
+}
+
+impl ActivationConfiguration {
+    fn default_mode() -> ActivationMode {
+        ActivationMode::Off
+    }
+
+    fn default_threshold() -> f32 {
+        0.02
+    }
+
+    fn default_minimum_duration_in_milliseconds() -> u32 {
+        250
+    }
+
+    fn default_hold_in_seconds() -> u32 {
+        5
+    }
+
+    fn default_pre_roll_in_seconds() -> u32 {
+        2
+    }
+
+    /// Check if the recordings are only made while the input level exceeds the threshold.
+    pub fn is_enabled(&self) -> bool {
+        self.mode != ActivationMode::Off
+    }
+}
+
+impl Default for ActivationConfiguration {
+    fn default() -> Self {
+        ActivationConfiguration {
+            mode: ActivationConfiguration::default_mode(),
+            threshold: ActivationConfiguration::default_threshold(),
+            minimum_duration_in_milliseconds:
+                ActivationConfiguration::default_minimum_duration_in_milliseconds(),
+            hold_in_seconds: ActivationConfiguration::default_hold_in_seconds(),
+            pre_roll_in_seconds: ActivationConfiguration::default_pre_roll_in_seconds(),
+        }
+    }
+}
+
+/// The samples of a captured buffer which belong to an event.
+#[derive(Debug, Default)]
+pub struct GateOutput {
+    /// Set if an event starts with the samples.
+    pub opened: bool,
+
+    /// The interleaved samples which have to be written to the file of the event.
+    pub samples: Vec<i16>,
+
+    /// Set if the event ended with the samples.
+    pub closed: bool,
+}
+
+/// Decides which of the captured samples belong to an event, based on the level of the input.
+pub struct ActivationGate {
+    threshold: f32,
+    channels: usize,
+    frames_to_open: u64,
+    frames_to_close: u64,
+    loud_frames: u64,
+    quiet_frames: u64,
+    pending_samples: Vec<i16>,
+    pre_roll_samples: VecDeque<i16>,
+    maximum_pre_roll_samples: usize,
+    is_open: bool,
+}
+
+impl ActivationGate {
+    pub fn new(
+        configuration: &ActivationConfiguration,
+        channels: u16,
+        samples_per_second: u32,
+    ) -> ActivationGate {
+        ActivationGate {
+            threshold: configuration.threshold,
+            channels: usize::from(channels.max(1)),
+            frames_to_open: u64::from(configuration.minimum_duration_in_milliseconds)
+                * u64::from(samples_per_second)
+                / 1000,
+            frames_to_close: u64::from(configuration.hold_in_seconds)
+                * u64::from(samples_per_second),
+            loud_frames: 0,
+            quiet_frames: 0,
+            pending_samples: vec![],
+            pre_roll_samples: VecDeque::new(),
+            maximum_pre_roll_samples: configuration.pre_roll_in_seconds as usize
+                * samples_per_second as usize
+                * usize::from(channels.max(1)),
+            is_open: false,
+        }
+    }
+
+    /// Check if an event is currently recorded.
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    /// Keep the samples before an event in memory, the oldest ones are dropped as soon as the
+    /// pre-roll is full.
+    fn add_to_pre_roll(&mut self, samples: Vec<i16>) {
+        self.pre_roll_samples.extend(samples);
+        let excess = self
+            .pre_roll_samples
+            .len()
+            .saturating_sub(self.maximum_pre_roll_samples);
+        // only whole frames are dropped, so the channels stay in order
+        let excess = (excess + self.channels - 1) / self.channels * self.channels;
+        self.pre_roll_samples
+            .drain(..excess.min(self.pre_roll_samples.len()));
+    }
+
+    /// Feed the next captured buffer through the gate. The samples which started an event are
+    /// kept until the event is confirmed, so the event contains its beginning and the pre-roll
+    /// before it.
+    pub fn process(&mut self, samples: Vec<i16>) -> GateOutput {
+        let frames = (samples.len() / self.channels) as u64;
+        let is_loud = get_rms(&samples) >= self.threshold;
+
+        if !self.is_open {
+            if !is_loud {
+                // a sound which was too short for an event is part of the pre-roll
+                self.loud_frames = 0;
+                let pending_samples = std::mem::take(&mut self.pending_samples);
+                self.add_to_pre_roll(pending_samples);
+                self.add_to_pre_roll(samples);
+                return GateOutput::default();
+            }
+            self.loud_frames += frames;
+            self.pending_samples.extend(samples);
+            if self.loud_frames < self.frames_to_open {
+                return GateOutput::default();
+            }
+            self.is_open = true;
+            self.quiet_frames = 0;
+            let mut event_samples: Vec<i16> = self.pre_roll_samples.drain(..).collect();
+            event_samples.append(&mut self.pending_samples);
+            return GateOutput {
+                opened: true,
+                samples: event_samples,
+                closed: false,
+            };
+        }
+
+        if is_loud {
+            self.quiet_frames = 0;
+        } else {
+            self.quiet_frames += frames;
+        }
+        let closed = self.quiet_frames >= self.frames_to_close;
+        if closed {
+            self.is_open = false;
+            self.loud_frames = 0;
+        }
+        GateOutput {
+            opened: false,
+            samples,
+            closed,
+        }
+    }
+}
+
+/// Get the RMS energy (between 0.0 and 1.0) of interleaved 16 bit samples.
+fn get_rms(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum_of_squares: f64 = samples
+        .iter()
+        .map(|sample| {
+            let value = f64::from(*sample) / 32768.0;
+            value * value
+        })
+        .sum();
+    (sum_of_squares / samples.len() as f64).sqrt() as f32
+}
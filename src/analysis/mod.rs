@@ -0,0 +1,467 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Duration as OldDuration, NaiveDateTime};
+use tracing::error;
+
+use crate::annotation::ReadError;
+use crate::wave::{open_samples, WaveFormat};
+
+/// The number of values per second of an energy envelope.
+pub const ENVELOPE_VALUES_PER_SECOND: usize = 10;
+
+/// The minimal quiet time between two loud passages which are counted as separate events.
+const MIN_EVENT_SEPARATION_IN_VALUES: usize = ENVELOPE_VALUES_PER_SECOND * 2;
+
+/// The length of the windows a breathing rate is estimated for.
+const BREATHING_RATE_WINDOW_IN_SECONDS: i64 = 5 * 60;
+
+/// The slowest (8 breaths/min) and fastest (30 breaths/min) breathing which can be detected.
+const MAX_BREATH_PERIOD_IN_VALUES: usize = 75;
+const MIN_BREATH_PERIOD_IN_VALUES: usize = 20;
+
+/// The number of interpolated values between two samples used for the true-peak estimation.
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// The number of samples on each side of an interpolated value used by the interpolation filter.
+const TRUE_PEAK_FILTER_HALF_LENGTH: usize = 8;
+
+/// The minimal correlation of two channels which are considered to carry the same signal.
+const DUPLICATE_CHANNEL_CORRELATION: f32 = 0.99;
+
+/// The maximal difference (in dB) of the noise floors of two duplicated channels.
+const DUPLICATE_CHANNEL_NOISE_FLOOR_DIFFERENCE_IN_DB: f32 = 1.0;
+
+/// The percentile of the energy envelope which is used as the noise floor.
+const NOISE_FLOOR_PERCENTILE: f32 = 0.1;
+
+/// The peak level (in dBFS) from which on a recording is considered to be clipping.
+const CLIPPING_LEVEL_IN_DBFS: f32 = -0.1;
+
+/// The lowest level (in dBFS) which is reported, digital silence would be infinitely low.
+const MINIMUM_LEVEL_IN_DBFS: f32 = -120.0;
+
+/// The result of comparing the two channels of a stereo recording.
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelComparison {
+    /// The correlation (between -1.0 and 1.0) of the two channels.
+    pub correlation: f32,
+
+    /// The noise floors (in dBFS) of the left and the right channel.
+    pub noise_floors_in_db: [f32; 2],
+}
+
+impl ChannelComparison {
+    /// Check if both channels carry the same signal, so recording in mono loses no information.
+    pub fn is_duplicate(&self) -> bool {
+        self.correlation >= DUPLICATE_CHANNEL_CORRELATION
+            && (self.noise_floors_in_db[0] - self.noise_floors_in_db[1]).abs()
+                <= DUPLICATE_CHANNEL_NOISE_FLOOR_DIFFERENCE_IN_DB
+    }
+}
+
+/// Get the noise floor (in dBFS) of a single channel, the quiet end of its energy envelope.
+fn get_noise_floor_in_db(samples: &[f32], samples_per_second: u32) -> f32 {
+    let mut envelope = get_energy_envelope(samples, samples_per_second);
+    if envelope.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    envelope.sort_by(|first, second| first.total_cmp(second));
+    let index = ((envelope.len() - 1) as f32 * NOISE_FLOOR_PERCENTILE) as usize;
+    20.0 * envelope[index].log10()
+}
+
+/// Compare the channels of an interleaved stereo recording by their correlation and their noise
+/// floors. `None` is returned if one of the channels is completely silent.
+pub fn compare_channels(samples: &[f32], samples_per_second: u32) -> Option<ChannelComparison> {
+    let left: Vec<f32> = samples.chunks_exact(2).map(|frame| frame[0]).collect();
+    let right: Vec<f32> = samples.chunks_exact(2).map(|frame| frame[1]).collect();
+    let mean = |channel: &[f32]| channel.iter().sum::<f32>() / channel.len().max(1) as f32;
+    let (left_mean, right_mean) = (mean(&left), mean(&right));
+
+    let mut covariance = 0.0;
+    let mut left_variance = 0.0;
+    let mut right_variance = 0.0;
+    for (left_sample, right_sample) in left.iter().zip(&right) {
+        let left_deviation = left_sample - left_mean;
+        let right_deviation = right_sample - right_mean;
+        covariance += left_deviation * right_deviation;
+        left_variance += left_deviation * left_deviation;
+        right_variance += right_deviation * right_deviation;
+    }
+    if left_variance <= f32::EPSILON || right_variance <= f32::EPSILON {
+        return None;
+    }
+
+    Some(ChannelComparison {
+        correlation: covariance / (left_variance * right_variance).sqrt(),
+        noise_floors_in_db: [
+            get_noise_floor_in_db(&left, samples_per_second),
+            get_noise_floor_in_db(&right, samples_per_second),
+        ],
+    })
+}
+
+/// Get the peak of a sample and of the values interpolated between it and the next sample. The
+/// window contains the samples the interpolation filter needs, the sample is the one at
+/// `TRUE_PEAK_FILTER_HALF_LENGTH - 1`.
+fn get_interpolated_peak(window: &[f32], coefficients: &[Vec<f32>]) -> f32 {
+    let mut peak = window[TRUE_PEAK_FILTER_HALF_LENGTH - 1].abs();
+    for phase_coefficients in coefficients {
+        let interpolated: f32 = phase_coefficients
+            .iter()
+            .zip(window)
+            .map(|(coefficient, sample)| coefficient * sample)
+            .sum();
+        peak = peak.max(interpolated.abs());
+    }
+    peak
+}
+
+/// Estimate the true peak (the peak of the reconstructed analog signal) of interleaved samples
+/// by oversampling them with a windowed sinc filter. The result is returned in dBTP. The samples
+/// are read one after another, so a recording does not have to be loaded as a whole.
+pub fn get_true_peak_in_db(mut samples: impl Iterator<Item = f32>, channels: u16) -> f32 {
+    let channels = usize::from(channels.max(1));
+    let half_length = TRUE_PEAK_FILTER_HALF_LENGTH as isize;
+
+    // precalculate the filter coefficients for every interpolated position between two samples
+    let coefficients: Vec<Vec<f32>> = (1..TRUE_PEAK_OVERSAMPLING)
+        .map(|phase| {
+            let fraction = phase as f32 / TRUE_PEAK_OVERSAMPLING as f32;
+            (-half_length + 1..=half_length)
+                .map(|tap| {
+                    let position = tap as f32 - fraction;
+                    let sinc = if position.abs() < f32::EPSILON {
+                        1.0
+                    } else {
+                        (std::f32::consts::PI * position).sin() / (std::f32::consts::PI * position)
+                    };
+                    let window =
+                        0.5 + 0.5 * (std::f32::consts::PI * position / half_length as f32).cos();
+                    sinc * window
+                })
+                .collect()
+        })
+        .collect();
+
+    // only the samples the interpolation needs are kept for every channel, the interpolation
+    // after a sample needs the ones up to half the filter length later. the recording is
+    // surrounded by silence.
+    let window_length = 2 * TRUE_PEAK_FILTER_HALF_LENGTH;
+    let mut windows = vec![vec![0.0f32; window_length]; channels];
+    let mut frame = vec![0.0f32; channels];
+    let mut added_frames = 0;
+    let mut padding_frames = 0;
+    let mut peak: f32 = 0.0;
+    loop {
+        // an incomplete frame at the end of the recording is ignored
+        let mut samples_in_frame = 0;
+        for (target, sample) in frame.iter_mut().zip(samples.by_ref().take(channels)) {
+            *target = sample;
+            samples_in_frame += 1;
+        }
+        if samples_in_frame < channels {
+            if padding_frames == TRUE_PEAK_FILTER_HALF_LENGTH {
+                break;
+            }
+            frame.iter_mut().for_each(|sample| *sample = 0.0);
+            padding_frames += 1;
+        }
+
+        for (window, sample) in windows.iter_mut().zip(&frame) {
+            window.copy_within(1.., 0);
+            window[window_length - 1] = *sample;
+        }
+        added_frames += 1;
+        if added_frames > TRUE_PEAK_FILTER_HALF_LENGTH {
+            for window in &windows {
+                peak = peak.max(get_interpolated_peak(window, &coefficients));
+            }
+        }
+    }
+
+    20.0 * peak.log10()
+}
+
+/// The sample peak and the RMS level of a recording.
+#[derive(Debug, Clone, Copy)]
+pub struct Levels {
+    pub peak_in_dbfs: f32,
+    pub rms_in_dbfs: f32,
+}
+
+impl Levels {
+    /// Check if the peak reaches the maximum level, the recording is distorted then.
+    pub fn is_clipping(&self) -> bool {
+        self.peak_in_dbfs >= CLIPPING_LEVEL_IN_DBFS
+    }
+}
+
+/// Convert a linear level (between 0.0 and 1.0) to dBFS, digital silence gets the lowest level.
+fn to_dbfs(level: f32) -> f32 {
+    (20.0 * level.log10()).max(MINIMUM_LEVEL_IN_DBFS)
+}
+
+/// Get the sample peak and the RMS level of all (interleaved) samples.
+pub fn get_levels(samples: &[f32]) -> Levels {
+    get_streamed_levels(samples.iter().copied())
+}
+
+/// Get the sample peak and the RMS level of (interleaved) samples which are read one after
+/// another, so a recording does not have to be loaded as a whole.
+fn get_streamed_levels(samples: impl Iterator<Item = f32>) -> Levels {
+    let mut peak = 0.0f32;
+    let mut sum_of_squares = 0.0f64;
+    let mut sample_count: u64 = 0;
+    for sample in samples {
+        peak = peak.max(sample.abs());
+        sum_of_squares += f64::from(sample) * f64::from(sample);
+        sample_count += 1;
+    }
+    let rms = (sum_of_squares / sample_count.max(1) as f64).sqrt() as f32;
+    Levels {
+        peak_in_dbfs: to_dbfs(peak),
+        rms_in_dbfs: to_dbfs(rms),
+    }
+}
+
+/// Measure the levels of a recorded (not yet encoded) segment. `None` is returned if the
+/// recording could not be read.
+pub fn measure_levels(path: &Path) -> Option<Levels> {
+    match open_samples(path) {
+        Ok((_, samples)) => Some(get_streamed_levels(samples)),
+        Err(error) => {
+            error!(
+                "Could not measure the levels of {}. The error was: {}",
+                path.display(),
+                error
+            );
+            None
+        }
+    }
+}
+
+/// Calculate the RMS energy envelope of the supplied samples with `ENVELOPE_VALUES_PER_SECOND`
+/// values per second.
+pub fn get_energy_envelope(samples: &[f32], samples_per_second: u32) -> Vec<f32> {
+    let mut envelope_builder = EnvelopeBuilder::new(samples_per_second);
+    for sample in samples {
+        envelope_builder.add_sample(*sample);
+    }
+    envelope_builder.finish()
+}
+
+/// Calculates the RMS energy envelope (like `get_energy_envelope`) of samples which are added one
+/// after another, so a recording does not have to be loaded as a whole.
+pub struct EnvelopeBuilder {
+    samples_per_value: usize,
+    samples_in_value: usize,
+    sum_of_squares: f32,
+    values: Vec<f32>,
+}
+
+impl EnvelopeBuilder {
+    pub fn new(samples_per_second: u32) -> EnvelopeBuilder {
+        EnvelopeBuilder {
+            samples_per_value: (samples_per_second as usize / ENVELOPE_VALUES_PER_SECOND).max(1),
+            samples_in_value: 0,
+            sum_of_squares: 0.0,
+            values: vec![],
+        }
+    }
+
+    pub fn add_sample(&mut self, sample: f32) {
+        self.sum_of_squares += sample * sample;
+        self.samples_in_value += 1;
+        if self.samples_in_value == self.samples_per_value {
+            self.values
+                .push((self.sum_of_squares / self.samples_per_value as f32).sqrt());
+            self.sum_of_squares = 0.0;
+            self.samples_in_value = 0;
+        }
+    }
+
+    /// Get the envelope, the samples of an incomplete value at the end are ignored.
+    pub fn finish(self) -> Vec<f32> {
+        self.values
+    }
+}
+
+/// The RMS energy envelope of a recording whose channels were mixed down to a single one.
+pub struct RecordingEnvelope {
+    pub format: WaveFormat,
+
+    /// The number of (complete) frames of the recording.
+    pub frames: u64,
+
+    /// The envelope with `ENVELOPE_VALUES_PER_SECOND` values per second (like the one of
+    /// `get_energy_envelope`).
+    pub values: Vec<f32>,
+}
+
+/// Calculate the RMS energy envelope of a wave file whose channels are mixed down to a single one.
+/// The samples are streamed, so the recording is never loaded as a whole.
+pub fn read_energy_envelope(path: &Path) -> Result<RecordingEnvelope, ReadError> {
+    let (format, mut samples) = open_samples(path)?;
+    let channels = usize::from(format.channels);
+    let mut envelope_builder = EnvelopeBuilder::new(format.samples_per_second);
+    let mut frames: u64 = 0;
+    loop {
+        // an incomplete frame at the end of the recording is ignored
+        let mut frame_sum = 0.0f32;
+        let mut samples_in_frame = 0;
+        for sample in samples.by_ref().take(channels) {
+            frame_sum += sample;
+            samples_in_frame += 1;
+        }
+        if samples_in_frame < channels {
+            break;
+        }
+
+        envelope_builder.add_sample(frame_sum / channels as f32);
+        frames += 1;
+    }
+    Ok(RecordingEnvelope {
+        format,
+        frames,
+        values: envelope_builder.finish(),
+    })
+}
+
+/// Find the events (loud passages like snoring, talking or coughing) in an energy envelope and
+/// return the indices of the values they start at. An event starts if the energy exceeds the
+/// threshold and ends once it stayed below the threshold for a short while.
+pub fn find_events(envelope: &[f32], threshold: f32) -> Vec<usize> {
+    let mut event_starts = vec![];
+    let mut quiet_values = MIN_EVENT_SEPARATION_IN_VALUES;
+    for (index, value) in envelope.iter().enumerate() {
+        if *value >= threshold {
+            if quiet_values >= MIN_EVENT_SEPARATION_IN_VALUES {
+                event_starts.push(index);
+            }
+            quiet_values = 0;
+        } else {
+            quiet_values += 1;
+        }
+    }
+    event_starts
+}
+
+/// Count the events (loud passages like snoring, talking or coughing) in an energy envelope.
+pub fn count_events(envelope: &[f32], threshold: f32) -> u32 {
+    find_events(envelope, threshold).len() as u32
+}
+
+/// The estimated breathing rate for a window of the recording.
+pub struct BreathingRateEstimate {
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+
+    /// The estimated breaths per minute or `None` if the window was not quiet enough or no
+    /// periodic breathing could be found.
+    pub breaths_per_minute: Option<f32>,
+}
+
+/// An EXPERIMENTAL estimator for the breathing rate based on the periodic low-frequency energy
+/// of the recordings during quiet periods.
+pub struct BreathingRateEstimator {
+    quiet_threshold: f32,
+    windows: BTreeMap<i64, Vec<f32>>,
+}
+
+impl BreathingRateEstimator {
+    pub fn new(quiet_threshold: f32) -> BreathingRateEstimator {
+        BreathingRateEstimator {
+            quiet_threshold,
+            windows: BTreeMap::new(),
+        }
+    }
+
+    /// Add the energy envelope of a recording which was started at the supplied time.
+    pub fn add_envelope(&mut self, start_time: NaiveDateTime, envelope: &[f32]) {
+        let start_timestamp_in_ms = start_time.and_utc().timestamp_millis();
+        for (index, value) in envelope.iter().enumerate() {
+            let value_timestamp_in_ms =
+                start_timestamp_in_ms + (index * 1000 / ENVELOPE_VALUES_PER_SECOND) as i64;
+            let window_key = value_timestamp_in_ms / 1000 / BREATHING_RATE_WINDOW_IN_SECONDS;
+            self.windows.entry(window_key).or_default().push(*value);
+        }
+    }
+
+    /// Get the estimated breathing rates for all windows which were covered by the recordings.
+    pub fn get_estimates(&self) -> Vec<BreathingRateEstimate> {
+        self.windows
+            .iter()
+            .filter_map(|(window_key, envelope)| {
+                let start_time =
+                    DateTime::from_timestamp(window_key * BREATHING_RATE_WINDOW_IN_SECONDS, 0)?
+                        .naive_utc();
+                Some(BreathingRateEstimate {
+                    start_time,
+                    end_time: start_time + OldDuration::seconds(BREATHING_RATE_WINDOW_IN_SECONDS),
+                    breaths_per_minute: self.estimate_breathing_rate(envelope),
+                })
+            })
+            .collect()
+    }
+
+    fn estimate_breathing_rate(&self, envelope: &[f32]) -> Option<f32> {
+        // we need at least a minute of audio to find a periodic signal
+        if envelope.len() < 60 * ENVELOPE_VALUES_PER_SECOND {
+            return None;
+        }
+
+        // the estimation only works during quiet periods, loud events would dominate the energy
+        let loud_values = envelope
+            .iter()
+            .filter(|value| **value > self.quiet_threshold)
+            .count();
+        if loud_values * 20 > envelope.len() {
+            return None;
+        }
+
+        // remove the slowly changing noise floor by subtracting a moving average of 10 seconds
+        let average_length = 10 * ENVELOPE_VALUES_PER_SECOND;
+        let detrended: Vec<f32> = (0..envelope.len())
+            .map(|index| {
+                let start = index.saturating_sub(average_length / 2);
+                let end = envelope.len().min(index + average_length / 2);
+                let average = envelope[start..end].iter().sum::<f32>() / (end - start) as f32;
+                envelope[index] - average
+            })
+            .collect();
+        let energy: f32 = detrended.iter().map(|value| value * value).sum();
+        if energy <= f32::EPSILON {
+            return None;
+        }
+
+        // find the breathing period with the strongest normalized autocorrelation
+        let (best_period, best_correlation) = (MIN_BREATH_PERIOD_IN_VALUES
+            ..=MAX_BREATH_PERIOD_IN_VALUES)
+            .map(|lag| {
+                let correlation: f32 = detrended
+                    .iter()
+                    .zip(detrended.iter().skip(lag))
+                    .map(|(first, second)| first * second)
+                    .sum();
+                (lag, correlation / energy)
+            })
+            .fold(
+                (0, 0.0),
+                |best, current| {
+                    if current.1 > best.1 {
+                        current
+                    } else {
+                        best
+                    }
+                },
+            );
+
+        // if there is no clear periodicity, we do not report a rate at all
+        if best_correlation < 0.3 {
+            return None;
+        }
+        Some(60.0 * ENVELOPE_VALUES_PER_SECOND as f32 / best_period as f32)
+    }
+}
@@ -1,10 +1,40 @@
 use chrono::{Duration as OldDuration, NaiveDateTime};
-use core::{fmt, mem};
-use log::{debug, error};
-use std::fs::File;
+use core::fmt;
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::io;
-use std::io::Read;
 use std::path::Path;
+use tracing::debug;
+
+use crate::naming::template::get_filename_template;
+use crate::wave::{read_data_size, read_format};
+
+lazy_static! {
+    static ref CORRECT_FILE_NAME_REGEX: Regex =
+        Regex::new(r".*(\d{4})(\d{2})(\d{2})_?(\d{2})(\d{2})(\d{2})_.*\.wav").unwrap();
+}
+
+/// Get the time a recording was started based on its file name (or path), which follows the
+/// filename template of the project or the naming of older versions. If the file name does not
+/// match the expected pattern, `None` is returned.
+pub fn get_recording_start_time(file_name: &str) -> Option<NaiveDateTime> {
+    let segment_name = Path::new(file_name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".wav"));
+    if let Some(parsed_file_name) =
+        segment_name.and_then(|segment_name| get_filename_template().parse(segment_name))
+    {
+        return Some(parsed_file_name.started_at);
+    }
+
+    let cap = CORRECT_FILE_NAME_REGEX.captures(file_name)?;
+    let current_timestamp_str = format!(
+        "{:02}.{:02}.{:04} {:02}:{:02}:{:02}",
+        &cap[3], &cap[2], &cap[1], &cap[4], &cap[5], &cap[6],
+    );
+    NaiveDateTime::parse_from_str(current_timestamp_str.as_str(), "%d.%m.%Y %H:%M:%S").ok()
+}
 
 #[derive(Debug)]
 pub enum ReadError {
@@ -18,6 +48,7 @@ pub enum ReadErrorKind {
     NotAWaveFile,
     NoFormatChunk,
     NoDataChunk,
+    UnsupportedSampleFormat,
 }
 
 impl ReadErrorKind {
@@ -27,6 +58,7 @@ impl ReadErrorKind {
             ReadErrorKind::NotAWaveFile => "not a WAVE file",
             ReadErrorKind::NoFormatChunk => "no format chunk found",
             ReadErrorKind::NoDataChunk => "no data chunk found",
+            ReadErrorKind::UnsupportedSampleFormat => "unsupported sample format",
         }
     }
 }
@@ -41,7 +73,7 @@ impl fmt::Display for ReadError {
 }
 
 pub struct WaveMetaReader {
-    _data_block_size_in_byte: u32,
+    _data_block_size_in_byte: u64,
     _bits_per_sample: u16,
     _channels: u16,
     _samples_per_second: u32,
@@ -49,95 +81,28 @@ pub struct WaveMetaReader {
 }
 
 impl WaveMetaReader {
+    /// Read the duration of a recording from its header. The duration is based on the sample rate
+    /// of the file itself, so the legacy recordings with 48 kHz and the ones with 44.1 kHz can be
+    /// mixed. Chunks before the samples (e.g. `LIST` chunks of other tools) are skipped.
     pub fn from_file(path: &str) -> Result<WaveMetaReader, ReadError> {
-        // try to open the audio file
-        let mut file_handle = match File::open(path) {
-            Ok(file) => file,
-            Err(e) => return Err(ReadError::Io(e)),
-        };
-
-        // read the first four bytes. they should contain the RIFF header if the file is valid
-        let mut riff_header = [0; 4];
-        let _ = file_handle.read(&mut riff_header);
-
-        // if the file does not have a RIFF header, we can not process it any further
-        if !vec![82, 73, 70, 70].eq(&riff_header) {
-            return Err(ReadError::Format(ReadErrorKind::NotARiffFile));
-        }
-
-        // read the file size from the file based on the file header
-        let mut file_size_based_on_heade_buffer = [0; 4];
-        let _ = file_handle.read(&mut file_size_based_on_heade_buffer);
-
-        // the next four bytes should be the WAVE header
-        let mut wave_header = [0; 4];
-        let _ = file_handle.read(&mut wave_header);
-
-        // if the file does not have a RIFF header, we can not process it any further
-        if !vec![87, 65, 86, 69].eq(&wave_header) {
-            return Err(ReadError::Format(ReadErrorKind::NotAWaveFile));
-        }
-
-        // the next three four should be 'fmt '
-        let mut fmt_header = [0; 4];
-        let _ = file_handle.read(&mut fmt_header);
-
-        // ensure we read the 'fmt' we expected
-        if !vec![102, 109, 116, 32].eq(&fmt_header) {
-            return Err(ReadError::Format(ReadErrorKind::NoFormatChunk));
+        let format = read_format(Path::new(path))?;
+        let data_block_size_in_byte = read_data_size(Path::new(path))?;
+        let bytes_per_frame = u64::from(format.bits_per_sample / 8) * u64::from(format.channels);
+        if bytes_per_frame == 0 || format.samples_per_second == 0 {
+            return Err(ReadError::Format(ReadErrorKind::UnsupportedSampleFormat));
         }
 
-        // skip the next few bytes (not interested in them for our use)
-        let mut _unused = [0; 6];
-        let _ = file_handle.read(&mut _unused);
-
-        //
-        let mut channels_byte = [0; 2];
-        let _ = file_handle.read(&mut channels_byte);
-        let channels: u16 = unsafe { mem::transmute(channels_byte) };
-
-        //
-        let mut samples_per_second_byte = [0; 4];
-        let _ = file_handle.read(&mut samples_per_second_byte);
-        let samples_per_second: u32 = unsafe { mem::transmute(samples_per_second_byte) };
-
-        // skip the next few bytes (not interested in them for our use)
-        let mut _unused2 = [0; 6];
-        let _ = file_handle.read(&mut _unused2);
-
-        //
-        let mut bits_per_sample_buffer = [0; 2];
-        let _ = file_handle.read(&mut bits_per_sample_buffer);
-        let bits_per_sample: u16 = unsafe { mem::transmute(bits_per_sample_buffer) };
-
-        // the next three four should be 'data'
-        let mut data_header = [0; 4];
-        let _ = file_handle.read(&mut data_header);
-
-        // ensure we read the 'fmt' we expected
-        if !vec![100, 97, 116, 97].eq(&data_header) {
-            error!("{:?}", data_header);
-            return Err(ReadError::Format(ReadErrorKind::NoDataChunk));
-        }
-
-        // get the size of the data block in byte
-        let mut data_block_size_in_byte_buffer = [0; 4];
-        let _ = file_handle.read(&mut data_block_size_in_byte_buffer);
-        let data_block_size_in_byte: u32 =
-            unsafe { mem::transmute(data_block_size_in_byte_buffer) };
-
         // calculate the information required for further processing
-        let number_of_samples =
-            data_block_size_in_byte / u32::from(bits_per_sample / 8) / u32::from(channels);
-        let duration = f64::from(number_of_samples) / f64::from(samples_per_second);
+        let number_of_samples = data_block_size_in_byte / bytes_per_frame;
+        let duration = number_of_samples as f64 / f64::from(format.samples_per_second);
 
         // return the gathered information
-        debug!("The data block for {} is {} bytes long with {} bits/sample, a sample rate of {} samples/second and {} channels, this results in {} samples and a duration of {} seconds.", Path::new(path).file_name().unwrap().to_str().unwrap(), data_block_size_in_byte, bits_per_sample, samples_per_second, channels, number_of_samples, duration);
+        debug!("The data block for {} is {} bytes long with {} bits/sample, a sample rate of {} samples/second and {} channels, this results in {} samples and a duration of {} seconds.", Path::new(path).file_name().unwrap().to_str().unwrap(), data_block_size_in_byte, format.bits_per_sample, format.samples_per_second, format.channels, number_of_samples, duration);
         Ok(WaveMetaReader {
             _data_block_size_in_byte: data_block_size_in_byte,
-            _bits_per_sample: bits_per_sample,
-            _channels: channels,
-            _samples_per_second: samples_per_second,
+            _bits_per_sample: format.bits_per_sample,
+            _channels: format.channels,
+            _samples_per_second: format.samples_per_second,
             duration_in_seconds: duration,
         })
     }
@@ -148,8 +113,8 @@ impl WaveMetaReader {
 }
 
 pub struct AnnotationLabel {
-    start_marker: f32,
-    end_marker: f32,
+    start_marker: f64,
+    end_marker: f64,
     used_label: String,
 }
 
@@ -162,14 +127,17 @@ impl AnnotationLabel {
     }
 }
 
+/// Creates the labels of a single recording. The durations are kept with their fractions, so the
+/// markers do not drift apart from the audio when the recordings do not last full seconds (e.g.
+/// if recordings with different sample rates are annotated together).
 pub struct FileAnnotator {
-    file_duration_in_seconds: u64,
-    slice_duration_in_seconds: u64,
-    file_start_time_in_seconds: u64,
+    file_duration_in_seconds: f64,
+    slice_duration_in_seconds: f64,
+    file_start_time_in_seconds: f64,
     file_base_time: NaiveDateTime,
     max_annotations: usize,
     next_annotation_idx: usize,
-    last_start_time: f32,
+    last_start_time: f64,
     is_range: bool,
 }
 
@@ -177,7 +145,7 @@ impl FileAnnotator {
     pub fn from(
         file_name: &str,
         file_start_date: NaiveDateTime,
-        start_time: u64,
+        start_time: f64,
         add_sub_markers: bool,
         is_range: bool,
     ) -> Option<FileAnnotator> {
@@ -190,9 +158,9 @@ impl FileAnnotator {
 
         // if we should add sub markers, determine a length for a sub-marker
         let slice_length = if add_sub_markers {
-            (meta_reader.get_duration() / 6.0) as u64
+            meta_reader.get_duration() / 6.0
         } else {
-            meta_reader.get_duration() as u64
+            meta_reader.get_duration()
         };
 
         // determine the nmber of labels we want to set for this part
@@ -200,10 +168,10 @@ impl FileAnnotator {
 
         // create the new file annotator
         Some(FileAnnotator {
-            file_duration_in_seconds: meta_reader.get_duration() as u64,
+            file_duration_in_seconds: meta_reader.get_duration(),
             slice_duration_in_seconds: slice_length,
             file_start_time_in_seconds: start_time,
-            last_start_time: start_time as f32,
+            last_start_time: start_time,
             max_annotations,
             is_range,
             file_base_time: file_start_date,
@@ -211,8 +179,8 @@ impl FileAnnotator {
         })
     }
 
-    pub fn get_end_time(&self) -> u64 {
-        self.file_start_time_in_seconds + self.file_duration_in_seconds as u64
+    pub fn get_end_time(&self) -> f64 {
+        self.file_start_time_in_seconds + self.file_duration_in_seconds
     }
 
     pub fn get_max_labels(&self) -> usize {
@@ -234,18 +202,20 @@ impl Iterator for FileAnnotator {
 
         // calculate the required times for the labels
         let old_last_start_time = self.last_start_time;
-        let end_marker_offset = self.slice_duration_in_seconds as f32;
+        let end_marker_offset = self.slice_duration_in_seconds;
         self.last_start_time += end_marker_offset;
 
         let new_end_time_for_slice = self.file_base_time
-            + OldDuration::seconds(
-                self.slice_duration_in_seconds as i64 * self.next_annotation_idx as i64,
+            + OldDuration::milliseconds(
+                (self.slice_duration_in_seconds * self.next_annotation_idx as f64 * 1000.0) as i64,
             );
 
         let actual_slice_start_time = if self.max_annotations > 1 {
             self.file_base_time
-                + OldDuration::seconds(
-                    self.slice_duration_in_seconds as i64 * (self.next_annotation_idx as i64 - 1),
+                + OldDuration::milliseconds(
+                    (self.slice_duration_in_seconds
+                        * (self.next_annotation_idx as f64 - 1.0)
+                        * 1000.0) as i64,
                 )
         } else {
             self.file_base_time
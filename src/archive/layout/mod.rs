@@ -0,0 +1,266 @@
+use std::fs::{create_dir_all, read_dir, read_to_string, rename, write};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDateTime;
+use tracing::info;
+
+use crate::annotation::get_recording_start_time;
+use crate::archive::SESSION_MANIFEST_SUFFIX;
+use crate::changelog::record_binary_version;
+use crate::encoding::OutputFormat;
+use crate::naming::template::get_safe_name;
+
+/// The version of the directory layout which is created by this version.
+pub const ARCHIVE_LAYOUT_VERSION: u32 = 1;
+
+/// The file in the root of the archive which stores the version of its layout.
+const LAYOUT_VERSION_FILE_NAME: &str = "layout_version";
+
+const RAW_FOLDER_NAME: &str = "raw";
+const ENCODED_FOLDER_NAME: &str = "encoded";
+const PREVIEWS_FOLDER_NAME: &str = "previews";
+const REPORTS_FOLDER_NAME: &str = "reports";
+pub(crate) const STATE_FOLDER_NAME: &str = "state";
+pub(crate) const TRASH_FOLDER_NAME: &str = "trash";
+
+/// A migration step which converts the layout of an archive to the next version.
+type Migration = fn(&Archive) -> io::Result<()>;
+
+/// The migration steps, the step at index `n` converts version `n` to version `n + 1`.
+const MIGRATIONS: [Migration; ARCHIVE_LAYOUT_VERSION as usize] = [migrate_flat_layout];
+
+/// Move all files of the flat layout (everything in the data directory) to the subfolders.
+/// Only files which follow the naming scheme of the recordings and the session manifests are
+/// touched.
+fn migrate_flat_layout(archive: &Archive) -> io::Result<()> {
+    for entry in read_dir(archive.get_root())? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name.to_string(),
+            None => continue,
+        };
+
+        let target_folder = if file_name.ends_with(SESSION_MANIFEST_SUFFIX) {
+            archive.get_state_folder()
+        } else {
+            let (segment_name, extension) = match file_name.split_once('.') {
+                Some(parts) => parts,
+                None => continue,
+            };
+            if get_recording_start_time(&format!("{}.wav", segment_name)).is_none() {
+                continue;
+            }
+            match extension {
+                "wav" => archive.get_raw_folder(),
+                "preview.opus" => archive.get_previews_folder(),
+                _ if [OutputFormat::Mp3, OutputFormat::Flac, OutputFormat::Ogg]
+                    .iter()
+                    .any(|format| format.get_extension() == extension) =>
+                {
+                    archive.get_encoded_folder()
+                }
+                _ => continue,
+            }
+        };
+        rename(&path, target_folder.join(&file_name))?;
+    }
+    Ok(())
+}
+
+/// The data directory with all recordings, encoded files, previews, reports and the state of the
+/// recorder. The layout of the directory is versioned and migrated automatically when opened.
+#[derive(Debug, Clone)]
+pub struct Archive {
+    root: PathBuf,
+    subfolder_pattern: Option<String>,
+    per_input_folders: bool,
+}
+
+impl Archive {
+    /// Open the archive in the supplied folder, create all subfolders and migrate an older layout
+    /// to the current one.
+    pub fn open(root: &Path) -> io::Result<Archive> {
+        let archive = Archive {
+            root: root.to_path_buf(),
+            subfolder_pattern: None,
+            per_input_folders: false,
+        };
+
+        // a new archive remembers the version which created it, so later upgrades are detected
+        let is_new = read_dir(root)
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(true);
+        for folder in archive.get_folders() {
+            create_dir_all(folder)?;
+        }
+
+        let mut version = archive.get_layout_version()?;
+        if version > ARCHIVE_LAYOUT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the layout version {} of {} is newer than the supported version {}",
+                    version,
+                    root.display(),
+                    ARCHIVE_LAYOUT_VERSION
+                ),
+            ));
+        }
+        while version < ARCHIVE_LAYOUT_VERSION {
+            info!(
+                "Migrating the layout of {} from version {} to {}",
+                root.display(),
+                version,
+                version + 1
+            );
+            MIGRATIONS[version as usize](&archive)?;
+            version += 1;
+            write(archive.get_layout_version_path(), format!("{}\n", version))?;
+        }
+        if is_new {
+            record_binary_version(root)?;
+        }
+        Ok(archive)
+    }
+
+    /// Store the files of the segments in subfolders named after the time the segment was
+    /// started (e.g. `%Y/%m/%d`). The pattern has to result in a relative path within the
+    /// folders.
+    pub fn set_subfolder_pattern(&mut self, pattern: Option<&str>) -> io::Result<()> {
+        if let Some(pattern) = pattern {
+            let is_valid_format = StrftimeItems::new(pattern).all(|item| item != Item::Error);
+            let is_relative = Path::new(pattern)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+            if !is_valid_format || !is_relative {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("'{}' is not a valid subfolder pattern", pattern),
+                ));
+            }
+        }
+        self.subfolder_pattern = pattern.map(|pattern| pattern.to_string());
+        Ok(())
+    }
+
+    /// Store the files of each input in a folder of its own, which is named after the input in
+    /// the project file. The subfolders for the start time are created within these folders.
+    pub fn set_per_input_folders(&mut self, per_input_folders: bool) {
+        self.per_input_folders = per_input_folders;
+    }
+
+    /// Get the subfolder of one of the folders of the archive for a segment of an input which was
+    /// started at the supplied time. The subfolder is created if it does not exist yet.
+    pub fn get_folder_at(
+        &self,
+        folder: &Path,
+        input_name: &str,
+        started_at: NaiveDateTime,
+    ) -> io::Result<PathBuf> {
+        let folder = if self.per_input_folders {
+            folder.join(get_safe_name(input_name))
+        } else {
+            folder.to_path_buf()
+        };
+        let folder = match &self.subfolder_pattern {
+            Some(pattern) => folder.join(started_at.format(pattern).to_string()),
+            None => folder,
+        };
+        create_dir_all(&folder)?;
+        Ok(folder)
+    }
+
+    /// Get the folder which corresponds to the (sub)folder of a file in another folder of the
+    /// archive, e.g. the folder of the encoded file for a recording. The folder is created if it
+    /// does not exist yet.
+    pub fn get_corresponding_folder(&self, file: &Path, folder: &Path) -> io::Result<PathBuf> {
+        let file_folder = file.parent().unwrap_or_else(|| Path::new(""));
+        let subfolder = self
+            .get_segment_folders()
+            .iter()
+            .find_map(|segment_folder| file_folder.strip_prefix(segment_folder).ok())
+            .map(|subfolder| subfolder.to_path_buf())
+            .unwrap_or_default();
+        let folder = folder.join(subfolder);
+        create_dir_all(&folder)?;
+        Ok(folder)
+    }
+
+    fn get_layout_version_path(&self) -> PathBuf {
+        self.root.join(LAYOUT_VERSION_FILE_NAME)
+    }
+
+    /// Get the version of the layout, an archive without a version file uses the flat layout
+    /// (version 0).
+    fn get_layout_version(&self) -> io::Result<u32> {
+        let path = self.get_layout_version_path();
+        if !path.exists() {
+            return Ok(0);
+        }
+        read_to_string(&path)?.trim().parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} does not contain a valid version", path.display()),
+            )
+        })
+    }
+
+    fn get_folders(&self) -> Vec<PathBuf> {
+        vec![
+            self.get_raw_folder(),
+            self.get_encoded_folder(),
+            self.get_previews_folder(),
+            self.get_reports_folder(),
+            self.get_state_folder(),
+        ]
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The folder for the recordings which were not encoded yet.
+    pub fn get_raw_folder(&self) -> PathBuf {
+        self.root.join(RAW_FOLDER_NAME)
+    }
+
+    /// The folder for the encoded recordings.
+    pub fn get_encoded_folder(&self) -> PathBuf {
+        self.root.join(ENCODED_FOLDER_NAME)
+    }
+
+    /// The folder for the small previews of the recordings.
+    pub fn get_previews_folder(&self) -> PathBuf {
+        self.root.join(PREVIEWS_FOLDER_NAME)
+    }
+
+    /// The folder for generated reports.
+    pub fn get_reports_folder(&self) -> PathBuf {
+        self.root.join(REPORTS_FOLDER_NAME)
+    }
+
+    /// The folder for the state of the recorder like the session manifests.
+    pub fn get_state_folder(&self) -> PathBuf {
+        self.root.join(STATE_FOLDER_NAME)
+    }
+
+    /// The folder the recordings are moved to after they were encoded (if they should be trashed
+    /// instead of deleted). It is created when the first recording is moved and never emptied.
+    pub fn get_trash_folder(&self) -> PathBuf {
+        self.root.join(TRASH_FOLDER_NAME)
+    }
+
+    /// Get the folders which contain files of the segments.
+    pub fn get_segment_folders(&self) -> Vec<PathBuf> {
+        vec![
+            self.get_raw_folder(),
+            self.get_encoded_folder(),
+            self.get_previews_folder(),
+        ]
+    }
+}
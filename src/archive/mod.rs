@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::fs::read_dir;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration as OldDuration, NaiveDate, NaiveDateTime};
+use lazy_static::lazy_static;
+use regex::Regex;
+use tracing::warn;
+
+use crate::annotation::get_recording_start_time;
+use crate::manifest::SessionManifest;
+use crate::naming::template::get_filename_template;
+
+pub mod layout;
+
+lazy_static! {
+    static ref CARD_AND_DEVICE_REGEX: Regex = Regex::new(r"_c(\d{2})d(\d{2})$").unwrap();
+    static ref EVENT_SUFFIX_REGEX: Regex = Regex::new(r"_e(\d{2,})$").unwrap();
+}
+
+/// The suffix of the session manifests.
+pub(crate) const SESSION_MANIFEST_SUFFIX: &str = "_session.json";
+
+/// A recorded segment together with all files (recording, encoded file, preview) which belong
+/// to it.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    name: String,
+    started_at: NaiveDateTime,
+    card: Option<u8>,
+    device: Option<u8>,
+    device_name: Option<String>,
+    input: Option<String>,
+    duration_in_seconds: Option<u32>,
+    events: Option<u32>,
+    channel_events: BTreeMap<String, u32>,
+    interestingness: Option<u8>,
+    rms_level_in_dbfs: Option<f32>,
+    silent: bool,
+    encoding: Option<String>,
+    files: Vec<PathBuf>,
+}
+
+impl Segment {
+    /// The name of the segment (the file name without any extension).
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_started_at(&self) -> NaiveDateTime {
+        self.started_at
+    }
+
+    pub fn get_card(&self) -> Option<u8> {
+        self.card
+    }
+
+    pub fn get_device(&self) -> Option<u8> {
+        self.device
+    }
+
+    /// The name of the input in the file name of the segment (if the filename template contains
+    /// it).
+    pub fn get_device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+
+    /// The name of the configured input which recorded the segment (if it is listed in a
+    /// session manifest).
+    pub fn get_input(&self) -> Option<&str> {
+        self.input.as_deref()
+    }
+
+    /// The duration of the segment (if it is listed in a session manifest).
+    pub fn get_duration_in_seconds(&self) -> Option<u32> {
+        self.duration_in_seconds
+    }
+
+    /// All files which belong to this segment.
+    pub fn get_files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Get the file of the segment with the supplied extension (e.g. `wav` or `mp3`).
+    pub fn get_file_with_extension(&self, extension: &str) -> Option<&Path> {
+        self.files
+            .iter()
+            .find(
+                |file| match file.file_name().and_then(|name| name.to_str()) {
+                    Some(file_name) => {
+                        file_name.split_once('.').map(|(_, rest)| rest) == Some(extension)
+                    }
+                    None => false,
+                },
+            )
+            .map(|file| file.as_path())
+    }
+
+    /// The number of events the analysis found in the segment (if it was analyzed while recording).
+    pub fn get_events(&self) -> Option<u32> {
+        self.events
+    }
+
+    /// The number of events per labeled channel (if the segment was recorded with more than one
+    /// channel and analyzed while recording).
+    pub fn get_channel_events(&self) -> &BTreeMap<String, u32> {
+        &self.channel_events
+    }
+
+    /// How interesting the segment is (between 0 and 100, if it was scored while recording).
+    pub fn get_interestingness(&self) -> Option<u8> {
+        self.interestingness
+    }
+
+    /// The RMS level (in dBFS) of the recording (if it was measured while recording).
+    pub fn get_rms_level_in_dbfs(&self) -> Option<f32> {
+        self.rms_level_in_dbfs
+    }
+
+    /// Check if the recorder found the segment to be silent (and did not encode it therefore).
+    pub fn is_silent(&self) -> bool {
+        self.silent
+    }
+
+    /// How the recorder encoded the segment (if it is listed in a session manifest).
+    pub fn get_encoding(&self) -> Option<&str> {
+        self.encoding.as_deref()
+    }
+}
+
+/// All segments which were recorded during a night. A night starts at noon of its date and ends
+/// at noon of the following day.
+#[derive(Debug, Clone)]
+pub struct Night {
+    date: NaiveDate,
+    segments: Vec<Segment>,
+}
+
+impl Night {
+    pub fn get_date(&self) -> NaiveDate {
+        self.date
+    }
+
+    pub fn get_segments(&self) -> &[Segment] {
+        &self.segments
+    }
+}
+
+/// A read-only view on the recordings stored in a data directory, which hides the layout of the
+/// files on disk.
+pub struct ArchiveReader {
+    root: PathBuf,
+}
+
+/// Collect all files in a folder and its subfolders.
+pub(crate) fn collect_files(folder: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in read_dir(folder)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        // symbolic links (e.g. of interesting segments) only point to files which are listed anyway
+        if entry.file_type()?.is_symlink() {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Get the name of the segment a file belongs to (the file name up to the first dot without an
+/// event count suffix).
+pub(crate) fn get_segment_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let segment_name = file_name.split('.').next()?;
+    Some(EVENT_SUFFIX_REGEX.replace(segment_name, "").to_string())
+}
+
+/// Get the night a recording belongs to, based on the time it was started.
+pub fn get_night_of(started_at: NaiveDateTime) -> NaiveDate {
+    (started_at - OldDuration::hours(12)).date()
+}
+
+impl ArchiveReader {
+    pub fn open(root: &Path) -> io::Result<ArchiveReader> {
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        Ok(ArchiveReader {
+            root: root.to_path_buf(),
+        })
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    fn get_files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        collect_files(&self.root, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// Get the paths of the manifests of all recording sessions.
+    pub fn get_session_files(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(self
+            .get_files()?
+            .into_iter()
+            .filter(|file| file.to_string_lossy().ends_with(SESSION_MANIFEST_SUFFIX))
+            .collect())
+    }
+
+    /// Get the manifests of all recording sessions, ordered by the time they were started.
+    pub fn get_sessions(&self) -> io::Result<Vec<SessionManifest>> {
+        let mut sessions = vec![];
+        for file in self.get_session_files()? {
+            match SessionManifest::from_file(&file) {
+                Ok(session) => sessions.push(session),
+                Err(error) => warn!("Skipping the manifest {}: {}", file.display(), error),
+            }
+        }
+        sessions.sort_by(|first, second| first.started_at.cmp(&second.started_at));
+        Ok(sessions)
+    }
+
+    /// Get all recorded segments, ordered by the time they were started.
+    pub fn get_segments(&self) -> io::Result<Vec<Segment>> {
+        let filename_template = get_filename_template();
+        let mut segments: BTreeMap<String, Segment> = BTreeMap::new();
+        for file in self.get_files()? {
+            // the names of the session manifests start with a timestamp as well
+            if file.to_string_lossy().ends_with(SESSION_MANIFEST_SUFFIX) {
+                continue;
+            }
+            let segment_name = match get_segment_name(&file) {
+                Some(segment_name) => segment_name,
+                None => continue,
+            };
+            if let Some(segment) = segments.get_mut(&segment_name) {
+                segment.files.push(file);
+                continue;
+            }
+
+            // files which do not follow the naming scheme of the recordings are ignored
+            let started_at = match get_recording_start_time(&format!("{}.wav", segment_name)) {
+                Some(started_at) => started_at,
+                None => continue,
+            };
+            let parsed_file_name = filename_template.parse(&segment_name);
+            let card_and_device = CARD_AND_DEVICE_REGEX.captures(&segment_name);
+            let get_number = |index: usize| {
+                card_and_device
+                    .as_ref()
+                    .and_then(|captures| captures[index].parse().ok())
+            };
+            segments.insert(
+                segment_name.clone(),
+                Segment {
+                    name: segment_name.clone(),
+                    started_at,
+                    card: parsed_file_name
+                        .as_ref()
+                        .and_then(|parsed_file_name| parsed_file_name.card)
+                        .or_else(|| get_number(1)),
+                    device: parsed_file_name
+                        .as_ref()
+                        .and_then(|parsed_file_name| parsed_file_name.device)
+                        .or_else(|| get_number(2)),
+                    device_name: parsed_file_name
+                        .and_then(|parsed_file_name| parsed_file_name.device_name),
+                    input: None,
+                    duration_in_seconds: None,
+                    events: None,
+                    channel_events: BTreeMap::new(),
+                    interestingness: None,
+                    rms_level_in_dbfs: None,
+                    silent: false,
+                    encoding: None,
+                    files: vec![file],
+                },
+            );
+        }
+
+        // add the information which is only known by the session manifests
+        for session in self.get_sessions()? {
+            for segment_manifest in session.segments {
+                let segment_name = get_segment_name(Path::new(&segment_manifest.file));
+                if let Some(segment) = segment_name.and_then(|name| segments.get_mut(&name)) {
+                    segment.input = Some(segment_manifest.input);
+                    segment.duration_in_seconds = Some(segment_manifest.duration_in_seconds);
+                    segment.events = segment_manifest.events;
+                    segment.channel_events = segment_manifest.channel_events;
+                    segment.interestingness = segment_manifest.interestingness;
+                    segment.rms_level_in_dbfs = segment_manifest.rms_level_in_dbfs;
+                    segment.silent = segment_manifest.silent;
+                    segment.encoding = segment_manifest.encoding;
+                }
+            }
+        }
+
+        let mut ordered_segments: Vec<Segment> = segments.into_values().collect();
+        ordered_segments.sort_by_key(|segment| segment.started_at);
+        Ok(ordered_segments)
+    }
+
+    /// Get all nights for which recordings are available, ordered by their date.
+    pub fn get_nights(&self) -> io::Result<Vec<Night>> {
+        let mut nights: BTreeMap<NaiveDate, Vec<Segment>> = BTreeMap::new();
+        for segment in self.get_segments()? {
+            nights
+                .entry(get_night_of(segment.started_at))
+                .or_default()
+                .push(segment);
+        }
+        Ok(nights
+            .into_iter()
+            .map(|(date, segments)| Night { date, segments })
+            .collect())
+    }
+}
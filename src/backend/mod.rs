@@ -0,0 +1,292 @@
+use core::fmt;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::activation::ActivationConfiguration;
+use crate::backend::watchdog::{record_audio_from_pcm_watched, CaptureWatchdogConfiguration};
+use crate::manifest::CaptureGap;
+use crate::shutdown::is_shutdown_requested;
+use crate::tee::TeeSource;
+use crate::{is_recording_tool_available, RecordingDeviceConfiguration};
+
+#[cfg(feature = "cpal")]
+pub mod native;
+pub mod pulse;
+pub mod watchdog;
+
+/// The number of blocks per second in which the samples of a stream are handed over.
+const STREAM_BLOCKS_PER_SECOND: u32 = 10;
+
+/// The backends which can be used for recording audio.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingBackend {
+    /// Use `arecord` if it is available and the native backend otherwise.
+    #[default]
+    Auto,
+
+    /// Record by calling the `arecord` tool of ALSA (Linux only).
+    Arecord,
+
+    /// Record using the cross-platform `cpal` library (requires the `cpal` feature).
+    Cpal,
+
+    /// Record from a PulseAudio or PipeWire source using `parecord` or `pw-record`.
+    Pulse,
+}
+
+/// Defines what the native backend does if the audio data can not be written fast enough.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackpressureStrategy {
+    /// Wait until the data could be written (the audio device might overrun).
+    Block,
+
+    /// Drop the audio data and mark the gap in the manifest.
+    #[default]
+    Drop,
+
+    /// Temporarily store the audio data in a ring file next to the recording.
+    Spill,
+}
+
+impl fmt::Display for BackpressureStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BackpressureStrategy::Block => write!(f, "block"),
+            BackpressureStrategy::Drop => write!(f, "drop"),
+            BackpressureStrategy::Spill => write!(f, "spill"),
+        }
+    }
+}
+
+/// Information about a successfully recorded audio file.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedSegment {
+    /// The path of the recording without the file extension.
+    pub file_prefix: String,
+
+    /// The number of frames which were lost since they could not be written in time.
+    pub dropped_frames: u64,
+
+    /// The number of frames which had to be stored temporarily in the spill file.
+    pub spilled_frames: u64,
+
+    /// The positions in the recording where audio data was dropped.
+    pub gaps: Vec<CaptureGap>,
+
+    /// The position of the recording within the captured duration if only an event was recorded.
+    pub event: Option<RecordedEvent>,
+}
+
+/// The position of an event which was recorded by the sound-activated recording.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordedEvent {
+    /// The time from the start of the capture until the event started.
+    pub offset_in_seconds: f64,
+
+    /// The duration of the recording of the event.
+    pub duration_in_seconds: f64,
+}
+
+impl fmt::Display for RecordingBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordingBackend::Auto => write!(f, "auto"),
+            RecordingBackend::Arecord => write!(f, "arecord"),
+            RecordingBackend::Cpal => write!(f, "cpal"),
+            RecordingBackend::Pulse => write!(f, "pulse"),
+        }
+    }
+}
+
+impl RecordingBackend {
+    /// Determine the backend which should actually be used for recording. If the selected backend
+    /// is not usable with this build, `None` is returned.
+    pub fn resolve(self) -> Option<RecordingBackend> {
+        match self {
+            RecordingBackend::Auto => {
+                if is_recording_tool_available() {
+                    Some(RecordingBackend::Arecord)
+                } else if cfg!(feature = "cpal") {
+                    Some(RecordingBackend::Cpal)
+                } else {
+                    None
+                }
+            }
+            RecordingBackend::Cpal if !cfg!(feature = "cpal") => None,
+            backend => Some(backend),
+        }
+    }
+}
+
+/// Record a single audio file with the supplied (resolved) backend. The captured samples are
+/// published on the tee as well (only supported by the native backend). The captures of `arecord`
+/// are restarted by the watchdog if they stall.
+pub fn record_audio_with_backend(
+    backend: RecordingBackend,
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_folder: String,
+    backpressure: BackpressureStrategy,
+    tee: Option<TeeSource>,
+    watchdog: &CaptureWatchdogConfiguration,
+) -> Option<RecordedSegment> {
+    match backend {
+        #[cfg(feature = "cpal")]
+        RecordingBackend::Cpal => native::record_audio_native(
+            configuration,
+            duration_in_seconds,
+            output_folder,
+            backpressure,
+            tee,
+        ),
+        RecordingBackend::Pulse => {
+            let file_prefix =
+                pulse::record_audio_pulse(configuration, duration_in_seconds, output_folder)?;
+            Some(RecordedSegment {
+                file_prefix,
+                ..Default::default()
+            })
+        }
+        _ => {
+            // the backpressure strategy and the tee only apply to the native backend
+            let _ = (backpressure, tee);
+            record_audio_from_pcm_watched(
+                configuration,
+                duration_in_seconds,
+                output_folder,
+                watchdog,
+            )
+        }
+    }
+}
+
+/// Capture the supplied duration with the supplied (resolved) backend, but only record the events
+/// in which the input level exceeds the threshold of the activation. Only the native backend
+/// supports this.
+pub fn record_events_with_backend(
+    backend: RecordingBackend,
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_folder: String,
+    backpressure: BackpressureStrategy,
+    activation: &ActivationConfiguration,
+    tee: Option<TeeSource>,
+) -> Option<Vec<RecordedSegment>> {
+    match backend {
+        #[cfg(feature = "cpal")]
+        RecordingBackend::Cpal => native::record_events_native(
+            configuration,
+            duration_in_seconds,
+            output_folder,
+            backpressure,
+            activation,
+            tee,
+        ),
+        _ => {
+            // the other backends can only record whole segments
+            let _ = (configuration, duration_in_seconds, output_folder);
+            let _ = (backpressure, activation, tee);
+            error!(
+                "The {} backend does not support the sound-activated recording",
+                backend
+            );
+            None
+        }
+    }
+}
+
+/// Capture the input of a device with the supplied (resolved) backend without writing it to a
+/// file. The interleaved samples (between -1.0 and 1.0) are handed to the consumer in blocks until
+/// it returns `false` or a shutdown is requested. Returns `false` if the input could not be
+/// captured.
+pub fn stream_with_backend<F>(
+    backend: RecordingBackend,
+    configuration: &RecordingDeviceConfiguration,
+    consume: F,
+) -> bool
+where
+    F: FnMut(Vec<f32>) -> bool,
+{
+    match backend {
+        #[cfg(feature = "cpal")]
+        RecordingBackend::Cpal => native::stream_native(configuration, consume),
+        RecordingBackend::Pulse => match pulse::get_pulse_stream_command(configuration) {
+            Some(stream_command) => stream_from_process(stream_command, configuration, consume),
+            None => false,
+        },
+        _ => {
+            let mut stream_command = Command::new("arecord");
+            stream_command
+                .arg(format!("-D{}", configuration.get_pcm()))
+                .arg(format!("-f{}", configuration.format.get_arecord_format()))
+                .arg(format!("-r{}", configuration.sample_rate))
+                .arg(if configuration.mono { "-c1" } else { "-c2" })
+                .arg("-traw")
+                .arg("-q");
+            stream_from_process(stream_command, configuration, consume)
+        }
+    }
+}
+
+/// Read the raw samples a recording tool writes to its standard output and hand them to the
+/// consumer. The tool is stopped afterwards.
+fn stream_from_process<F>(
+    mut stream_command: Command,
+    configuration: &RecordingDeviceConfiguration,
+    mut consume: F,
+) -> bool
+where
+    F: FnMut(Vec<f32>) -> bool,
+{
+    let tool = stream_command.get_program().to_string_lossy().to_string();
+    let channels = if configuration.mono { 1 } else { 2 };
+    let bytes_per_sample = usize::from(configuration.format.get_bits_per_sample() / 8);
+    let frames_per_block = (configuration.sample_rate / STREAM_BLOCKS_PER_SECOND).max(1) as usize;
+    let mut block = vec![0u8; frames_per_block * channels * bytes_per_sample];
+
+    let mut child = match stream_command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(error) => {
+            error!("Could not execute {}: {}", tool, error);
+            return false;
+        }
+    };
+    let mut output = match child.stdout.take() {
+        Some(output) => output,
+        None => return false,
+    };
+
+    let is_finished = loop {
+        if is_shutdown_requested() {
+            break true;
+        }
+        // on Ctrl-C the tool gets the SIGINT as well and closes its output
+        if let Err(error) = output.read_exact(&mut block) {
+            if is_shutdown_requested() {
+                break true;
+            }
+            error!("{} stopped delivering audio data: {}", tool, error);
+            break false;
+        }
+        let samples = block
+            .chunks_exact(bytes_per_sample)
+            .map(|bytes| configuration.format.decode_sample(bytes))
+            .collect();
+        if !consume(samples) {
+            break true;
+        }
+    };
+    let _ = child.kill();
+    let _ = child.wait();
+    is_finished
+}
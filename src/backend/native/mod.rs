@@ -0,0 +1,686 @@
+use std::fs::{remove_file, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use ::cpal::{
+    BufferSize, BuildStreamError, Device, FromSample, InputCallbackInfo, SampleFormat, SampleRate,
+    SizedSample, Stream, StreamConfig,
+};
+use tracing::{error, warn};
+
+use crate::activation::{ActivationConfiguration, ActivationGate};
+use crate::backend::{BackpressureStrategy, RecordedEvent, RecordedSegment};
+use crate::manifest::CaptureGap;
+use crate::shutdown::is_recording_stop_requested;
+use crate::tee::TeeSource;
+use crate::wave::WaveWriter;
+use crate::{
+    finish_partial_file, get_output_file_path, get_partial_file_path, RecordingDeviceConfiguration,
+};
+
+/// The number of buffers which can be queued between the audio device and the file writer.
+const QUEUED_BUFFERS: usize = 64;
+
+/// The maximum size of the ring file used by the spill strategy.
+const SPILL_FILE_SIZE_IN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// A ring file which stores samples which could not be queued for writing in time.
+struct SpillFile {
+    file: std::fs::File,
+    read_position: u64,
+    write_position: u64,
+}
+
+impl SpillFile {
+    fn create(path: &Path) -> io::Result<SpillFile> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(SpillFile {
+            file,
+            read_position: 0,
+            write_position: 0,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.read_position == self.write_position
+    }
+
+    fn free_space(&self) -> u64 {
+        SPILL_FILE_SIZE_IN_BYTES - (self.write_position - self.read_position)
+    }
+
+    fn push(&mut self, samples: &[i16]) -> io::Result<()> {
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        let mut written = 0;
+        while written < bytes.len() {
+            // the data might wrap around at the end of the ring file
+            let offset = self.write_position % SPILL_FILE_SIZE_IN_BYTES;
+            let length = (bytes.len() - written).min((SPILL_FILE_SIZE_IN_BYTES - offset) as usize);
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.write_all(&bytes[written..written + length])?;
+            written += length;
+            self.write_position += length as u64;
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self, maximum_samples: usize) -> io::Result<Vec<i16>> {
+        let available = (self.write_position - self.read_position) as usize;
+        let mut bytes = vec![0; available.min(maximum_samples * 2)];
+        let mut read = 0;
+        while read < bytes.len() {
+            let offset = self.read_position % SPILL_FILE_SIZE_IN_BYTES;
+            let length = (bytes.len() - read).min((SPILL_FILE_SIZE_IN_BYTES - offset) as usize);
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.read_exact(&mut bytes[read..read + length])?;
+            read += length;
+            self.read_position += length as u64;
+        }
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect())
+    }
+}
+
+/// The state which is shared between the audio callback and the file writer.
+struct CaptureState {
+    channels: u16,
+    samples_per_second: u32,
+    samples_to_capture: u64,
+    captured_samples: AtomicU64,
+    dropped_samples: AtomicU64,
+    spilled_samples: AtomicU64,
+    gaps: Mutex<Vec<CaptureGap>>,
+    spill_file: Option<Mutex<SpillFile>>,
+}
+
+impl CaptureState {
+    fn get_offset_in_seconds(&self, samples: u64) -> f64 {
+        samples as f64 / f64::from(self.channels) / f64::from(self.samples_per_second)
+    }
+
+    fn mark_gap(&self, offset_in_samples: u64, length_in_samples: usize) {
+        self.dropped_samples
+            .fetch_add(length_in_samples as u64, Ordering::SeqCst);
+        let offset_in_seconds = self.get_offset_in_seconds(offset_in_samples);
+        let duration_in_seconds = self.get_offset_in_seconds(length_in_samples as u64);
+        let mut gaps = self.gaps.lock().unwrap();
+
+        // consecutive dropped buffers are combined into a single gap
+        if let Some(last_gap) = gaps.last_mut() {
+            if (last_gap.offset_in_seconds + last_gap.duration_in_seconds - offset_in_seconds).abs()
+                < 0.001
+            {
+                last_gap.duration_in_seconds += duration_in_seconds;
+                return;
+            }
+        }
+        gaps.push(CaptureGap {
+            offset_in_seconds,
+            duration_in_seconds,
+        });
+    }
+
+    /// Hand the samples of the audio device over to the writer using the selected strategy.
+    fn handle_samples(
+        &self,
+        samples: Vec<i16>,
+        sender: &SyncSender<Vec<i16>>,
+        backpressure: BackpressureStrategy,
+    ) {
+        // ignore everything which was captured after the requested duration
+        let offset = self.captured_samples.load(Ordering::SeqCst);
+        if offset >= self.samples_to_capture {
+            return;
+        }
+        let remaining_samples = (self.samples_to_capture - offset) as usize;
+        let mut samples = samples;
+        samples.truncate(remaining_samples);
+        let length = samples.len();
+
+        match backpressure {
+            BackpressureStrategy::Block => {
+                let _ = sender.send(samples);
+            }
+            BackpressureStrategy::Drop => {
+                if let Err(TrySendError::Full(_)) = sender.try_send(samples) {
+                    self.mark_gap(offset, length);
+                }
+            }
+            BackpressureStrategy::Spill => {
+                let mut spill_file = self.spill_file.as_ref().unwrap().lock().unwrap();
+
+                // as long as spilled data is pending, new data has to be spilled too to keep
+                // the order of the samples
+                let samples = if spill_file.is_empty() {
+                    match sender.try_send(samples) {
+                        Ok(()) => None,
+                        Err(TrySendError::Full(samples)) => Some(samples),
+                        Err(TrySendError::Disconnected(_)) => None,
+                    }
+                } else {
+                    Some(samples)
+                };
+
+                if let Some(samples) = samples {
+                    if spill_file.free_space() < (length * 2) as u64
+                        || spill_file.push(&samples).is_err()
+                    {
+                        self.mark_gap(offset, length);
+                    } else {
+                        self.spilled_samples
+                            .fetch_add(length as u64, Ordering::SeqCst);
+                    }
+                }
+            }
+        }
+        self.captured_samples
+            .fetch_add(length as u64, Ordering::SeqCst);
+    }
+
+    fn has_spilled_samples(&self) -> bool {
+        match &self.spill_file {
+            Some(spill_file) => !spill_file.lock().unwrap().is_empty(),
+            None => false,
+        }
+    }
+
+    /// Get the next samples which should be written to the file.
+    fn receive_samples(&self, receiver: &Receiver<Vec<i16>>) -> Result<Vec<i16>, RecvTimeoutError> {
+        // queued samples are always older than spilled ones
+        if let Ok(samples) = receiver.try_recv() {
+            return Ok(samples);
+        }
+        if let Some(spill_file) = &self.spill_file {
+            let mut spill_file = spill_file.lock().unwrap();
+            if !spill_file.is_empty() {
+                return spill_file
+                    .pop(self.samples_per_second as usize)
+                    .map_err(|_| RecvTimeoutError::Disconnected);
+            }
+        }
+        receiver.recv_timeout(Duration::from_millis(100))
+    }
+}
+
+fn find_input_device(source: &Option<String>) -> Option<Device> {
+    let host = ::cpal::default_host();
+
+    // if no source was selected, we just use the default input device of the system
+    let source_name = match source {
+        Some(source_name) => source_name,
+        None => return host.default_input_device(),
+    };
+
+    // otherwise we try to find the first device which contains the selected name
+    host.input_devices()
+        .ok()?
+        .find(|device| match device.name() {
+            Ok(device_name) => device_name.contains(source_name.as_str()),
+            Err(_) => false,
+        })
+}
+
+fn build_input_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    sender: SyncSender<Vec<i16>>,
+    state: Arc<CaptureState>,
+    backpressure: BackpressureStrategy,
+) -> Result<Stream, BuildStreamError>
+where
+    T: SizedSample,
+    i16: FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &InputCallbackInfo| {
+            let samples = data
+                .iter()
+                .map(|sample| sample.to_sample::<i16>())
+                .collect();
+            state.handle_samples(samples, &sender, backpressure);
+        },
+        |error| error!("The audio input stream reported an error: {}", error),
+        None,
+    )
+}
+
+/// A running capture of an input device.
+struct Capture {
+    stream: Stream,
+    state: Arc<CaptureState>,
+    receiver: Receiver<Vec<i16>>,
+    spill_file_path: Option<PathBuf>,
+}
+
+/// Get the number of channels the native backend records of a device.
+fn get_channels(configuration: &RecordingDeviceConfiguration) -> u16 {
+    if configuration.mono {
+        1
+    } else {
+        2
+    }
+}
+
+/// Open the input device and start capturing the requested duration.
+fn start_capture(
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    spill_file_path: PathBuf,
+    backpressure: BackpressureStrategy,
+) -> Option<Capture> {
+    let device = match find_input_device(&configuration.source) {
+        Some(device) => device,
+        None => {
+            error!("Could not find a suitable input device for the native backend");
+            return None;
+        }
+    };
+
+    // the sample format is determined by the device, we always convert it to 16 bit samples
+    let sample_format = match device.default_input_config() {
+        Ok(default_config) => default_config.sample_format(),
+        Err(error) => {
+            error!("Could not query the input device configuration: {}", error);
+            return None;
+        }
+    };
+    let channels = get_channels(configuration);
+    let stream_config = StreamConfig {
+        channels,
+        sample_rate: SampleRate(configuration.sample_rate),
+        buffer_size: BufferSize::Default,
+    };
+
+    // the spill strategy needs a ring file next to the recording
+    let spill_file = if backpressure == BackpressureStrategy::Spill {
+        match SpillFile::create(&spill_file_path) {
+            Ok(spill_file) => Some(Mutex::new(spill_file)),
+            Err(error) => {
+                error!("Could not create the spill file: {}", error);
+                return None;
+            }
+        }
+    } else {
+        None
+    };
+
+    let state = Arc::new(CaptureState {
+        channels,
+        samples_per_second: configuration.sample_rate,
+        samples_to_capture: u64::from(duration_in_seconds)
+            * u64::from(configuration.sample_rate)
+            * u64::from(channels),
+        captured_samples: AtomicU64::new(0),
+        dropped_samples: AtomicU64::new(0),
+        spilled_samples: AtomicU64::new(0),
+        gaps: Mutex::new(vec![]),
+        spill_file,
+    });
+
+    // open the stream with the sample type of the device
+    let (sender, receiver) = sync_channel(QUEUED_BUFFERS);
+    let callback_state = state.clone();
+    let maybe_stream = match sample_format {
+        SampleFormat::I16 => build_input_stream::<i16>(
+            &device,
+            &stream_config,
+            sender,
+            callback_state,
+            backpressure,
+        ),
+        SampleFormat::U16 => build_input_stream::<u16>(
+            &device,
+            &stream_config,
+            sender,
+            callback_state,
+            backpressure,
+        ),
+        SampleFormat::F32 => build_input_stream::<f32>(
+            &device,
+            &stream_config,
+            sender,
+            callback_state,
+            backpressure,
+        ),
+        unsupported_format => {
+            error!("The sample format {} is not supported", unsupported_format);
+            return None;
+        }
+    };
+    let stream = match maybe_stream {
+        Ok(stream) => stream,
+        Err(error) => {
+            error!("Could not open the input stream: {}", error);
+            return None;
+        }
+    };
+    if let Err(error) = stream.play() {
+        error!("Could not start the input stream: {}", error);
+        return None;
+    }
+
+    Some(Capture {
+        stream,
+        state,
+        receiver,
+        spill_file_path: (backpressure == BackpressureStrategy::Spill).then_some(spill_file_path),
+    })
+}
+
+impl Capture {
+    /// Hand the captured samples to the writer until the requested duration was captured and
+    /// written (or a shutdown or a split was requested). The writer returns `false` if the samples
+    /// could not be written, which stops the capture.
+    fn run<F>(self, mut write_samples: F) -> Option<Arc<CaptureState>>
+    where
+        F: FnMut(Vec<i16>) -> bool,
+    {
+        let mut idle_timeouts = 0;
+        loop {
+            if is_recording_stop_requested() {
+                break;
+            }
+            match self.state.receive_samples(&self.receiver) {
+                Ok(samples) => {
+                    idle_timeouts = 0;
+                    if !write_samples(samples) {
+                        return None;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.state.captured_samples.load(Ordering::SeqCst)
+                        >= self.state.samples_to_capture
+                        && !self.state.has_spilled_samples()
+                    {
+                        break;
+                    }
+
+                    // if the device does not deliver data for several seconds, we give up
+                    idle_timeouts += 1;
+                    if idle_timeouts >= 50 {
+                        error!("The input stream stopped delivering audio data");
+                        return None;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    error!("The audio data could not be received from the input stream");
+                    return None;
+                }
+            }
+        }
+        drop(self.stream);
+        if let Some(spill_file_path) = &self.spill_file_path {
+            let _ = remove_file(spill_file_path);
+        }
+        Some(self.state)
+    }
+}
+
+/// Capture the input of a device without writing it to a file. The interleaved samples (between
+/// -1.0 and 1.0) are handed to the consumer until it returns `false` or a shutdown is requested.
+pub fn stream_native<F>(configuration: &RecordingDeviceConfiguration, mut consume: F) -> bool
+where
+    F: FnMut(Vec<f32>) -> bool,
+{
+    // nothing is written, so the samples which can not be handed over in time are just dropped
+    let capture = match start_capture(
+        configuration,
+        u32::MAX,
+        std::env::temp_dir().join("schlaflosigkeit-stream.spill"),
+        BackpressureStrategy::Drop,
+    ) {
+        Some(capture) => capture,
+        None => return false,
+    };
+    let mut is_stopped = false;
+    let result = capture.run(|samples| {
+        is_stopped = !consume(
+            samples
+                .iter()
+                .map(|sample| f32::from(*sample) / 32768.0)
+                .collect(),
+        );
+        !is_stopped
+    });
+    result.is_some() || is_stopped
+}
+
+/// Record a single audio file using the native (cpal) backend.
+pub fn record_audio_native(
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_folder: String,
+    backpressure: BackpressureStrategy,
+    tee: Option<TeeSource>,
+) -> Option<RecordedSegment> {
+    let channels = get_channels(configuration);
+
+    // create the output file before the stream is started
+    let output_file = get_output_file_path(configuration, output_folder.as_str());
+    let partial_file = get_partial_file_path(&output_file);
+    let mut wave_writer =
+        match WaveWriter::create(&partial_file, channels, configuration.sample_rate) {
+            Ok(writer) => writer,
+            Err(error) => {
+                error!("Could not create {}: {}", output_file.display(), error);
+                return None;
+            }
+        };
+
+    let capture = start_capture(
+        configuration,
+        duration_in_seconds,
+        output_file.with_extension("spill"),
+        backpressure,
+    )?;
+    let state = capture.run(|samples| {
+        if let Some(tee) = &tee {
+            tee.publish(channels, configuration.sample_rate, &samples);
+        }
+        match wave_writer.write_samples(&samples) {
+            Ok(()) => true,
+            Err(error) => {
+                error!("Could not write to {}: {}", output_file.display(), error);
+                false
+            }
+        }
+    })?;
+
+    // update the header of the file with the final sizes
+    if let Err(error) = wave_writer
+        .finalize()
+        .and_then(|_| finish_partial_file(&output_file))
+    {
+        error!("Could not finalize {}: {}", output_file.display(), error);
+        return None;
+    }
+
+    let dropped_samples = state.dropped_samples.load(Ordering::SeqCst);
+    let spilled_samples = state.spilled_samples.load(Ordering::SeqCst);
+    if dropped_samples > 0 {
+        warn!(
+            "{} could not be written in time, {} frames were dropped",
+            output_file.display(),
+            dropped_samples / u64::from(channels)
+        );
+    }
+
+    let gaps = state.gaps.lock().unwrap().clone();
+    Some(RecordedSegment {
+        file_prefix: output_file.with_extension("").to_str()?.to_string(),
+        dropped_frames: dropped_samples / u64::from(channels),
+        spilled_frames: spilled_samples / u64::from(channels),
+        gaps,
+        ..Default::default()
+    })
+}
+
+/// The file of an event which is currently recorded.
+struct EventFile {
+    output_file: PathBuf,
+    writer: WaveWriter,
+    offset_in_frames: u64,
+    frames: u64,
+}
+
+impl EventFile {
+    fn create(
+        configuration: &RecordingDeviceConfiguration,
+        output_folder: &str,
+        offset_in_frames: u64,
+    ) -> io::Result<EventFile> {
+        let output_file = get_output_file_path(configuration, output_folder);
+        let writer = WaveWriter::create(
+            &get_partial_file_path(&output_file),
+            get_channels(configuration),
+            configuration.sample_rate,
+        )?;
+        Ok(EventFile {
+            output_file,
+            writer,
+            offset_in_frames,
+            frames: 0,
+        })
+    }
+
+    fn finish(self, state: &CaptureState) -> Option<RecordedSegment> {
+        let output_file = self.output_file;
+        if let Err(error) = self
+            .writer
+            .finalize()
+            .and_then(|_| finish_partial_file(&output_file))
+        {
+            error!("Could not finalize {}: {}", output_file.display(), error);
+            return None;
+        }
+
+        // only the gaps within the event are relevant for its file
+        let samples_per_second = f64::from(state.samples_per_second);
+        let offset_in_seconds = self.offset_in_frames as f64 / samples_per_second;
+        let duration_in_seconds = self.frames as f64 / samples_per_second;
+        let gaps: Vec<CaptureGap> = state
+            .gaps
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|gap| {
+                gap.offset_in_seconds >= offset_in_seconds
+                    && gap.offset_in_seconds < offset_in_seconds + duration_in_seconds
+            })
+            .map(|gap| CaptureGap {
+                offset_in_seconds: gap.offset_in_seconds - offset_in_seconds,
+                duration_in_seconds: gap.duration_in_seconds,
+            })
+            .collect();
+        let dropped_frames = gaps
+            .iter()
+            .map(|gap| (gap.duration_in_seconds * samples_per_second).round() as u64)
+            .sum();
+
+        Some(RecordedSegment {
+            file_prefix: output_file.with_extension("").to_str()?.to_string(),
+            dropped_frames,
+            gaps,
+            event: Some(RecordedEvent {
+                offset_in_seconds,
+                duration_in_seconds,
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+/// Capture the requested duration using the native (cpal) backend, but only write the audio to
+/// files while the input level exceeds the threshold. Each event gets a file of its own, an event
+/// which lasts until the end of the duration is finished with it.
+pub fn record_events_native(
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_folder: String,
+    backpressure: BackpressureStrategy,
+    activation: &ActivationConfiguration,
+    tee: Option<TeeSource>,
+) -> Option<Vec<RecordedSegment>> {
+    let channels = get_channels(configuration);
+    let spill_file_path =
+        get_output_file_path(configuration, output_folder.as_str()).with_extension("spill");
+    let capture = start_capture(
+        configuration,
+        duration_in_seconds,
+        spill_file_path,
+        backpressure,
+    )?;
+
+    let mut gate = ActivationGate::new(activation, channels, configuration.sample_rate);
+    let mut current_event: Option<EventFile> = None;
+    let mut finished_events = vec![];
+    let mut processed_frames = 0;
+    let state = capture.run(|samples| {
+        processed_frames += (samples.len() / usize::from(channels)) as u64;
+        // the tee gets the whole capture, not only the events
+        if let Some(tee) = &tee {
+            tee.publish(channels, configuration.sample_rate, &samples);
+        }
+        let output = gate.process(samples);
+        let frames = (output.samples.len() / usize::from(channels)) as u64;
+        if output.opened {
+            match EventFile::create(
+                configuration,
+                &output_folder,
+                processed_frames.saturating_sub(frames),
+            ) {
+                Ok(event_file) => current_event = Some(event_file),
+                Err(error) => error!("Could not create the file for an event: {}", error),
+            }
+        }
+        if let Some(event_file) = &mut current_event {
+            // an event which can not be written is finished early, the capture continues
+            if let Err(error) = event_file.writer.write_samples(&output.samples) {
+                error!(
+                    "Could not write to {}: {}",
+                    event_file.output_file.display(),
+                    error
+                );
+                finished_events.extend(current_event.take());
+            } else {
+                event_file.frames += frames;
+            }
+        }
+        if output.closed {
+            finished_events.extend(current_event.take());
+        }
+        true
+    })?;
+    finished_events.extend(current_event.take());
+
+    let dropped_samples = state.dropped_samples.load(Ordering::SeqCst);
+    if dropped_samples > 0 {
+        warn!(
+            "The audio data could not be written in time, {} frames were dropped",
+            dropped_samples / u64::from(channels)
+        );
+    }
+    Some(
+        finished_events
+            .into_iter()
+            .filter_map(|event_file| event_file.finish(&state))
+            .collect(),
+    )
+}
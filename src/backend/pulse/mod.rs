@@ -0,0 +1,175 @@
+use std::process::{Command, Stdio};
+
+use tracing::error;
+
+use crate::shutdown::{is_recording_stop_requested, wait_for_recording_process};
+use crate::{
+    finish_partial_file, get_output_file_path, get_partial_file_path, RecordingDeviceConfiguration,
+};
+
+/// The exit code of `timeout` if the command had to be stopped after the duration.
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+fn is_tool_available(tool: &str) -> bool {
+    match Command::new(tool)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(exit_status) => exit_status.success(),
+        Err(_) => false,
+    }
+}
+
+/// Get the tool which can be used for recording from PulseAudio or PipeWire sources.
+pub fn get_pulse_recording_tool() -> Option<&'static str> {
+    if !is_tool_available("timeout") {
+        return None;
+    }
+    ["parecord", "pw-record"]
+        .iter()
+        .find(|tool| is_tool_available(tool))
+        .copied()
+}
+
+/// Record a single audio file from a PulseAudio or PipeWire source and return the path of the
+/// recording without the file extension.
+pub fn record_audio_pulse(
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_folder: String,
+) -> Option<String> {
+    let source = match &configuration.source {
+        Some(source) => source,
+        None => {
+            error!("No source was configured for recording with the pulse backend");
+            return None;
+        }
+    };
+    let tool = match get_pulse_recording_tool() {
+        Some(tool) => tool,
+        None => {
+            error!("Neither parecord nor pw-record seem to be available on your computer");
+            return None;
+        }
+    };
+    let channels = if configuration.mono { 1 } else { 2 };
+    let output_file = get_output_file_path(configuration, output_folder.as_str());
+
+    // both tools record until they get interrupted, SIGINT lets them finalize the file header
+    let mut record_command = Command::new("timeout");
+    record_command
+        .arg("--signal=INT")
+        .arg(format!("{}", duration_in_seconds))
+        .arg(tool);
+    if tool == "parecord" {
+        record_command
+            .arg(format!("--device={}", source))
+            .arg("--file-format=wav")
+            .arg(format!(
+                "--format={}",
+                configuration.format.get_pulse_format()
+            ))
+            .arg(format!("--rate={}", configuration.sample_rate))
+            .arg(format!("--channels={}", channels));
+    } else {
+        record_command
+            .arg(format!("--target={}", source))
+            .arg(format!(
+                "--format={}",
+                configuration.format.get_pipewire_format()
+            ))
+            .arg(format!("--rate={}", configuration.sample_rate))
+            .arg(format!("--channels={}", channels));
+    }
+
+    // pw-record derives the container from the file extension, so it writes to the final file
+    let uses_partial_file = tool == "parecord";
+    let recording_file = if uses_partial_file {
+        get_partial_file_path(&output_file)
+    } else {
+        output_file.clone()
+    };
+    record_command
+        .arg(recording_file.to_str()?)
+        .stderr(Stdio::null())
+        .stdout(Stdio::null());
+
+    // the recording was successful if it was stopped by the timeout, a shutdown or a split (the tools
+    // finalize the file on SIGINT, which timeout forwards)
+    let record_status = record_command
+        .spawn()
+        .and_then(|mut child| wait_for_recording_process(&mut child));
+    match record_status {
+        Ok(exit_status)
+            if exit_status.code() == Some(TIMEOUT_EXIT_CODE) || is_recording_stop_requested() =>
+        {
+            if uses_partial_file {
+                if let Err(error) = finish_partial_file(&output_file) {
+                    error!(
+                        "Could not rename the finished recording {}: {}",
+                        recording_file.display(),
+                        error
+                    );
+                    return None;
+                }
+            }
+            Some(output_file.with_extension("").to_str()?.to_string())
+        }
+        Ok(exit_status) => {
+            error!("{} stopped unexpectedly ({})", tool, exit_status);
+            None
+        }
+        Err(error) => {
+            error!("Could not execute {}: {}", tool, error);
+            None
+        }
+    }
+}
+
+/// Build the command which writes the raw samples of a PulseAudio or PipeWire source to its
+/// standard output (until it gets stopped).
+pub fn get_pulse_stream_command(configuration: &RecordingDeviceConfiguration) -> Option<Command> {
+    let source = match &configuration.source {
+        Some(source) => source,
+        None => {
+            error!("No source was configured for recording with the pulse backend");
+            return None;
+        }
+    };
+    let tool = match get_pulse_recording_tool() {
+        Some(tool) => tool,
+        None => {
+            error!("Neither parecord nor pw-record seem to be available on your computer");
+            return None;
+        }
+    };
+    let channels = if configuration.mono { 1 } else { 2 };
+
+    let mut stream_command = Command::new(tool);
+    if tool == "parecord" {
+        stream_command
+            .arg(format!("--device={}", source))
+            .arg("--raw")
+            .arg(format!(
+                "--format={}",
+                configuration.format.get_pulse_format()
+            ));
+    } else {
+        stream_command
+            .arg(format!("--target={}", source))
+            .arg(format!(
+                "--format={}",
+                configuration.format.get_pipewire_format()
+            ));
+    }
+    stream_command
+        .arg(format!("--rate={}", configuration.sample_rate))
+        .arg(format!("--channels={}", channels));
+    if tool == "pw-record" {
+        // pw-record writes to the standard output if the file is '-'
+        stream_command.arg("-");
+    }
+    Some(stream_command)
+}
@@ -0,0 +1,233 @@
+use std::fs::{metadata, remove_file};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::backend::RecordedSegment;
+use crate::manifest::CaptureGap;
+use crate::shutdown::{is_recording_stop_requested, wait_for_recording_process};
+use crate::wave::{append_recording, read_data_size, repair_header};
+use crate::{
+    finish_partial_file, get_arecord_command, get_output_file_path, get_partial_file_path,
+    record_audio_from_pcm, RecordingDeviceConfiguration,
+};
+
+/// The time between two checks of the size of a recording.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The settings of the watchdog which restarts the capture of a device whose recording stopped
+/// growing while the other devices continue.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CaptureWatchdogConfiguration {
+    #[serde(default = "CaptureWatchdogConfiguration::default_enabled")]
+    pub enabled: bool,
+
+    /// The time (in seconds) a recording may not grow before its capture is restarted.
+    #[serde(default = "CaptureWatchdogConfiguration::default_stall_timeout_in_seconds")]
+    pub stall_timeout_in_seconds: u32,
+}
+
+impl CaptureWatchdogConfiguration {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_stall_timeout_in_seconds() -> u32 {
+        10
+    }
+
+    /// Get the time a recording may not grow, `None` is returned if the watchdog is disabled.
+    pub fn get_stall_timeout(&self) -> Option<Duration> {
+        (self.enabled && self.stall_timeout_in_seconds > 0)
+            .then(|| Duration::from_secs(u64::from(self.stall_timeout_in_seconds)))
+    }
+}
+
+impl Default for CaptureWatchdogConfiguration {
+    fn default() -> Self {
+        CaptureWatchdogConfiguration {
+            enabled: CaptureWatchdogConfiguration::default_enabled(),
+            stall_timeout_in_seconds:
+                CaptureWatchdogConfiguration::default_stall_timeout_in_seconds(),
+        }
+    }
+}
+
+/// Watches the file a recording process writes to in a thread of its own and kills the process
+/// once the file did not grow for the stall timeout.
+pub struct CaptureWatchdog {
+    is_stopped: Arc<AtomicBool>,
+    thread: JoinHandle<Option<Instant>>,
+}
+
+impl CaptureWatchdog {
+    pub fn start(process_id: u32, path: PathBuf, stall_timeout: Duration) -> CaptureWatchdog {
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let thread_is_stopped = is_stopped.clone();
+        let thread = spawn(move || {
+            let mut last_size = 0;
+            let mut last_growth = Instant::now();
+            while !thread_is_stopped.load(Ordering::SeqCst) {
+                let size = metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+                if size > last_size {
+                    last_size = size;
+                    last_growth = Instant::now();
+                } else if last_growth.elapsed() >= stall_timeout {
+                    #[cfg(unix)]
+                    unsafe {
+                        libc::kill(process_id as libc::pid_t, libc::SIGKILL);
+                    }
+                    #[cfg(not(unix))]
+                    let _ = process_id;
+                    return Some(last_growth);
+                }
+                sleep(POLL_INTERVAL);
+            }
+            None
+        });
+        CaptureWatchdog { is_stopped, thread }
+    }
+
+    /// Stop watching the recording. The time the recording stopped growing is returned if the
+    /// process was killed since it stalled.
+    pub fn stop(self) -> Option<Instant> {
+        self.is_stopped.store(true, Ordering::SeqCst);
+        self.thread.join().unwrap_or(None)
+    }
+}
+
+/// Get the duration (in seconds) of the samples which were recorded so far.
+fn get_recorded_seconds(configuration: &RecordingDeviceConfiguration, path: &Path) -> f64 {
+    let channels = if configuration.mono { 1 } else { 2 };
+    let bytes_per_second = f64::from(configuration.sample_rate)
+        * f64::from(configuration.format.get_bits_per_sample() / 8 * channels);
+    read_data_size(path).unwrap_or(0) as f64 / bytes_per_second.max(1.0)
+}
+
+/// Record a single audio file from the ALSA PCM of a device while a watchdog watches the growth of
+/// the recording. If the capture stalls, only the process of this device is killed and restarted
+/// for the remaining duration. The samples of the restarted capture are appended to the recording
+/// and the missing time is marked as a gap, so the samples captured before the stall are kept.
+pub fn record_audio_from_pcm_watched(
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_folder: String,
+    watchdog: &CaptureWatchdogConfiguration,
+) -> Option<RecordedSegment> {
+    let stall_timeout = match watchdog.get_stall_timeout() {
+        Some(stall_timeout) => stall_timeout,
+        None => {
+            return record_audio_from_pcm(configuration, duration_in_seconds, output_folder).map(
+                |file_prefix| RecordedSegment {
+                    file_prefix,
+                    ..Default::default()
+                },
+            )
+        }
+    };
+    let output_file = get_output_file_path(configuration, &output_folder);
+    let partial_file = get_partial_file_path(&output_file);
+    let restart_file = get_partial_file_path(&output_file.with_extension("restart.wav"));
+    let capture_start = Instant::now();
+    let capture_duration = Duration::from_secs(u64::from(duration_in_seconds));
+    let mut gaps: Vec<CaptureGap> = vec![];
+    let mut remaining_in_seconds = duration_in_seconds;
+
+    loop {
+        // the captures after a restart are appended to the recording
+        let capture_file = if gaps.is_empty() {
+            &partial_file
+        } else {
+            &restart_file
+        };
+        let mut child = get_arecord_command(configuration, remaining_in_seconds, capture_file)
+            .spawn()
+            .ok()?;
+        let capture_watchdog =
+            CaptureWatchdog::start(child.id(), capture_file.clone(), stall_timeout);
+        let record_status = wait_for_recording_process(&mut child);
+        let stalled_since = capture_watchdog.stop();
+
+        // the header of a killed or interrupted capture was not finalized
+        let is_finished = match record_status {
+            Ok(exit_status) => {
+                exit_status.success()
+                    || ((stalled_since.is_some() || is_recording_stop_requested())
+                        && repair_header(capture_file).is_ok())
+            }
+            Err(_) => false,
+        };
+        if !gaps.is_empty() {
+            if is_finished {
+                if let Err(error) = append_recording(&partial_file, &restart_file) {
+                    error!(
+                        "Could not append the restarted capture to {}. The error was: {}",
+                        partial_file.display(),
+                        error
+                    );
+                }
+            } else {
+                error!(
+                    "The restarted capture of card {} and device {} failed, keeping the recording until the stall",
+                    configuration.card, configuration.device
+                );
+            }
+            let _ = remove_file(&restart_file);
+        } else if !is_finished {
+            return None;
+        }
+
+        let stalled_since = match stalled_since {
+            Some(stalled_since) if is_finished => stalled_since,
+            _ => break,
+        };
+        let recorded_seconds = get_recorded_seconds(configuration, &partial_file);
+        let missing_seconds = capture_start.elapsed().as_secs_f64() - recorded_seconds;
+        warn!(
+            "The capture of card {} and device {} stalled for {} seconds, {:.1} seconds of the recording are missing",
+            configuration.card,
+            configuration.device,
+            stalled_since.elapsed().as_secs(),
+            missing_seconds.max(0.0)
+        );
+        gaps.push(CaptureGap {
+            offset_in_seconds: recorded_seconds,
+            duration_in_seconds: missing_seconds.max(0.0),
+        });
+        remaining_in_seconds = capture_duration
+            .saturating_sub(capture_start.elapsed())
+            .as_secs() as u32;
+        if remaining_in_seconds == 0 || is_recording_stop_requested() {
+            break;
+        }
+        warn!(
+            "Restarting the capture of card {} and device {} for the remaining {} seconds of the segment",
+            configuration.card, configuration.device, remaining_in_seconds
+        );
+    }
+
+    if let Err(error) = finish_partial_file(&output_file) {
+        error!(
+            "Could not rename the finished recording {}: {}",
+            partial_file.display(),
+            error
+        );
+        return None;
+    }
+    let dropped_frames = gaps
+        .iter()
+        .map(|gap| (gap.duration_in_seconds * f64::from(configuration.sample_rate)).round() as u64)
+        .sum();
+    Some(RecordedSegment {
+        file_prefix: output_file.with_extension("").to_str()?.to_string(),
+        dropped_frames,
+        gaps,
+        ..Default::default()
+    })
+}
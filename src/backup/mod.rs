@@ -0,0 +1,138 @@
+use std::fs::read_dir;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::archive::layout::Archive;
+use crate::status::STATUS_FILE_NAME;
+use crate::upload::UploadConfiguration;
+
+/// The subfolder of the upload destination the metadata is backed up to if no destination is set.
+const UPLOAD_BACKUP_FOLDER_NAME: &str = "metadata";
+
+/// The settings for mirroring the metadata of the archive (the session manifests, the latency
+/// calibration, the reports and label files) to a second location after every session.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BackupConfiguration {
+    /// The rsync destination (a local folder or e.g. `backup:metadata/`) the metadata is mirrored
+    /// to. The `metadata` subfolder of the upload destination is used if none is set.
+    #[serde(default = "BackupConfiguration::default_destination")]
+    pub destination: Option<String>,
+
+    /// Additional files (e.g. the labels written by the annotate command) which are mirrored to
+    /// the top of the destination.
+    #[serde(default = "BackupConfiguration::default_label_files")]
+    pub label_files: Vec<String>,
+}
+
+impl BackupConfiguration {
+    fn default_destination() -> Option<String> {
+        None
+    }
+
+    fn default_label_files() -> Vec<String> {
+        vec![]
+    }
+
+    /// Get the destination the metadata is mirrored to, `None` if neither a destination nor an
+    /// upload destination is configured.
+    pub fn get_destination(&self, upload: Option<&UploadConfiguration>) -> Option<String> {
+        if let Some(destination) = &self.destination {
+            return Some(destination.clone());
+        }
+        let upload_destination = upload?.destination.trim_end_matches('/');
+        Some(if upload_destination.ends_with(':') {
+            format!("{}{}/", upload_destination, UPLOAD_BACKUP_FOLDER_NAME)
+        } else {
+            format!("{}/{}/", upload_destination, UPLOAD_BACKUP_FOLDER_NAME)
+        })
+    }
+}
+
+/// Get the metadata files of the archive which are backed up: the JSON files of the state folder
+/// (the session manifests and the latency calibration) and everything in the reports folder. The
+/// paths are marked with `/./`, so their part relative to the archive is kept at the destination.
+fn get_metadata_files(archive: &Archive) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for folder in [archive.get_state_folder(), archive.get_reports_folder()] {
+        let is_state_folder = folder == archive.get_state_folder();
+        let folder_name = match folder.file_name() {
+            Some(folder_name) => folder_name.to_os_string(),
+            None => continue,
+        };
+        let entries = match read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let is_json = path
+                .extension()
+                .is_some_and(|extension| extension == "json");
+            // the status of the recorder changes all the time and is no metadata of the recordings
+            let is_status_file = path
+                .file_name()
+                .is_some_and(|file_name| file_name == STATUS_FILE_NAME);
+            if !path.is_file() || (is_state_folder && (!is_json || is_status_file)) {
+                continue;
+            }
+            if let Some(file_name) = path.file_name() {
+                files.push(
+                    archive
+                        .get_root()
+                        .join(".")
+                        .join(&folder_name)
+                        .join(file_name),
+                );
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Mirror the metadata of the archive and the configured label files to the destination with
+/// rsync, independent of the upload of the encoded files. The number of files which were mirrored
+/// is returned.
+pub fn back_up_metadata(
+    archive: &Archive,
+    config: &BackupConfiguration,
+    destination: &str,
+) -> Result<usize, String> {
+    let mut files = get_metadata_files(archive)
+        .map_err(|error| format!("could not collect the metadata: {}", error))?;
+    for label_file in &config.label_files {
+        let label_file = Path::new(label_file);
+        match (label_file.parent(), label_file.file_name()) {
+            (Some(folder), Some(file_name)) if label_file.is_file() => {
+                files.push(folder.join(".").join(file_name))
+            }
+            _ => warn!(
+                "The label file {} does not exist, it is not backed up",
+                label_file.display()
+            ),
+        }
+    }
+    if files.is_empty() {
+        return Ok(0);
+    }
+
+    let status = Command::new("rsync")
+        .arg("--archive")
+        .arg("--relative")
+        .args(&files)
+        .arg(destination)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| format!("could not run rsync: {}", error))?;
+    if !status.success() {
+        return Err(format!("rsync failed with {}", status));
+    }
+    Ok(files.len())
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+/// The settings for comparing a night against the nights which were recorded before it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BaselineConfiguration {
+    /// The number of preceding nights the baseline is computed from.
+    #[serde(default = "BaselineConfiguration::default_nights")]
+    pub nights: usize,
+
+    /// The number of preceding nights which are required before nights are flagged at all.
+    #[serde(default = "BaselineConfiguration::default_minimum_nights")]
+    pub minimum_nights: usize,
+
+    /// A night is flagged if its event rate is this many times higher (or lower) than the median
+    /// of the baseline.
+    #[serde(default = "BaselineConfiguration::default_deviation_factor")]
+    pub deviation_factor: f32,
+
+    /// A night is flagged if the median gain which was applied to its segments differs by more
+    /// than this (in dB) from the baseline, which indicates a louder or quieter room.
+    #[serde(default = "BaselineConfiguration::default_gain_deviation_in_db")]
+    pub gain_deviation_in_db: f32,
+}
+
+impl BaselineConfiguration {
+    fn default_nights() -> usize {
+        14
+    }
+
+    fn default_minimum_nights() -> usize {
+        3
+    }
+
+    fn default_deviation_factor() -> f32 {
+        3.0
+    }
+
+    fn default_gain_deviation_in_db() -> f32 {
+        6.0
+    }
+}
+
+impl Default for BaselineConfiguration {
+    fn default() -> Self {
+        BaselineConfiguration {
+            nights: BaselineConfiguration::default_nights(),
+            minimum_nights: BaselineConfiguration::default_minimum_nights(),
+            deviation_factor: BaselineConfiguration::default_deviation_factor(),
+            gain_deviation_in_db: BaselineConfiguration::default_gain_deviation_in_db(),
+        }
+    }
+}
+
+/// The figures of a single night which are compared against the baseline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NightMetrics {
+    pub events_per_hour: Option<f32>,
+    pub applied_gain_in_db: Option<f32>,
+}
+
+/// Get the median of the supplied values, `None` if there are no values.
+pub fn get_median(mut values: Vec<f32>) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let middle = values.len() / 2;
+    if values.len() % 2 == 0 {
+        Some((values[middle - 1] + values[middle]) / 2.0)
+    } else {
+        Some(values[middle])
+    }
+}
+
+/// Get a percentile (between 0.0 and 100.0) of the supplied values, interpolating between the two
+/// closest values. `None` is returned if there are no values.
+pub fn get_percentile(mut values: Vec<f32>, percentile: f32) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let position = (percentile.clamp(0.0, 100.0) / 100.0) * (values.len() - 1) as f32;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    Some(values[lower] + (values[upper] - values[lower]) * (position - lower as f32))
+}
+
+/// The median figures of the nights a night is compared against.
+#[derive(Debug, Clone, Copy)]
+pub struct Baseline {
+    pub nights: usize,
+    pub events_per_hour: Option<f32>,
+    pub applied_gain_in_db: Option<f32>,
+}
+
+impl Baseline {
+    /// Compute the baseline from the metrics of the preceding nights. `None` is returned if there
+    /// are not enough nights for a meaningful comparison.
+    pub fn from_nights(
+        preceding_nights: &[NightMetrics],
+        config: &BaselineConfiguration,
+    ) -> Option<Baseline> {
+        let first = preceding_nights.len().saturating_sub(config.nights);
+        let nights = &preceding_nights[first..];
+        if nights.is_empty() || nights.len() < config.minimum_nights {
+            return None;
+        }
+        Some(Baseline {
+            nights: nights.len(),
+            events_per_hour: get_median(
+                nights
+                    .iter()
+                    .filter_map(|night| night.events_per_hour)
+                    .collect(),
+            ),
+            applied_gain_in_db: get_median(
+                nights
+                    .iter()
+                    .filter_map(|night| night.applied_gain_in_db)
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Get a description of each way the night deviates significantly from the baseline.
+    pub fn get_anomalies(
+        &self,
+        night: &NightMetrics,
+        config: &BaselineConfiguration,
+    ) -> Vec<String> {
+        let mut anomalies = vec![];
+        if let (Some(events_per_hour), Some(baseline)) =
+            (night.events_per_hour, self.events_per_hour)
+        {
+            // nights are compared against at least one event per hour, so single events in an
+            // otherwise quiet night are not flagged
+            let usual = baseline.max(1.0);
+            if events_per_hour >= usual * config.deviation_factor {
+                anomalies.push(format!(
+                    "{:.1}x more events than usual ({:.1}/h instead of {:.1}/h)",
+                    events_per_hour / usual,
+                    events_per_hour,
+                    baseline
+                ));
+            } else if baseline >= 1.0 && events_per_hour * config.deviation_factor <= baseline {
+                anomalies.push(format!(
+                    "fewer events than usual ({:.1}/h instead of {:.1}/h)",
+                    events_per_hour, baseline
+                ));
+            }
+        }
+        if let (Some(applied_gain), Some(baseline)) =
+            (night.applied_gain_in_db, self.applied_gain_in_db)
+        {
+            // a lower gain was needed for a louder recording
+            let difference = applied_gain - baseline;
+            if difference.abs() > config.gain_deviation_in_db {
+                anomalies.push(format!(
+                    "{} than usual (gain of {:.1} dB instead of {:.1} dB)",
+                    if difference < 0.0 {
+                        "louder"
+                    } else {
+                        "quieter"
+                    },
+                    applied_gain,
+                    baseline
+                ));
+            }
+        }
+        anomalies
+    }
+}
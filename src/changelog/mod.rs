@@ -0,0 +1,169 @@
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::crate_version;
+use tracing::{error, info, warn};
+
+use crate::archive::layout::STATE_FOLDER_NAME;
+
+/// The file in the state folder of the archive which stores the version of the tool which touched
+/// the archive last.
+const BINARY_VERSION_FILE_NAME: &str = "binary_version";
+
+/// A change of the behavior of the tool which affects the layout of the archive or the results of
+/// the analysis of the recordings.
+pub struct BehaviorChange {
+    /// The version of the tool which introduced the change.
+    pub version: &'static str,
+
+    pub summary: &'static str,
+
+    /// The change alters how the data which was recorded before is interpreted, so it has to be
+    /// acknowledged before the archive is touched.
+    pub reinterprets_data: bool,
+}
+
+/// All changes of the behavior which affect existing archives, in the order they were made.
+pub const BEHAVIOR_CHANGES: &[BehaviorChange] = &[
+    BehaviorChange {
+        version: "0.4.0",
+        summary: "The files are stored in the 'raw', 'encoded', 'previews', 'reports' and 'state' folders, a flat data directory is migrated automatically",
+        reinterprets_data: false,
+    },
+    BehaviorChange {
+        version: "0.4.0",
+        summary: "Segments are renamed with their event count (e.g. '_e07'), the suffix is ignored when reading the start time",
+        reinterprets_data: false,
+    },
+    BehaviorChange {
+        version: "0.4.0",
+        summary: "The start time in the file names is read in the configured timestamp_timezone and with the configured filename_template",
+        reinterprets_data: true,
+    },
+    BehaviorChange {
+        version: "0.4.0",
+        summary: "Annotations use the sample rate of every file, so the labels of mixed 48 kHz and 44.1 kHz recordings move",
+        reinterprets_data: true,
+    },
+    BehaviorChange {
+        version: "0.4.0",
+        summary: "Segments may overlap into the next one and may be trimmed or cut at stalls, the manifest lists the overlap and the gaps",
+        reinterprets_data: true,
+    },
+];
+
+/// Get the numbers of a version like `0.4.0`, a suffix like `-beta` is ignored.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut numbers = version
+        .trim()
+        .split(['-', '+'])
+        .next()?
+        .split('.')
+        .map(|number| number.parse::<u32>().ok());
+    Some((
+        numbers.next()??,
+        numbers.next()??,
+        numbers.next().flatten().unwrap_or(0),
+    ))
+}
+
+fn get_binary_version_path(root: &Path) -> PathBuf {
+    root.join(STATE_FOLDER_NAME).join(BINARY_VERSION_FILE_NAME)
+}
+
+/// Remember that the current version of the tool touched the archive in the supplied folder.
+pub fn record_binary_version(root: &Path) -> io::Result<()> {
+    write(
+        get_binary_version_path(root),
+        format!("{}\n", crate_version!()),
+    )
+}
+
+/// Get the changes of the behavior since the supplied version, all changes are returned if the
+/// version is not known.
+pub fn get_changes_since(version: Option<&str>) -> Vec<&'static BehaviorChange> {
+    let since = version.and_then(parse_version);
+    let current = parse_version(crate_version!());
+    BEHAVIOR_CHANGES
+        .iter()
+        .filter(|change| {
+            let change_version = parse_version(change.version);
+            since.map_or(true, |since| change_version > Some(since)) && change_version <= current
+        })
+        .collect()
+}
+
+/// Check if the archive in the supplied folder was touched by an older version of the tool before.
+/// The changes of the behavior since then are logged and the current version is remembered. If one
+/// of the changes alters how the existing data is interpreted, the upgrade has to be acknowledged,
+/// otherwise `false` is returned and the archive must not be touched.
+pub fn check_archive_upgrade(root: &Path, is_acknowledged: bool) -> bool {
+    // a new archive has nothing which could be interpreted differently
+    let is_empty = read_dir(root)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(true);
+    if is_empty {
+        return true;
+    }
+
+    // archives of versions which did not remember their version get all changes
+    let recorded_version = read_to_string(get_binary_version_path(root))
+        .ok()
+        .map(|version| version.trim().to_string());
+    if recorded_version.as_deref() == Some(crate_version!()) {
+        return true;
+    }
+    if recorded_version.as_deref().and_then(parse_version) > parse_version(crate_version!()) {
+        warn!(
+            "The data directory {} was used by the newer version {} before, this version {} may not handle all of its data",
+            root.display(),
+            recorded_version.unwrap_or_default(),
+            crate_version!()
+        );
+        return true;
+    }
+
+    let previous_version = match &recorded_version {
+        Some(recorded_version) => format!("version {}", recorded_version),
+        None => "an older version".to_string(),
+    };
+    let changes = get_changes_since(recorded_version.as_deref());
+    let needs_acknowledgement = changes.iter().any(|change| change.reinterprets_data);
+    if needs_acknowledgement && !is_acknowledged {
+        error!(
+            "The data directory {} was used by {} before, version {} interprets some of the existing data differently:",
+            root.display(),
+            previous_version,
+            crate_version!()
+        );
+        for change in changes.iter().filter(|change| change.reinterprets_data) {
+            error!("{}: {}", change.version, change.summary);
+        }
+        error!("Run the command again with --acknowledge-upgrade to continue. Terminating.");
+        return false;
+    }
+    if !changes.is_empty() {
+        info!(
+            "The data directory {} was used by {} before, the changes of version {} are:",
+            root.display(),
+            previous_version,
+            crate_version!()
+        );
+        for change in &changes {
+            info!("{}: {}", change.version, change.summary);
+        }
+    }
+
+    // a flat archive does not have a state folder before it is migrated
+    if let Err(error) =
+        create_dir_all(root.join(STATE_FOLDER_NAME)).and_then(|_| record_binary_version(root))
+    {
+        warn!(
+            "Could not remember the version of the data directory {}. The error was: {}",
+            root.display(),
+            error
+        );
+    }
+    true
+}
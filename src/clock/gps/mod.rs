@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Duration as OldDuration, Local, NaiveDate, NaiveTime, Utc};
+use tracing::{debug, error};
+
+use crate::clock::TimestampProvider;
+
+/// The serial device a GPS receiver is usually attached to on a Raspberry Pi.
+pub const DEFAULT_GPS_DEVICE: &str = "/dev/ttyAMA0";
+
+/// A fix which is older than this is not used anymore (e.g. the receiver was disconnected).
+const MAXIMUM_FIX_AGE: Duration = Duration::from_secs(10);
+
+/// The last time which was received from the GPS receiver and when it was received.
+type LastFix = Arc<Mutex<Option<(DateTime<Utc>, Instant)>>>;
+
+/// Verify the checksum of a NMEA sentence (the XOR of all characters between `$` and `*`).
+fn has_valid_checksum(sentence: &str) -> bool {
+    let (content, checksum) = match sentence
+        .strip_prefix('$')
+        .and_then(|sentence| sentence.split_once('*'))
+    {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let calculated = content.bytes().fold(0u8, |checksum, byte| checksum ^ byte);
+    u8::from_str_radix(checksum.trim(), 16) == Ok(calculated)
+}
+
+/// Parse the time of a valid RMC sentence (e.g. `$GPRMC,123519.00,A,...,230394,...*hh`).
+fn parse_rmc_sentence(sentence: &str) -> Option<DateTime<Utc>> {
+    if !has_valid_checksum(sentence) {
+        return None;
+    }
+    let fields: Vec<&str> = sentence.split(',').collect();
+    if fields.len() < 10 || !fields[0].ends_with("RMC") || fields[2] != "A" {
+        return None;
+    }
+
+    let time = NaiveTime::parse_from_str(fields[1], "%H%M%S%.f").ok()?;
+    let date = NaiveDate::parse_from_str(fields[9], "%d%m%y").ok()?;
+    Some(date.and_time(time).and_utc())
+}
+
+/// Provides the time of a GPS receiver which sends NMEA sentences over a serial device. The
+/// serial device has to be configured (e.g. the baud rate) before it is used.
+pub struct GpsTimestampProvider {
+    last_fix: LastFix,
+}
+
+impl GpsTimestampProvider {
+    pub fn open(device: &str) -> Result<GpsTimestampProvider, String> {
+        let file = File::open(device)
+            .map_err(|error| format!("could not open the GPS device {}: {}", device, error))?;
+        let last_fix: LastFix = Arc::new(Mutex::new(None));
+
+        // the sentences are read in the background, so the time is available without waiting
+        let thread_last_fix = last_fix.clone();
+        let device = device.to_string();
+        spawn(move || {
+            for line in BufReader::new(file).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(error) => {
+                        error!(
+                            "Could not read from the GPS device {}. The error was: {}",
+                            device, error
+                        );
+                        return;
+                    }
+                };
+                if let Some(time) = parse_rmc_sentence(line.trim()) {
+                    debug!("Received the time {} from the GPS receiver", time);
+                    *thread_last_fix.lock().unwrap() = Some((time, Instant::now()));
+                }
+            }
+        });
+
+        Ok(GpsTimestampProvider { last_fix })
+    }
+}
+
+impl TimestampProvider for GpsTimestampProvider {
+    fn get_name(&self) -> &'static str {
+        "gps"
+    }
+
+    fn now(&self) -> Option<DateTime<Local>> {
+        let (time, received_at) = (*self.last_fix.lock().unwrap())?;
+        let age = received_at.elapsed();
+        if age > MAXIMUM_FIX_AGE {
+            return None;
+        }
+        let age = OldDuration::from_std(age).ok()?;
+        Some((time + age).with_timezone(&Local))
+    }
+}
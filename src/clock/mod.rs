@@ -0,0 +1,230 @@
+use core::fmt;
+use std::sync::RwLock;
+use std::time::Instant;
+
+use chrono::{DateTime, Duration as OldDuration, Local, NaiveDate};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+#[cfg(feature = "gps")]
+pub mod gps;
+#[cfg(feature = "rtc")]
+pub mod rtc;
+
+lazy_static! {
+    static ref TIMESTAMP_PROVIDER: RwLock<Option<Box<dyn TimestampProvider>>> = RwLock::new(None);
+
+    /// The time and the monotonic clock at the moment a backwards jump was detected. Once set,
+    /// all timestamps are derived from the monotonic clock.
+    static ref MONOTONIC_ANCHOR: RwLock<Option<(DateTime<Local>, Instant)>> = RwLock::new(None);
+}
+
+/// The name of the source of the timestamps after a backwards jump of the clock was detected.
+pub const MONOTONIC_SOURCE_NAME: &str = "monotonic";
+
+/// Differences (in milliseconds) between the clock and the monotonic clock below this are not
+/// treated as a jump (e.g. the slow adjustments of NTP).
+const MAXIMUM_CLOCK_DEVIATION_IN_MS: i64 = 2000;
+
+/// A system clock before this date was obviously never set (e.g. a Raspberry Pi without network
+/// and real-time clock starts at the time it was last shut down or in 1970).
+const MINIMUM_PLAUSIBLE_DATE: (i32, u32, u32) = (2021, 1, 1);
+
+/// A source for the current time which is used for the file names and the manifests.
+pub trait TimestampProvider: Send + Sync {
+    /// The name of the source which is stored for every segment.
+    fn get_name(&self) -> &'static str;
+
+    /// Get the current time or `None` if the source can not provide it right now.
+    fn now(&self) -> Option<DateTime<Local>>;
+}
+
+/// The sources which can be configured for the timestamps.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampSource {
+    #[default]
+    System,
+
+    /// The time of a GPS receiver (NMEA sentences on a serial device), requires the `gps` feature.
+    Gps,
+
+    /// The time of a real-time clock like the DS3231, requires the `rtc` feature.
+    Rtc,
+}
+
+impl fmt::Display for TimestampSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TimestampSource::System => write!(f, "system"),
+            TimestampSource::Gps => write!(f, "gps"),
+            TimestampSource::Rtc => write!(f, "rtc"),
+        }
+    }
+}
+
+/// The configuration of the source for the timestamps.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ClockConfiguration {
+    #[serde(default = "ClockConfiguration::default_source")]
+    pub source: TimestampSource,
+
+    /// The device of the source, e.g. the serial device of a GPS receiver (`/dev/ttyAMA0`) or the
+    /// name of a real-time clock (`rtc0`).
+    #[serde(default = "ClockConfiguration::default_device")]
+    pub device: Option<String>,
+
+    /// Only use the source if the system clock is obviously wrong.
+    #[serde(default = "ClockConfiguration::default_only_if_system_clock_is_bad")]
+    pub only_if_system_clock_is_bad: bool,
+}
+
+impl ClockConfiguration {
+    fn default_source() -> TimestampSource {
+        TimestampSource::System
+    }
+
+    fn default_device() -> Option<String> {
+        None
+    }
+
+    fn default_only_if_system_clock_is_bad() -> bool {
+        true
+    }
+}
+
+impl Default for ClockConfiguration {
+    fn default() -> Self {
+        ClockConfiguration {
+            source: ClockConfiguration::default_source(),
+            device: ClockConfiguration::default_device(),
+            only_if_system_clock_is_bad: ClockConfiguration::default_only_if_system_clock_is_bad(),
+        }
+    }
+}
+
+/// Check if the system clock is obviously wrong.
+pub fn is_system_clock_bad() -> bool {
+    let (year, month, day) = MINIMUM_PLAUSIBLE_DATE;
+    match NaiveDate::from_ymd_opt(year, month, day) {
+        Some(minimum_date) => Local::now().date_naive() < minimum_date,
+        None => false,
+    }
+}
+
+/// Create the provider for the configured source.
+fn create_provider(
+    configuration: &ClockConfiguration,
+) -> Result<Option<Box<dyn TimestampProvider>>, String> {
+    match configuration.source {
+        TimestampSource::System => Ok(None),
+        #[cfg(feature = "gps")]
+        TimestampSource::Gps => {
+            let device = configuration
+                .device
+                .clone()
+                .unwrap_or_else(|| gps::DEFAULT_GPS_DEVICE.to_string());
+            Ok(Some(Box::new(gps::GpsTimestampProvider::open(&device)?)))
+        }
+        #[cfg(feature = "rtc")]
+        TimestampSource::Rtc => {
+            let device = configuration
+                .device
+                .clone()
+                .unwrap_or_else(|| rtc::DEFAULT_RTC_DEVICE.to_string());
+            Ok(Some(Box::new(rtc::RtcTimestampProvider::open(&device)?)))
+        }
+        #[allow(unreachable_patterns)]
+        source => Err(format!(
+            "the timestamp source {} is not supported by this build",
+            source
+        )),
+    }
+}
+
+/// Select the source which is used for all following timestamps.
+pub fn initialize_clock(configuration: &ClockConfiguration) -> Result<(), String> {
+    if configuration.only_if_system_clock_is_bad && !is_system_clock_bad() {
+        info!(
+            "The system clock seems to be correct, {} is not used for the timestamps",
+            configuration.source
+        );
+        return Ok(());
+    }
+    let provider = create_provider(configuration)?;
+    if let Some(provider) = &provider {
+        info!("Using {} for the timestamps", provider.get_name());
+    }
+    *TIMESTAMP_PROVIDER.write().unwrap() = provider;
+    Ok(())
+}
+
+/// Get the current time together with the name of the source it was taken from. If the
+/// configured source can not provide the time, the system clock is used.
+pub fn now_with_source() -> (DateTime<Local>, &'static str) {
+    if let Some((anchor_time, anchor_instant)) = *MONOTONIC_ANCHOR.read().unwrap() {
+        let elapsed = OldDuration::from_std(anchor_instant.elapsed()).unwrap_or_default();
+        return (anchor_time + elapsed, MONOTONIC_SOURCE_NAME);
+    }
+    if let Some(provider) = TIMESTAMP_PROVIDER.read().unwrap().as_ref() {
+        match provider.now() {
+            Some(now) => return (now, provider.get_name()),
+            None => warn!(
+                "The {} clock could not provide the time, using the system clock",
+                provider.get_name()
+            ),
+        }
+    }
+    (Local::now(), "system")
+}
+
+/// Get the current time of the configured source.
+pub fn now() -> DateTime<Local> {
+    now_with_source().0
+}
+
+/// Detects backwards jumps of the clock (e.g. if NTP steps the clock) by comparing it with the
+/// monotonic clock. After a jump, all timestamps are derived from the monotonic clock, so the file
+/// names of a session never collide or go out of order.
+pub struct ClockJumpDetector {
+    last_time: DateTime<Local>,
+    last_instant: Instant,
+}
+
+impl ClockJumpDetector {
+    pub fn new() -> ClockJumpDetector {
+        ClockJumpDetector {
+            last_time: now(),
+            last_instant: Instant::now(),
+        }
+    }
+
+    /// Check if the clock jumped backwards since the last check and return the size of the jump.
+    pub fn check(&mut self) -> Option<OldDuration> {
+        let current_time = now();
+        let elapsed = OldDuration::from_std(self.last_instant.elapsed()).unwrap_or_default();
+        let expected_time = self.last_time + elapsed;
+        self.last_time = current_time;
+        self.last_instant = Instant::now();
+
+        let jump = expected_time - current_time;
+        if jump.num_milliseconds() <= MAXIMUM_CLOCK_DEVIATION_IN_MS {
+            return None;
+        }
+        warn!(
+            "The clock jumped back by {:.1} seconds, using the monotonic clock from now on",
+            jump.num_milliseconds() as f64 / 1000.0
+        );
+        *MONOTONIC_ANCHOR.write().unwrap() = Some((expected_time, Instant::now()));
+        self.last_time = expected_time;
+        Some(jump)
+    }
+}
+
+impl Default for ClockJumpDetector {
+    fn default() -> Self {
+        ClockJumpDetector::new()
+    }
+}
@@ -0,0 +1,44 @@
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::clock::TimestampProvider;
+
+/// The first real-time clock of the system.
+pub const DEFAULT_RTC_DEVICE: &str = "rtc0";
+
+/// Provides the time of a real-time clock (e.g. a DS3231 attached via I²C) which is handled by
+/// the kernel driver and available below `/sys/class/rtc`.
+pub struct RtcTimestampProvider {
+    since_epoch_path: PathBuf,
+}
+
+impl RtcTimestampProvider {
+    pub fn open(device: &str) -> Result<RtcTimestampProvider, String> {
+        let since_epoch_path = Path::new("/sys/class/rtc").join(device).join("since_epoch");
+        if !since_epoch_path.exists() {
+            return Err(format!(
+                "the real-time clock {} could not be found, is the kernel driver loaded?",
+                device
+            ));
+        }
+        Ok(RtcTimestampProvider { since_epoch_path })
+    }
+}
+
+impl TimestampProvider for RtcTimestampProvider {
+    fn get_name(&self) -> &'static str {
+        "rtc"
+    }
+
+    fn now(&self) -> Option<DateTime<Local>> {
+        // the kernel only exposes the time of the clock with a resolution of one second
+        let seconds: i64 = read_to_string(&self.since_epoch_path)
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        Some(DateTime::from_timestamp(seconds, 0)?.with_timezone(&Local))
+    }
+}
@@ -0,0 +1,339 @@
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+use chrono::NaiveDate;
+use clap::Clap;
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::analysis::{
+    count_events, get_energy_envelope, BreathingRateEstimate, BreathingRateEstimator,
+};
+use crate::annotation::get_recording_start_time;
+use crate::archive::ArchiveReader;
+use crate::decoding::decode_to_pcm;
+use crate::naming::EventNamingConfiguration;
+use crate::priority::{mark_as_running, Subsystem};
+use crate::silence::SilenceConfiguration;
+use crate::wave::read_broadcast_extension;
+use crate::InsomniaProject;
+
+/// The extensions of the files of a segment which are replayed, in the order they are preferred.
+pub(crate) const AUDIO_EXTENSIONS: [&str; 4] = ["wav", "flac", "mp3", "ogg"];
+
+/// Analyze recorded (or encoded) audio files.
+#[derive(Clap)]
+pub struct AnalyzeCommandOptions {
+    /// The folder where all audio files which should be analyzed are stored.
+    #[clap(index = 1)]
+    input_folder: String,
+
+    /// EXPERIMENTAL: Estimate the breathing rate for every 5 minutes of quiet recordings.
+    #[clap(long)]
+    breathing_rate: bool,
+
+    /// The RMS level (0.0 - 1.0) below which the recording is considered to be quiet.
+    #[clap(long, default_value = "0.05")]
+    quiet_threshold: f32,
+
+    /// Run the event and silence detection over the segments of the archive in the input folder
+    /// again, with the settings of the project and the ones of `--settings`, and compare the
+    /// outcomes. Nothing is written to the archive.
+    #[clap(long, requires = "settings")]
+    replay: bool,
+
+    /// The file with the alternative `[event_naming]` and `[silence]` settings for the replay
+    /// (e.g. a copy of the project file), missing sections use the ones of the project.
+    #[clap(long)]
+    settings: Option<String>,
+
+    /// Only replay the night starting at this date (e.g. `2021-03-14`).
+    #[clap(long)]
+    night: Option<String>,
+}
+
+/// The detection settings of a replay. Any other content of the file (like the rest of a project
+/// file) is ignored.
+#[derive(Deserialize)]
+struct ReplaySettings {
+    event_naming: Option<EventNamingConfiguration>,
+    silence: Option<SilenceConfiguration>,
+}
+
+/// The thresholds the detection is replayed with.
+struct DetectionThresholds {
+    event_threshold: f32,
+    silence_threshold: f32,
+}
+
+/// The outcome of the detection for a segment.
+#[derive(PartialEq)]
+struct DetectionOutcome {
+    events: u32,
+    silent: bool,
+}
+
+/// Read the alternative thresholds, the ones of the project are used for missing sections.
+fn read_replay_thresholds(
+    path: &str,
+    config: &InsomniaProject,
+) -> Result<DetectionThresholds, String> {
+    let content = read_to_string(path).map_err(|error| error.to_string())?;
+    let settings: ReplaySettings = toml::from_str(&content).map_err(|error| error.to_string())?;
+    Ok(DetectionThresholds {
+        event_threshold: settings
+            .event_naming
+            .map_or(config.event_naming.threshold, |event_naming| {
+                event_naming.threshold
+            }),
+        silence_threshold: settings
+            .silence
+            .map_or(config.silence.threshold, |silence| silence.threshold),
+    })
+}
+
+/// Decode a segment once and run the detection with every set of thresholds.
+fn detect(path: &Path, thresholds: &[&DetectionThresholds]) -> Option<Vec<DetectionOutcome>> {
+    let decoder = match decode_to_pcm(path) {
+        Ok(decoder) => decoder,
+        Err(error) => {
+            error!(
+                "Could not read {}. The error was: {}",
+                path.display(),
+                error
+            );
+            return None;
+        }
+    };
+    let format = decoder.get_format();
+    let samples: Vec<f32> = decoder.map(|frame| frame.get_mono()).collect();
+    let envelope = get_energy_envelope(&samples, format.samples_per_second);
+    Some(
+        thresholds
+            .iter()
+            .map(|thresholds| DetectionOutcome {
+                events: count_events(&envelope, thresholds.event_threshold),
+                silent: envelope
+                    .iter()
+                    .all(|value| *value < thresholds.silence_threshold),
+            })
+            .collect(),
+    )
+}
+
+/// Replay the detection over the recorded nights with the thresholds of the project and the
+/// alternative ones and print the outcomes side by side.
+fn replay_nights(options: &AnalyzeCommandOptions, config: &InsomniaProject) {
+    let settings_file = options.settings.as_deref().unwrap_or_default();
+    let alternative = match read_replay_thresholds(settings_file, config) {
+        Ok(alternative) => alternative,
+        Err(error) => {
+            error!(
+                "Could not read the settings {}: {}. Terminating.",
+                settings_file, error
+            );
+            return;
+        }
+    };
+    let current = DetectionThresholds {
+        event_threshold: config.event_naming.threshold,
+        silence_threshold: config.silence.threshold,
+    };
+    let selected_night = match options
+        .night
+        .as_deref()
+        .map(|night| NaiveDate::parse_from_str(night, "%Y-%m-%d"))
+        .transpose()
+    {
+        Ok(selected_night) => selected_night,
+        Err(error) => {
+            error!("The night is not a valid date: {}. Terminating.", error);
+            return;
+        }
+    };
+    let nights = match ArchiveReader::open(Path::new(&options.input_folder))
+        .and_then(|archive_reader| archive_reader.get_nights())
+    {
+        Ok(nights) => nights,
+        Err(error) => {
+            error!(
+                "Could not read the recordings in {}. Terminating. The error was: {}",
+                options.input_folder, error
+            );
+            return;
+        }
+    };
+
+    println!(
+        "[*] Event threshold:\t\t{} (current) / {} (alternative)",
+        current.event_threshold, alternative.event_threshold
+    );
+    println!(
+        "[*] Silence threshold:\t\t{} (current) / {} (alternative)",
+        current.silence_threshold, alternative.silence_threshold
+    );
+    for night in nights.iter().filter(|night| {
+        selected_night.map_or(true, |selected_night| night.get_date() == selected_night)
+    }) {
+        let mut replayed_segments = 0;
+        let mut stored_events: Option<u32> = None;
+        let mut totals = [(0, 0), (0, 0)];
+        let mut changed_segments = vec![];
+        for segment in night.get_segments() {
+            let audio_file = match AUDIO_EXTENSIONS
+                .iter()
+                .find_map(|extension| segment.get_file_with_extension(extension))
+            {
+                Some(audio_file) => audio_file,
+                None => continue,
+            };
+            info!("Replaying {}", audio_file.display());
+            let outcomes = match detect(audio_file, &[&current, &alternative]) {
+                Some(outcomes) => outcomes,
+                None => continue,
+            };
+            replayed_segments += 1;
+            if let Some(events) = segment.get_events() {
+                stored_events = Some(stored_events.unwrap_or(0) + events);
+            }
+            for (total, outcome) in totals.iter_mut().zip(&outcomes) {
+                total.0 += outcome.events;
+                total.1 += u32::from(outcome.silent);
+            }
+            if outcomes[0] != outcomes[1] {
+                changed_segments.push((segment.get_name().to_string(), outcomes));
+            }
+        }
+
+        println!(
+            "[*] Night {}:\t\t{} segment(s) replayed",
+            night.get_date(),
+            replayed_segments
+        );
+        println!(
+            "    [-] Events:\t\t\t{} (stored) / {} (current) / {} (alternative)",
+            stored_events.map_or_else(|| "-".to_string(), |events| events.to_string()),
+            totals[0].0,
+            totals[1].0
+        );
+        println!(
+            "    [-] Silent segments:\t{} (current) / {} (alternative)",
+            totals[0].1, totals[1].1
+        );
+        println!("    [-] Changed segments:\t{}", changed_segments.len());
+        for (name, outcomes) in changed_segments {
+            let describe = |outcome: &DetectionOutcome| {
+                if outcome.silent {
+                    format!("{} events, silent", outcome.events)
+                } else {
+                    format!("{} events", outcome.events)
+                }
+            };
+            println!(
+                "        [-] {}:\t{} -> {}",
+                name,
+                describe(&outcomes[0]),
+                describe(&outcomes[1])
+            );
+        }
+    }
+}
+
+/// Estimate the breathing rate (EXPERIMENTAL) for every window which is covered by the supplied
+/// audio files. Files without a known start time or which can not be decoded are skipped.
+pub(crate) fn estimate_breathing_rates<P: AsRef<Path>>(
+    audio_files: &[P],
+    quiet_threshold: f32,
+) -> Vec<BreathingRateEstimate> {
+    let mut breathing_rate_estimator = BreathingRateEstimator::new(quiet_threshold);
+
+    // process all recordings to get the energy over time
+    for audio_file in audio_files {
+        let audio_file = audio_file.as_ref();
+
+        // the start time of the broadcast extension is preferred over the one of the file name
+        let broadcast_extension = read_broadcast_extension(audio_file).ok().flatten();
+        let start_time = match broadcast_extension
+            .map(|extension| extension.origination_time)
+            .or_else(|| audio_file.to_str().and_then(get_recording_start_time))
+        {
+            Some(start_time) => start_time,
+            None => {
+                info!(
+                    "Skipping {} since the filename did not match the expected pattern",
+                    audio_file.display()
+                );
+                continue;
+            }
+        };
+
+        let decoder = match decode_to_pcm(audio_file) {
+            Ok(decoder) => decoder,
+            Err(error) => {
+                error!(
+                    "Could not read {}. The error was: {}",
+                    audio_file.display(),
+                    error
+                );
+                continue;
+            }
+        };
+        let format = decoder.get_format();
+        let samples: Vec<f32> = decoder.map(|frame| frame.get_mono()).collect();
+        let envelope = get_energy_envelope(&samples, format.samples_per_second);
+        breathing_rate_estimator.add_envelope(start_time, &envelope);
+    }
+    breathing_rate_estimator.get_estimates()
+}
+
+pub fn run_command_analyze(options: AnalyzeCommandOptions, config: InsomniaProject) {
+    if !options.breathing_rate && !options.replay {
+        error!("No analysis was selected. Terminating.");
+        return;
+    }
+
+    // a recorder with lower priority encoders pauses them while the analysis is running
+    let _activity_marker = mark_as_running(&config, Subsystem::Analysis);
+    if options.replay {
+        replay_nights(&options, &config);
+        if !options.breathing_rate {
+            return;
+        }
+    }
+
+    // get all recordings in the order they were recorded
+    let mut ordered_file_list: Vec<String> = match read_dir(&options.input_folder) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.path().to_str().map(|path| path.to_string()))
+            .collect(),
+        Err(error) => {
+            error!("Could not read the input folder. The error was: {}", error);
+            return;
+        }
+    };
+    ordered_file_list.sort();
+
+    warn!(
+        "The breathing-rate estimation is EXPERIMENTAL and must not be used for medical purposes"
+    );
+    let estimates = estimate_breathing_rates(&ordered_file_list, options.quiet_threshold);
+
+    // print the estimated rates for each window
+    println!("[*] Breathing rate (EXPERIMENTAL):");
+    for estimate in estimates {
+        match estimate.breaths_per_minute {
+            Some(breaths_per_minute) => println!(
+                "    [-] {} - {}\t{:.1} breaths/min",
+                estimate.start_time.format("%Y-%m-%d %H:%M"),
+                estimate.end_time.format("%H:%M"),
+                breaths_per_minute
+            ),
+            None => println!(
+                "    [-] {} - {}\tn/a",
+                estimate.start_time.format("%Y-%m-%d %H:%M"),
+                estimate.end_time.format("%H:%M")
+            ),
+        }
+    }
+}
@@ -1,17 +1,46 @@
-use crate::annotation::FileAnnotator;
+use crate::annotation::{get_recording_start_time, FileAnnotator};
+use crate::archive::collect_files;
+use crate::naming::template::get_safe_name;
 use crate::InsomniaProject;
-use chrono::{TimeZone, Utc};
 use clap::Clap;
-use lazy_static::lazy_static;
-use log::{error, info};
-use regex::Regex;
-use std::borrow::Borrow;
-use std::fs::{read_dir, OpenOptions};
-use std::io::Write;
-
-lazy_static! {
-    static ref CORRECT_FILE_NAME_REGEX: Regex =
-        Regex::new(r".*(\d{4})(\d{2})(\d{2})(\d{2})(\d{2})(\d{2})_.*\.wav").unwrap();
+use std::collections::HashSet;
+use std::fs::{read_dir, read_to_string, File, OpenOptions};
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{error, info};
+
+/// The name of the output file which writes the labels to stdout instead.
+const STDOUT_NAME: &str = "-";
+
+/// What should happen if the output file already has some content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IfExistsAction {
+    /// Add all labels to the end of the file.
+    Append,
+
+    /// Replace the content of the file.
+    Overwrite,
+
+    /// Do not touch the file at all.
+    Abort,
+
+    /// Only add the labels of files which were not processed before.
+    Merge,
+}
+
+impl FromStr for IfExistsAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "append" => Ok(IfExistsAction::Append),
+            "overwrite" => Ok(IfExistsAction::Overwrite),
+            "abort" => Ok(IfExistsAction::Abort),
+            "merge" => Ok(IfExistsAction::Merge),
+            _ => Err(format!("unknown action '{}'", value)),
+        }
+    }
 }
 
 /// A subcommand for controlling testing
@@ -21,9 +50,18 @@ pub struct AnnotateCommandOptions {
     #[clap(index = 1)]
     input_folder: String,
 
-    /// The file in which the annotation labels should be stored.
-    #[clap(index = 2)]
-    output_file: String,
+    /// The file in which the annotation labels should be stored, `-` writes them to stdout.
+    #[clap(
+        index = 2,
+        required_unless_present = "output",
+        conflicts_with = "output"
+    )]
+    output_file: Option<String>,
+
+    /// The file in which the annotation labels should be stored, `-` writes them to stdout (an
+    /// alternative to passing the file as the second argument).
+    #[clap(long)]
+    output: Option<String>,
 
     /// Use range information in the label text instead of just the start time of the label.
     #[clap(long)]
@@ -32,9 +70,174 @@ pub struct AnnotateCommandOptions {
     /// Add markers every 10 minutes (if the range is longer then that).
     #[clap(long)]
     add_sub_markers: bool,
+
+    /// What to do if the output file already has content. With `merge`, only the labels of files
+    /// which were not annotated before are added, so re-running after adding files is safe.
+    #[clap(long, default_value = "merge", possible_values = &["append", "overwrite", "abort", "merge"])]
+    if_exists: IfExistsAction,
+
+    /// Only annotate the recordings of this input, if the files of each input are stored in a
+    /// folder of its own. Otherwise a label file is created for each input.
+    #[clap(long)]
+    input: Option<String>,
+}
+
+impl AnnotateCommandOptions {
+    fn get_output_file(&self) -> &str {
+        self.output
+            .as_deref()
+            .or(self.output_file.as_deref())
+            .unwrap_or(STDOUT_NAME)
+    }
+
+    /// Check if the labels are written to stdout, the log messages have to go to stderr then.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.get_output_file() == STDOUT_NAME
+    }
+}
+
+/// Get the path of the file which tracks the audio files already annotated in the output file.
+fn get_processed_list_path(output_file: &str) -> String {
+    format!("{}.processed", output_file)
+}
+
+/// Read the lines of a file into a set, a missing file results in an empty set.
+fn read_line_set(path: &str) -> HashSet<String> {
+    match read_to_string(path) {
+        Ok(content) => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.to_string())
+            .collect(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+/// Open the label file and the list of already processed files, depending on what should happen
+/// with existing content. The already processed files and the existing labels are returned as
+/// well (both are empty if nothing should be merged).
+fn open_output_file(
+    output_file: &str,
+    if_exists: IfExistsAction,
+) -> Option<(File, File, HashSet<String>, HashSet<String>)> {
+    // check if there is already something in the output file and decide what to do with it
+    let has_content = Path::new(output_file)
+        .metadata()
+        .map(|metadata| metadata.len() > 0)
+        .unwrap_or(false);
+    if has_content && if_exists == IfExistsAction::Abort {
+        error!(
+            "The output file {} already has content. Not touching it.",
+            output_file
+        );
+        return None;
+    }
+    let overwrite = if_exists == IfExistsAction::Overwrite;
+    let processed_list_path = get_processed_list_path(output_file);
+    let (processed_files, existing_labels) = if if_exists == IfExistsAction::Merge {
+        (
+            read_line_set(&processed_list_path),
+            read_line_set(output_file),
+        )
+    } else {
+        (HashSet::new(), HashSet::new())
+    };
+
+    let label_file = match OpenOptions::new()
+        .write(true)
+        .append(!overwrite)
+        .truncate(overwrite)
+        .create(true)
+        .open(output_file)
+    {
+        Ok(file) => file,
+        Err(error) => {
+            error!(
+                "Could not open output file. The error was: {}",
+                error.to_string()
+            );
+            return None;
+        }
+    };
+
+    // keep track of the files which were annotated, so a later merge can skip them
+    let processed_list_file = match OpenOptions::new()
+        .write(true)
+        .append(!overwrite)
+        .truncate(overwrite)
+        .create(true)
+        .open(&processed_list_path)
+    {
+        Ok(file) => file,
+        Err(error) => {
+            error!(
+                "Could not open the list of processed files {}. The error was: {}",
+                processed_list_path, error
+            );
+            return None;
+        }
+    };
+
+    Some((
+        label_file,
+        processed_list_file,
+        processed_files,
+        existing_labels,
+    ))
+}
+
+/// Get the folders of the inputs whose recordings should be annotated, if the files of each input
+/// are stored in a folder of its own. The inputs are returned with their folders (in the order of
+/// their names), only the folders which exist are part of the list.
+fn get_input_folders(
+    options: &AnnotateCommandOptions,
+    config: &InsomniaProject,
+) -> Vec<(String, PathBuf)> {
+    let mut input_names: Vec<&String> = config
+        .input
+        .keys()
+        .filter(|input_name| {
+            options
+                .input
+                .as_ref()
+                .map_or(true, |selected_input| selected_input == *input_name)
+        })
+        .collect();
+    input_names.sort();
+    input_names
+        .into_iter()
+        .map(|input_name| {
+            (
+                input_name.clone(),
+                Path::new(&options.input_folder).join(get_safe_name(input_name)),
+            )
+        })
+        .filter(|(_, folder)| folder.is_dir())
+        .collect()
+}
+
+/// Get the label file for the recordings of an input, the name of the input is added to the name
+/// of the output file (e.g. `labels_bedroom.txt`).
+fn get_input_output_file(output_file: &str, input_name: &str) -> String {
+    let output_path = Path::new(output_file);
+    let stem = output_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(output_file);
+    let file_name = match output_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some(extension) => format!("{}_{}.{}", stem, get_safe_name(input_name), extension),
+        None => format!("{}_{}", stem, get_safe_name(input_name)),
+    };
+    output_path
+        .with_file_name(file_name)
+        .to_string_lossy()
+        .to_string()
 }
 
-pub fn run_command_annotate(options: AnnotateCommandOptions, _: InsomniaProject) {
+pub fn run_command_annotate(options: AnnotateCommandOptions, config: InsomniaProject) {
     /*
     // ensure ta input folder was specified
     if !argument_matches.is_present("input_folder") {
@@ -48,77 +251,153 @@ pub fn run_command_annotate(options: AnnotateCommandOptions, _: InsomniaProject)
         return;
     }*/
 
-    //
-    let mut label_file = match OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(options.output_file)
-    {
-        Ok(file) => file,
-        Err(error) => {
-            error!(
-                "Could not open output file. The error was: {}",
-                error.to_string()
-            );
+    if !config.per_input_folders {
+        if options.input.is_some() {
+            error!("An input can only be selected if the files of each input are stored in a folder of its own (per_input_folders)");
             return;
         }
-    };
 
-    // loop through all found files and try to process them
-    let mut ordered_file_list: Vec<String> = vec![];
-    for maybe_audio_file_path in read_dir(options.input_folder).unwrap() {
-        let audio_file_path_obj = maybe_audio_file_path.unwrap().path();
-        let audio_file_path = audio_file_path_obj.to_str().unwrap();
-        ordered_file_list.push(audio_file_path.to_string())
+        // loop through all found files and try to process them
+        let mut ordered_file_list: Vec<String> = vec![];
+        for maybe_audio_file_path in read_dir(&options.input_folder).unwrap() {
+            let audio_file_path_obj = maybe_audio_file_path.unwrap().path();
+            let audio_file_path = audio_file_path_obj.to_str().unwrap();
+            ordered_file_list.push(audio_file_path.to_string())
+        }
+        annotate_files(ordered_file_list, options.get_output_file(), &options);
+        return;
+    }
+
+    // the recordings of each input are on a timeline of their own, so they get a label file each
+    let input_folders = get_input_folders(&options, &config);
+    if input_folders.is_empty() {
+        error!(
+            "Could not find the folder of any input in {}",
+            options.input_folder
+        );
+        return;
+    }
+    if input_folders.len() > 1 && options.writes_to_stdout() {
+        error!("The labels of several inputs cannot be written to stdout, select one of them with --input");
+        return;
+    }
+    for (input_name, input_folder) in &input_folders {
+        // the folder of an input may contain the subfolders for the start times of the segments
+        let mut files = vec![];
+        if let Err(error) = collect_files(input_folder, &mut files) {
+            error!(
+                "Could not list the recordings in {}. The error was: {}",
+                input_folder.display(),
+                error
+            );
+            continue;
+        }
+        let output_file = if input_folders.len() > 1 {
+            get_input_output_file(options.get_output_file(), input_name)
+        } else {
+            options.get_output_file().to_string()
+        };
+        info!(
+            "Annotating the recordings of {} in {}",
+            input_name, output_file
+        );
+        let ordered_file_list = files
+            .iter()
+            .filter_map(|file| file.to_str())
+            .map(|file| file.to_string())
+            .collect();
+        annotate_files(ordered_file_list, &output_file, &options);
     }
-    ordered_file_list.sort_by(|a, b| a.partial_cmp(b).unwrap());
+}
+
+/// Write the labels for the supplied recordings to the output file.
+fn annotate_files(
+    mut ordered_file_list: Vec<String>,
+    output_file: &str,
+    options: &AnnotateCommandOptions,
+) {
+    // labels written to stdout are not tracked, so the options for existing content do not apply
+    let (mut label_writer, mut processed_list_file, processed_files, existing_labels) =
+        if output_file == STDOUT_NAME {
+            (
+                Box::new(stdout()) as Box<dyn Write>,
+                None,
+                HashSet::new(),
+                HashSet::new(),
+            )
+        } else {
+            match open_output_file(output_file, options.if_exists) {
+                Some((label_file, processed_list_file, processed_files, existing_labels)) => (
+                    Box::new(label_file) as Box<dyn Write>,
+                    Some(processed_list_file),
+                    processed_files,
+                    existing_labels,
+                ),
+                None => return,
+            }
+        };
 
-    let mut file_start_time = 0;
+    // the recordings are annotated in the order they were recorded, which is not necessarily the
+    // order of their names
+    ordered_file_list.sort_by_key(|file| (get_recording_start_time(file), file.clone()));
+
+    let mut file_start_time = 0.0;
 
     // loop through all found files and try to process them
     for audio_file_path in ordered_file_list {
         // ensure the skip all files which do not match the expected pattern
-        if !CORRECT_FILE_NAME_REGEX.is_match(audio_file_path.borrow()) {
+        let initial_parsed_start_datetime = match get_recording_start_time(&audio_file_path) {
+            Some(start_time) => start_time,
+            None => {
+                info!(
+                    "Skipping {} since the filename did not match the expected pattern",
+                    audio_file_path
+                );
+                continue;
+            }
+        };
+
+        let maybe_file_annotator = FileAnnotator::from(
+            &audio_file_path,
+            initial_parsed_start_datetime,
+            file_start_time,
+            options.add_sub_markers,
+            options.range,
+        );
+        if maybe_file_annotator.is_none() {
+            error!("Could not get a file annotator for {}", audio_file_path);
+            continue;
+        }
+        let file_annotator = maybe_file_annotator.unwrap();
+        let max_labels = file_annotator.get_max_labels();
+
+        //
+        file_start_time = file_annotator.get_end_time();
+
+        // files which were already annotated still count for the start time of the next file
+        let file_name = Path::new(&audio_file_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&audio_file_path)
+            .to_string();
+        if processed_files.contains(&file_name) {
             info!(
-                "Skipping {} since the filename did not match the expected pattern",
+                "Skipping {} since it was already annotated",
                 audio_file_path
             );
             continue;
         }
 
-        // even if there should be one match, try to "loop" through it
-        for cap in CORRECT_FILE_NAME_REGEX.captures_iter(audio_file_path.borrow()) {
-            let current_timestamp_str = format!(
-                "{:02}.{:02}.{:04} {:02}:{:02}:{:02}",
-                &cap[3], &cap[2], &cap[1], &cap[4], &cap[5], &cap[6],
-            );
-
-            let initial_parsed_start_datetime = Utc
-                .datetime_from_str(current_timestamp_str.as_str(), "%d.%m.%Y %H:%M:%S")
-                .unwrap()
-                .naive_utc();
-
-            let maybe_file_annotator = FileAnnotator::from(
-                &audio_file_path,
-                initial_parsed_start_datetime,
-                file_start_time as u64,
-                options.add_sub_markers,
-                options.range,
-            );
-            if maybe_file_annotator.is_none() {
-                error!("Could not get a file annotator for {}", audio_file_path);
+        //
+        for current_label in file_annotator.take(max_labels) {
+            let label_line = current_label.get_label_line();
+            if existing_labels.contains(label_line.trim_end_matches('\n')) {
                 continue;
             }
-            let file_annotator = maybe_file_annotator.unwrap();
-            let max_labels = file_annotator.get_max_labels();
-
-            //
-            file_start_time = file_annotator.get_end_time();
-
-            //
-            for current_label in file_annotator.take(max_labels) {
-                let _ = write!(&mut label_file, "{}", current_label.get_label_line());
-            }
+            let _ = write!(&mut label_writer, "{}", label_line);
+        }
+        if let Some(processed_list_file) = processed_list_file.as_mut() {
+            let _ = writeln!(processed_list_file, "{}", file_name);
         }
     }
 }
@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use chrono::Local;
+use clap::Clap;
+use tracing::{error, info};
+
+use crate::archive::layout::Archive;
+use crate::latency::{load_calibration, measure_latency, store_calibration, LatencyMeasurement};
+use crate::manifest::MANIFEST_TIMESTAMP_FORMAT;
+use crate::InsomniaProject;
+
+/// Measure the latency between an output device (e.g. the one playing masking noise) and the
+/// input devices by playing a chirp and detecting it in the recording.
+#[derive(Clap)]
+pub struct CalibrateCommandOptions {
+    /// The output device the chirp is played on (e.g. 'plughw:0,0'), the device of the output
+    /// section of the project is used if none is specified.
+    #[clap(long)]
+    output: Option<String>,
+
+    /// The name of the input device which should be calibrated (all inputs if none is specified).
+    #[clap(index = 1)]
+    input: Option<String>,
+}
+
+pub fn run_command_calibrate(options: CalibrateCommandOptions, config: InsomniaProject) {
+    if config.output.is_none() && options.output.is_none() {
+        error!("No output device was selected (with --output or in the project). Terminating.");
+        return;
+    }
+    let mut output = config.output.clone().unwrap_or_default();
+    if options.output.is_some() {
+        output.device = options.output.clone();
+    }
+    if let Err(error) = output.validate() {
+        error!("The output can not be used: {}. Terminating.", error);
+        return;
+    }
+
+    let archive = match Archive::open(Path::new(&config.data_directory)) {
+        Ok(archive) => archive,
+        Err(error) => {
+            error!(
+                "Could not open the data directory. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    let mut calibration = match load_calibration(&archive.get_state_folder()) {
+        Ok(calibration) => calibration,
+        Err(error) => {
+            error!(
+                "Could not read the previous calibration. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+
+    let mut input_names: Vec<&String> = match &options.input {
+        Some(input_name) if !config.input.contains_key(input_name) => {
+            error!("The input {} is not configured. Terminating.", input_name);
+            return;
+        }
+        Some(input_name) => vec![input_name],
+        None => config.input.keys().collect(),
+    };
+    input_names.sort();
+
+    for input_name in input_names {
+        // the plug layer converts the format of the chirp if the device does not support it
+        let input_device = &config.input[input_name];
+        let input_pcm = input_device
+            .pcm
+            .clone()
+            .unwrap_or_else(|| format!("plughw:{},{}", input_device.card, input_device.device));
+        info!(
+            "Measuring the latency between {} and {} ({})",
+            output.get_device_name(),
+            input_name,
+            input_pcm
+        );
+        match measure_latency(&input_pcm, &output, &archive.get_state_folder()) {
+            Ok((latency_in_ms, correlation)) => {
+                println!("[*] Latency of {}:\t\t{:.1} ms", input_name, latency_in_ms);
+                println!("    [-] Correlation:\t\t{:.2}", correlation);
+                calibration.insert(
+                    input_name.clone(),
+                    LatencyMeasurement {
+                        output: output.get_device_name().to_string(),
+                        latency_in_ms,
+                        correlation,
+                        measured_at: Local::now().format(MANIFEST_TIMESTAMP_FORMAT).to_string(),
+                    },
+                );
+            }
+            Err(error) => error!(
+                "Could not measure the latency of {}. The error was: {}",
+                input_name, error
+            ),
+        }
+    }
+
+    if let Err(error) = store_calibration(&archive.get_state_folder(), &calibration) {
+        error!("Could not store the calibration. The error was: {}", error);
+    }
+}
@@ -1,7 +1,29 @@
+use std::collections::BTreeMap;
+
 use clap::Clap;
-use log::warn;
+use toml::Value;
+use tracing::{debug, error, warn};
+
+use crate::backend::RecordingBackend;
+use crate::encoding::OutputFormat;
+use crate::naming::EventNamingMode;
+use crate::silence::SilenceMode;
+use crate::{
+    get_available_devices, get_supported_sample_rates, InsomniaProject,
+    RecordingDeviceConfiguration,
+};
+
+/// The duration (in hours) of a night which is assumed for projecting the size of its recordings
+/// if no recording window is configured.
+const PROJECTED_NIGHT_DURATION_IN_HOURS: f64 = 8.0;
+
+/// The bitrate (in kbit/s) ffmpeg encodes mp3 files with if neither a bitrate nor a quality is set.
+const DEFAULT_MP3_BITRATE: f64 = 128.0;
 
-use crate::InsomniaProject;
+/// The average bitrates (in kbit/s) of the variable bitrate qualities 0 to 9 of the mp3 encoder.
+const MP3_QUALITY_BITRATES: [f64; 10] = [
+    245.0, 225.0, 190.0, 175.0, 165.0, 130.0, 115.0, 100.0, 85.0, 65.0,
+];
 
 /// A sub-command for showing configuration options and storing an example configuration
 #[derive(Clap)]
@@ -9,6 +31,346 @@ pub struct ConfigCommandOptions {
     ///
     #[clap(long)]
     save_sample: bool,
+
+    /// Show the differences between the project file and another project file.
+    #[clap(long)]
+    diff: Option<String>,
+
+    /// Check the project file for suspicious combinations of settings and suggest how to fix
+    /// them.
+    #[clap(long)]
+    lint: bool,
+}
+
+/// A suspicious setting of the project file and a concrete suggestion how to fix it.
+struct LintFinding {
+    message: String,
+    fix: String,
+}
+
+impl LintFinding {
+    fn new(message: String, fix: String) -> LintFinding {
+        LintFinding { message, fix }
+    }
+}
+
+/// Flatten a configuration into a map of dotted keys and their (TOML formatted) values.
+fn flatten_configuration(prefix: &str, value: &Value, flattened: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Table(table) => {
+            for (key, child_value) in table {
+                let child_prefix = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_configuration(&child_prefix, child_value, flattened);
+            }
+        }
+        _ => {
+            flattened.insert(prefix.to_string(), value.to_string());
+        }
+    }
+}
+
+fn get_flattened_configuration(config: &InsomniaProject) -> Option<BTreeMap<String, String>> {
+    let value = match Value::try_from(config) {
+        Ok(value) => value,
+        Err(error) => {
+            error!(
+                "Could not serialize the configuration. The error was: {}",
+                error
+            );
+            return None;
+        }
+    };
+    let mut flattened = BTreeMap::new();
+    flatten_configuration("", &value, &mut flattened);
+    Some(flattened)
+}
+
+fn print_configuration_diff(config: &InsomniaProject, other_project_file: &str) {
+    let other_config = match InsomniaProject::from_file(other_project_file) {
+        Ok(other_config) => other_config,
+        Err(error) => {
+            error!("{}", error);
+            return;
+        }
+    };
+    let (flattened, other_flattened) = match (
+        get_flattened_configuration(config),
+        get_flattened_configuration(&other_config),
+    ) {
+        (Some(flattened), Some(other_flattened)) => (flattened, other_flattened),
+        _ => return,
+    };
+
+    // print all keys which were removed, added or changed in the other project file
+    println!("[*] Differences to {}:", other_project_file);
+    let mut difference_count = 0;
+    for (key, value) in &flattened {
+        match other_flattened.get(key) {
+            None => println!("    [-] {} = {}", key, value),
+            Some(other_value) if other_value != value => {
+                println!("    [~] {} = {} -> {}", key, value, other_value)
+            }
+            Some(_) => continue,
+        }
+        difference_count += 1;
+    }
+    for (key, other_value) in &other_flattened {
+        if !flattened.contains_key(key) {
+            println!("    [+] {} = {}", key, other_value);
+            difference_count += 1;
+        }
+    }
+    println!("[*] Number of differences:\t{}", difference_count);
+}
+
+/// Get the inputs of the project sorted by their names.
+fn get_sorted_inputs(config: &InsomniaProject) -> Vec<(&String, &RecordingDeviceConfiguration)> {
+    let mut inputs: Vec<_> = config.input.iter().collect();
+    inputs.sort_by(|first, second| first.0.cmp(second.0));
+    inputs
+}
+
+fn lint_channels(config: &InsomniaProject) -> Vec<LintFinding> {
+    let mut findings = vec![];
+    for (input_name, input_device) in get_sorted_inputs(config) {
+        if input_device.mono && input_device.channel_labels.len() > 1 {
+            findings.push(LintFinding::new(
+                format!(
+                    "{} is recorded in mono but has {} channel labels",
+                    input_name,
+                    input_device.channel_labels.len()
+                ),
+                format!(
+                    "set 'mono = false' for {} to record both channels or keep only the label '{}'",
+                    input_name, input_device.channel_labels[0]
+                ),
+            ));
+        }
+        if input_device.mono && input_device.auto_mono {
+            findings.push(LintFinding::new(
+                format!(
+                    "{} is always recorded in mono, so 'auto_mono' has no effect",
+                    input_name
+                ),
+                format!(
+                    "remove 'auto_mono' from {} or set 'mono = false' to let the channels decide",
+                    input_name
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+/// Get the projected number of bytes per second the encoded recordings of an input (and the kept
+/// recordings) use.
+fn get_projected_bytes_per_second(
+    config: &InsomniaProject,
+    input_device: &RecordingDeviceConfiguration,
+) -> f64 {
+    let channels = if input_device.mono { 1.0 } else { 2.0 };
+    let recording_bytes_per_second = f64::from(input_device.sample_rate)
+        * f64::from(input_device.format.get_bits_per_sample())
+        / 8.0
+        * channels;
+    let encoded_bytes_per_second = match input_device.get_output_format(config.output_format) {
+        OutputFormat::Mp3 => {
+            let bitrate = match (config.encoding.mp3_bitrate, config.encoding.mp3_quality) {
+                (Some(bitrate), _) => f64::from(bitrate),
+                (None, Some(quality)) => MP3_QUALITY_BITRATES[usize::from(quality.min(9))],
+                (None, None) => DEFAULT_MP3_BITRATE,
+            };
+            bitrate * 1000.0 / 8.0
+        }
+        OutputFormat::Ogg => {
+            // the nominal bitrate of vorbis grows with the quality (64 kbit/s at quality 0)
+            let quality = f64::from(config.encoding.vorbis_quality).max(-1.0);
+            (64.0 + 16.0 * quality.min(4.0) + 32.0 * (quality - 4.0).max(0.0)) * 1000.0 / 8.0
+        }
+        OutputFormat::Flac => {
+            let sample_rate = config
+                .encoding
+                .sample_rate
+                .unwrap_or(input_device.sample_rate);
+            recording_bytes_per_second / 2.0 * f64::from(sample_rate)
+                / f64::from(input_device.sample_rate.max(1))
+        }
+    };
+    if config.encoding.keep_wav {
+        encoded_bytes_per_second + recording_bytes_per_second
+    } else {
+        encoded_bytes_per_second
+    }
+}
+
+fn lint_storage(config: &InsomniaProject) -> Vec<LintFinding> {
+    let mut findings = vec![];
+    if config.input.is_empty() {
+        return findings;
+    }
+    let night_duration_in_hours = match config
+        .schedule
+        .as_ref()
+        .map(|schedule| schedule.get_window())
+    {
+        Some(Ok(window)) => window.get_duration().num_minutes() as f64 / 60.0,
+        _ => PROJECTED_NIGHT_DURATION_IN_HOURS,
+    };
+    let bytes_per_night: f64 = config
+        .input
+        .values()
+        .map(|input_device| get_projected_bytes_per_second(config, input_device))
+        .sum::<f64>()
+        * night_duration_in_hours
+        * 3600.0;
+    let gigabytes_per_night = bytes_per_night / 1024.0 / 1024.0 / 1024.0;
+
+    if let Some(keep_gigabytes) = config.retention.keep_gigabytes {
+        if keep_gigabytes < gigabytes_per_night {
+            findings.push(LintFinding::new(
+                format!(
+                    "the retention keeps {} GB, but a night of {:.1} hours is projected to use {:.2} GB",
+                    keep_gigabytes, night_duration_in_hours, gigabytes_per_night
+                ),
+                format!(
+                    "raise 'keep_gigabytes' in [retention] to at least {:.1} (or a multiple for several nights) or {}",
+                    gigabytes_per_night.ceil(),
+                    if config.encoding.keep_wav {
+                        "set 'keep_wav = false' in [encoding]"
+                    } else {
+                        "lower the bitrate"
+                    }
+                ),
+            ));
+        }
+    }
+    if let Some(max_storage_gb) = config.max_storage_gb {
+        if max_storage_gb < gigabytes_per_night {
+            findings.push(LintFinding::new(
+                format!(
+                    "the storage quota of {} GB is smaller than the projected {:.2} GB of a night",
+                    max_storage_gb, gigabytes_per_night
+                ),
+                format!(
+                    "raise 'max_storage_gb' to at least {:.1} or record fewer inputs",
+                    gigabytes_per_night.ceil()
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+fn lint_schedule(config: &InsomniaProject) -> Vec<LintFinding> {
+    let schedule = match &config.schedule {
+        Some(schedule) => schedule,
+        None => return vec![],
+    };
+    let segment_duration = config.defaults.get_segment_duration();
+    match schedule.get_window() {
+        Ok(window)
+            if window.get_duration().num_seconds() < i64::from(segment_duration.get_seconds()) =>
+        {
+            vec![LintFinding::new(
+                format!(
+                    "the recording window from {} to {} is shorter than a segment ({})",
+                    schedule.start, schedule.stop, segment_duration
+                ),
+                format!(
+                    "set 'duration' in [defaults] to at most {} minutes or widen the [schedule]",
+                    window.get_duration().num_minutes().max(1)
+                ),
+            )]
+        }
+        Ok(_) => vec![],
+        Err(error) => vec![LintFinding::new(
+            format!("the recording window can not be used: {}", error),
+            "write the 'start' and the 'stop' of the [schedule] as different HH:MM times"
+                .to_string(),
+        )],
+    }
+}
+
+fn lint_sample_rates(config: &InsomniaProject) -> Vec<LintFinding> {
+    let mut findings = vec![];
+    for (input_name, input_device) in get_sorted_inputs(config) {
+        if let Some(sample_rate) = config.encoding.sample_rate {
+            if sample_rate > input_device.sample_rate {
+                findings.push(LintFinding::new(
+                    format!(
+                        "the recordings of {} are resampled from {} Hz to {} Hz, which only adds size",
+                        input_name, input_device.sample_rate, sample_rate
+                    ),
+                    format!(
+                        "set 'sample_rate' in [encoding] to at most {} or remove it",
+                        input_device.sample_rate
+                    ),
+                ));
+            }
+        }
+
+        // only the hardware devices recorded with arecord can be asked for their sample rates,
+        // the plug devices convert every rate
+        let is_hardware_device = input_device
+            .pcm
+            .as_ref()
+            .map_or(true, |pcm| pcm.starts_with("hw:"));
+        if input_device.get_backend(config.backend).resolve() != Some(RecordingBackend::Arecord)
+            || !is_hardware_device
+        {
+            continue;
+        }
+        match get_supported_sample_rates(input_device) {
+            Some((minimum, maximum))
+                if input_device.sample_rate < minimum || input_device.sample_rate > maximum =>
+            {
+                let supported_sample_rate = input_device.sample_rate.clamp(minimum, maximum);
+                findings.push(LintFinding::new(
+                    format!(
+                        "{} ({}) does not support {} Hz, only {} to {} Hz",
+                        input_name,
+                        input_device.get_pcm(),
+                        input_device.sample_rate,
+                        minimum,
+                        maximum
+                    ),
+                    format!(
+                        "set 'sample_rate = {}' for {} or record from 'plughw:{},{}' to resample",
+                        supported_sample_rate, input_name, input_device.card, input_device.device
+                    ),
+                ));
+            }
+            Some(_) => {}
+            None => debug!(
+                "Could not query the sample rates of {}, the device is missing or busy",
+                input_name
+            ),
+        }
+    }
+    findings
+}
+
+/// Check the project file for settings which are valid on their own but do not fit together.
+fn print_lint_findings(config: &InsomniaProject) {
+    let findings: Vec<LintFinding> = vec![
+        lint_channels(config),
+        lint_storage(config),
+        lint_schedule(config),
+        lint_sample_rates(config),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    for finding in &findings {
+        println!("[!] {}", finding.message);
+        println!("    [-] Fix: {}", finding.fix);
+    }
+    println!("[*] Number of findings:\t\t{}", findings.len());
 }
 
 pub fn run_command_config(options: ConfigCommandOptions, config: InsomniaProject) {
@@ -17,8 +379,326 @@ pub fn run_command_config(options: ConfigCommandOptions, config: InsomniaProject
         return;
     }
 
+    // compare the configuration with another one instead of printing it
+    if let Some(other_project_file) = &options.diff {
+        print_configuration_diff(&config, other_project_file);
+        return;
+    }
+
+    // only report the suspicious settings instead of printing the configuration
+    if options.lint {
+        print_lint_findings(&config);
+        return;
+    }
+
+    // the names of the devices are only available if the devices can be queried
+    let available_devices = get_available_devices().unwrap_or_default();
+    let device_blacklist = match config.get_device_blacklist() {
+        Ok(device_blacklist) => device_blacklist,
+        Err(error) => {
+            error!(
+                "The device blacklist contains an invalid pattern. The error was: {}",
+                error
+            );
+            vec![]
+        }
+    };
+
     // just print the information from the configuration file
     println!("[*] Data directory:\t\t{}", config.data_directory);
+    if let Some(subfolder_pattern) = &config.subfolder_pattern {
+        println!("[*] Subfolder pattern:\t\t{}", subfolder_pattern);
+    }
+    println!("[*] Per-input folders:\t\t{}", config.per_input_folders);
+    println!("[*] Filename template:\t\t{}", config.filename_template);
+    if let Some(label) = &config.label {
+        println!("[*] Label:\t\t\t{}", label);
+    }
+    println!("[*] Start alignment:\t\t{}", config.start_alignment);
+    println!("[*] Timestamp time zone:\t{}", config.timestamp_timezone);
+    if let Some(status_file) = &config.status_file {
+        println!("[*] Status file:\t\t{}", status_file);
+    }
+    println!("[*] Recording backend:\t\t{}", config.backend);
+    println!("[*] Backpressure strategy:\t{}", config.backpressure);
+    println!("[*] Normalization:\t\t{}", config.normalization.enabled);
+    if config.normalization.enabled {
+        println!(
+            "    [-] True peak ceiling:\t{} dBTP",
+            config.normalization.true_peak_ceiling
+        );
+        println!(
+            "    [-] Maximum gain:\t\t{} dB",
+            config.normalization.maximum_gain
+        );
+    }
+    if let Some(sync) = &config.sync {
+        println!(
+            "[*] Start synchronization:\t{} ({})",
+            sync.role, sync.address
+        );
+    }
+    if config.control.enabled {
+        println!(
+            "[*] Control socket:\t\t{}",
+            config
+                .control
+                .get_socket_path(&config.data_directory)
+                .display()
+        );
+    }
+    if let Some(tee) = &config.tee {
+        println!("[*] Tee socket:\t\t{}", tee.socket);
+        println!("    [-] Queued blocks:\t\t{}", tee.maximum_queued_blocks);
+    }
+    println!("[*] Output format:\t\t{}", config.output_format);
+    println!("[*] Vorbis quality:\t\t{}", config.encoding.vorbis_quality);
+    if let Some(sample_rate) = config.encoding.sample_rate {
+        println!("[*] Resample to:\t\t{} Hz", sample_rate);
+    }
+    println!("[*] Keep recordings:\t\t{}", config.encoding.keep_wav);
+    if !config.encoding.keep_wav {
+        println!("    [-] Removal:\t\t{}", config.encoding.removal);
+    }
+    println!(
+        "[*] Encoding workers:\t\t{} (queue length {})",
+        config.encoding.workers, config.encoding.maximum_queue_length
+    );
+    println!("[*] Create previews:\t\t{}", config.create_previews);
+    println!("[*] Event naming:\t\t{}", config.event_naming.mode);
+    if config.event_naming.mode != EventNamingMode::Off {
+        println!("    [-] Threshold:\t\t{}", config.event_naming.threshold);
+    }
+    println!("[*] Cough detection:\t\t{}", config.cough_detection.enabled);
+    if config.cough_detection.enabled {
+        println!(
+            "    [-] Threshold:\t\t{} dB",
+            config.cough_detection.threshold_in_db
+        );
+        println!(
+            "    [-] Minimum frequency:\t{} Hz",
+            config.cough_detection.minimum_frequency_in_hz
+        );
+    }
+    println!("[*] Silent segments:\t\t{}", config.silence.mode);
+    if config.silence.mode != SilenceMode::Off {
+        println!("    [-] Threshold:\t\t{}", config.silence.threshold);
+    }
+    if config.silence.is_trimming() {
+        println!(
+            "[*] Trimmed silence:\t\t{}{}{} (keeping {} s, at most {} s)",
+            if config.silence.trim_leading_silence {
+                "leading"
+            } else {
+                ""
+            },
+            if config.silence.trim_leading_silence && config.silence.trim_trailing_silence {
+                " and "
+            } else {
+                ""
+            },
+            if config.silence.trim_trailing_silence {
+                "trailing"
+            } else {
+                ""
+            },
+            config.silence.trim_padding_in_seconds,
+            config.silence.maximum_trim_in_seconds
+        );
+    }
+    if config.silence.detached_after_in_minutes > 0 {
+        println!(
+            "[*] Detached microphones:\tnear-silent ({} %) for {} min",
+            config.silence.near_silent_percentage, config.silence.detached_after_in_minutes
+        );
+    } else {
+        println!("[*] Detached microphones:\tnot detected");
+    }
+    if config.hotplug.enabled {
+        println!(
+            "[*] Hot-plug recovery:\t\tretry every {} seconds",
+            config.hotplug.retry_interval_in_seconds
+        );
+    } else {
+        println!("[*] Hot-plug recovery:\t\tdisabled");
+    }
+    match config.capture_watchdog.get_stall_timeout() {
+        Some(stall_timeout) => println!(
+            "[*] Capture watchdog:\t\trestart after {} seconds without data",
+            stall_timeout.as_secs()
+        ),
+        None => println!("[*] Capture watchdog:\t\tdisabled"),
+    }
+    println!(
+        "[*] Retry failed inputs:\t{}",
+        if config.retry.maximum_attempts > 0 {
+            format!("give up after {} failures", config.retry.maximum_attempts)
+        } else {
+            "forever".to_string()
+        }
+    );
+    println!(
+        "    [-] Delay:\t\t\t{} to {} seconds",
+        config.retry.initial_delay_in_seconds, config.retry.maximum_delay_in_seconds
+    );
+    println!("[*] Sound activation:\t\t{}", config.activation.mode);
+    if config.activation.is_enabled() {
+        println!("    [-] Threshold:\t\t{}", config.activation.threshold);
+        println!(
+            "    [-] Minimum duration:\t{} ms",
+            config.activation.minimum_duration_in_milliseconds
+        );
+        println!(
+            "    [-] Hold:\t\t\t{} seconds",
+            config.activation.hold_in_seconds
+        );
+        println!(
+            "    [-] Pre-roll:\t\t{} seconds",
+            config.activation.pre_roll_in_seconds
+        );
+    }
+    println!("[*] Timestamp source:\t\t{}", config.clock.source);
+    if let Some(device) = &config.clock.device {
+        println!("    [-] Device:\t\t{}", device);
+    }
+    println!(
+        "[*] Priorities:\t\t\tencoding {}, analysis {}",
+        config.priority.encoding, config.priority.analysis
+    );
+    println!(
+        "[*] Minimum free space:\t\t{} MB ({} otherwise)",
+        config.storage.minimum_free_space_in_mb, config.storage.action
+    );
+    if let Some(max_storage_gb) = config.max_storage_gb {
+        println!(
+            "[*] Storage quota:\t\t{} GB ({} otherwise)",
+            max_storage_gb, config.quota_action
+        );
+    }
+    if config.retention.is_enabled() {
+        println!("[*] Retention:");
+        if let Some(keep_days) = config.retention.keep_days {
+            println!("    [-] Keep days:\t\t{}", keep_days);
+        }
+        if let Some(keep_gigabytes) = config.retention.keep_gigabytes {
+            println!("    [-] Keep size:\t\t{} GB", keep_gigabytes);
+        }
+    }
+    println!(
+        "[*] Power:\t\t\t{} W base, {} W CPU",
+        config.power.base_power_in_watts, config.power.cpu_power_in_watts
+    );
+    println!(
+        "[*] Baseline:\t\t\tlast {} nights (at least {}), {}x events, {} dB gain",
+        config.baseline.nights,
+        config.baseline.minimum_nights,
+        config.baseline.deviation_factor,
+        config.baseline.gain_deviation_in_db
+    );
+    if let Some(schedule) = &config.schedule {
+        println!(
+            "[*] Recording window:\t\t{} - {}",
+            schedule.start, schedule.stop
+        );
+    }
+    if config.stop.is_set() {
+        println!("[*] Stop conditions:");
+        if let Some(maximum_segments) = config.stop.maximum_segments {
+            println!("    [-] Segments:\t\t{}", maximum_segments);
+        }
+        if let Some(total_duration) = &config.stop.total_duration {
+            println!("    [-] Total duration:\t{}", total_duration);
+        }
+        if let Some(until) = &config.stop.until {
+            println!("    [-] Until:\t\t\t{}", until);
+        }
+    }
+    if let Some(upload) = &config.upload {
+        println!("[*] Upload destination:\t\t{}", upload.destination);
+        println!("    [-] Concurrency:\t\t{}", upload.concurrency);
+        if let Some(bandwidth_limit) = upload.bandwidth_limit_in_kbits {
+            println!("    [-] Bandwidth limit:\t{} kbit/s", bandwidth_limit);
+        }
+    }
+    if let Some(backup) = &config.backup {
+        println!(
+            "[*] Metadata backup:\t\t{}",
+            backup
+                .get_destination(config.upload.as_ref())
+                .unwrap_or_else(|| "no destination".to_string())
+        );
+        for label_file in &backup.label_files {
+            println!("    [-] Label file:\t\t{}", label_file);
+        }
+    }
+    if let Some(output) = &config.output {
+        println!("[*] Output:\t\t\t{}", output.get_device_name());
+        println!("    [-] Backend:\t\t{}", output.backend);
+        println!("    [-] Volume:\t\t\t{:.0} %", output.volume * 100.0);
+    }
+    if let Some(telemetry) = &config.telemetry {
+        println!("[*] Span export:\t\t{}", telemetry.otlp_endpoint);
+        println!("    [-] Service name:\t\t{}", telemetry.service_name);
+    }
+    if let Some(update) = &config.update {
+        println!("[*] Updates:\t\t\t{}", update.repository);
+        println!("    [-] Asset:\t\t\t{}", update.get_asset());
+        println!(
+            "    [-] Signed:\t\t\t{}",
+            if update.public_key.is_some() {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+        if update.automatic {
+            println!(
+                "    [-] Automatic:\t\tevery {} h",
+                update.check_interval_in_hours
+            );
+        }
+    }
+    if let Some(command) = &config.hooks.on_segment_finished {
+        println!("[*] Segment hook:\t\t{}", command);
+        println!("    [-] Timeout:\t\t{} s", config.hooks.timeout_in_seconds);
+    }
+    if let Some(command) = &config.hooks.on_microphone_detached {
+        println!("[*] Detached microphone hook:\t{}", command);
+        println!("    [-] Timeout:\t\t{} s", config.hooks.timeout_in_seconds);
+    }
+    if let Some(command) = &config.hooks.on_input_failed {
+        println!("[*] Failed input hook:\t\t{}", command);
+        println!("    [-] Timeout:\t\t{} s", config.hooks.timeout_in_seconds);
+    }
+    if !config.maintenance.is_empty() {
+        println!("[*] Maintenance tasks:\t\t{}", config.maintenance.len());
+        for task in &config.maintenance {
+            println!("    [-] {}:\t\t{}", task.name, task.schedule);
+        }
+    }
+    println!(
+        "[*] Defaults:\t\t\t{} Hz, {}, {}, {}",
+        config.defaults.sample_rate,
+        config.defaults.format,
+        if config.defaults.mono {
+            "mono"
+        } else {
+            "stereo"
+        },
+        config.defaults.get_segment_duration()
+    );
+    if config.defaults.overlap_in_seconds > 0 {
+        println!(
+            "    [-] Overlap:\t\t{} s",
+            config.defaults.overlap_in_seconds
+        );
+    }
+    if !config.device_blacklist.is_empty() {
+        println!(
+            "[*] Device blacklist:\t\t{}",
+            config.device_blacklist.join(", ")
+        );
+    }
     println!("[*] Input device count:\t\t{}", config.input.len());
     for current_input_device_name in config.input.keys() {
         println!("    [-] Defined name:\t\t{}", current_input_device_name);
@@ -30,9 +710,65 @@ pub fn run_command_config(options: ConfigCommandOptions, config: InsomniaProject
             "        [-] Device:\t\t{}",
             config.input[current_input_device_name].device
         );
+        if let Some(device_info) = available_devices.iter().find(|device_info| {
+            device_info.card == config.input[current_input_device_name].card
+                && device_info.device == config.input[current_input_device_name].device
+        }) {
+            println!("        [-] Name:\t\t{}", device_info.description);
+            if device_info.is_blacklisted(&device_blacklist) {
+                println!("        [-] Blacklisted:\t\ttrue");
+                warn!(
+                    "The device {} of {} matches a pattern of the device blacklist",
+                    device_info.description, current_input_device_name
+                );
+            }
+        }
         println!(
             "        [-] Mono:\t\t{}",
             config.input[current_input_device_name].mono
         );
+        if config.input[current_input_device_name].auto_mono {
+            println!("        [-] Auto mono:\t\ttrue");
+        }
+        println!(
+            "        [-] Format:\t\t{}",
+            config.input[current_input_device_name].format
+        );
+        println!(
+            "        [-] Sample rate:\t\t{} Hz",
+            config.input[current_input_device_name].sample_rate
+        );
+        if let Some(output_format) = &config.input[current_input_device_name].output_format {
+            println!("        [-] Output format:\t{}", output_format);
+        }
+        if let Some(schedule) = &config.input[current_input_device_name].schedule {
+            println!("        [-] Schedule:\t\t{}", schedule);
+        }
+        if !config.input[current_input_device_name]
+            .channel_labels
+            .is_empty()
+        {
+            println!(
+                "        [-] Channel labels:\t{}",
+                config.input[current_input_device_name]
+                    .channel_labels
+                    .join(", ")
+            );
+        }
+        if let Some(capture_volume) = &config.input[current_input_device_name].capture_volume {
+            println!(
+                "        [-] Capture volume:\t{} ({})",
+                capture_volume, config.input[current_input_device_name].mixer_control
+            );
+        }
+        if let Some(pcm) = &config.input[current_input_device_name].pcm {
+            println!("        [-] PCM:\t\t\t{}", pcm);
+        }
+        if let Some(backend) = &config.input[current_input_device_name].backend {
+            println!("        [-] Backend:\t\t{}", backend);
+        }
+        if let Some(source) = &config.input[current_input_device_name].source {
+            println!("        [-] Source:\t\t{}", source);
+        }
     }
 }
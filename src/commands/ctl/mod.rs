@@ -0,0 +1,37 @@
+use clap::Clap;
+use tracing::error;
+
+use crate::control::{send_control_command, ControlCommand};
+use crate::InsomniaProject;
+
+/// Send a command to the recorder which is running for the project.
+#[derive(Clap)]
+pub struct CtlCommandOptions {
+    /// What the recorder should do: `pause` finishes the current segment and waits until the
+    /// recording is resumed with `resume`, `split` starts the next segment now and `shutdown`
+    /// stops the recorder gracefully.
+    #[clap(index = 1, possible_values = &["pause", "resume", "split", "shutdown"])]
+    command: ControlCommand,
+}
+
+pub fn run_command_ctl(options: CtlCommandOptions, config: InsomniaProject) {
+    let socket_path = config.control.get_socket_path(&config.data_directory);
+    match send_control_command(&socket_path, options.command) {
+        Ok(reply) if reply == "ok" => {
+            println!(
+                "[*] The recorder accepted the command:\t{}",
+                options.command
+            )
+        }
+        Ok(reply) => error!(
+            "The recorder did not accept the command {}: {}",
+            options.command,
+            reply.trim_start_matches("error: ")
+        ),
+        Err(error) => error!(
+            "Could not send the command to the recorder on {}, is it running? The error was: {}",
+            socket_path.display(),
+            error
+        ),
+    }
+}
@@ -0,0 +1,282 @@
+use std::fs::write;
+use std::path::Path;
+
+use chrono::{Duration as OldDuration, NaiveDate, NaiveDateTime, Timelike};
+use clap::Clap;
+use tracing::{error, info, warn};
+
+use crate::analysis::{find_events, get_energy_envelope, ENVELOPE_VALUES_PER_SECOND};
+use crate::archive::{ArchiveReader, Segment};
+use crate::cuesheet::{CueSheet, CueTrack, MAXIMUM_TRACKS};
+use crate::decoding::decode_to_pcm;
+use crate::priority::{mark_as_running, Subsystem};
+use crate::InsomniaProject;
+
+/// The extensions of the files of a segment which are used for the cue sheet, in the order they
+/// are preferred.
+const AUDIO_EXTENSIONS: [&str; 4] = ["wav", "flac", "mp3", "ogg"];
+
+/// Create a cue sheet for all segments of a night merged into a single file, with tracks at the
+/// full hours, at the changes of the encoding and at the detected events.
+#[derive(Clap)]
+pub struct CuesheetCommandOptions {
+    /// The date the night starts at (e.g. `2021-03-14`).
+    #[clap(index = 1)]
+    night: String,
+
+    /// The input whose segments are merged (can be omitted if only one input recorded that
+    /// night).
+    #[clap(long)]
+    input: Option<String>,
+
+    /// The name of the merged file the cue sheet refers to, e.g. the encoded segments merged with
+    /// `merge_mp3s.sh` (`<night>_<input>.<output format>` if none is specified).
+    #[clap(long)]
+    file: Option<String>,
+
+    /// The file the cue sheet is written to, it is printed if none is specified.
+    #[clap(long)]
+    output: Option<String>,
+
+    /// Only add tracks at the full hours instead of analyzing the segments for events.
+    #[clap(long)]
+    no_events: bool,
+}
+
+impl CuesheetCommandOptions {
+    /// Check if the cue sheet is written to stdout, the log messages have to go to stderr then.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.output.is_none()
+    }
+}
+
+/// Get the name of the input which recorded a segment.
+fn get_input_name(segment: &Segment) -> String {
+    match (
+        segment.get_input(),
+        segment.get_card(),
+        segment.get_device(),
+    ) {
+        (Some(input), _, _) => input.to_string(),
+        (None, Some(card), Some(device)) => format!("c{:02}d{:02}", card, device),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Decode a segment to get its duration and the offsets (in seconds) of its events.
+fn analyze_segment(path: &Path, threshold: Option<f32>) -> Option<(f64, Vec<f64>)> {
+    let decoder = match decode_to_pcm(path) {
+        Ok(decoder) => decoder,
+        Err(error) => {
+            error!(
+                "Could not read {}. The error was: {}",
+                path.display(),
+                error
+            );
+            return None;
+        }
+    };
+    let format = decoder.get_format();
+    let samples: Vec<f32> = decoder.map(|frame| frame.get_mono()).collect();
+    let duration_in_seconds = samples.len() as f64 / f64::from(format.samples_per_second.max(1));
+    let event_offsets = match threshold {
+        Some(threshold) => {
+            let envelope = get_energy_envelope(&samples, format.samples_per_second);
+            find_events(&envelope, threshold)
+                .into_iter()
+                .map(|index| index as f64 / ENVELOPE_VALUES_PER_SECOND as f64)
+                .collect()
+        }
+        None => vec![],
+    };
+    Some((duration_in_seconds, event_offsets))
+}
+
+/// Get the first full hour after a point in time.
+fn get_next_full_hour(time: NaiveDateTime) -> NaiveDateTime {
+    let full_hour = time
+        .with_minute(0)
+        .and_then(|time| time.with_second(0))
+        .and_then(|time| time.with_nanosecond(0))
+        .unwrap_or(time);
+    full_hour + OldDuration::hours(1)
+}
+
+pub fn run_command_cuesheet(options: CuesheetCommandOptions, config: InsomniaProject) {
+    let night = match NaiveDate::parse_from_str(&options.night, "%Y-%m-%d") {
+        Ok(night) => night,
+        Err(error) => {
+            error!(
+                "The night '{}' is not a valid date: {}. Terminating.",
+                options.night, error
+            );
+            return;
+        }
+    };
+    let nights = match ArchiveReader::open(Path::new(&config.data_directory))
+        .and_then(|archive_reader| archive_reader.get_nights())
+    {
+        Ok(nights) => nights,
+        Err(error) => {
+            error!(
+                "Could not read the recordings. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    let segments = match nights
+        .iter()
+        .find(|candidate| candidate.get_date() == night)
+    {
+        Some(night) => night.get_segments(),
+        None => {
+            error!("No segments were recorded in the night of {}", night);
+            return;
+        }
+    };
+
+    // the segments of different inputs overlap, so only one input can be merged
+    let mut input_names: Vec<String> = segments.iter().map(get_input_name).collect();
+    input_names.sort();
+    input_names.dedup();
+    let input_name = match (&options.input, input_names.as_slice()) {
+        (Some(input), _) => input.clone(),
+        (None, [input]) => input.clone(),
+        (None, _) => {
+            error!(
+                "Several inputs recorded in the night of {}, please select one of {} with --input. Terminating.",
+                night,
+                input_names.join(", ")
+            );
+            return;
+        }
+    };
+    let segments: Vec<&Segment> = segments
+        .iter()
+        .filter(|segment| get_input_name(segment) == input_name)
+        .collect();
+    if segments.is_empty() {
+        error!(
+            "The input {} did not record in the night of {}",
+            input_name, night
+        );
+        return;
+    }
+
+    // decoding the segments can take a while, so it is done with the priority of an analysis
+    let _activity_marker = mark_as_running(&config, Subsystem::Analysis);
+    let threshold = (!options.no_events).then_some(config.event_naming.threshold);
+    let default_duration_in_seconds =
+        f64::from(config.defaults.get_segment_duration().get_seconds());
+
+    // the offsets are based on the segments being concatenated without the gaps between them
+    let mut hour_tracks = vec![CueTrack {
+        title: format!("Start {}", segments[0].get_started_at().format("%H:%M:%S")),
+        offset_in_seconds: 0.0,
+    }];
+    let mut event_tracks = vec![];
+    let mut previous_encoding = segments[0].get_encoding();
+    let mut next_full_hour = get_next_full_hour(segments[0].get_started_at());
+    let mut segment_offset_in_seconds = 0.0;
+    for segment in segments {
+        let audio_file = AUDIO_EXTENSIONS
+            .iter()
+            .find_map(|extension| segment.get_file_with_extension(extension));
+        let analysis = match (audio_file, threshold, segment.get_duration_in_seconds()) {
+            (Some(audio_file), Some(_), _) | (Some(audio_file), None, None) => {
+                info!("Analyzing {}", audio_file.display());
+                analyze_segment(audio_file, threshold)
+            }
+            _ => None,
+        };
+        let (duration_in_seconds, event_offsets) = analysis.unwrap_or_else(|| {
+            let duration_in_seconds = segment
+                .get_duration_in_seconds()
+                .map_or(default_duration_in_seconds, f64::from);
+            (duration_in_seconds, vec![])
+        });
+
+        // a change of the encoding explains a change of the quality of the merged file
+        let started_at = segment.get_started_at();
+        if let Some(encoding) = segment.get_encoding() {
+            if previous_encoding.is_some_and(|previous_encoding| previous_encoding != encoding) {
+                hour_tracks.push(CueTrack {
+                    title: format!("{} encoded as {}", started_at.format("%H:%M"), encoding),
+                    offset_in_seconds: segment_offset_in_seconds,
+                });
+            }
+            previous_encoding = Some(encoding);
+        }
+        let ended_at =
+            started_at + OldDuration::milliseconds((duration_in_seconds * 1000.0) as i64);
+        while next_full_hour < ended_at {
+            // a full hour between two segments starts with the following segment
+            let offset_in_segment =
+                (next_full_hour - started_at).num_milliseconds().max(0) as f64 / 1000.0;
+            hour_tracks.push(CueTrack {
+                title: next_full_hour.format("%H:%M").to_string(),
+                offset_in_seconds: segment_offset_in_seconds + offset_in_segment,
+            });
+            next_full_hour += OldDuration::hours(1);
+        }
+        for event_offset in event_offsets {
+            let event_time = started_at + OldDuration::milliseconds((event_offset * 1000.0) as i64);
+            event_tracks.push(CueTrack {
+                title: format!("Event {}", event_time.format("%H:%M:%S")),
+                offset_in_seconds: segment_offset_in_seconds + event_offset,
+            });
+        }
+        segment_offset_in_seconds += duration_in_seconds;
+    }
+
+    // the full hours and the encoding changes are always kept, the events are thinned out evenly
+    // if there are too many
+    let available_tracks = MAXIMUM_TRACKS.saturating_sub(hour_tracks.len());
+    if event_tracks.len() > available_tracks {
+        warn!(
+            "Only {} of the {} events fit into the cue sheet",
+            available_tracks,
+            event_tracks.len()
+        );
+        let step = (event_tracks.len() + available_tracks - 1) / available_tracks.max(1);
+        event_tracks = event_tracks.into_iter().step_by(step.max(1)).collect();
+        event_tracks.truncate(available_tracks);
+    }
+
+    let file_name = options.file.clone().unwrap_or_else(|| {
+        format!(
+            "{}_{}.{}",
+            night,
+            input_name,
+            config.output_format.get_extension()
+        )
+    });
+    let mut cue_sheet = CueSheet::new(&format!("Night of {} ({})", night, input_name), &file_name);
+    if let Some(label) = &config.label {
+        cue_sheet.set_performer(label);
+    }
+    let skipped_tracks =
+        cue_sheet.add_tracks(hour_tracks.into_iter().chain(event_tracks).collect());
+    if skipped_tracks > 0 {
+        info!(
+            "{} track(s) were skipped since they would be shorter than the minimum track length",
+            skipped_tracks
+        );
+    }
+
+    match &options.output {
+        Some(output) => match write(output, cue_sheet.to_string()) {
+            Ok(()) => println!(
+                "[*] The cue sheet with {} tracks was written to {}",
+                cue_sheet.get_tracks().len(),
+                output
+            ),
+            Err(error) => error!(
+                "Could not write the cue sheet to {}. The error was: {}",
+                output, error
+            ),
+        },
+        None => print!("{}", cue_sheet),
+    }
+}
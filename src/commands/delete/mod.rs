@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::fs::{read_dir, remove_file};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate};
+use clap::Clap;
+use tracing::{error, warn};
+
+use crate::archive::layout::Archive;
+use crate::archive::{get_night_of, get_segment_name, ArchiveReader};
+use crate::lock::ArchiveLock;
+use crate::manifest::{ManifestWriter, SessionManifest, MANIFEST_TIMESTAMP_FORMAT};
+use crate::InsomniaProject;
+
+/// Delete the recordings, encoded files, previews and the session manifest entries of one or more
+/// nights. Only lists what would be deleted unless `--execute` is specified.
+#[derive(Clap)]
+pub struct DeleteCommandOptions {
+    /// Delete the night which starts at this date (e.g. `2024-03-12`).
+    #[clap(long)]
+    night: Option<String>,
+
+    /// Delete all nights which start before this date (e.g. `2024-01-01`).
+    #[clap(long)]
+    before: Option<String>,
+
+    /// Actually delete the files instead of only listing them.
+    #[clap(long)]
+    execute: bool,
+
+    /// Do not ask for a confirmation before deleting.
+    #[clap(long)]
+    yes: bool,
+
+    /// Wait until the subcommand which currently modifies the data directory (e.g. a running
+    /// recorder) finished instead of failing.
+    #[clap(long)]
+    wait: bool,
+}
+
+/// The nights which are selected for deletion.
+enum NightSelection {
+    Single(NaiveDate),
+    Before(NaiveDate),
+}
+
+impl NightSelection {
+    fn contains(&self, night: NaiveDate) -> bool {
+        match self {
+            NightSelection::Single(selected_night) => night == *selected_night,
+            NightSelection::Before(date) => night < *date,
+        }
+    }
+}
+
+/// What happens to a session manifest when the selected nights are deleted.
+enum ManifestChange {
+    /// The manifest only lists segments of the selected nights and is deleted.
+    Delete(PathBuf),
+
+    /// The segments of the selected nights are removed from the manifest.
+    Update(PathBuf, usize),
+}
+
+/// Check if a segment of a session manifest was recorded in one of the selected nights.
+fn is_selected(started_at: &str, selection: &NightSelection) -> bool {
+    DateTime::parse_from_str(started_at, MANIFEST_TIMESTAMP_FORMAT)
+        .map(|started_at| selection.contains(get_night_of(started_at.naive_local())))
+        .unwrap_or(false)
+}
+
+/// Collect the symbolic links (e.g. of the interesting segments) in a folder and its subfolders
+/// which point to files of the supplied segments.
+fn collect_symlinks(folder: &Path, segment_names: &HashSet<String>, links: &mut Vec<PathBuf>) {
+    let entries = match read_dir(folder) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type)
+                if file_type.is_symlink()
+                    && get_segment_name(&path)
+                        .is_some_and(|name| segment_names.contains(&name)) =>
+            {
+                links.push(path)
+            }
+            Ok(file_type) if file_type.is_dir() => collect_symlinks(&path, segment_names, links),
+            _ => {}
+        }
+    }
+}
+
+/// Ask the user to confirm an action on the terminal.
+pub(crate) fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+pub fn run_command_delete(options: DeleteCommandOptions, config: InsomniaProject) {
+    let parse_date = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d");
+    let selection = match (&options.night, &options.before) {
+        (Some(night), None) => parse_date(night).map(NightSelection::Single),
+        (None, Some(before)) => parse_date(before).map(NightSelection::Before),
+        _ => {
+            error!(
+                "Please select either a night with --night or a date with --before. Terminating."
+            );
+            return;
+        }
+    };
+    let selection = match selection {
+        Ok(selection) => selection,
+        Err(error) => {
+            error!("The selected date is not valid: {}. Terminating.", error);
+            return;
+        }
+    };
+
+    // the recordings must not change while they are deleted
+    let _archive_lock = if options.execute {
+        let archive_lock = Archive::open(Path::new(&config.data_directory))
+            .and_then(|archive| ArchiveLock::acquire(&archive, "delete", options.wait));
+        match archive_lock {
+            Ok(archive_lock) => Some(archive_lock),
+            Err(error) => {
+                error!(
+                    "Could not lock the data directory. Terminating. The error was: {}",
+                    error
+                );
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    let archive_reader = match ArchiveReader::open(Path::new(&config.data_directory)) {
+        Ok(archive_reader) => archive_reader,
+        Err(error) => {
+            error!(
+                "Could not open the data directory. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    let (nights, session_files) = match archive_reader
+        .get_nights()
+        .and_then(|nights| Ok((nights, archive_reader.get_session_files()?)))
+    {
+        Ok(nights_and_session_files) => nights_and_session_files,
+        Err(error) => {
+            error!(
+                "Could not read the recordings. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+
+    // all files of the segments of the selected nights (wherever they are stored in the archive)
+    let mut selected_nights = vec![];
+    let mut files = vec![];
+    let mut segment_names = HashSet::new();
+    for night in nights
+        .iter()
+        .filter(|night| selection.contains(night.get_date()))
+    {
+        selected_nights.push(night.get_date());
+        for segment in night.get_segments() {
+            segment_names.insert(segment.get_name().to_string());
+            files.extend(segment.get_files().iter().cloned());
+        }
+    }
+    collect_symlinks(archive_reader.get_root(), &segment_names, &mut files);
+
+    // the entries of the selected nights are removed from the manifests, even if their files were
+    // already deleted by hand
+    let mut manifest_changes = vec![];
+    for session_file in session_files {
+        let session = match SessionManifest::from_file(&session_file) {
+            Ok(session) => session,
+            Err(error) => {
+                warn!(
+                    "Skipping the manifest {}: {}",
+                    session_file.display(),
+                    error
+                );
+                continue;
+            }
+        };
+        let selected_segments = session
+            .segments
+            .iter()
+            .filter(|segment| is_selected(&segment.started_at, &selection))
+            .count();
+        if selected_segments == session.segments.len()
+            && (selected_segments > 0 || is_selected(&session.started_at, &selection))
+        {
+            manifest_changes.push(ManifestChange::Delete(session_file));
+        } else if selected_segments > 0 {
+            manifest_changes.push(ManifestChange::Update(session_file, selected_segments));
+        }
+    }
+
+    if files.is_empty() && manifest_changes.is_empty() {
+        println!("[*] Nothing was recorded in the selected nights");
+        return;
+    }
+    let size_in_bytes: u64 = files
+        .iter()
+        .filter_map(|file| file.symlink_metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    let night_list: Vec<String> = selected_nights
+        .iter()
+        .map(|night| night.to_string())
+        .collect();
+    println!("[*] Nights:\t\t\t{}", night_list.join(", "));
+    for file in &files {
+        println!("    [-] {}", file.display());
+    }
+    for manifest_change in &manifest_changes {
+        match manifest_change {
+            ManifestChange::Delete(path) => println!("    [-] {}", path.display()),
+            ManifestChange::Update(path, segments) => println!(
+                "    [-] {} ({} segment(s) are removed from it)",
+                path.display(),
+                segments
+            ),
+        }
+    }
+    println!(
+        "[*] {} file(s) ({} MB) and {} manifest(s) are affected",
+        files.len(),
+        size_in_bytes / 1024 / 1024,
+        manifest_changes.len()
+    );
+
+    if !options.execute {
+        println!("[*] Nothing was deleted, use --execute to delete the files");
+        return;
+    }
+    if !options.yes {
+        if !io::stdin().is_terminal() {
+            error!("The deletion can not be confirmed without a terminal, please use --yes. Terminating.");
+            return;
+        }
+        if !confirm("Delete these files?") {
+            println!("[*] Nothing was deleted");
+            return;
+        }
+    }
+
+    // the files are deleted before the manifests, so an interrupted deletion can be repeated
+    let mut deleted_files = 0;
+    for file in &files {
+        match remove_file(file) {
+            Ok(()) => deleted_files += 1,
+            Err(error) => warn!(
+                "Could not delete {}. The error was: {}",
+                file.display(),
+                error
+            ),
+        }
+    }
+    for manifest_change in manifest_changes {
+        match manifest_change {
+            ManifestChange::Delete(path) => {
+                if let Err(error) = remove_file(&path) {
+                    warn!(
+                        "Could not delete {}. The error was: {}",
+                        path.display(),
+                        error
+                    );
+                }
+            }
+            ManifestChange::Update(path, _) => match ManifestWriter::open(&path) {
+                Ok(manifest_writer) => manifest_writer.update_session(|session| {
+                    session
+                        .segments
+                        .retain(|segment| !is_selected(&segment.started_at, &selection))
+                }),
+                Err(error) => warn!(
+                    "Could not update {}. The error was: {}",
+                    path.display(),
+                    error
+                ),
+            },
+        }
+    }
+    println!("[*] Deleted {} of {} file(s)", deleted_files, files.len());
+}
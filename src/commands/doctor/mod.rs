@@ -0,0 +1,573 @@
+use std::env::temp_dir;
+use std::fs::{metadata, remove_file, write, OpenOptions};
+use std::io::{self, IsTerminal};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use chrono::Local;
+use clap::Clap;
+
+use crate::backend::pulse::get_pulse_recording_tool;
+use crate::backend::RecordingBackend;
+use crate::clock::{initialize_clock, is_system_clock_bad, now_with_source, TimestampSource};
+use crate::encoding::OutputFormat;
+use crate::scheduler::Scheduler;
+use crate::storage::get_free_space_in_bytes;
+use crate::sync::SyncRole;
+use crate::{
+    get_available_devices, is_recording_tool_available, probe_channels, resolve_pcm_name,
+    DeviceInfo, InsomniaProject,
+};
+
+/// The free space (in bytes) of the data directory below which a warning is shown.
+const MINIMUM_FREE_SPACE_IN_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Check the setup of the recorder (tools, devices, channels, storage, configuration, clock and
+/// permissions) and print hints for fixing the found problems.
+#[derive(Clap)]
+pub struct DoctorCommandOptions {
+    /// Do not use colors for the results.
+    #[clap(long)]
+    no_color: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CheckStatus {
+    Ok,
+    Warning,
+    Failure,
+}
+
+/// A group of checks which is run against the project.
+type Check = fn(&InsomniaProject) -> Vec<CheckResult>;
+
+/// The result of a single check with a hint how a problem can be fixed.
+struct CheckResult {
+    status: CheckStatus,
+    message: String,
+    hint: Option<String>,
+}
+
+impl CheckResult {
+    fn ok(message: String) -> CheckResult {
+        CheckResult {
+            status: CheckStatus::Ok,
+            message,
+            hint: None,
+        }
+    }
+
+    fn warning(message: String, hint: &str) -> CheckResult {
+        CheckResult {
+            status: CheckStatus::Warning,
+            message,
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    fn failure(message: String, hint: &str) -> CheckResult {
+        CheckResult {
+            status: CheckStatus::Failure,
+            message,
+            hint: Some(hint.to_string()),
+        }
+    }
+}
+
+fn is_tool_available(tool: &str, version_argument: &str) -> bool {
+    match Command::new(tool)
+        .arg(version_argument)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(exit_status) => exit_status.success(),
+        Err(_) => false,
+    }
+}
+
+/// Get the backends which are used by the configured input devices.
+fn get_used_backends(config: &InsomniaProject) -> Vec<(String, Option<RecordingBackend>)> {
+    config
+        .input
+        .iter()
+        .map(|(input_name, input_device)| {
+            (
+                input_name.clone(),
+                input_device.get_backend(config.backend).resolve(),
+            )
+        })
+        .collect()
+}
+
+fn check_tools(config: &InsomniaProject) -> Vec<CheckResult> {
+    let mut results = vec![];
+    let used_backends = get_used_backends(config);
+    let uses_backend = |backend| {
+        used_backends
+            .iter()
+            .any(|(_, used_backend)| *used_backend == Some(backend))
+    };
+
+    if is_recording_tool_available() {
+        results.push(CheckResult::ok("arecord is available".to_string()));
+    } else if uses_backend(RecordingBackend::Arecord) {
+        results.push(CheckResult::failure(
+            "arecord is not available".to_string(),
+            "install the ALSA tools (e.g. 'sudo apt install alsa-utils')",
+        ));
+    } else {
+        results.push(CheckResult::warning(
+            "arecord is not available".to_string(),
+            "install the ALSA tools (e.g. 'sudo apt install alsa-utils') for the arecord backend",
+        ));
+    }
+
+    if uses_backend(RecordingBackend::Pulse) {
+        match get_pulse_recording_tool() {
+            Some(tool) => results.push(CheckResult::ok(format!("{} is available", tool))),
+            None => results.push(CheckResult::failure(
+                "neither parecord nor pw-record (with timeout) is available".to_string(),
+                "install the PulseAudio or PipeWire utilities (e.g. 'sudo apt install pulseaudio-utils')",
+            )),
+        }
+    }
+
+    // mp3 files can be encoded without ffmpeg, but all other formats and the previews require it
+    let output_formats: Vec<OutputFormat> = config
+        .input
+        .values()
+        .map(|input_device| input_device.get_output_format(config.output_format))
+        .collect();
+    let requires_ffmpeg = config.create_previews
+        || !cfg!(feature = "lame")
+        || output_formats
+            .iter()
+            .any(|output_format| *output_format != OutputFormat::Mp3);
+    if is_tool_available("ffmpeg", "-version") {
+        results.push(CheckResult::ok("ffmpeg is available".to_string()));
+    } else if requires_ffmpeg {
+        results.push(CheckResult::failure(
+            "ffmpeg is not available, the recordings can not be encoded".to_string(),
+            "install ffmpeg (e.g. 'sudo apt install ffmpeg') or record with '--no-encoding'",
+        ));
+    } else {
+        results.push(CheckResult::ok(
+            "ffmpeg is not available, but not required".to_string(),
+        ));
+    }
+    results
+}
+
+fn check_devices(config: &InsomniaProject) -> Vec<CheckResult> {
+    let mut results = vec![];
+    if config.input.is_empty() {
+        results.push(CheckResult::failure(
+            "no input device is configured".to_string(),
+            "add an [input.<name>] section to the project file",
+        ));
+    }
+
+    let available_devices = match get_available_devices() {
+        Ok(available_devices) => {
+            results.push(CheckResult::ok(format!(
+                "{} capture device(s) found",
+                available_devices.len()
+            )));
+            available_devices
+        }
+        Err(_) => {
+            results.push(CheckResult::failure(
+                "could not find any capture device".to_string(),
+                "check that the microphone is connected and listed by 'arecord -l'",
+            ));
+            vec![]
+        }
+    };
+    let device_blacklist = config.get_device_blacklist().unwrap_or_default();
+
+    for (input_name, backend) in get_used_backends(config) {
+        let input_device = &config.input[&input_name];
+        let backend = match backend {
+            Some(backend) => backend,
+            None => {
+                results.push(CheckResult::failure(
+                    format!(
+                        "the backend {} of {} is not available in this build",
+                        input_device.get_backend(config.backend),
+                        input_name
+                    ),
+                    "select another backend or rebuild with the required features",
+                ));
+                continue;
+            }
+        };
+        if backend != RecordingBackend::Arecord {
+            results.push(CheckResult::ok(format!(
+                "{} is recorded with the {} backend and selected by its name",
+                input_name, backend
+            )));
+            continue;
+        }
+
+        // devices selected by their PCM name are resolved like the record command does it
+        let card_and_device = match &input_device.pcm {
+            Some(pcm) => resolve_pcm_name(pcm, &available_devices),
+            None => Some((input_device.card, input_device.device)),
+        };
+        let device_info: Option<&DeviceInfo> = card_and_device.and_then(|(card, device)| {
+            available_devices
+                .iter()
+                .find(|device_info| device_info.card == card && device_info.device == device)
+        });
+        match device_info {
+            Some(device_info) if device_info.is_blacklisted(&device_blacklist) => {
+                results.push(CheckResult::warning(
+                    format!(
+                        "{} uses {}, which matches the device blacklist",
+                        input_name, device_info.description
+                    ),
+                    "check that this is really the microphone and not a webcam or HDMI port",
+                ))
+            }
+            Some(device_info) => results.push(CheckResult::ok(format!(
+                "{} uses {} (card {}, device {})",
+                input_name, device_info.description, device_info.card, device_info.device
+            ))),
+            None => results.push(CheckResult::failure(
+                format!("the device of {} could not be found", input_name),
+                "compare the card and device (or the PCM name) with the output of 'arecord -l'",
+            )),
+        }
+    }
+    results
+}
+
+fn check_output(config: &InsomniaProject) -> Vec<CheckResult> {
+    let output = match &config.output {
+        Some(output) => output,
+        None => {
+            return vec![CheckResult::ok(
+                "no output device is configured".to_string(),
+            )]
+        }
+    };
+    match output.validate() {
+        Ok(()) => vec![CheckResult::ok(format!(
+            "{} can be played on with {}",
+            output.get_device_name(),
+            output.backend.get_tool()
+        ))],
+        Err(error) => vec![CheckResult::failure(
+            format!("the output can not be used: {}", error),
+            "compare the device with the output of 'aplay -L' (or 'pactl list short sinks')",
+        )],
+    }
+}
+
+fn check_channels(config: &InsomniaProject) -> Vec<CheckResult> {
+    let mut results = vec![];
+    if !is_recording_tool_available() {
+        return results;
+    }
+
+    // only the stereo inputs recorded with arecord can be probed
+    let mut input_names: Vec<String> = get_used_backends(config)
+        .into_iter()
+        .filter(|(_, backend)| *backend == Some(RecordingBackend::Arecord))
+        .map(|(input_name, _)| input_name)
+        .filter(|input_name| !config.input[input_name].mono)
+        .collect();
+    input_names.sort();
+    for input_name in input_names {
+        match probe_channels(&config.input[&input_name], &temp_dir()) {
+            Ok(comparison) if comparison.is_duplicate() => {
+                results.push(CheckResult::warning(
+                    format!(
+                        "both channels of {} carry the same signal (correlation {:.3})",
+                        input_name, comparison.correlation
+                    ),
+                    "set 'mono = true' or 'auto_mono = true' for the input to halve the size of the recordings",
+                ))
+            }
+            Ok(comparison) => results.push(CheckResult::ok(format!(
+                "{} records two different channels (correlation {:.3}, noise floors {:.1} dBFS ({}) and {:.1} dBFS ({}))",
+                input_name,
+                comparison.correlation,
+                comparison.noise_floors_in_db[0],
+                config.input[&input_name].get_channel_label(0, 2),
+                comparison.noise_floors_in_db[1],
+                config.input[&input_name].get_channel_label(1, 2)
+            ))),
+            Err(error) => results.push(CheckResult::warning(
+                format!("could not compare the channels of {}: {}", input_name, error),
+                "check the cabling of the microphone or record in mono",
+            )),
+        }
+    }
+    results
+}
+
+fn check_storage(config: &InsomniaProject) -> Vec<CheckResult> {
+    let mut results = vec![];
+    let data_directory = Path::new(&config.data_directory);
+    if !data_directory.is_dir() {
+        results.push(CheckResult::warning(
+            format!(
+                "the data directory {} does not exist",
+                config.data_directory
+            ),
+            "it will be created by the record command, check that the path is correct",
+        ));
+        return results;
+    }
+
+    // write a small file to be sure the recordings can be stored
+    let test_file = data_directory.join(".doctor");
+    match write(&test_file, b"schlaflosigkeit") {
+        Ok(()) => {
+            let _ = remove_file(&test_file);
+            results.push(CheckResult::ok(format!(
+                "the data directory {} is writable",
+                config.data_directory
+            )));
+        }
+        Err(error) => results.push(CheckResult::failure(
+            format!(
+                "the data directory {} is not writable: {}",
+                config.data_directory, error
+            ),
+            "change the owner or the permissions of the data directory",
+        )),
+    }
+
+    match get_free_space_in_bytes(data_directory) {
+        Some(free_space)
+            if free_space
+                < MINIMUM_FREE_SPACE_IN_BYTES
+                    .max(config.storage.get_minimum_free_space_in_bytes()) =>
+        {
+            results.push(CheckResult::warning(
+                format!(
+                    "only {} MB are free in the data directory",
+                    free_space / 1024 / 1024
+                ),
+                "free some space or move the data directory to a larger drive",
+            ))
+        }
+        Some(free_space) => results.push(CheckResult::ok(format!(
+            "{} MB are free in the data directory",
+            free_space / 1024 / 1024
+        ))),
+        None => results.push(CheckResult::warning(
+            "could not determine the free space of the data directory".to_string(),
+            "check the free space with 'df -h'",
+        )),
+    }
+    results
+}
+
+fn check_configuration(config: &InsomniaProject) -> Vec<CheckResult> {
+    let mut results = vec![];
+    match config.encoding.validate() {
+        Ok(()) => results.push(CheckResult::ok(
+            "the encoding settings are valid".to_string(),
+        )),
+        Err(error) => results.push(CheckResult::failure(
+            format!("invalid encoding settings: {}", error),
+            "fix the [encoding] section of the project file",
+        )),
+    }
+    match config.get_device_blacklist() {
+        Ok(_) => results.push(CheckResult::ok("the device blacklist is valid".to_string())),
+        Err(error) => results.push(CheckResult::failure(
+            format!(
+                "the device blacklist contains an invalid pattern: {}",
+                error
+            ),
+            "fix the regular expressions of 'device_blacklist'",
+        )),
+    }
+    match Scheduler::new(&config.maintenance, Local::now().naive_local()) {
+        Ok(_) => results.push(CheckResult::ok(format!(
+            "{} maintenance task(s) configured",
+            config.maintenance.len()
+        ))),
+        Err(error) => results.push(CheckResult::failure(
+            format!("invalid maintenance schedule: {}", error),
+            "fix the schedules of the [[maintenance]] sections",
+        )),
+    }
+    results
+}
+
+fn check_clock(config: &InsomniaProject) -> Vec<CheckResult> {
+    let mut results = vec![];
+    if is_system_clock_bad() {
+        results.push(CheckResult::failure(
+            format!(
+                "the system clock is obviously wrong ({})",
+                Local::now().format("%Y-%m-%d %H:%M")
+            ),
+            "set the clock, enable NTP or configure a GPS receiver or real-time clock in [clock]",
+        ));
+    } else {
+        results.push(CheckResult::ok("the system clock is plausible".to_string()));
+    }
+
+    // the synchronization state is only known on systems with systemd
+    if let Ok(output) = Command::new("timedatectl")
+        .args(["show", "--property=NTPSynchronized", "--value"])
+        .stderr(Stdio::null())
+        .output()
+    {
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "yes" => results.push(CheckResult::ok(
+                "the system clock is synchronized via NTP".to_string(),
+            )),
+            "no" => results.push(CheckResult::warning(
+                "the system clock is not synchronized via NTP".to_string(),
+                "enable the synchronization with 'sudo timedatectl set-ntp true'",
+            )),
+            _ => {}
+        }
+    }
+
+    if config.clock.source != TimestampSource::System {
+        match initialize_clock(&config.clock) {
+            Ok(()) => {
+                let (now, source) = now_with_source();
+                let offset = (now - Local::now()).num_milliseconds() as f64 / 1000.0;
+                results.push(CheckResult::ok(format!(
+                    "{} provides the time (offset to the system clock: {:.1} s)",
+                    source, offset
+                )));
+            }
+            Err(error) => results.push(CheckResult::failure(
+                format!("the timestamp source can not be used: {}", error),
+                "check the [clock] section and the features of this build",
+            )),
+        }
+    }
+
+    if let Some(sync) = &config.sync {
+        if sync.role == SyncRole::Follower {
+            let is_reachable = sync
+                .address
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addresses| addresses.next())
+                .map(|address| TcpStream::connect_timeout(&address, Duration::from_secs(2)).is_ok())
+                .unwrap_or(false);
+            if is_reachable {
+                results.push(CheckResult::ok(format!(
+                    "the leader {} is reachable",
+                    sync.address
+                )));
+            } else {
+                results.push(CheckResult::warning(
+                    format!("the leader {} is not reachable", sync.address),
+                    "start the recorder on the leader first or check the address and firewall",
+                ));
+            }
+        }
+    }
+    results
+}
+
+fn check_permissions(config: &InsomniaProject) -> Vec<CheckResult> {
+    let mut results = vec![];
+    if metadata("/dev/snd").is_err() {
+        results.push(CheckResult::warning(
+            "/dev/snd does not exist".to_string(),
+            "ALSA devices are only available on Linux with a loaded sound driver",
+        ));
+        return results;
+    }
+
+    let mut cards: Vec<u8> = config
+        .input
+        .values()
+        .filter(|input_device| input_device.pcm.is_none())
+        .map(|input_device| input_device.card)
+        .collect();
+    cards.sort_unstable();
+    cards.dedup();
+    for card in cards {
+        let control = format!("/dev/snd/controlC{}", card);
+        match OpenOptions::new().read(true).open(&control) {
+            Ok(_) => results.push(CheckResult::ok(format!("{} can be accessed", control))),
+            Err(error) if error.kind() == io::ErrorKind::PermissionDenied => {
+                results.push(CheckResult::failure(
+                    format!("{} can not be accessed", control),
+                    "add the user to the audio group ('sudo usermod -aG audio $USER') and log in again",
+                ))
+            }
+            Err(error) => results.push(CheckResult::warning(
+                format!("{} can not be opened: {}", control, error),
+                "check that the card exists with 'arecord -l'",
+            )),
+        }
+    }
+    results
+}
+
+fn print_results(title: &str, results: &[CheckResult], use_colors: bool) {
+    println!("[*] {}", title);
+    for result in results {
+        let (label, color) = match result.status {
+            CheckStatus::Ok => ("ok", "32"),
+            CheckStatus::Warning => ("warning", "33"),
+            CheckStatus::Failure => ("failure", "31"),
+        };
+        if use_colors {
+            println!("    [\x1b[{}m{}\x1b[0m] {}", color, label, result.message);
+        } else {
+            println!("    [{}] {}", label, result.message);
+        }
+        if let Some(hint) = &result.hint {
+            println!("        [-] Hint: {}", hint);
+        }
+    }
+}
+
+pub fn run_command_doctor(options: DoctorCommandOptions, config: InsomniaProject) {
+    let use_colors = !options.no_color && io::stdout().is_terminal();
+    let checks: [(&str, Check); 8] = [
+        ("Tools", check_tools),
+        ("Devices", check_devices),
+        ("Output", check_output),
+        ("Channels", check_channels),
+        ("Storage", check_storage),
+        ("Configuration", check_configuration),
+        ("Clock", check_clock),
+        ("Permissions", check_permissions),
+    ];
+
+    let mut all_results = vec![];
+    for (title, check) in checks.iter() {
+        let results = check(&config);
+        print_results(title, &results, use_colors);
+        all_results.extend(results);
+    }
+
+    let count = |status| {
+        all_results
+            .iter()
+            .filter(|result| result.status == status)
+            .count()
+    };
+    println!(
+        "[*] Summary:\t\t\t{} ok, {} warning(s), {} failure(s)",
+        count(CheckStatus::Ok),
+        count(CheckStatus::Warning),
+        count(CheckStatus::Failure)
+    );
+    if count(CheckStatus::Failure) > 0 {
+        println!(
+            "    [-] Fix the failures before recording or include this output in a bug report"
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::{Duration, SystemTime};
+
+use clap::Clap;
+use tracing::{error, info};
+
+use crate::archive::collect_files;
+use crate::archive::layout::{Archive, TRASH_FOLDER_NAME};
+use crate::encoding::{convert_audio, ConvertOptions, OutputFormat, RemovalMode};
+use crate::lock::ArchiveLock;
+use crate::InsomniaProject;
+
+/// Recordings which were modified more recently than this might still be recorded.
+const MINIMUM_AGE: Duration = Duration::from_secs(60);
+
+/// Encode all recordings which were not encoded yet (e.g. after recording with `--no-encoding`).
+#[derive(Clap)]
+pub struct EncodeCommandOptions {
+    /// The folder with the recordings, the encoded files are stored next to them (the data
+    /// directory of the project is used if none is specified).
+    #[clap(index = 1)]
+    input_folder: Option<String>,
+
+    /// The number of recordings which are encoded at the same time (the configured number of
+    /// encoding workers is used if none is specified).
+    #[clap(long)]
+    jobs: Option<usize>,
+
+    /// Wait until the subcommand which currently modifies the data directory (e.g. a running
+    /// recorder) finished instead of failing.
+    #[clap(long)]
+    wait: bool,
+}
+
+/// Get all recordings in the input folder (and its subfolders) which do not have an encoded file in
+/// the corresponding folder of the output folder. The recordings are returned together with the
+/// folder their encoded file is stored in. Recordings in the trash folder are ignored.
+fn get_unencoded_recordings(
+    input_folder: &Path,
+    output_folder: &Path,
+    trash_folder: &Path,
+    output_format: OutputFormat,
+) -> std::io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = vec![];
+    collect_files(input_folder, &mut files)?;
+
+    let mut recordings = vec![];
+    for path in files {
+        if path.extension().and_then(|extension| extension.to_str()) != Some("wav")
+            || path.starts_with(trash_folder)
+        {
+            continue;
+        }
+        let file_name = match path.file_stem().and_then(|file_stem| file_stem.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+        let subfolder = path
+            .parent()
+            .and_then(|folder| folder.strip_prefix(input_folder).ok())
+            .unwrap_or_else(|| Path::new(""));
+        let encoded_folder = output_folder.join(subfolder);
+        let encoded_path =
+            encoded_folder.join(format!("{}.{}", file_name, output_format.get_extension()));
+        if encoded_path.exists() {
+            continue;
+        }
+
+        let is_recent = path
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age < MINIMUM_AGE);
+        if is_recent {
+            info!(
+                "Skipping {} since it might still be recorded",
+                path.display()
+            );
+            continue;
+        }
+        recordings.push((path, encoded_folder));
+    }
+    recordings.sort();
+    Ok(recordings)
+}
+
+pub fn run_command_encode(options: EncodeCommandOptions, config: InsomniaProject) {
+    if let Err(error) = config.encoding.validate() {
+        error!("Invalid encoding settings: {}. Terminating.", error);
+        return;
+    }
+
+    // the recordings of the data directory are stored in the raw folder of the archive, other
+    // folders get their own trash folder. the data directory is locked, so a running recorder
+    // does not encode the same recordings.
+    let mut _archive_lock = None;
+    let (input_folder, output_folder, trash_folder) = match &options.input_folder {
+        Some(input_folder) => (
+            PathBuf::from(input_folder),
+            PathBuf::from(input_folder),
+            Path::new(input_folder).join(TRASH_FOLDER_NAME),
+        ),
+        None => match Archive::open(Path::new(&config.data_directory)).and_then(|archive| {
+            ArchiveLock::acquire(&archive, "encode", options.wait)
+                .map(|archive_lock| (archive, archive_lock))
+        }) {
+            Ok((archive, archive_lock)) => {
+                _archive_lock = Some(archive_lock);
+                (
+                    archive.get_raw_folder(),
+                    archive.get_encoded_folder(),
+                    archive.get_trash_folder(),
+                )
+            }
+            Err(error) => {
+                error!(
+                    "Could not open the data directory. Terminating. The error was: {}",
+                    error
+                );
+                return;
+            }
+        },
+    };
+
+    let recordings = match get_unencoded_recordings(
+        &input_folder,
+        &output_folder,
+        &trash_folder,
+        config.output_format,
+    ) {
+        Ok(recordings) => recordings,
+        Err(error) => {
+            error!(
+                "Could not read the input folder. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    let total = recordings.len();
+    if total == 0 {
+        println!("[*] No recordings have to be encoded");
+        return;
+    }
+    let jobs = options.jobs.unwrap_or(config.encoding.workers).max(1);
+    println!(
+        "[*] Encoding {} recording(s) with {} job(s) to {}",
+        total, jobs, config.output_format
+    );
+
+    // every worker takes the next recording until all are encoded
+    let queue = Arc::new(Mutex::new(recordings.into_iter().collect::<VecDeque<_>>()));
+    let finished = Arc::new(AtomicUsize::new(0));
+    let failed = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..jobs.min(total))
+        .map(|_| {
+            let queue = queue.clone();
+            let finished = finished.clone();
+            let failed = failed.clone();
+            let config = config.clone();
+            let trash_folder =
+                (config.encoding.removal == RemovalMode::Trash).then(|| trash_folder.clone());
+            spawn(move || loop {
+                let (recording, encoded_folder) = match queue.lock().unwrap().pop_front() {
+                    Some(recording) => recording,
+                    None => return,
+                };
+                // the subfolders of the recordings are mirrored in the output folder
+                let result = create_dir_all(&encoded_folder)
+                    .map_err(|error| error.to_string())
+                    .and_then(|_| {
+                        let options = ConvertOptions {
+                            output_folder: encoded_folder,
+                            output_format: config.output_format,
+                            encoding: config.encoding.clone(),
+                            normalization: config.normalization.clone(),
+                            gain_in_db: config.normalization.get_gain(&recording),
+                            label: config.label.clone(),
+                            trash_folder: trash_folder.clone(),
+                        };
+                        convert_audio(&recording, &options).map_err(|error| error.to_string())
+                    });
+                let status = match result {
+                    Ok(_) => "encoded",
+                    Err(_) => {
+                        failed.fetch_add(1, Ordering::SeqCst);
+                        "failed"
+                    }
+                };
+                let done = finished.fetch_add(1, Ordering::SeqCst) + 1;
+                println!(
+                    "    [-] {}/{}\t{} ({})",
+                    done,
+                    total,
+                    recording.display(),
+                    status
+                );
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    let failed = failed.load(Ordering::SeqCst);
+    println!(
+        "[*] Encoded {} of {} recording(s), {} failed",
+        total - failed,
+        total,
+        failed
+    );
+}
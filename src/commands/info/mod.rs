@@ -0,0 +1,46 @@
+use clap::{crate_version, Clap};
+use serde_json::{Map, Value};
+use tracing::error;
+
+use crate::changelog::BEHAVIOR_CHANGES;
+use crate::schemas::get_schemas;
+
+/// Show the version of the tool and of the JSON formats it writes.
+#[derive(Clap)]
+pub struct InfoCommandOptions {
+    /// Print the JSON schemas of all formats instead (as a single JSON object by their names).
+    #[clap(long)]
+    schemas: bool,
+
+    /// Print the changes of the behavior which affect existing data directories instead.
+    #[clap(long)]
+    changelog: bool,
+}
+
+pub fn run_command_info(options: InfoCommandOptions) {
+    let schemas = get_schemas();
+    if options.schemas {
+        let documents: Map<String, Value> = schemas
+            .into_iter()
+            .map(|schema| (schema.name.to_string(), schema.document))
+            .collect();
+        match serde_json::to_string_pretty(&documents) {
+            Ok(json) => println!("{}", json),
+            Err(error) => error!("Could not print the schemas. The error was: {}", error),
+        }
+        return;
+    }
+    if options.changelog {
+        for change in BEHAVIOR_CHANGES {
+            let marker = if change.reinterprets_data { "!" } else { "-" };
+            println!("[{}] {}:\t{}", marker, change.version, change.summary);
+        }
+        return;
+    }
+
+    println!("[*] Version:\t\t\t{}", crate_version!());
+    println!("[*] JSON formats:");
+    for schema in schemas {
+        println!("    [-] {}:\t{}", schema.name, schema.version);
+    }
+}
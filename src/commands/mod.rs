@@ -1,3 +1,31 @@
+#[cfg(feature = "analysis")]
+pub mod analyze;
+#[cfg(feature = "analysis")]
 pub mod annotate;
+#[cfg(feature = "recorder")]
+pub mod calibrate;
 pub mod config;
+#[cfg(feature = "recorder")]
+pub mod ctl;
+#[cfg(feature = "analysis")]
+pub mod cuesheet;
+pub mod delete;
+pub mod doctor;
+pub mod encode;
+pub mod info;
+#[cfg(feature = "recorder")]
+pub mod monitor;
+pub mod prune;
+#[cfg(feature = "recorder")]
 pub mod record;
+#[cfg(feature = "analysis")]
+pub mod report;
+#[cfg(feature = "recorder")]
+pub mod run;
+pub mod stats;
+#[cfg(feature = "recorder")]
+pub mod systemd_unit;
+#[cfg(feature = "recorder")]
+pub mod test;
+pub mod update;
+pub mod upload;
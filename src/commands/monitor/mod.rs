@@ -0,0 +1,222 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::Clap;
+use tracing::{error, info};
+
+use crate::analysis::{get_levels, Levels};
+use crate::backend::stream_with_backend;
+use crate::shutdown::{install_signal_handlers, sleep_unless_shutdown};
+use crate::InsomniaProject;
+
+/// The lowest level which is shown by the meter.
+const METER_FLOOR_IN_DBFS: f32 = -60.0;
+
+/// The number of characters of the bar of the meter.
+const METER_WIDTH: usize = 40;
+
+/// The time between two redraws of the meter.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The time the highest peak is kept on the meter.
+const PEAK_HOLD: Duration = Duration::from_secs(2);
+
+/// Show a live level meter of the configured inputs without writing any files, to help with
+/// positioning the microphones and setting the gain before a night is recorded.
+#[derive(Clap)]
+pub struct MonitorCommandOptions {
+    /// The name of the input device which should be monitored (all inputs if none is specified).
+    #[clap(index = 1)]
+    input: Option<String>,
+
+    /// Stop after the supplied number of seconds instead of running until Ctrl-C is pressed.
+    #[clap(long)]
+    duration: Option<u64>,
+}
+
+/// The levels of a single channel as shown by the meter.
+struct ChannelMeter {
+    label: String,
+    levels: Option<Levels>,
+    held_peak_in_dbfs: f32,
+    held_since: Instant,
+    has_clipped: bool,
+}
+
+impl ChannelMeter {
+    fn new(label: String) -> ChannelMeter {
+        ChannelMeter {
+            label,
+            levels: None,
+            held_peak_in_dbfs: METER_FLOOR_IN_DBFS,
+            held_since: Instant::now(),
+            has_clipped: false,
+        }
+    }
+
+    fn update(&mut self, levels: Levels) {
+        if levels.peak_in_dbfs >= self.held_peak_in_dbfs || self.held_since.elapsed() >= PEAK_HOLD {
+            self.held_peak_in_dbfs = levels.peak_in_dbfs;
+            self.held_since = Instant::now();
+        }
+        self.has_clipped |= levels.is_clipping();
+        self.levels = Some(levels);
+    }
+
+    fn render(&self, input_name: &str) -> String {
+        let levels = match &self.levels {
+            Some(levels) => levels,
+            None => {
+                return format!(
+                    "{:<12} {:<8} waiting for audio data",
+                    input_name, self.label
+                )
+            }
+        };
+        format!(
+            "{:<12} {:<8} [{}] RMS {:>6.1} dBFS  peak {:>6.1} dBFS{}",
+            input_name,
+            self.label,
+            render_bar(levels.rms_in_dbfs, self.held_peak_in_dbfs),
+            levels.rms_in_dbfs,
+            self.held_peak_in_dbfs,
+            if self.has_clipped { "  CLIPPED" } else { "" }
+        )
+    }
+}
+
+/// Get the position of a level on the bar of the meter.
+fn get_meter_position(level_in_dbfs: f32) -> usize {
+    let fraction = (1.0 - level_in_dbfs / METER_FLOOR_IN_DBFS).clamp(0.0, 1.0);
+    (fraction * METER_WIDTH as f32).round() as usize
+}
+
+/// Draw the RMS level as a bar and mark the held peak on it.
+fn render_bar(rms_in_dbfs: f32, peak_in_dbfs: f32) -> String {
+    let rms_position = get_meter_position(rms_in_dbfs);
+    let peak_position = get_meter_position(peak_in_dbfs);
+    (1..=METER_WIDTH)
+        .map(|position| {
+            if position == peak_position {
+                '|'
+            } else if position <= rms_position {
+                '#'
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+pub fn run_command_monitor(options: MonitorCommandOptions, config: InsomniaProject) {
+    let mut input_names: Vec<&String> = match &options.input {
+        Some(input_name) if !config.input.contains_key(input_name) => {
+            error!("The input {} is not configured. Terminating.", input_name);
+            return;
+        }
+        Some(input_name) => vec![input_name],
+        None => config.input.keys().collect(),
+    };
+    input_names.sort();
+
+    install_signal_handlers();
+
+    // every input is captured by its own thread, which updates the meters of its channels
+    let is_stopped = Arc::new(AtomicBool::new(false));
+    let mut meters = vec![];
+    let mut capture_threads = vec![];
+    for input_name in input_names {
+        let input_device = config.input[input_name].clone();
+        let configured_backend = input_device.get_backend(config.backend);
+        let backend = match configured_backend.resolve() {
+            Some(backend) => backend,
+            None => {
+                error!(
+                    "The recording backend '{}' is not available in this build. Terminating.",
+                    configured_backend
+                );
+                return;
+            }
+        };
+        info!("Monitoring {} using the {} backend", input_name, backend);
+
+        let channels = if input_device.mono { 1 } else { 2 };
+        let channel_meters: Arc<Mutex<Vec<ChannelMeter>>> = Arc::new(Mutex::new(
+            (0..channels)
+                .map(|channel| ChannelMeter::new(input_device.get_channel_label(channel, channels)))
+                .collect(),
+        ));
+        meters.push((input_name.clone(), channel_meters.clone()));
+        let thread_input_name = input_name.clone();
+        let thread_is_stopped = is_stopped.clone();
+        capture_threads.push(thread::spawn(move || {
+            let is_captured = stream_with_backend(backend, &input_device, |samples| {
+                let mut channel_meters = channel_meters.lock().unwrap();
+                for (channel, channel_meter) in channel_meters.iter_mut().enumerate() {
+                    let channel_samples: Vec<f32> = samples
+                        .iter()
+                        .skip(channel)
+                        .step_by(channels)
+                        .copied()
+                        .collect();
+                    channel_meter.update(get_levels(&channel_samples));
+                }
+                !thread_is_stopped.load(Ordering::SeqCst)
+            });
+            if !is_captured {
+                error!("Could not capture the audio of {}", thread_input_name);
+            }
+        }));
+    }
+
+    // the meter is redrawn in place on a terminal, otherwise a reading is printed every second
+    let is_terminal = io::stdout().is_terminal();
+    let started_at = Instant::now();
+    let mut drawn_lines = 0;
+    let mut last_printed_at: Option<Instant> = None;
+    loop {
+        let lines: Vec<String> = meters
+            .iter()
+            .flat_map(|(input_name, channel_meters)| {
+                channel_meters
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|channel_meter| channel_meter.render(input_name))
+                    .collect::<Vec<String>>()
+            })
+            .collect();
+        let mut stdout = io::stdout();
+        if is_terminal {
+            if drawn_lines > 0 {
+                let _ = write!(stdout, "\x1b[{}A", drawn_lines);
+            }
+            for line in &lines {
+                let _ = writeln!(stdout, "\x1b[2K{}", line);
+            }
+            drawn_lines = lines.len();
+        } else if last_printed_at.map_or(true, |printed_at| printed_at.elapsed().as_secs() >= 1) {
+            for line in &lines {
+                let _ = writeln!(stdout, "{}", line);
+            }
+            last_printed_at = Some(Instant::now());
+        }
+        let _ = stdout.flush();
+
+        let is_expired = options
+            .duration
+            .is_some_and(|duration| started_at.elapsed().as_secs() >= duration);
+        let are_captures_stopped = capture_threads.iter().all(|thread| thread.is_finished());
+        if is_expired || are_captures_stopped || !sleep_unless_shutdown(REDRAW_INTERVAL) {
+            break;
+        }
+    }
+
+    is_stopped.store(true, Ordering::SeqCst);
+    for capture_thread in capture_threads {
+        let _ = capture_thread.join();
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use clap::Clap;
+use tracing::error;
+
+use crate::archive::layout::Archive;
+use crate::lock::ArchiveLock;
+use crate::retention::prune_archive;
+use crate::InsomniaProject;
+
+/// Remove the oldest encoded recordings which exceed the retention policy of the project.
+#[derive(Clap)]
+pub struct PruneCommandOptions {
+    /// Only list the files which would be removed.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// The number of days the encoded recordings are kept (overrides the project).
+    #[clap(long)]
+    keep_days: Option<u32>,
+
+    /// The size (in GB) the encoded recordings may use (overrides the project).
+    #[clap(long)]
+    keep_gigabytes: Option<f64>,
+
+    /// Wait until the subcommand which currently modifies the data directory (e.g. a running
+    /// recorder) finished instead of failing.
+    #[clap(long)]
+    wait: bool,
+}
+
+pub fn run_command_prune(options: PruneCommandOptions, config: InsomniaProject) {
+    let mut retention = config.retention;
+    if options.keep_days.is_some() {
+        retention.keep_days = options.keep_days;
+    }
+    if options.keep_gigabytes.is_some() {
+        retention.keep_gigabytes = options.keep_gigabytes;
+    }
+    if !retention.is_enabled() {
+        error!("No retention policy is configured, please set keep_days or keep_gigabytes. Terminating.");
+        return;
+    }
+
+    let archive = match Archive::open(Path::new(&config.data_directory)) {
+        Ok(archive) => archive,
+        Err(error) => {
+            error!(
+                "Could not open the data directory. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    let _archive_lock = if options.dry_run {
+        None
+    } else {
+        match ArchiveLock::acquire(&archive, "prune", options.wait) {
+            Ok(archive_lock) => Some(archive_lock),
+            Err(error) => {
+                error!(
+                    "Could not lock the data directory. Terminating. The error was: {}",
+                    error
+                );
+                return;
+            }
+        }
+    };
+    let summary = match prune_archive(&archive, &retention, options.dry_run) {
+        Ok(summary) => summary,
+        Err(error) => {
+            error!(
+                "Could not prune the data directory. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+
+    for file in &summary.files {
+        println!("    [-] {}", file.display());
+    }
+    println!(
+        "[*] {} {} file(s) ({} MB)",
+        if options.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        },
+        summary.files.len(),
+        summary.size_in_bytes / 1024 / 1024
+    );
+}
@@ -1,50 +1,798 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::read_to_string;
+use std::io;
+use std::mem::replace;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::thread::{sleep, spawn, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use chrono::{Local, Timelike};
+use chrono::{DateTime, Local, NaiveDateTime, Timelike};
 use clap::Clap;
-use log::{error, info};
+use tracing::{debug, error, info, info_span, warn, Span};
 
-use crate::{get_available_cards, is_recording_tool_available, record_audio, InsomniaProject};
+use crate::analysis::measure_levels;
+use crate::archive::layout::Archive;
+use crate::backend::pulse::get_pulse_recording_tool;
+use crate::backend::{record_audio_with_backend, record_events_with_backend, RecordingBackend};
+use crate::backup::back_up_metadata;
+use crate::clock;
+use crate::clock::{initialize_clock, ClockJumpDetector, TimestampSource};
+use crate::control::{is_paused, ControlServer};
+use crate::daemon::{LOG_FILE_NAME, PID_FILE_NAME};
+#[cfg(feature = "dashboard")]
+use crate::dashboard::Dashboard;
+use crate::defaults::SegmentDuration;
+use crate::detection::{detect_in_recording, CoughDetector};
+use crate::encoding::queue::EncodingQueue;
+use crate::encoding::{remove_recording, ConvertOptions, RemovalMode};
+use crate::hooks::{CommandTemplate, FinishedSegment};
+use crate::hotplug::HotplugWatch;
+use crate::interest::score_session_segment;
+use crate::latency::load_calibration;
+use crate::lock::ArchiveLock;
+use crate::manifest::{ManifestWriter, SegmentManifest, MANIFEST_TIMESTAMP_FORMAT};
+use crate::mixer::{set_capture_volume, CaptureVolume};
+use crate::naming::template::set_filename_template;
+use crate::naming::{apply_event_naming, count_events_in_recording, EventNamingMode};
+use crate::power::CpuTimes;
+use crate::priority::{is_running, Subsystem};
+use crate::recovery::{recover_recordings, Recovery};
+use crate::retention::{prune_archive, RetentionConfiguration};
+use crate::retry::{CaptureBackoff, CaptureFailure};
+use crate::scheduler::{CronExpression, RecordingWindow, Scheduler, StartAlignment};
+use crate::shutdown::{
+    clear_split_request, install_signal_handlers, is_shutdown_requested, is_split_requested,
+    sleep_unless_recording_stopped, sleep_unless_shutdown,
+};
+use crate::silence::{
+    find_silence_trim, measure_silence, MicrophoneActivity, MicrophoneWatch, SilenceConfiguration,
+    SilenceMode,
+};
+use crate::status::{InputState, StatusBoard, STATUS_FILE_NAME};
+use crate::storage::{
+    get_free_space_in_bytes, purge_oldest_files, LowSpaceAction, QuotaAction, QuotaTracker,
+    StorageConfiguration,
+};
+use crate::sync::{synchronize_start, SyncInformation};
+use crate::systemd::{extend_watchdog, notify, start_watchdog};
+use crate::tee::TeeServer;
+use crate::timezone::{set_timestamp_timezone, to_timestamp_timezone};
+use crate::update::UpdateChecker;
+use crate::wave::{
+    read_format, trim_recording, write_broadcast_extension, BroadcastExtension, SampleFormat,
+};
+use crate::{
+    convert_audio, create_preview_file, get_available_devices, is_mono_supported,
+    is_recording_tool_available, probe_channels, resolve_pcm_name, DeviceInfo, InsomniaProject,
+    RecordingDeviceConfiguration,
+};
 
 /// Record audio files with a specific timing for later analysis (will be produce a lot of data).
-#[derive(Clap)]
+#[derive(Clap, Default)]
 pub struct RecordCommandOptions {
-    /// Select the number of minutes to record in a single file.
-    #[clap(long, default_value = "1")]
-    duration: u8,
+    /// Select the duration of a single file, either in minutes or with a unit like `30s` or `90s`
+    /// (the default duration of the project is used if none is specified).
+    #[clap(long)]
+    duration: Option<SegmentDuration>,
 
     /// Disable the encoding of the recorded files to mp3 using ffmpeg.
     #[clap(long)]
     no_encoding: bool,
+
+    /// Keep the recorded files after they were encoded.
+    #[clap(long)]
+    keep_wav: bool,
+
+    /// Record from all audio devices which are not blacklisted instead of the configured inputs.
+    #[clap(long)]
+    all_devices: bool,
+
+    /// A label for this session (e.g. 'with-new-pillow') which overrides the configured one.
+    #[clap(long)]
+    label: Option<String>,
+
+    /// Record the time until the first boundary of the start alignment (e.g. the next full minute)
+    /// as a shorter (partial) segment instead of discarding it.
+    #[clap(long)]
+    grace_capture: bool,
+
+    /// Stop after recording this number of segments (overrides the stop section of the project).
+    #[clap(long)]
+    max_segments: Option<u32>,
+
+    /// Stop after this time, e.g. `8h` or `7h 30m` (overrides the stop section of the project).
+    #[clap(long)]
+    total_duration: Option<String>,
+
+    /// Stop at this time of the day, e.g. `07:00` (overrides the stop section of the project).
+    #[clap(long)]
+    until: Option<String>,
+
+    /// Detach from the terminal and record in the background, the log messages are written to a
+    /// file.
+    #[clap(long)]
+    daemon: bool,
+
+    /// The file the process id of the recorder in the background is stored in (the state folder of
+    /// the data directory is used if none is specified).
+    #[clap(long)]
+    pid_file: Option<String>,
+
+    /// The file the recorder in the background logs to (the state folder of the data directory is
+    /// used if none is specified).
+    #[clap(long)]
+    log_file: Option<String>,
+
+    /// Wait until the subcommand which currently modifies the data directory (e.g. another
+    /// recorder or the prune command) finished instead of failing.
+    #[clap(long)]
+    wait: bool,
+
+    /// Show a dashboard with the state of the inputs instead of the scrolling log (only if the
+    /// output is a terminal).
+    #[cfg(feature = "dashboard")]
+    #[clap(long, conflicts_with = "daemon")]
+    dashboard: bool,
 }
 
+impl RecordCommandOptions {
+    /// Check if the recorder should run in the background.
+    pub fn runs_as_daemon(&self) -> bool {
+        self.daemon
+    }
+
+    /// Check if the dashboard replaces the log, the log messages have to be kept for it then.
+    #[cfg(feature = "dashboard")]
+    pub fn shows_dashboard(&self) -> bool {
+        self.dashboard && io::IsTerminal::is_terminal(&io::stdout())
+    }
+
+    /// Get the PID file and the log file of the recorder in the background.
+    pub fn get_daemon_files(&self, config: &InsomniaProject) -> io::Result<(PathBuf, PathBuf)> {
+        let state_folder = match (&self.pid_file, &self.log_file) {
+            (Some(_), Some(_)) => PathBuf::new(),
+            _ => Archive::open(Path::new(&config.data_directory))?.get_state_folder(),
+        };
+        let pid_file = match &self.pid_file {
+            Some(pid_file) => PathBuf::from(pid_file),
+            None => state_folder.join(PID_FILE_NAME),
+        };
+        let log_file = match &self.log_file {
+            Some(log_file) => PathBuf::from(log_file),
+            None => state_folder.join(LOG_FILE_NAME),
+        };
+        Ok((pid_file, log_file))
+    }
+}
+
+/// The shortest partial segment which is recorded until the first full minute.
+const MINIMUM_GRACE_CAPTURE_IN_SECONDS: u32 = 5;
+
+/// The shortest segment which is recorded until the next wall-clock boundary, a shorter time is
+/// added to the following segment instead (at most half of the recording duration is added to
+/// segments shorter than that).
+const MINIMUM_ALIGNED_SEGMENT_IN_MS: i64 = 10_000;
+
+/// The shortest duration of a segment which can be selected.
+const MINIMUM_SEGMENT_DURATION_IN_SECONDS: u32 = 5;
+
+/// The time the recording loop may take longer than a segment before the watchdog of systemd
+/// considers the recorder to be hung.
+const WATCHDOG_GRACE_PERIOD: Duration = Duration::from_secs(120);
+
+/// The interval in which the retention policy is applied while recording.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// The interval in which a paused recording checks if it was resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 fn wait_until_full_minute() {
     let last_timestamp = Local::now().naive_local();
-    sleep(Duration::from_secs(u64::from(60 - last_timestamp.second())));
+    sleep_unless_shutdown(Duration::from_secs(u64::from(60 - last_timestamp.second())));
+}
+
+/// Wait until the next boundary of the start alignment of the project (e.g. the next full hour),
+/// the recording starts immediately without an alignment.
+fn wait_for_start_alignment(alignment: StartAlignment) {
+    let now = Local::now().naive_local();
+    let remaining = alignment
+        .get_time_until_boundary(now)
+        .to_std()
+        .unwrap_or_default();
+    if remaining.is_zero() {
+        info!("The current time is {}. Starting immediately.", now);
+        return;
+    }
+    info!(
+        "The current time is {}. We are waiting for the next {} boundary to start.",
+        now, alignment
+    );
+    extend_watchdog(remaining + WATCHDOG_GRACE_PERIOD);
+    sleep_unless_shutdown(remaining);
+}
+
+/// Get the duration (in seconds) of a segment which starts at the supplied time, so it ends on the
+/// next wall-clock boundary of the recording duration (the multiples of the duration since
+/// midnight, e.g. every full quarter of an hour). Since every segment ends on a boundary, the time
+/// between two segments does not add up and the start of the segments stays aligned with the
+/// clock over the whole night.
+fn get_aligned_segment_duration(now: NaiveDateTime, recording_duration: u32) -> u32 {
+    let recording_duration_in_ms = i64::from(recording_duration.max(1)) * 1000;
+    let elapsed_in_ms =
+        i64::from(now.num_seconds_from_midnight()) * 1000 + i64::from(now.nanosecond() / 1_000_000);
+    let mut boundary_in_ms =
+        (elapsed_in_ms / recording_duration_in_ms + 1) * recording_duration_in_ms;
+    if boundary_in_ms - elapsed_in_ms
+        < MINIMUM_ALIGNED_SEGMENT_IN_MS.min(recording_duration_in_ms / 2)
+    {
+        boundary_in_ms += recording_duration_in_ms;
+    }
+    ((boundary_in_ms - elapsed_in_ms + 500) / 1000) as u32
+}
+
+/// Wait until the recording is resumed on the control socket (or a shutdown is requested), the
+/// maintenance tasks are run in the meantime.
+fn wait_while_paused(scheduler: &mut Scheduler) {
+    info!("The recording is paused");
+    notify("STATUS=Paused");
+    while is_paused() {
+        extend_watchdog(PAUSE_POLL_INTERVAL + WATCHDOG_GRACE_PERIOD);
+        if !sleep_unless_shutdown(PAUSE_POLL_INTERVAL) {
+            return;
+        }
+        if !scheduler.is_empty() {
+            scheduler.run_due_tasks(clock::now().naive_local());
+        }
+    }
+}
+
+/// Wait until the recording window opens (if it is not open already). The maintenance tasks are
+/// still run while waiting.
+fn wait_for_recording_window(window: &RecordingWindow, scheduler: &mut Scheduler) {
+    let now = clock::now().naive_local();
+    let start = window.get_next_start(now);
+    if start <= now {
+        return;
+    }
+    info!("Waiting for the recording window which opens at {}", start);
+    notify(&format!(
+        "STATUS=Waiting for the recording window at {}",
+        start
+    ));
+    loop {
+        extend_watchdog(Duration::from_secs(60) + WATCHDOG_GRACE_PERIOD);
+        let now = clock::now().naive_local();
+        if start <= now {
+            return;
+        }
+        let remaining = (start - now).to_std().unwrap_or_default();
+        if !sleep_unless_shutdown(remaining.min(Duration::from_secs(60))) {
+            return;
+        }
+        if !scheduler.is_empty() {
+            scheduler.run_due_tasks(clock::now().naive_local());
+        }
+    }
+}
+
+/// Get the duration (in seconds) of the partial segment which is recorded until the next boundary
+/// of the start alignment, `None` if the recorder should wait for the boundary instead.
+fn get_grace_duration(options: &RecordCommandOptions, config: &InsomniaProject) -> Option<u32> {
+    if !options.grace_capture {
+        return None;
+    }
+    if config.sync.is_some() {
+        warn!("The time until the first boundary is not recorded since the start is synchronized");
+        return None;
+    }
+    if config.start_alignment == StartAlignment::None {
+        return None;
+    }
+    let remaining = config
+        .start_alignment
+        .get_time_until_boundary(Local::now().naive_local());
+    let remaining_in_seconds = u32::try_from(remaining.num_seconds()).ok()?;
+    if remaining_in_seconds < MINIMUM_GRACE_CAPTURE_IN_SECONDS {
+        return None;
+    }
+    Some(remaining_in_seconds)
+}
+
+/// Wait until the next boundary of the start alignment or the time agreed on with the other
+/// machine.
+fn synchronize_or_wait(config: &InsomniaProject) -> Option<SyncInformation> {
+    // the connection attempts and the wait for the agreed start are each bounded by the timeout
+    if let Some(sync_configuration) = &config.sync {
+        extend_watchdog(
+            Duration::from_secs(2 * sync_configuration.timeout_in_seconds + 60)
+                + WATCHDOG_GRACE_PERIOD,
+        );
+    }
+    match &config.sync {
+        Some(sync_configuration) => match synchronize_start(sync_configuration) {
+            Ok(sync_information) => Some(sync_information),
+            Err(error) => {
+                error!(
+                    "The start could not be synchronized as {}, starting without it. The error was: {}",
+                    sync_configuration.role, error
+                );
+                wait_for_start_alignment(config.start_alignment);
+                None
+            }
+        },
+        None => {
+            wait_for_start_alignment(config.start_alignment);
+            None
+        }
+    }
+}
+
+/// Check the free space of the data directory before a segment is started. The oldest files are
+/// purged (if configured) and the action for the remaining lack of space is returned, `None` if
+/// enough space is free (or it could not be determined).
+fn check_free_space(archive: &Archive, storage: &StorageConfiguration) -> Option<LowSpaceAction> {
+    let minimum_free_space = storage.get_minimum_free_space_in_bytes();
+    let free_space = get_free_space_in_bytes(archive.get_root())?;
+    if free_space >= minimum_free_space {
+        return None;
+    }
+    error!(
+        "Only {} MB are free in the data directory, but {} MB are required",
+        free_space / 1024 / 1024,
+        storage.minimum_free_space_in_mb
+    );
+    if storage.action != LowSpaceAction::Purge {
+        return Some(storage.action);
+    }
+
+    match purge_oldest_files(archive, minimum_free_space) {
+        Ok(removed_files) => warn!("Removed {} file(s) to free space", removed_files),
+        Err(error) => error!("Could not free space. The error was: {}", error),
+    }
+    match get_free_space_in_bytes(archive.get_root()) {
+        Some(free_space) if free_space >= minimum_free_space => None,
+        _ => {
+            error!("Not enough space could be freed by removing old files");
+            Some(LowSpaceAction::Stop)
+        }
+    }
+}
+
+/// Check the size of the recordings against the storage quota before a segment is started. The
+/// oldest segments are removed (if configured) and `true` is returned if the recordings still
+/// exceed the quota, so the recording has to stop.
+fn check_quota(
+    archive: &Archive,
+    quota_tracker: &mut QuotaTracker,
+    quota_action: QuotaAction,
+    include_unencoded: bool,
+) -> bool {
+    match quota_tracker.is_exceeded(archive) {
+        Ok(false) => return false,
+        Ok(true) => {}
+        Err(error) => {
+            error!(
+                "Could not determine the size of the recordings. The error was: {}",
+                error
+            );
+            return false;
+        }
+    }
+    let message = format!(
+        "The recordings use {} MB and exceed the storage quota of {} MB",
+        quota_tracker.get_used_in_bytes() / 1024 / 1024,
+        quota_tracker.get_limit_in_bytes() / 1024 / 1024
+    );
+    warn!("{}", message);
+    notify(&format!("STATUS={}", message));
+    if quota_action != QuotaAction::Rotate {
+        return true;
+    }
+
+    match quota_tracker.rotate(archive, include_unencoded) {
+        Ok(removed_segments) => warn!(
+            "Removed the {} oldest segment(s) to stay within the storage quota",
+            removed_segments
+        ),
+        Err(error) => error!(
+            "Could not remove the oldest segments. The error was: {}",
+            error
+        ),
+    }
+    if quota_tracker.get_used_in_bytes() > quota_tracker.get_limit_in_bytes() {
+        error!("Not enough segments could be removed to stay within the storage quota");
+        return true;
+    }
+    false
+}
+
+/// Remove the encoded recordings which exceed the retention policy.
+fn apply_retention(archive: &Archive, retention: &RetentionConfiguration) {
+    match prune_archive(archive, retention, false) {
+        Ok(summary) if !summary.files.is_empty() => info!(
+            "Removed {} file(s) ({} MB) which exceeded the retention policy",
+            summary.files.len(),
+            summary.size_in_bytes / 1024 / 1024
+        ),
+        Ok(_) => debug!("No file exceeded the retention policy"),
+        Err(error) => error!(
+            "Could not apply the retention policy. The error was: {}",
+            error
+        ),
+    }
+}
+
+/// Mirror the metadata of the archive (like the manifest of the finished session) to the backup
+/// destination, so it survives the loss of the data directory.
+fn back_up_session(archive: &Archive, config: &InsomniaProject, destination: &str) {
+    let backup = match &config.backup {
+        Some(backup) => backup,
+        None => return,
+    };
+    match back_up_metadata(archive, backup, destination) {
+        Ok(files) => info!("Backed up {} metadata file(s) to {}", files, destination),
+        Err(error) => error!(
+            "Could not back up the metadata to {}. The error was: {}",
+            destination, error
+        ),
+    }
+}
+
+/// Create the manifest of a new session with the synchronization, the label and the latencies of
+/// the last calibration. The span of the session is the parent of the spans of its segments.
+fn start_session(
+    archive: &Archive,
+    config: &InsomniaProject,
+    sync_information: Option<SyncInformation>,
+) -> (Arc<ManifestWriter>, Span) {
+    let manifest_writer = Arc::new(ManifestWriter::new(
+        &archive.get_state_folder().to_string_lossy(),
+        clock::now(),
+    ));
+    let session_span = info_span!(
+        parent: None,
+        "session",
+        manifest = %manifest_writer.get_path().display(),
+        label = config.label.as_deref().unwrap_or_default()
+    );
+    info!(
+        "Writing the session manifest to {}",
+        manifest_writer.get_path().display()
+    );
+    if sync_information.is_some() {
+        manifest_writer.update_session(|session| session.sync = sync_information);
+    }
+    if let Some(label) = &config.label {
+        info!("Labeling the session as {}", label);
+        manifest_writer.update_session(|session| session.label = Some(label.clone()));
+    }
+
+    // the latencies of the last calibration allow aligning the recordings with the playback
+    match load_calibration(&archive.get_state_folder()) {
+        Ok(calibration) if !calibration.is_empty() => {
+            manifest_writer.update_session(|session| {
+                session.latencies_in_ms = calibration
+                    .into_iter()
+                    .map(|(input_name, measurement)| (input_name, measurement.latency_in_ms))
+                    .collect()
+            });
+        }
+        Ok(_) => {}
+        Err(error) => warn!(
+            "Could not read the latency calibration. The error was: {}",
+            error
+        ),
+    }
+    (manifest_writer, session_span)
+}
+
+/// Cut off the silence at the start and the end of a recording and store the removed durations
+/// in the manifest of the session, so the start of the remaining audio can be reconstructed.
+fn trim_silence(
+    recording: &Path,
+    silence: &SilenceConfiguration,
+    manifest_writer: &ManifestWriter,
+) {
+    let trim = match find_silence_trim(recording, silence) {
+        Some(trim) => trim,
+        None => return,
+    };
+    if let Err(error) = trim_recording(recording, trim.leading_frames, trim.trailing_frames) {
+        error!(
+            "Could not trim the silence of {}. The error was: {}",
+            recording.display(),
+            error
+        );
+        return;
+    }
+    info!(
+        "Trimmed {:.1} seconds of silence at the start and {:.1} seconds at the end of {}",
+        trim.get_leading_in_seconds(),
+        trim.get_trailing_in_seconds(),
+        recording.display()
+    );
+    manifest_writer.update_segment(&get_file_name(&recording.to_string_lossy()), |segment| {
+        segment.leading_trim_in_seconds =
+            Some(trim.get_leading_in_seconds()).filter(|seconds| *seconds > 0.0);
+        segment.trailing_trim_in_seconds =
+            Some(trim.get_trailing_in_seconds()).filter(|seconds| *seconds > 0.0);
+    });
+}
+
+/// Warn about an input whose microphone probably fell off (and tell when it recovered). The
+/// warning is shown on the dashboard, as status of the systemd service and passed to the hook.
+fn report_microphone_activity(
+    activity: MicrophoneActivity,
+    input_name: &str,
+    recording: &Path,
+    microphone_hook: Option<&CommandTemplate>,
+    hook_timeout: Duration,
+    status_board: &StatusBoard,
+) {
+    match activity {
+        MicrophoneActivity::Unchanged => {}
+        MicrophoneActivity::Detached {
+            silent_since,
+            silent_for_in_seconds,
+        } => {
+            let message = format!(
+                "{} is near-silent since {} while another input still records sound, its \
+                 microphone might have fallen off or be covered",
+                input_name,
+                silent_since.format("%H:%M")
+            );
+            warn!("{}", message);
+            notify(&format!("STATUS={}", message));
+            status_board.update_input(input_name, |input_status| {
+                input_status.last_error = Some(format!(
+                    "near-silent since {}",
+                    silent_since.format("%H:%M")
+                ))
+            });
+            if let Some(microphone_hook) = microphone_hook {
+                let silent_segment = FinishedSegment {
+                    path: recording,
+                    device: input_name,
+                    started_at: silent_since,
+                    duration_in_seconds: silent_for_in_seconds,
+                };
+                if let Err(error) = microphone_hook.run(&silent_segment, hook_timeout) {
+                    warn!(
+                        "The command for the detached microphone of {} failed: {}",
+                        input_name, error
+                    );
+                }
+            }
+        }
+        MicrophoneActivity::Recovered => {
+            info!("{} records sound again", input_name);
+            notify("STATUS=Recording");
+            status_board.update_input(input_name, |input_status| input_status.last_error = None);
+        }
+    }
+}
+
+/// Alert that an input is given up after its recording failed too often. The alert is shown on the
+/// dashboard, as status of the systemd service and passed to the hook.
+fn report_input_failure(
+    input_name: &str,
+    failures: u32,
+    failing_since: DateTime<Local>,
+    raw_folder: &Path,
+    input_failed_hook: Option<&CommandTemplate>,
+    hook_timeout: Duration,
+    status_board: &StatusBoard,
+) {
+    let message = format!(
+        "Giving up {} since its recording failed {} times in a row (since {})",
+        input_name,
+        failures,
+        failing_since.format("%H:%M")
+    );
+    error!("{}", message);
+    notify(&format!("STATUS={}", message));
+    status_board.update_input(input_name, |input_status| {
+        input_status.state = InputState::GaveUp;
+        input_status.last_error = Some(format!(
+            "gave up after {} failures at {}",
+            failures,
+            Local::now().format("%H:%M")
+        ));
+    });
+    if let Some(input_failed_hook) = input_failed_hook {
+        let failed_input = FinishedSegment {
+            path: raw_folder,
+            device: input_name,
+            started_at: failing_since,
+            duration_in_seconds: (Local::now() - failing_since).num_seconds().max(0) as u32,
+        };
+        if let Err(error) = input_failed_hook.run(&failed_input, hook_timeout) {
+            warn!(
+                "The command for the failed input {} failed: {}",
+                input_name, error
+            );
+        }
+    }
 }
 
 fn is_valid_device_selection(
-    available_audio_devices: &HashMap<u8, (u8, u8)>,
+    available_audio_devices: &[DeviceInfo],
     audio_card: u8,
     audio_device: u8,
 ) -> bool {
-    let current_audio_card_device_tuple = available_audio_devices.get(&audio_card);
-    if current_audio_card_device_tuple.is_some() {
-        let (_, cur_device) = current_audio_card_device_tuple.unwrap();
-        if *cur_device == audio_device {
-            return true;
+    available_audio_devices.iter().any(|available_device| {
+        available_device.card == audio_card && available_device.device == audio_device
+    })
+}
+
+fn get_file_name(path: &str) -> String {
+    match Path::new(path).file_name() {
+        Some(file_name) => file_name.to_string_lossy().to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// Get the name of the host which is stored as the originator of the recordings.
+fn get_host_name() -> String {
+    read_to_string("/proc/sys/kernel/hostname")
+        .map(|host_name| host_name.trim().to_string())
+        .unwrap_or_else(|_| "localhost".to_string())
+}
+
+/// Add the broadcast extension with the start time and the origin to a recording, so other audio
+/// tools can align the recordings.
+fn add_broadcast_extension(
+    file_prefix: &str,
+    input_name: &str,
+    device: &RecordingDeviceConfiguration,
+    started_at: NaiveDateTime,
+) {
+    let path = format!("{}.wav", file_prefix);
+    let result = read_format(Path::new(&path))
+        .map_err(|error| error.to_string())
+        .and_then(|format| {
+            let extension = BroadcastExtension::new(
+                &format!(
+                    "{} (card {}, device {})",
+                    input_name, device.card, device.device
+                ),
+                &get_host_name(),
+                input_name,
+                started_at,
+                format.samples_per_second,
+            );
+            write_broadcast_extension(Path::new(&path), &extension)
+                .map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        warn!(
+            "Could not add the broadcast extension to {}. The error was: {}",
+            path, error
+        );
+    }
+}
+
+/// Encode the recordings an interrupted session left behind with the workers of the queue. The
+/// recordings which do not fit into the queue are kept for the next session.
+fn queue_recovered_recordings(
+    recovery: Recovery,
+    encoding_queue: &EncodingQueue,
+    archive: &Arc<Archive>,
+    config: &InsomniaProject,
+) {
+    let label = recovery.manifest_writer.get_label();
+    let manifest_writer = Arc::new(recovery.manifest_writer);
+    let mut skipped_recordings = 0;
+    for recording in recovery.recordings {
+        let output_format = recording
+            .input
+            .as_ref()
+            .and_then(|input_name| config.input.get(input_name))
+            .map(|input_device| input_device.get_output_format(config.output_format))
+            .unwrap_or(config.output_format);
+        let recording_path = PathBuf::from(format!("{}.wav", recording.file_prefix));
+        let file_name = get_file_name(&recording_path.to_string_lossy());
+        let manifest_writer = manifest_writer.clone();
+        let archive = archive.clone();
+        let encoding = config.encoding.clone();
+        let normalization = config.normalization.clone();
+        let label = label.clone();
+        let trash_folder =
+            (config.encoding.removal == RemovalMode::Trash).then(|| archive.get_trash_folder());
+        let queued_result = encoding_queue.try_submit(move || {
+            let encoded_folder = archive
+                .get_corresponding_folder(&recording_path, &archive.get_encoded_folder())
+                .unwrap_or_else(|_| archive.get_encoded_folder());
+            let encoding_start = Instant::now();
+            let gain_in_db = normalization.get_gain(&recording_path);
+            let result = convert_audio(
+                &recording_path,
+                &ConvertOptions {
+                    output_folder: encoded_folder,
+                    output_format,
+                    encoding,
+                    normalization,
+                    gain_in_db,
+                    label,
+                    trash_folder,
+                },
+            );
+            let applied_gain = result.ok().and(gain_in_db);
+            let encoding_time = encoding_start.elapsed().as_secs_f32();
+            manifest_writer.update_segment(&file_name, |segment| {
+                segment.applied_gain_in_db = applied_gain;
+                segment.encoding_time_in_seconds = Some(encoding_time);
+            });
+        });
+        if queued_result.is_err() {
+            skipped_recordings += 1;
         }
     }
-    false
+    if skipped_recordings > 0 {
+        warn!(
+            "{} recording(s) of an interrupted session did not fit into the encoding queue, they are encoded when the next session starts",
+            skipped_recordings
+        );
+    }
 }
 
 pub fn run_command_record(options: RecordCommandOptions, config: InsomniaProject) {
-    // before we continue we should ensure that the required recording tool is available
-    if !is_recording_tool_available() {
-        error!("The arecord tool seems not to be available on your computer. Terminating.");
-        return;
+    run_recorder(options, config, StatusBoard::new());
+}
+
+/// Record until a shutdown is requested. The state of the recorder is published on the status
+/// board, so others (like the dashboard or the metrics of the `run` command) can show it.
+pub fn run_recorder(
+    options: RecordCommandOptions,
+    mut config: InsomniaProject,
+    status_board: StatusBoard,
+) {
+    // Ctrl-C (or a SIGTERM) lets the current segment finish and encode before the recorder exits
+    install_signal_handlers();
+
+    // the watchdog of systemd (if it is enabled) is pinged as long as the recording makes progress,
+    // the device checks before the first segment may take a while
+    start_watchdog(Duration::from_secs(600));
+
+    // the devices which should never be recorded from are used for validating the selection
+    let device_blacklist = match config.get_device_blacklist() {
+        Ok(device_blacklist) => device_blacklist,
+        Err(error) => {
+            error!(
+                "The device blacklist contains an invalid pattern. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+
+    // replace the configured inputs by all discovered devices (if requested)
+    if options.all_devices {
+        let available_audio_devices = get_available_devices().unwrap_or_default();
+        config.input = available_audio_devices
+            .iter()
+            .filter(|device_info| {
+                let is_blacklisted = device_info.is_blacklisted(&device_blacklist);
+                if is_blacklisted {
+                    info!(
+                        "Skipping the blacklisted device {} (card {}, device {})",
+                        device_info.description, device_info.card, device_info.device
+                    );
+                }
+                !is_blacklisted
+            })
+            .map(|device_info| {
+                let mut input_device =
+                    config.new_input_device(device_info.card, device_info.device);
+                input_device.backend = Some(RecordingBackend::Arecord);
+                (
+                    format!("{}_{}", device_info.card_name, device_info.device),
+                    input_device,
+                )
+            })
+            .collect();
     }
 
     // ensure that at least one input device is configured
@@ -53,39 +801,531 @@ pub fn run_command_record(options: RecordCommandOptions, config: InsomniaProject
         return;
     }
 
-    // get all audio devices of the computer
-    let available_audio_devices = get_available_cards()
-        .map_err(|_error| panic!("Could not find any suitable audio devices. Terminating."))
-        .unwrap();
+    // the recordings are named after the inputs (the environment of the run command might have
+    // changed the template)
+    for (input_name, input_device) in config.input.iter_mut() {
+        input_device.name = Some(input_name.clone());
+    }
+    set_filename_template(config.filename_template.clone());
+    set_timestamp_timezone(config.timestamp_timezone.clone());
+
+    // determine which backend should be used for recording the audio files of each device
+    let mut backends = HashMap::new();
+    for (input_name, input_device) in &config.input {
+        let configured_backend = input_device.get_backend(config.backend);
+        match configured_backend.resolve() {
+            Some(backend) => {
+                info!("Using the {} backend for recording {}", backend, input_name);
+                backends.insert(input_name.clone(), backend);
+            }
+            None => {
+                error!(
+                    "The recording backend '{}' is not available in this build. Terminating.",
+                    configured_backend
+                );
+                return;
+            }
+        }
+    }
+    let uses_backend = |backend| backends.values().any(|current| *current == backend);
+    if uses_backend(RecordingBackend::Cpal) {
+        info!(
+            "The {} strategy is used if the recordings can not be written in time",
+            config.backpressure
+        );
+    }
+
+    // the level of the input is only known while capturing with the native backend
+    if config.activation.is_enabled() {
+        if let Some(input_name) = config
+            .input
+            .keys()
+            .find(|input_name| backends[*input_name] != RecordingBackend::Cpal)
+        {
+            error!(
+                "The sound-activated recording requires the cpal backend, but {} uses the {} backend. Terminating.",
+                input_name, backends[input_name]
+            );
+            return;
+        }
+        info!(
+            "Only the events above a level of {} are recorded",
+            config.activation.threshold
+        );
+    }
+
+    // only the native backend captures the audio in-process, so only it can publish it
+    let tee_server = match &config.tee {
+        Some(tee) => {
+            if let Some(input_name) = config
+                .input
+                .keys()
+                .find(|input_name| backends[*input_name] != RecordingBackend::Cpal)
+            {
+                error!(
+                    "The tee requires the cpal backend, but {} uses the {} backend. Terminating.",
+                    input_name, backends[input_name]
+                );
+                return;
+            }
+            match TeeServer::start(tee) {
+                Ok(tee_server) => Some(tee_server),
+                Err(error) => {
+                    error!(
+                        "Could not listen on {}. Terminating. The error was: {}",
+                        tee.socket, error
+                    );
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+
+    // before we continue we should ensure that the required recording tool is available
+    if uses_backend(RecordingBackend::Arecord) && !is_recording_tool_available() {
+        error!("The arecord tool seems not to be available on your computer. Terminating.");
+        return;
+    }
+    if uses_backend(RecordingBackend::Pulse) && get_pulse_recording_tool().is_none() {
+        error!(
+            "Neither parecord nor pw-record seem to be available on your computer. Terminating."
+        );
+        return;
+    }
 
     // get the recording duration
-    let recording_duration = 60 * u32::from(options.duration);
+    let recording_duration = options
+        .duration
+        .unwrap_or_else(|| config.defaults.get_segment_duration())
+        .get_seconds();
+
+    // overlapping recordings of an input run at the same time, which the hardware devices of ALSA
+    // do not allow
+    let segment_overlap = config.defaults.overlap_in_seconds;
+    if segment_overlap > 0 {
+        if segment_overlap >= recording_duration {
+            error!("The overlap of the segments has to be shorter than a segment. Terminating.");
+            return;
+        }
+        if let Some(input_name) = config
+            .input
+            .keys()
+            .find(|input_name| backends[*input_name] == RecordingBackend::Arecord)
+        {
+            error!(
+                "The segments of {} can not overlap since arecord opens the hardware device \
+                 exclusively, use the pulse or the cpal backend instead. Terminating.",
+                input_name
+            );
+            return;
+        }
+        info!(
+            "Every recording continues {} seconds into the next segment",
+            segment_overlap
+        );
+    }
 
     // check if we should encode the files or not
     let should_encode_files = !options.no_encoding;
     if !should_encode_files {
         info!("Encoding of the audio files was disabled by a runtime flag");
     }
+    if options.keep_wav {
+        config.encoding.keep_wav = true;
+    }
+    if options.label.is_some() {
+        config.label = options.label.clone();
+    }
 
-    // be sure that the audio device selection makes sense
-    for current_device_key in config.input.keys() {
-        let current_device = config.input[current_device_key].clone();
-        if !is_valid_device_selection(
+    // be sure that the audio device selection makes sense (the other backends select devices
+    // by their name)
+    let mut hotplug_watch = None;
+    if uses_backend(RecordingBackend::Arecord) {
+        // get all audio devices of the computer
+        let available_audio_devices = get_available_devices()
+            .map_err(|_error| panic!("Could not find any suitable audio devices. Terminating."))
+            .unwrap();
+
+        let input_names: Vec<String> = config.input.keys().cloned().collect();
+        for current_device_key in &input_names {
+            if backends[current_device_key] != RecordingBackend::Arecord {
+                continue;
+            }
+
+            // devices selected by their PCM name get the card and device number they currently use
+            if let Some(pcm) = config.input[current_device_key].pcm.clone() {
+                let (card, device) = match resolve_pcm_name(&pcm, &available_audio_devices) {
+                    Some(card_and_device) => card_and_device,
+                    None => panic!("The audio device {} could not be found.", pcm),
+                };
+                info!(
+                    "The audio device {} of {} is card {} and device {}",
+                    pcm, current_device_key, card, device
+                );
+                let configured_device = config.input.get_mut(current_device_key).unwrap();
+                configured_device.card = card;
+                configured_device.device = device;
+            }
+
+            let current_device = config.input[current_device_key].clone();
+            if let Some(device_info) = available_audio_devices.iter().find(|device_info| {
+                device_info.card == current_device.card
+                    && device_info.device == current_device.device
+            }) {
+                if device_info.is_blacklisted(&device_blacklist) {
+                    warn!(
+                        "The device {} of {} matches a pattern of the device blacklist",
+                        device_info.description, current_device_key
+                    );
+                }
+            }
+            if !is_valid_device_selection(
+                &available_audio_devices,
+                current_device.card,
+                current_device.device,
+            ) {
+                panic!(
+                    "An invalid combination of audio devices (cd:{},{}) was detected.",
+                    current_device.card, current_device.device
+                );
+            }
+        }
+
+        // a USB device which is plugged in again might get another card number, so the cards are
+        // recognized by their names then
+        hotplug_watch = HotplugWatch::new(
+            &config.hotplug,
+            config
+                .input
+                .iter()
+                .filter(|(input_name, _)| backends[*input_name] == RecordingBackend::Arecord),
             &available_audio_devices,
-            current_device.card,
-            current_device.device,
+        )
+        .map(Arc::new);
+    }
+
+    // PulseAudio and PipeWire sources can only be selected by their name
+    for (input_name, input_device) in &config.input {
+        if backends[input_name] == RecordingBackend::Pulse && input_device.source.is_none() {
+            error!(
+                "No source was configured for {} which uses the pulse backend. Terminating.",
+                input_name
+            );
+            return;
+        }
+    }
+
+    // the native backend converts all captured samples to 16 bit
+    for (input_name, input_device) in &config.input {
+        if backends[input_name] == RecordingBackend::Cpal
+            && input_device.format != SampleFormat::S16
+        {
+            error!(
+                "The sample format {} was configured for {}, but the cpal backend only supports s16. Terminating.",
+                input_device.format, input_name
+            );
+            return;
+        }
+    }
+
+    // the capture volume is set before anything is recorded, so the gain does not depend on what
+    // was set with alsamixer the last time
+    for (input_name, input_device) in &config.input {
+        let capture_volume = match input_device
+            .capture_volume
+            .as_deref()
+            .map(CaptureVolume::parse)
+            .transpose()
+        {
+            Ok(Some(capture_volume)) => capture_volume,
+            Ok(None) => continue,
+            Err(error) => {
+                error!(
+                    "Invalid capture volume for {}: {}. Terminating.",
+                    input_name, error
+                );
+                return;
+            }
+        };
+        if backends[input_name] != RecordingBackend::Arecord {
+            warn!(
+                "The capture volume of {} can only be set for the arecord backend, it is not changed",
+                input_name
+            );
+            continue;
+        }
+        match set_capture_volume(
+            input_device.card,
+            &input_device.mixer_control,
+            capture_volume,
         ) {
-            panic!(
-                "An invalid combination of audio devices (cd:{},{}) was detected.",
-                current_device.card, current_device.device
+            Ok(()) => info!(
+                "The capture volume of {} was set to {} ({} of card {})",
+                input_name, capture_volume, input_device.mixer_control, input_device.card
+            ),
+            Err(error) => warn!(
+                "Could not set the capture volume of {}. The error was: {}",
+                input_name, error
+            ),
+        }
+    }
+
+    // ensure the encoders can be used with the configured settings
+    if should_encode_files {
+        if let Err(error) = config.encoding.validate() {
+            error!("Invalid encoding settings: {}. Terminating.", error);
+            return;
+        }
+    }
+
+    // the recorder only installs updates whose signature can be verified
+    if let Some(update) = &config.update {
+        if let Err(error) = update.validate() {
+            error!("Invalid update settings: {}. Terminating.", error);
+            return;
+        }
+    }
+
+    // select the source of the timestamps before the first file is named
+    if let Err(error) = initialize_clock(&config.clock) {
+        error!(
+            "The timestamp source could not be initialized. Terminating. The error was: {}",
+            error
+        );
+        return;
+    }
+    let uses_timestamp_source = config.clock.source != TimestampSource::System;
+
+    // the maintenance tasks are validated before the recording starts
+    let mut scheduler = match Scheduler::new(&config.maintenance, clock::now().naive_local()) {
+        Ok(scheduler) => scheduler,
+        Err(error) => {
+            error!(
+                "The maintenance tasks could not be scheduled. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+
+    // the hook is validated before the recording starts, so a typo does not go unnoticed all night
+    let segment_hook = match config
+        .hooks
+        .on_segment_finished
+        .as_deref()
+        .map(CommandTemplate::parse)
+        .transpose()
+    {
+        Ok(segment_hook) => segment_hook.map(Arc::new),
+        Err(error) => {
+            error!(
+                "Invalid command for finished segments: {}. Terminating.",
+                error
             );
+            return;
+        }
+    };
+    let microphone_hook = match config
+        .hooks
+        .on_microphone_detached
+        .as_deref()
+        .map(CommandTemplate::parse)
+        .transpose()
+    {
+        Ok(microphone_hook) => microphone_hook.map(Arc::new),
+        Err(error) => {
+            error!(
+                "Invalid command for detached microphones: {}. Terminating.",
+                error
+            );
+            return;
+        }
+    };
+    let input_failed_hook = match config
+        .hooks
+        .on_input_failed
+        .as_deref()
+        .map(CommandTemplate::parse)
+        .transpose()
+    {
+        Ok(input_failed_hook) => input_failed_hook.map(Arc::new),
+        Err(error) => {
+            error!("Invalid command for failed inputs: {}. Terminating.", error);
+            return;
+        }
+    };
+    let hook_timeout = Duration::from_secs(config.hooks.timeout_in_seconds);
+
+    // the stop conditions are validated before the recording starts, the options of the command
+    // override the ones of the project
+    let mut stop_configuration = config.stop.clone();
+    if options.max_segments.is_some() {
+        stop_configuration.maximum_segments = options.max_segments;
+    }
+    if options.total_duration.is_some() {
+        stop_configuration.total_duration = options.total_duration.clone();
+    }
+    if options.until.is_some() {
+        stop_configuration.until = options.until.clone();
+    }
+    if let Err(error) = stop_configuration.get_condition(clock::now().naive_local()) {
+        error!("Invalid stop condition: {}. Terminating.", error);
+        return;
+    }
+
+    // the recording window is validated before the recording starts
+    let recording_window = match config
+        .schedule
+        .as_ref()
+        .map(|schedule| schedule.get_window())
+        .transpose()
+    {
+        Ok(recording_window) => recording_window,
+        Err(error) => {
+            error!("Invalid schedule: {}. Terminating.", error);
+            return;
+        }
+    };
+
+    // the schedules of the devices are validated before the recording starts
+    let mut device_schedules = HashMap::new();
+    for (input_name, input_device) in &config.input {
+        if let Some(schedule) = &input_device.schedule {
+            match CronExpression::parse(schedule) {
+                Ok(schedule) => {
+                    device_schedules.insert(input_name.clone(), schedule);
+                }
+                Err(error) => {
+                    error!(
+                        "The schedule of {} is invalid. Terminating. The error was: {}",
+                        input_name, error
+                    );
+                    return;
+                }
+            }
         }
     }
 
+    // the metadata is mirrored to the backup destination after every session
+    let backup_destination = match &config.backup {
+        Some(backup) => match backup.get_destination(config.upload.as_ref()) {
+            Some(destination) => Some(destination),
+            None => {
+                error!(
+                    "The backup of the metadata needs a destination or an upload destination. \
+                     Terminating."
+                );
+                return;
+            }
+        },
+        None => None,
+    };
+
     // ensure a sensible recording duration was selected
-    if recording_duration < 60 || recording_duration > 3600 {
-        panic!("Please select a recording duration between 1 and 60 minutes.");
+    if !(MINIMUM_SEGMENT_DURATION_IN_SECONDS..=3600).contains(&recording_duration) {
+        panic!("Please select a recording duration between 5 seconds and 60 minutes.");
+    }
+
+    // the archive owns the layout of the data directory and migrates older layouts
+    let mut archive = match Archive::open(Path::new(&config.data_directory)) {
+        Ok(archive) => archive,
+        Err(error) => {
+            error!(
+                "Could not open the data directory {}. Terminating. The error was: {}",
+                config.data_directory, error
+            );
+            return;
+        }
+    };
+    let _archive_lock = match ArchiveLock::acquire(&archive, "record", options.wait) {
+        Ok(archive_lock) => archive_lock,
+        Err(error) => {
+            error!(
+                "Could not lock the data directory. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    if let Err(error) = archive.set_subfolder_pattern(config.subfolder_pattern.as_deref()) {
+        error!("Invalid subfolder pattern: {}. Terminating.", error);
+        return;
     }
+    archive.set_per_input_folders(config.per_input_folders);
+    let archive = Arc::new(archive);
+
+    // other programs can pause, resume, split or stop the recording on the control socket (the
+    // socket of another recorder is never replaced, since the data directory is locked)
+    let _control_server = if config.control.enabled {
+        let socket_path = config.control.get_socket_path(&config.data_directory);
+        match ControlServer::start(&socket_path) {
+            Ok(control_server) => Some(control_server),
+            Err(error) => {
+                warn!(
+                    "Could not listen for commands on {}. The error was: {}",
+                    socket_path.display(),
+                    error
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // the recordings an interrupted session left behind are repaired and added to its manifest
+    let recovery = match recover_recordings(&archive, &config.input) {
+        Ok(recovery) => recovery,
+        Err(error) => {
+            warn!(
+                "Could not recover the recordings of an interrupted session. The error was: {}",
+                error
+            );
+            None
+        }
+    };
+
+    // inputs which carry the same signal on both channels are switched to mono (if requested)
+    for (input_name, input_device) in config.input.iter_mut() {
+        if !input_device.auto_mono || input_device.mono {
+            continue;
+        }
+        if backends[input_name] != RecordingBackend::Arecord {
+            warn!(
+                "The channels of {} can only be compared with the arecord backend, recording in stereo",
+                input_name
+            );
+            continue;
+        }
+        match probe_channels(input_device, &archive.get_state_folder()) {
+            Ok(comparison) if comparison.is_duplicate() => {
+                if is_mono_supported(input_device, &archive.get_state_folder()) {
+                    info!(
+                        "Both channels of {} carry the same signal (correlation {:.3}), recording in mono",
+                        input_name, comparison.correlation
+                    );
+                    input_device.mono = true;
+                } else {
+                    warn!(
+                        "Both channels of {} carry the same signal, but the device can not be recorded in mono",
+                        input_name
+                    );
+                }
+            }
+            Ok(comparison) => info!(
+                "{} records two different channels (correlation {:.3}), recording in stereo",
+                input_name, comparison.correlation
+            ),
+            Err(error) => warn!(
+                "Could not compare the channels of {}, recording in stereo. The error was: {}",
+                input_name, error
+            ),
+        }
+    }
+
+    // all checks passed, systemd can consider the recorder as started
+    notify("READY=1");
 
     // just print the information where we store the files
     info!(
@@ -93,50 +1333,918 @@ pub fn run_command_record(options: RecordCommandOptions, config: InsomniaProject
         config.data_directory
     );
 
-    // wait until we reached the next full minute
-    info!(
-        "The current time is {}. We are waiting for the next full minute to start.",
-        Local::now().naive_local()
+    // the size of the recordings is tracked if the project has a storage quota
+    let mut quota_tracker = match config.max_storage_gb {
+        Some(max_storage_gb) => match QuotaTracker::new(&archive, max_storage_gb) {
+            Ok(quota_tracker) => Some(quota_tracker),
+            Err(error) => {
+                error!(
+                    "Could not determine the size of the recordings. Terminating. The error was: {}",
+                    error
+                );
+                return;
+            }
+        },
+        None => None,
+    };
+
+    // outside of the recording window, the recorder waits until the window opens
+    if let Some(window) = &recording_window {
+        wait_for_recording_window(window, &mut scheduler);
+    }
+
+    // wait until we reached the next boundary of the start alignment (or the time agreed on with
+    // the other machine), the time until then can be recorded as a partial segment instead
+    let mut grace_duration = get_grace_duration(&options, &config);
+    let sync_information = match grace_duration {
+        Some(grace_duration) => {
+            info!(
+                "Recording a partial segment of {} seconds until the next {} boundary",
+                grace_duration, config.start_alignment
+            );
+            None
+        }
+        None => synchronize_or_wait(&config),
+    };
+    if is_shutdown_requested() {
+        info!("The recording was stopped before it started");
+        return;
+    }
+
+    // the manifest lists all segments which were recorded in this session
+    let (mut manifest_writer, mut session_span) =
+        start_session(&archive, &config, sync_information);
+
+    // the recordings are post-processed by a fixed number of workers to not starve the recorder,
+    // the workers pause while a subsystem with a higher priority (e.g. the analysis) is running
+    let preceding_subsystems = config
+        .priority
+        .get_preceding_subsystems(Subsystem::Encoding);
+    let state_folder = archive.get_state_folder();
+    let encoding_queue = EncodingQueue::with_pause_condition(
+        config.encoding.workers.max(1),
+        config.encoding.maximum_queue_length.max(1),
+        Arc::new(move || {
+            preceding_subsystems
+                .iter()
+                .any(|subsystem| is_running(&state_folder, *subsystem))
+        }),
+    );
+
+    // the retention policy is applied before the first segment and once per interval afterwards
+    let mut last_retention: Option<Instant> = None;
+
+    // the recorder in the background keeps itself up to date (if enabled)
+    let mut update_checker = match &config.update {
+        Some(update) if update.automatic && options.daemon => {
+            match UpdateChecker::new(update.clone()) {
+                Ok(update_checker) => Some(update_checker),
+                Err(error) => {
+                    warn!("Automatic updates are disabled: {}", error);
+                    None
+                }
+            }
+        }
+        Some(update) if update.automatic => {
+            info!("Automatic updates are only installed by the recorder in the background");
+            None
+        }
+        _ => None,
+    };
+
+    // the recovered recordings are encoded with their original names and timestamps
+    if let Some(recovery) = recovery {
+        info!(
+            "Found {} unencoded recording(s) of an interrupted session",
+            recovery.recordings.len()
+        );
+        if should_encode_files {
+            queue_recovered_recordings(recovery, &encoding_queue, &archive, &config);
+        }
+    }
+
+    // failing inputs are retried with an increasing delay and given up after too many failures
+    let capture_backoff = Arc::new(CaptureBackoff::new(&config.retry));
+
+    // the silence of the segments of all inputs is compared to notice a microphone which fell off,
+    // a single input has nothing to be compared with
+    let microphone_watch = MicrophoneWatch::new(&config.silence)
+        .filter(|_| config.input.len() > 1)
+        .map(Arc::new);
+
+    // the state of the recorder is shared with the dashboard, the metrics of the run command and
+    // the status file
+    status_board.set_status_file(
+        config
+            .status_file
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| archive.get_state_folder().join(STATUS_FILE_NAME)),
     );
-    wait_until_full_minute();
+    status_board.set_inputs(config.input.keys());
+    #[cfg(feature = "dashboard")]
+    let dashboard = options
+        .shows_dashboard()
+        .then(|| Dashboard::start(status_board.clone()));
 
     // record audio files endlessly and convert them to mp3s (if requested)
+    let mut clock_jump_detector = ClockJumpDetector::new();
+    let mut overlapping_handles: Vec<JoinHandle<Vec<(String, u64)>>> = vec![];
+    // the stop conditions were validated before, the time is counted from the actual start
+    let stop_condition = stop_configuration
+        .get_condition(clock::now().naive_local())
+        .unwrap();
+    if let Some(deadline) = stop_condition.get_deadline() {
+        info!("The recording stops at {}", deadline);
+    }
+    let mut segment_count = 0;
     loop {
-        let handles = config
+        if is_shutdown_requested() {
+            break;
+        }
+        if let Some(reason) = stop_condition.get_reason(segment_count, clock::now().naive_local()) {
+            info!("The recording is finished since {}", reason);
+            break;
+        }
+
+        // a paused recording continues with a new segment of the same session when it is resumed
+        if is_paused() {
+            status_board.update(|status| status.paused = true);
+            wait_while_paused(&mut scheduler);
+            status_board.update(|status| status.paused = false);
+            continue;
+        }
+
+        // every segment ends on the next wall-clock boundary, so a late start (e.g. while the
+        // previous recording was finished) is made up by a slightly shorter segment
+        let segment_duration =
+            get_aligned_segment_duration(clock::now().naive_local(), recording_duration);
+
+        // the last segment of a recording window ends with the window, a new session is started
+        // when the window opens again
+        let segment_duration = match &recording_window {
+            Some(window) => {
+                let now = clock::now().naive_local();
+                let remaining_in_seconds =
+                    (window.get_stop_after(now) - now).num_milliseconds() as f64 / 1000.0;
+                if !window.contains(now.time()) || remaining_in_seconds < 1.0 {
+                    info!("The recording window closed, the session is finished");
+                    for handle in overlapping_handles.drain(..) {
+                        let _ = handle.join();
+                    }
+                    if let Some(destination) = &backup_destination {
+                        encoding_queue.wait_until_idle();
+                        back_up_session(&archive, &config, destination);
+                    }
+
+                    // a downloaded update is installed between two sessions
+                    if let Some(update_checker) = &update_checker {
+                        if update_checker.has_staged_update() {
+                            encoding_queue.wait_until_idle();
+                            update_checker.install_and_restart();
+                        }
+                    }
+                    if window.contains(now.time()) {
+                        sleep(Duration::from_secs(1));
+                    }
+                    wait_for_recording_window(window, &mut scheduler);
+                    if is_shutdown_requested() {
+                        break;
+                    }
+                    (manifest_writer, session_span) =
+                        start_session(&archive, &config, synchronize_or_wait(&config));
+                    continue;
+                }
+                segment_duration.min(remaining_in_seconds.round() as u32)
+            }
+            None => segment_duration,
+        };
+
+        // the last segment ends at the stop time
+        let segment_duration = match stop_condition.get_deadline() {
+            Some(deadline) => {
+                let now = clock::now().naive_local();
+                let remaining_in_seconds = (deadline - now).num_milliseconds() as f64 / 1000.0;
+                segment_duration.min(remaining_in_seconds.round().max(1.0) as u32)
+            }
+            None => segment_duration,
+        };
+
+        // the partial segment until the first full minute is only recorded once
+        let is_partial = grace_duration.is_some();
+        let segment_duration = grace_duration
+            .take()
+            .map_or(segment_duration, |grace_duration| {
+                grace_duration.min(segment_duration)
+            });
+        extend_watchdog(
+            Duration::from_secs(u64::from(segment_duration) + 60) + WATCHDOG_GRACE_PERIOD,
+        );
+        notify("STATUS=Recording");
+        let segment_span = info_span!(
+            parent: &session_span,
+            "segment",
+            duration_in_seconds = segment_duration,
+            is_partial
+        );
+        let _segment_entered = segment_span.enter();
+
+        // the recording is stopped once all inputs were given up
+        if config
+            .input
+            .keys()
+            .all(|key| capture_backoff.has_given_up(key))
+        {
+            error!("Stopping the recording since all inputs were given up");
+            break;
+        }
+
+        // only the devices whose schedule matches the start of the segment are recorded
+        let segment_start = clock::now().naive_local();
+        let scheduled_inputs: Vec<&String> = config
             .input
             .keys()
+            .filter(|key| !capture_backoff.has_given_up(key))
+            .filter(|key| {
+                device_schedules
+                    .get(*key)
+                    .map_or(true, |schedule| schedule.matches(&segment_start))
+            })
+            .collect();
+        if scheduled_inputs.is_empty() {
+            debug!("No input is scheduled at {}", segment_start);
+            if !scheduler.is_empty() {
+                scheduler.run_due_tasks(clock::now().naive_local());
+            }
+            wait_until_full_minute();
+            continue;
+        }
+
+        if config.retention.is_enabled()
+            && last_retention.map_or(true, |last_retention| {
+                last_retention.elapsed() >= RETENTION_INTERVAL
+            })
+        {
+            apply_retention(&archive, &config.retention);
+            last_retention = Some(Instant::now());
+        }
+
+        if let Some(quota_tracker) = &mut quota_tracker {
+            if check_quota(
+                &archive,
+                quota_tracker,
+                config.quota_action,
+                !should_encode_files,
+            ) {
+                error!("Stopping the recording since the storage quota is exceeded");
+                break;
+            }
+        }
+
+        // a full disk would let the recordings fail, so the free space is checked before every
+        // segment
+        let low_space_action = check_free_space(&archive, &config.storage);
+        status_board.update(|status| {
+            status.free_space_in_bytes = get_free_space_in_bytes(archive.get_root());
+            status.encoding_queue_depth = encoding_queue.get_depth();
+        });
+        if low_space_action == Some(LowSpaceAction::Stop) {
+            error!("Stopping the recording since the data directory is running out of space");
+            break;
+        }
+        let should_encode_segment = should_encode_files && low_space_action.is_none();
+        if should_encode_files && !should_encode_segment {
+            warn!(
+                "The recordings are not encoded while the data directory is running out of space"
+            );
+        }
+
+        // a split which was requested while no segment was recorded does not cut the new one short
+        clear_split_request();
+        let cpu_times_at_start = CpuTimes::read();
+        let segment_end = Instant::now() + Duration::from_secs(u64::from(segment_duration));
+        let handles = scheduled_inputs
+            .into_iter()
             .map(|key| {
-                let current_device = config.input[key].clone();
-                let output_folder = config.data_directory.clone();
+                let input_name = key.clone();
+                let backend = backends[key];
+                let mut current_device = config.input[key].clone();
+                let hotplug_watch = hotplug_watch.clone();
+                if let Some((card, device)) = hotplug_watch
+                    .as_ref()
+                    .and_then(|hotplug_watch| hotplug_watch.get_card_and_device(key))
+                {
+                    current_device.card = card;
+                    current_device.device = device;
+                }
+                let archive = archive.clone();
+                let should_create_preview = config.create_previews;
+                let backpressure = config.backpressure;
+                let capture_watchdog = config.capture_watchdog.clone();
+                let manifest_writer = manifest_writer.clone();
+                let encoding_queue = encoding_queue.clone();
+                let encoding = config.encoding.clone();
+                let normalization = config.normalization.clone();
+                let output_format = current_device.get_output_format(config.output_format);
+                let event_naming = config.event_naming.clone();
+                let silence = config.silence.clone();
+                let cough_detector = config
+                    .cough_detection
+                    .enabled
+                    .then(|| CoughDetector::new(&config.cough_detection));
+                let activation = config.activation.clone();
+                let label = config.label.clone();
+                let trash_folder = (config.encoding.removal == RemovalMode::Trash)
+                    .then(|| archive.get_trash_folder());
+                let segment_hook = segment_hook.clone();
+                let microphone_hook = microphone_hook.clone();
+                let microphone_watch = microphone_watch.clone();
+                let input_failed_hook = input_failed_hook.clone();
+                let capture_backoff = capture_backoff.clone();
+                let tee = tee_server
+                    .as_ref()
+                    .map(|tee_server| tee_server.get_source(&input_name));
+                let status_board = status_board.clone();
+                let device_span = info_span!(
+                    "device",
+                    input = %input_name,
+                    backend = %backend
+                );
                 spawn(move || {
-                    let file_prefix = record_audio(
-                        current_device.card,
-                        current_device.device,
-                        recording_duration,
-                        current_device.mono,
-                        output_folder,
-                    );
-                    if file_prefix.is_some() {
-                        let file_prefix_unwrapped = file_prefix.unwrap();
+                    let _device_entered = device_span.enter();
+                    let (started_at, timestamp_source) = clock::now_with_source();
+                    status_board.update_input(&input_name, |input_status| {
+                        input_status.state = InputState::Recording;
+                        input_status.segment_started_at = Some(started_at);
+                        input_status.segment_duration_in_seconds = segment_duration;
+                    });
+
+                    // the files of the segment are stored in the subfolders for its input and its
+                    // start time
+                    let get_folder = |folder: PathBuf| {
+                        archive
+                            .get_folder_at(
+                                &folder,
+                                &input_name,
+                                to_timestamp_timezone(started_at).naive_local(),
+                            )
+                            .unwrap_or_else(|error| {
+                                warn!(
+                                    "Could not create the subfolder of {}. The error was: {}",
+                                    folder.display(),
+                                    error
+                                );
+                                folder
+                            })
+                    };
+                    let raw_folder = get_folder(archive.get_raw_folder());
+                    let encoded_folder = get_folder(archive.get_encoded_folder());
+                    let previews_folder = get_folder(archive.get_previews_folder());
+
+                    // the sound-activated recording produces a file per event of the segment
+                    let record = |current_device: &RecordingDeviceConfiguration,
+                                  duration_in_seconds: u32| {
+                        if activation.is_enabled() {
+                            record_events_with_backend(
+                                backend,
+                                current_device,
+                                duration_in_seconds,
+                                raw_folder.to_string_lossy().to_string(),
+                                backpressure,
+                                &activation,
+                                tee.clone(),
+                            )
+                        } else {
+                            record_audio_with_backend(
+                                backend,
+                                current_device,
+                                duration_in_seconds,
+                                raw_folder.to_string_lossy().to_string(),
+                                backpressure,
+                                tee.clone(),
+                                &capture_watchdog,
+                            )
+                            .map(|recorded_segment| vec![recorded_segment])
+                        }
+                    };
+                    let segment_end = Instant::now()
+                        + Duration::from_secs(u64::from(segment_duration + segment_overlap));
+                    let mut maybe_recorded_segments =
+                        record(&current_device, segment_duration + segment_overlap);
+
+                    // if the card of the input disappeared (e.g. a USB hiccup), the rest of the
+                    // segment is recorded as soon as the card is available again. other failures
+                    // are retried with an increasing delay until the input is given up.
+                    while maybe_recorded_segments.is_none() {
+                        error!(
+                            "Failed to record an audio stream from card {} and device {}",
+                            current_device.card, current_device.device
+                        );
+                        status_board.update_input(&input_name, |input_status| {
+                            input_status.state = InputState::Failed;
+                            input_status.last_error = Some(format!(
+                                "recording failed at {}",
+                                clock::now().format("%H:%M")
+                            ));
+                        });
+                        let reappeared_device = hotplug_watch.as_ref().and_then(|hotplug_watch| {
+                            hotplug_watch.wait_for_device(&input_name, segment_end)
+                        });
+                        if let Some((card, device)) = reappeared_device {
+                            current_device.card = card;
+                            current_device.device = device;
+                        } else {
+                            match capture_backoff.add_failure(&input_name) {
+                                CaptureFailure::GiveUp {
+                                    failures,
+                                    failing_since,
+                                } => {
+                                    report_input_failure(
+                                        &input_name,
+                                        failures,
+                                        failing_since,
+                                        &raw_folder,
+                                        input_failed_hook.as_deref(),
+                                        hook_timeout,
+                                        &status_board,
+                                    );
+                                    break;
+                                }
+                                CaptureFailure::Retry { failures, delay } => {
+                                    // the delay is kept even if the segment ends earlier, so
+                                    // a failing input does not restart the segments immediately
+                                    let remaining = segment_end.saturating_duration_since(Instant::now());
+                                    warn!(
+                                        "The recording of {} failed {} time(s) in a row, retrying in {} seconds",
+                                        input_name,
+                                        failures,
+                                        delay.as_secs()
+                                    );
+                                    if !sleep_unless_recording_stopped(delay.min(remaining)) || delay >= remaining {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        let remaining_in_seconds =
+                            segment_end.saturating_duration_since(Instant::now()).as_secs() as u32;
+                        if remaining_in_seconds == 0 || is_split_requested() {
+                            break;
+                        }
+                        info!(
+                            "Resuming the recording of {} for the remaining {} seconds of the segment",
+                            input_name, remaining_in_seconds
+                        );
+                        status_board.update_input(&input_name, |input_status| {
+                            input_status.state = InputState::Recording;
+                            input_status.last_error = None;
+                        });
+                        maybe_recorded_segments = record(&current_device, remaining_in_seconds);
+                    }
+                    let recorded_segments = match maybe_recorded_segments {
+                        Some(recorded_segments) => recorded_segments,
+                        None => return vec![],
+                    };
+                    capture_backoff.add_success(&input_name);
+                    status_board.update_input(&input_name, |input_status| {
+                        // with an overlap, the next segment of the input is recording already
+                        if input_status.segment_started_at == Some(started_at) {
+                            input_status.state = InputState::Waiting;
+                        }
+                        input_status.recorded_segments += recorded_segments.len() as u32;
+                    });
+                    if activation.is_enabled() && recorded_segments.is_empty() {
+                        info!("No event was recorded by {} in this segment", input_name);
+                    }
+
+                    let mut recorded_files = vec![];
+                    for recorded_segment in recorded_segments {
+                        // every recording is post-processed on its own
+                        let input_name = input_name.clone();
+                        let current_device = current_device.clone();
+                        let manifest_writer = manifest_writer.clone();
+                        let encoding = encoding.clone();
+                        let normalization = normalization.clone();
+                        let event_naming = event_naming.clone();
+                        let silence = silence.clone();
+                        let cough_detector = cough_detector.clone();
+                        let label = label.clone();
+                        let trash_folder = trash_folder.clone();
+                        let segment_hook = segment_hook.clone();
+                        let microphone_hook = microphone_hook.clone();
+                        let microphone_watch = microphone_watch.clone();
+                        let status_board = status_board.clone();
+                        let raw_folder = raw_folder.clone();
+                        let encoded_folder = encoded_folder.clone();
+                        let previews_folder = previews_folder.clone();
+
+                        // an event starts within the captured duration
+                        let (started_at, duration_in_seconds) = match recorded_segment.event {
+                            Some(event) => (
+                                started_at
+                                    + chrono::Duration::milliseconds(
+                                        (event.offset_in_seconds * 1000.0) as i64,
+                                    ),
+                                event.duration_in_seconds.ceil().max(1.0) as u32,
+                            ),
+                            None => (started_at, segment_duration),
+                        };
+
+                        let is_activated = recorded_segment.event.is_some();
+                        let file_prefix_unwrapped = recorded_segment.file_prefix.clone();
                         info!(
                             "The recording {} of card {} and device {} was finished",
                             file_prefix_unwrapped, current_device.card, current_device.device
                         );
-                    } else {
-                        error!(
-                            "Failed to record an audio stream from card {} and device {}",
-                            current_device.card, current_device.device
+
+                        add_broadcast_extension(
+                            &file_prefix_unwrapped,
+                            &input_name,
+                            &current_device,
+                            to_timestamp_timezone(started_at).naive_local(),
                         );
+
+                        // add the recording to the manifest of the session
+                        let manifest_file_name =
+                            get_file_name(&format!("{}.wav", file_prefix_unwrapped));
+                        manifest_writer.add_segment(SegmentManifest {
+                            file: manifest_file_name.clone(),
+                            input: input_name.clone(),
+                            started_at: to_timestamp_timezone(started_at)
+                                .format(MANIFEST_TIMESTAMP_FORMAT)
+                                .to_string(),
+                            duration_in_seconds,
+                            dropped_frames: recorded_segment.dropped_frames,
+                            spilled_frames: recorded_segment.spilled_frames,
+                            gaps: recorded_segment.gaps,
+                            partial: is_partial && recorded_segment.event.is_none(),
+                            overlap_in_seconds: (segment_overlap > 0 && !is_activated)
+                                .then_some(segment_overlap),
+                            activated: is_activated,
+                            timestamp_source: if uses_timestamp_source
+                                || timestamp_source == clock::MONOTONIC_SOURCE_NAME
+                            {
+                                Some(timestamp_source.to_string())
+                            } else {
+                                None
+                            },
+                            ..Default::default()
+                        });
+
+                        let segment_file_name = manifest_file_name.clone();
+                        let recorded_size = Path::new(&format!("{}.wav", file_prefix_unwrapped))
+                            .metadata()
+                            .map(|metadata| metadata.len())
+                            .unwrap_or(0);
+
+                        // post-process the file in the background to not delay the next recording,
+                        // at least its levels are measured
+                        let should_count_events = event_naming.mode != EventNamingMode::Off;
+                        let should_check_silence = silence.mode != SilenceMode::Off;
+                        let queued_manifest_writer = manifest_writer.clone();
+                        let queued_status_board = status_board.clone();
+                        let queued_file_name = manifest_file_name.clone();
+                        let planned_encoding = if should_encode_segment {
+                            encoding.describe(output_format)
+                        } else if should_encode_files {
+                            "wav (low disk space)".to_string()
+                        } else {
+                            "wav".to_string()
+                        };
+                        let queued_result = encoding_queue.try_submit(move || {
+                            let recording = PathBuf::from(format!("{}.wav", file_prefix_unwrapped));
+
+                            // the levels show if the gain of the input was set sensibly
+                            if let Some(levels) = measure_levels(&recording) {
+                                info!(
+                                    "The levels of {} are {:.1} dBFS (peak) and {:.1} dBFS (RMS)",
+                                    recording.display(),
+                                    levels.peak_in_dbfs,
+                                    levels.rms_in_dbfs
+                                );
+                                if levels.is_clipping() {
+                                    warn!(
+                                        "{} is clipping, the gain of {} might be too high",
+                                        recording.display(),
+                                        input_name
+                                    );
+                                }
+                                manifest_writer.update_segment(&manifest_file_name, |segment| {
+                                    segment.peak_level_in_dbfs = Some(levels.peak_in_dbfs);
+                                    segment.rms_level_in_dbfs = Some(levels.rms_in_dbfs);
+                                });
+                                status_board.update_input(&input_name, |input_status| {
+                                    input_status.levels = Some(levels)
+                                });
+                            }
+
+                            // the share of silent passages is kept for the segments which are
+                            // checked for silence or for a microphone which fell off, the latter
+                            // ignores the events of the sound activation which are never silent
+                            let microphone_watch = microphone_watch.filter(|_| !is_activated);
+                            let silence_percentage = if should_check_silence
+                                || microphone_watch.is_some()
+                            {
+                                measure_silence(&recording, silence.threshold)
+                            } else {
+                                None
+                            };
+                            if let Some(silence_percentage) = silence_percentage {
+                                manifest_writer.update_segment(&manifest_file_name, |segment| {
+                                    segment.silence_percentage = Some(silence_percentage)
+                                });
+                                if let Some(microphone_watch) = &microphone_watch {
+                                    let activity = microphone_watch.add_segment(
+                                        &input_name,
+                                        started_at,
+                                        duration_in_seconds,
+                                        silence_percentage,
+                                    );
+                                    report_microphone_activity(
+                                        activity,
+                                        &input_name,
+                                        &recording,
+                                        microphone_hook.as_deref(),
+                                        hook_timeout,
+                                        &status_board,
+                                    );
+                                }
+                            }
+
+                            // silent segments are neither analyzed nor encoded
+                            let is_silent = should_check_silence
+                                && silence_percentage.is_some_and(|percentage| percentage >= 100.0);
+                            if is_silent {
+                                manifest_writer.update_segment(&manifest_file_name, |segment| {
+                                    segment.silent = true
+                                });
+                                if silence.mode == SilenceMode::Discard {
+                                    info!("Discarding {} since it is silent", recording.display());
+                                    remove_recording(&recording, trash_folder.as_deref());
+                                    return;
+                                }
+                                info!("{} is silent, it is not encoded", recording.display());
+                            }
+
+                            // the padding around the sound is cut off before the recording is
+                            // analyzed and encoded, the manifest keeps how much was removed
+                            if silence.is_trimming() && !is_silent {
+                                trim_silence(&recording, &silence, &manifest_writer);
+                            }
+
+                            // the events have to be counted before the recording is encoded
+                            let events = if should_count_events && !is_silent {
+                                count_events_in_recording(
+                                    &file_prefix_unwrapped,
+                                    event_naming.threshold,
+                                )
+                            } else {
+                                None
+                            };
+                            // the coughs as well
+                            if let Some(cough_detector) = cough_detector.filter(|_| !is_silent) {
+                                if let Some(coughs) =
+                                    detect_in_recording(&cough_detector, &recording)
+                                {
+                                    debug!(
+                                        "Found {} cough(s) in {}",
+                                        coughs.len(),
+                                        recording.display()
+                                    );
+                                    manifest_writer
+                                        .update_segment(&manifest_file_name, |segment| {
+                                            segment.cough_offsets_in_seconds = Some(coughs)
+                                        });
+                                }
+                            }
+                            if should_create_preview && !is_silent {
+                                create_preview_file(
+                                    file_prefix_unwrapped.clone(),
+                                    &previews_folder,
+                                );
+                            }
+                            // the hook gets the encoded file if the recording was encoded
+                            let mut finished_file = recording.clone();
+                            if should_encode_segment && !is_silent {
+                                let encoding_start = Instant::now();
+                                let gain_in_db = normalization.get_gain(&recording);
+                                let result = convert_audio(
+                                    &recording,
+                                    &ConvertOptions {
+                                        output_folder: encoded_folder.clone(),
+                                        output_format,
+                                        encoding,
+                                        normalization,
+                                        gain_in_db,
+                                        label,
+                                        trash_folder,
+                                    },
+                                );
+                                let applied_gain = result
+                                    .map(|encoded_file| finished_file = encoded_file)
+                                    .ok()
+                                    .and(gain_in_db);
+                                let encoding_time = encoding_start.elapsed().as_secs_f32();
+                                manifest_writer.update_segment(&manifest_file_name, |segment| {
+                                    segment.applied_gain_in_db = applied_gain;
+                                    segment.encoding_time_in_seconds = Some(encoding_time);
+                                });
+                            }
+                            if let Some(events) = events {
+                                let channels = events.per_channel.len();
+                                let channel_events = events
+                                    .per_channel
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(channel, channel_events)| {
+                                        (
+                                            current_device.get_channel_label(channel, channels),
+                                            *channel_events,
+                                        )
+                                    })
+                                    .collect();
+                                manifest_writer.update_segment(&manifest_file_name, |segment| {
+                                    segment.events = Some(events.total);
+                                    segment.channel_events = channel_events;
+                                });
+                                match apply_event_naming(
+                                    &[
+                                        raw_folder.clone(),
+                                        encoded_folder.clone(),
+                                        previews_folder.clone(),
+                                    ],
+                                    &get_file_name(&file_prefix_unwrapped),
+                                    events.total,
+                                    &event_naming,
+                                ) {
+                                    Ok(moved_files) => {
+                                        if let Some((_, new_file)) = moved_files
+                                            .into_iter()
+                                            .find(|(file, _)| *file == finished_file)
+                                        {
+                                            finished_file = new_file;
+                                        }
+                                    }
+                                    Err(error) => error!(
+                                        "Could not mark {} with its event count. The error was: {}",
+                                        file_prefix_unwrapped, error
+                                    ),
+                                }
+                            }
+                            // the score is computed once all figures of the segment are known
+                            let mut interestingness = None;
+                            manifest_writer.update_session(|manifest| {
+                                interestingness =
+                                    score_session_segment(manifest, &manifest_file_name)
+                            });
+                            if let Some(interestingness) = interestingness {
+                                debug!(
+                                    "{} has an interestingness of {}",
+                                    recording.display(),
+                                    interestingness
+                                );
+                                status_board.add_scored_segment(
+                                    &input_name,
+                                    &manifest_file_name,
+                                    interestingness,
+                                );
+                            }
+                            if let Some(segment_hook) = segment_hook {
+                                let finished_segment = FinishedSegment {
+                                    path: &finished_file,
+                                    device: &input_name,
+                                    started_at,
+                                    duration_in_seconds,
+                                };
+                                if let Err(error) =
+                                    segment_hook.run(&finished_segment, hook_timeout)
+                                {
+                                    warn!(
+                                        "The command for the finished segment {} failed: {}",
+                                        file_prefix_unwrapped, error
+                                    );
+                                }
+                            }
+                        });
+                        // the change points of the encoding explain quality changes in the night
+                        match queued_result {
+                            Ok(depth) => {
+                                info!("{} segment(s) are waiting for being encoded", depth);
+                                queued_manifest_writer
+                                    .update_segment(&queued_file_name, |segment| {
+                                        segment.encoding_queue_depth = Some(depth)
+                                    });
+                                queued_status_board
+                                    .update(|status| status.encoding_queue_depth = depth);
+                                queued_manifest_writer
+                                    .set_segment_encoding(&queued_file_name, planned_encoding);
+                            }
+                            Err(error) => {
+                                warn!(
+                                    "{} is not post-processed since {}, the recording is kept",
+                                    queued_file_name, error
+                                );
+                                let kept_encoding = if should_encode_segment {
+                                    "wav (encoding queue full)".to_string()
+                                } else {
+                                    planned_encoding
+                                };
+                                queued_manifest_writer
+                                    .set_segment_encoding(&queued_file_name, kept_encoding);
+                            }
+                        }
+                        recorded_files.push((segment_file_name, recorded_size));
                     }
+                    recorded_files
                 })
             })
             .collect::<Vec<JoinHandle<_>>>();
 
+        // with an overlap, the recordings continue while the next segment is started, so the
+        // threads of the previous segment are waited for instead. a split finishes the recordings
+        // of both segments, so the split request is not cleared while one of them still runs.
+        let handles = if segment_overlap > 0 {
+            sleep_unless_recording_stopped(segment_end.saturating_duration_since(Instant::now()));
+            if is_split_requested() {
+                overlapping_handles.drain(..).chain(handles).collect()
+            } else {
+                replace(&mut overlapping_handles, handles)
+            }
+        } else {
+            handles
+        };
+
         // wait for the recording threads to finish, should be nearly the same but we better
         // try to sync everything here
-        for handle in handles {
-            handle.join().unwrap();
+        let (recorded_segments, recorded_sizes): (Vec<String>, Vec<u64>) = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .unzip();
+        if let Some(quota_tracker) = &mut quota_tracker {
+            quota_tracker.add(recorded_sizes.iter().sum());
+        }
+
+        // the CPU utilization is used for estimating the consumed energy
+        let cpu_utilization = match (cpu_times_at_start, CpuTimes::read()) {
+            (Some(cpu_times_at_start), Some(cpu_times_at_end)) => {
+                cpu_times_at_end.get_utilization_since(&cpu_times_at_start)
+            }
+            _ => None,
+        };
+        if cpu_utilization.is_some() {
+            for segment_file_name in &recorded_segments {
+                manifest_writer.update_segment(segment_file_name, |segment| {
+                    segment.cpu_utilization = cpu_utilization
+                });
+            }
         }
+
+        // the segments which were recorded while the clock jumped back are marked
+        if let Some(jump) = clock_jump_detector.check() {
+            let jump_in_seconds = jump.num_milliseconds() as f64 / 1000.0;
+            for segment_file_name in &recorded_segments {
+                manifest_writer.update_segment(segment_file_name, |segment| {
+                    segment.clock_jump_in_seconds = Some(jump_in_seconds)
+                });
+            }
+        }
+
+        // start the maintenance tasks which are due while no segment is about to start
+        if !scheduler.is_empty() {
+            scheduler.run_due_tasks(clock::now().naive_local());
+        }
+
+        // without a recording window there are no sessions, so a downloaded update is installed
+        // between two segments (after the last ones were post-processed)
+        if let Some(update_checker) = &mut update_checker {
+            update_checker.poll();
+            if recording_window.is_none() && update_checker.has_staged_update() {
+                info!("Waiting for the post-processing of the last segments before updating");
+                encoding_queue.wait_until_idle();
+                update_checker.install_and_restart();
+            }
+        }
+        segment_count += 1;
         info!("All recording threads finished, continuing for the next run...");
     }
+
+    // the recordings of the last segment are post-processed before the recorder exits
+    info!("Stopping the recording, waiting for the post-processing of the last segments");
+    for handle in overlapping_handles {
+        let _ = handle.join();
+    }
+    notify("STOPPING=1");
+    encoding_queue.wait_until_idle();
+    if let Some(destination) = &backup_destination {
+        back_up_session(&archive, &config, destination);
+    }
+    info!("The recording was stopped");
+    status_board.update(|status| {
+        status.encoding_queue_depth = encoding_queue.get_depth();
+        status.stopped_at = Some(Local::now());
+    });
+    #[cfg(feature = "dashboard")]
+    if let Some(dashboard) = dashboard {
+        dashboard.stop();
+    }
 }
@@ -0,0 +1,664 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Duration as OldDuration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use clap::Clap;
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::analysis::BreathingRateEstimate;
+use crate::archive::{get_night_of, ArchiveReader};
+use crate::baseline::{get_median, Baseline, NightMetrics};
+use crate::commands::analyze::{estimate_breathing_rates, AUDIO_EXTENSIONS};
+use crate::interest::{compare_interestingness, get_usual_rms_level, score_segment};
+use crate::manifest::{SegmentManifest, MANIFEST_TIMESTAMP_FORMAT};
+use crate::power::{estimate_energy, EnergyEstimate};
+use crate::priority::{mark_as_running, Subsystem};
+use crate::schemas::REPORT_VERSION;
+use crate::InsomniaProject;
+
+/// The label which is used in the comparison for sessions without a label.
+const UNLABELED: &str = "(none)";
+
+/// The format of the times of the coughs in the JSON output.
+const COUGH_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// The format of the windows of the breathing rate in the JSON output.
+const BREATHING_RATE_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// The number of the most interesting segments which are listed for every night.
+const MOST_INTERESTING_SEGMENTS: usize = 3;
+
+/// Summarize the recorded nights.
+#[derive(Clap)]
+pub struct ReportCommandOptions {
+    /// The folder with the recordings and session manifests (the data directory of the project
+    /// is used if none is specified).
+    #[clap(index = 1)]
+    input_folder: Option<String>,
+
+    /// Compare the sessions by their label instead of summarizing each night.
+    #[clap(long)]
+    by_label: bool,
+
+    /// Only print the nights which deviate significantly from the nights before them.
+    #[clap(long)]
+    anomalies_only: bool,
+
+    /// EXPERIMENTAL: Estimate the breathing rate for every 5 minutes of quiet recordings of each
+    /// night (the recordings of all nights are decoded for it).
+    #[clap(long)]
+    breathing_rate: bool,
+
+    /// The RMS level (0.0 - 1.0) below which the recording is considered to be quiet.
+    #[clap(long, default_value = "0.05")]
+    quiet_threshold: f32,
+
+    /// Print the summary of the nights as JSON (see `info --schemas` for its format).
+    #[clap(long)]
+    json: bool,
+}
+
+impl ReportCommandOptions {
+    /// Check if the report is written as JSON, the log messages have to go to stderr then.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.json
+    }
+}
+
+/// The estimated energy consumption of a night in the JSON output.
+#[derive(Serialize)]
+struct EnergyReport {
+    capture_in_wh: f32,
+    encoding_in_wh: f32,
+    total_in_wh: f32,
+}
+
+/// A segment which should be listened to first in the JSON output.
+#[derive(Serialize)]
+struct InterestingSegmentReport {
+    file: String,
+    input: String,
+    interestingness: u8,
+}
+
+/// The EXPERIMENTAL estimate of the breathing rate for 5 minutes of a night in the JSON output.
+#[derive(Serialize)]
+struct BreathingRateReport {
+    started_at: String,
+    ended_at: String,
+
+    /// The estimated breaths per minute, `None` if the window was not quiet enough.
+    breaths_per_minute: Option<f32>,
+
+    /// The start and the end of the window, which are only printed.
+    #[serde(skip)]
+    window: (NaiveDateTime, NaiveDateTime),
+}
+
+impl From<BreathingRateEstimate> for BreathingRateReport {
+    fn from(estimate: BreathingRateEstimate) -> Self {
+        BreathingRateReport {
+            started_at: estimate
+                .start_time
+                .format(BREATHING_RATE_TIME_FORMAT)
+                .to_string(),
+            ended_at: estimate
+                .end_time
+                .format(BREATHING_RATE_TIME_FORMAT)
+                .to_string(),
+            breaths_per_minute: estimate.breaths_per_minute,
+            window: (estimate.start_time, estimate.end_time),
+        }
+    }
+}
+
+/// The summary of a night in the JSON output.
+#[derive(Serialize)]
+struct NightReport {
+    night: String,
+    labels: Vec<String>,
+    segments: usize,
+    recorded_in_seconds: f32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    events: Option<u32>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    channel_events: BTreeMap<String, u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    coughs: Option<u32>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cough_times: Vec<String>,
+
+    /// The coughs per hour of the night, which are only printed.
+    #[serde(skip)]
+    coughs_per_hour: BTreeMap<NaiveDateTime, u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_level_in_dbfs: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rms_level_in_dbfs: Option<f32>,
+
+    /// The EXPERIMENTAL breathing rate, if it was estimated for the report.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    breathing_rates: Vec<BreathingRateReport>,
+
+    energy: EnergyReport,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    most_interesting: Vec<InterestingSegmentReport>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_nights: Option<usize>,
+
+    anomalies: Vec<String>,
+}
+
+/// The JSON output of the report.
+#[derive(Serialize)]
+struct Report {
+    version: u32,
+    nights: Vec<NightReport>,
+}
+
+impl NightReport {
+    fn print(&self) {
+        println!("[*] Night of {}", self.night);
+        if !self.labels.is_empty() {
+            println!("    [-] Label:\t\t\t{}", self.labels.join(", "));
+        }
+        println!("    [-] Segments:\t\t{}", self.segments);
+        println!(
+            "    [-] Recorded:\t\t{:.1} h",
+            self.recorded_in_seconds / 3600.0
+        );
+        if let Some(events) = self.events {
+            println!("    [-] Events:\t\t\t{}", events);
+        }
+        for (channel, channel_events) in &self.channel_events {
+            println!("        [-] {}:\t\t{}", channel, channel_events);
+        }
+        if let Some(coughs) = self.coughs {
+            println!("    [-] Coughs:\t\t\t{}", coughs);
+        }
+        for (hour, coughs) in &self.coughs_per_hour {
+            println!("        [-] {}:\t\t{}", hour.format("%H:00"), coughs);
+        }
+        if let Some(peak_level) = self.peak_level_in_dbfs {
+            println!("    [-] Peak level:\t\t{:.1} dBFS", peak_level);
+        }
+        if let Some(rms_level) = self.rms_level_in_dbfs {
+            println!("    [-] RMS level (median):\t{:.1} dBFS", rms_level);
+        }
+        if !self.breathing_rates.is_empty() {
+            println!("    [-] Breathing rate (EXPERIMENTAL):");
+        }
+        for breathing_rate in &self.breathing_rates {
+            let (start_time, end_time) = breathing_rate.window;
+            match breathing_rate.breaths_per_minute {
+                Some(breaths_per_minute) => println!(
+                    "        [-] {} - {}:\t{:.1} breaths/min",
+                    start_time.format("%H:%M"),
+                    end_time.format("%H:%M"),
+                    breaths_per_minute
+                ),
+                None => println!(
+                    "        [-] {} - {}:\tn/a",
+                    start_time.format("%H:%M"),
+                    end_time.format("%H:%M")
+                ),
+            }
+        }
+        println!(
+            "    [-] Energy (capture):\t{:.2} Wh",
+            self.energy.capture_in_wh
+        );
+        println!(
+            "    [-] Energy (encoding):\t{:.2} Wh",
+            self.energy.encoding_in_wh
+        );
+        println!(
+            "    [-] Energy (total):\t\t{:.2} Wh",
+            self.energy.total_in_wh
+        );
+        for segment in &self.most_interesting {
+            println!(
+                "    [-] Interesting:\t\t{} ({}, {})",
+                segment.file, segment.input, segment.interestingness
+            );
+        }
+        if let Some(baseline_nights) = self.baseline_nights {
+            if self.anomalies.is_empty() {
+                println!(
+                    "    [-] Baseline:\t\tas usual (compared to {} nights)",
+                    baseline_nights
+                );
+            }
+            for anomaly in &self.anomalies {
+                println!("    [!] Unusual:\t\t{}", anomaly);
+            }
+        }
+    }
+}
+
+/// The segments of a single session which were recorded during a night.
+struct NightlySession<'a> {
+    cpu_cores: usize,
+    label: Option<&'a str>,
+    segments: Vec<(NaiveDateTime, &'a SegmentManifest)>,
+}
+
+/// Get the time (in seconds) any of the segments was recording. Segments of different inputs
+/// which were recorded at the same time are only counted once.
+fn get_recorded_time_in_seconds(segments: &[(NaiveDateTime, &SegmentManifest)]) -> f32 {
+    let mut intervals: Vec<(i64, i64)> = segments
+        .iter()
+        .map(|(started_at, segment)| {
+            let start = started_at.and_utc().timestamp();
+            (start, start + i64::from(segment.duration_in_seconds))
+        })
+        .collect();
+    intervals.sort_unstable();
+
+    let mut recorded_time = 0;
+    let mut current_end = i64::MIN;
+    for (start, end) in intervals {
+        let start = start.max(current_end);
+        if end > start {
+            recorded_time += end - start;
+        }
+        current_end = current_end.max(end);
+    }
+    recorded_time as f32
+}
+
+/// The summary of the segments of one or more sessions.
+struct SessionSummary {
+    segment_count: usize,
+    recorded_time_in_seconds: f32,
+    events: Option<u32>,
+    channel_events: BTreeMap<String, u32>,
+    coughs: Option<u32>,
+    cough_times: Vec<NaiveDateTime>,
+    applied_gain_in_db: Option<f32>,
+    peak_level_in_dbfs: Option<f32>,
+    rms_level_in_dbfs: Option<f32>,
+    energy: EnergyEstimate,
+    most_interesting: Vec<InterestingSegmentReport>,
+}
+
+impl SessionSummary {
+    /// Get the figures which are compared against the baseline.
+    fn get_metrics(&self) -> NightMetrics {
+        let recorded_hours = self.recorded_time_in_seconds / 3600.0;
+        NightMetrics {
+            events_per_hour: self
+                .events
+                .filter(|_| recorded_hours > 0.0)
+                .map(|events| events as f32 / recorded_hours),
+            applied_gain_in_db: self.applied_gain_in_db,
+        }
+    }
+}
+
+/// Count the coughs in every hour (by the start of the hour) they happened in.
+fn get_coughs_per_hour(cough_times: &[NaiveDateTime]) -> BTreeMap<NaiveDateTime, u32> {
+    let mut coughs_per_hour = BTreeMap::new();
+    for cough_time in cough_times {
+        let hour = cough_time.date().and_time(NaiveTime::MIN)
+            + OldDuration::hours(i64::from(cough_time.hour()));
+        *coughs_per_hour.entry(hour).or_default() += 1;
+    }
+    coughs_per_hour
+}
+
+fn summarize_sessions(
+    nightly_sessions: &[&NightlySession],
+    config: &InsomniaProject,
+) -> SessionSummary {
+    let mut summary = SessionSummary {
+        segment_count: 0,
+        recorded_time_in_seconds: 0.0,
+        events: None,
+        channel_events: BTreeMap::new(),
+        coughs: None,
+        cough_times: vec![],
+        applied_gain_in_db: None,
+        peak_level_in_dbfs: None,
+        rms_level_in_dbfs: None,
+        energy: EnergyEstimate::default(),
+        most_interesting: vec![],
+    };
+    let mut applied_gains = vec![];
+    let mut rms_levels = vec![];
+    for nightly_session in nightly_sessions {
+        let segments: Vec<&SegmentManifest> = nightly_session
+            .segments
+            .iter()
+            .map(|(_, segment)| *segment)
+            .collect();
+        let session_time = get_recorded_time_in_seconds(&nightly_session.segments);
+        let session_energy = estimate_energy(
+            &segments,
+            session_time,
+            nightly_session.cpu_cores,
+            &config.power,
+        );
+
+        summary.segment_count += segments.len();
+        summary.recorded_time_in_seconds += session_time;
+        summary.energy.capture_in_wh += session_energy.capture_in_wh;
+        summary.energy.encoding_in_wh += session_energy.encoding_in_wh;
+        for segment_events in segments.iter().filter_map(|segment| segment.events) {
+            summary.events = Some(summary.events.unwrap_or(0) + segment_events);
+        }
+        for segment in &segments {
+            for (channel, channel_events) in &segment.channel_events {
+                *summary.channel_events.entry(channel.clone()).or_default() += channel_events;
+            }
+        }
+        // the offsets of the coughs start at the recording, which begins after the trimmed silence
+        for (started_at, segment) in &nightly_session.segments {
+            let cough_offsets = match &segment.cough_offsets_in_seconds {
+                Some(cough_offsets) => cough_offsets,
+                None => continue,
+            };
+            summary.coughs = Some(summary.coughs.unwrap_or(0) + cough_offsets.len() as u32);
+            let leading_trim = segment.leading_trim_in_seconds.unwrap_or(0.0);
+            summary
+                .cough_times
+                .extend(cough_offsets.iter().map(|cough_offset| {
+                    *started_at
+                        + OldDuration::milliseconds(
+                            ((leading_trim + f64::from(*cough_offset)) * 1000.0) as i64,
+                        )
+                }));
+        }
+        applied_gains.extend(
+            segments
+                .iter()
+                .filter_map(|segment| segment.applied_gain_in_db),
+        );
+        for peak_level in segments
+            .iter()
+            .filter_map(|segment| segment.peak_level_in_dbfs)
+        {
+            summary.peak_level_in_dbfs = Some(
+                summary
+                    .peak_level_in_dbfs
+                    .map_or(peak_level, |maximum| maximum.max(peak_level)),
+            );
+        }
+        rms_levels.extend(
+            segments
+                .iter()
+                .filter_map(|segment| segment.rms_level_in_dbfs),
+        );
+        // segments which were recorded before the scores were stored are scored now
+        for segment in &segments {
+            let interestingness = segment.interestingness.unwrap_or_else(|| {
+                score_segment(
+                    segment,
+                    get_usual_rms_level(segments.iter().copied(), &segment.input),
+                )
+            });
+            summary.most_interesting.push(InterestingSegmentReport {
+                file: segment.file.clone(),
+                input: segment.input.clone(),
+                interestingness,
+            });
+        }
+    }
+    summary
+        .most_interesting
+        .sort_by(|a, b| compare_interestingness(Some(a.interestingness), Some(b.interestingness)));
+    summary.most_interesting.truncate(MOST_INTERESTING_SEGMENTS);
+    summary.cough_times.sort_unstable();
+    summary.applied_gain_in_db = get_median(applied_gains);
+    summary.rms_level_in_dbfs = get_median(rms_levels);
+    summary
+}
+
+pub fn run_command_report(options: ReportCommandOptions, config: InsomniaProject) {
+    if options.by_label && options.json {
+        error!("The comparison by label can not be written as JSON. Terminating.");
+        return;
+    }
+    if options.by_label && options.breathing_rate {
+        error!("The breathing rate can not be compared by label. Terminating.");
+        return;
+    }
+
+    // a recorder with lower priority encoders pauses them while the report is generated
+    let _activity_marker = mark_as_running(&config, Subsystem::Analysis);
+    let input_folder = options
+        .input_folder
+        .unwrap_or_else(|| config.data_directory.clone());
+    let archive_reader = match ArchiveReader::open(Path::new(&input_folder)) {
+        Ok(archive_reader) => archive_reader,
+        Err(error) => {
+            error!(
+                "Could not read the session manifests in {}. The error was: {}",
+                input_folder, error
+            );
+            return;
+        }
+    };
+    let sessions = match archive_reader.get_sessions() {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            error!(
+                "Could not read the session manifests in {}. The error was: {}",
+                input_folder, error
+            );
+            return;
+        }
+    };
+
+    // group the segments of all sessions by the night they were recorded in
+    let mut nights: BTreeMap<NaiveDate, Vec<NightlySession>> = BTreeMap::new();
+    for session in &sessions {
+        let mut segments_per_night: BTreeMap<NaiveDate, Vec<_>> = BTreeMap::new();
+        for segment in &session.segments {
+            let started_at =
+                match DateTime::parse_from_str(&segment.started_at, MANIFEST_TIMESTAMP_FORMAT) {
+                    Ok(started_at) => started_at.naive_local(),
+                    Err(_) => continue,
+                };
+            segments_per_night
+                .entry(get_night_of(started_at))
+                .or_default()
+                .push((started_at, segment));
+        }
+        for (night, segments) in segments_per_night {
+            nights.entry(night).or_default().push(NightlySession {
+                cpu_cores: session.cpu_cores.unwrap_or(1),
+                label: session.label.as_deref(),
+                segments,
+            });
+        }
+    }
+
+    if nights.is_empty() {
+        error!("No recorded segments found in {}", input_folder);
+        return;
+    }
+
+    if options.by_label {
+        print_label_comparison(&nights, &config);
+        return;
+    }
+
+    // the recordings are only decoded for the breathing rate if it was asked for
+    let mut breathing_rates = BTreeMap::new();
+    if options.breathing_rate {
+        warn!(
+            "The breathing-rate estimation is EXPERIMENTAL and must not be used for medical purposes"
+        );
+        let archived_nights = match archive_reader.get_nights() {
+            Ok(archived_nights) => archived_nights,
+            Err(error) => {
+                error!(
+                    "Could not read the recordings in {}. The error was: {}",
+                    input_folder, error
+                );
+                return;
+            }
+        };
+        for archived_night in archived_nights
+            .iter()
+            .filter(|archived_night| nights.contains_key(&archived_night.get_date()))
+        {
+            let audio_files: Vec<&Path> = archived_night
+                .get_segments()
+                .iter()
+                .filter_map(|segment| {
+                    AUDIO_EXTENSIONS
+                        .iter()
+                        .find_map(|extension| segment.get_file_with_extension(extension))
+                })
+                .collect();
+            breathing_rates.insert(
+                archived_night.get_date(),
+                estimate_breathing_rates(&audio_files, options.quiet_threshold),
+            );
+        }
+    }
+
+    // every night is compared against the nights which were recorded before it
+    let mut preceding_nights: Vec<NightMetrics> = vec![];
+    let mut night_reports = vec![];
+    for (night, nightly_sessions) in &nights {
+        let sessions: Vec<&NightlySession> = nightly_sessions.iter().collect();
+        let summary = summarize_sessions(&sessions, &config);
+        let metrics = summary.get_metrics();
+        let baseline = Baseline::from_nights(&preceding_nights, &config.baseline);
+        preceding_nights.push(metrics);
+        let anomalies = baseline
+            .map(|baseline| baseline.get_anomalies(&metrics, &config.baseline))
+            .unwrap_or_default();
+        if options.anomalies_only && anomalies.is_empty() {
+            continue;
+        }
+        let mut labels: Vec<String> = nightly_sessions
+            .iter()
+            .filter_map(|nightly_session| nightly_session.label.map(str::to_string))
+            .collect();
+        labels.sort_unstable();
+        labels.dedup();
+
+        let night_report = NightReport {
+            night: night.to_string(),
+            labels,
+            segments: summary.segment_count,
+            recorded_in_seconds: summary.recorded_time_in_seconds,
+            events: summary.events,
+            channel_events: summary.channel_events,
+            coughs: summary.coughs,
+            cough_times: summary
+                .cough_times
+                .iter()
+                .map(|cough_time| cough_time.format(COUGH_TIME_FORMAT).to_string())
+                .collect(),
+            coughs_per_hour: get_coughs_per_hour(&summary.cough_times),
+            peak_level_in_dbfs: summary.peak_level_in_dbfs,
+            rms_level_in_dbfs: summary.rms_level_in_dbfs,
+            breathing_rates: breathing_rates
+                .remove(night)
+                .unwrap_or_default()
+                .into_iter()
+                .map(BreathingRateReport::from)
+                .collect(),
+            energy: EnergyReport {
+                capture_in_wh: summary.energy.capture_in_wh,
+                encoding_in_wh: summary.energy.encoding_in_wh,
+                total_in_wh: summary.energy.get_total_in_wh(),
+            },
+            most_interesting: summary.most_interesting,
+            baseline_nights: baseline.map(|baseline| baseline.nights),
+            anomalies,
+        };
+        if options.json {
+            night_reports.push(night_report);
+        } else {
+            night_report.print();
+        }
+    }
+
+    if options.json {
+        let report = Report {
+            version: REPORT_VERSION,
+            nights: night_reports,
+        };
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(error) => error!("Could not create the JSON report. The error was: {}", error),
+        }
+    }
+}
+
+/// Print the averages per night of the sessions with the same label, so the labeled sessions can
+/// be compared as cohorts.
+fn print_label_comparison(
+    nights: &BTreeMap<NaiveDate, Vec<NightlySession>>,
+    config: &InsomniaProject,
+) {
+    let mut cohorts: BTreeMap<&str, Vec<(NaiveDate, &NightlySession)>> = BTreeMap::new();
+    for (night, nightly_sessions) in nights {
+        for nightly_session in nightly_sessions {
+            cohorts
+                .entry(nightly_session.label.unwrap_or(UNLABELED))
+                .or_default()
+                .push((*night, nightly_session));
+        }
+    }
+
+    for (label, cohort) in cohorts {
+        let mut cohort_nights: Vec<NaiveDate> = cohort.iter().map(|(night, _)| *night).collect();
+        cohort_nights.dedup();
+        let night_count = cohort_nights.len() as f32;
+        let sessions: Vec<&NightlySession> = cohort.iter().map(|(_, session)| *session).collect();
+        let summary = summarize_sessions(&sessions, config);
+        let recorded_hours = summary.recorded_time_in_seconds / 3600.0;
+
+        println!("[*] Label {}", label);
+        println!("    [-] Nights:\t\t\t{}", cohort_nights.len());
+        println!(
+            "    [-] Recorded per night:\t{:.1} h",
+            recorded_hours / night_count
+        );
+        if let Some(events) = summary.events {
+            println!(
+                "    [-] Events per night:\t{:.1}",
+                events as f32 / night_count
+            );
+            if recorded_hours > 0.0 {
+                println!(
+                    "    [-] Events per hour:\t{:.1}",
+                    events as f32 / recorded_hours
+                );
+            }
+        }
+        for (channel, channel_events) in &summary.channel_events {
+            println!(
+                "        [-] {}:\t\t{:.1} per night",
+                channel,
+                *channel_events as f32 / night_count
+            );
+        }
+        if let Some(coughs) = summary.coughs {
+            println!(
+                "    [-] Coughs per night:\t{:.1}",
+                coughs as f32 / night_count
+            );
+        }
+        println!(
+            "    [-] Energy per night:\t{:.2} Wh",
+            summary.energy.get_total_in_wh() / night_count
+        );
+    }
+}
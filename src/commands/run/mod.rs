@@ -0,0 +1,123 @@
+use std::env;
+use std::ffi::OsString;
+use std::thread;
+
+use clap::Clap;
+use toml::Value;
+use tracing::{error, info, warn};
+
+use crate::commands::record::{run_recorder, RecordCommandOptions};
+use crate::commands::upload::{run_command_upload, UploadCommandOptions};
+use crate::metrics::start_metrics_server;
+use crate::shutdown::{install_signal_handlers, request_shutdown};
+use crate::status::StatusBoard;
+use crate::InsomniaProject;
+
+/// The prefix of the environment variables which override settings of the project file.
+const ENVIRONMENT_PREFIX: &str = "SCHLAFLOSIGKEIT_";
+
+/// Run the recorder (with its encoding and maintenance tasks) and the uploads in a single process,
+/// e.g. as the entrypoint of a container. The log is written as JSON to stdout, the health and the
+/// metrics are served over HTTP and a SIGTERM lets the current segments finish before exiting.
+#[derive(Clap)]
+pub struct RunCommandOptions {
+    /// The address the health (`/health`) and metrics (`/metrics`) endpoints are served on.
+    #[clap(long, default_value = "0.0.0.0:9090")]
+    listen: String,
+}
+
+/// Parse the value of an environment variable like a value in the project file. Values which are
+/// not valid TOML (e.g. paths) are used as strings.
+fn parse_environment_value(value: &str) -> Value {
+    toml::from_str::<Value>(&format!("value = {}", value))
+        .ok()
+        .and_then(|table| table.get("value").cloned())
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+/// Override the settings of the project with the environment variables which start with
+/// `SCHLAFLOSIGKEIT_`. The sections of the key are separated by two underscores, e.g.
+/// `SCHLAFLOSIGKEIT_DATA_DIRECTORY` or `SCHLAFLOSIGKEIT_UPLOAD__DESTINATION`. Other variables
+/// and the ones which are not valid UTF-8 are skipped.
+fn apply_environment<I>(config: &InsomniaProject, variables: I) -> Result<InsomniaProject, String>
+where
+    I: IntoIterator<Item = (OsString, OsString)>,
+{
+    let mut value = Value::try_from(config).map_err(|error| error.to_string())?;
+    for (name, variable_value) in variables {
+        let name = match name.into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let key = match name.strip_prefix(ENVIRONMENT_PREFIX) {
+            Some(key) if !key.is_empty() => key.to_lowercase(),
+            _ => continue,
+        };
+        let variable_value = match variable_value.into_string() {
+            Ok(variable_value) => variable_value,
+            Err(_) => {
+                warn!("The value of {} is not valid UTF-8, it is ignored", name);
+                continue;
+            }
+        };
+        let path: Vec<&str> = key.split("__").collect();
+        let mut table = value
+            .as_table_mut()
+            .ok_or_else(|| "the project is not a table".to_string())?;
+        for section in &path[..path.len() - 1] {
+            table = table
+                .entry(section.to_string())
+                .or_insert_with(|| Value::Table(Default::default()))
+                .as_table_mut()
+                .ok_or_else(|| format!("{} is not a section of the project file", section))?;
+        }
+        table.insert(
+            path[path.len() - 1].to_string(),
+            parse_environment_value(&variable_value),
+        );
+        info!("The setting {} is set by the environment", path.join("."));
+    }
+    value
+        .try_into()
+        .map_err(|error: toml::de::Error| error.to_string())
+}
+
+pub fn run_command_run(options: RunCommandOptions, config: InsomniaProject) {
+    let config = match apply_environment(&config, env::vars_os()) {
+        Ok(config) => config,
+        Err(error) => {
+            error!(
+                "The settings of the environment are invalid: {}. Terminating.",
+                error
+            );
+            return;
+        }
+    };
+
+    // a SIGTERM of the container runtime stops the uploads as well as the recorder
+    install_signal_handlers();
+    let status_board = StatusBoard::new();
+    if let Err(error) = start_metrics_server(&options.listen, status_board.clone()) {
+        error!(
+            "Could not serve the metrics on {}. Terminating. The error was: {}",
+            options.listen, error
+        );
+        return;
+    }
+
+    // the uploads run next to the recorder and finish their current files on a shutdown
+    let upload_thread = config.upload.is_some().then(|| {
+        let config = config.clone();
+        thread::spawn(move || run_command_upload(UploadCommandOptions::watching(), config))
+    });
+
+    run_recorder(RecordCommandOptions::default(), config, status_board);
+
+    // the recorder also returns on errors, the uploads are stopped then as well
+    request_shutdown();
+
+    if let Some(upload_thread) = upload_thread {
+        info!("Waiting for the running uploads to finish");
+        let _ = upload_thread.join();
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use clap::Clap;
+use tracing::error;
+
+use crate::archive::{ArchiveReader, Night};
+use crate::baseline::{get_median, get_percentile};
+use crate::priority::{mark_as_running, Subsystem};
+use crate::InsomniaProject;
+
+/// The percentiles which are printed for the figures of the nights.
+const PERCENTILES: [f32; 3] = [10.0, 50.0, 90.0];
+
+/// Print totals and percentiles of all recordings in the archive.
+#[derive(Clap)]
+pub struct StatsCommandOptions {
+    /// The folder with the recordings and session manifests (the data directory of the project
+    /// is used if none is specified).
+    #[clap(index = 1)]
+    input_folder: Option<String>,
+}
+
+/// The size and the number of the files which are stored in one format.
+#[derive(Default)]
+struct StorageUsage {
+    files: usize,
+    size_in_bytes: u64,
+}
+
+/// Describe the 10th, 50th and 90th percentile of the values, formatted with the supplied
+/// function.
+fn describe_percentiles<F>(values: &[f32], format: F) -> String
+where
+    F: Fn(f32) -> String,
+{
+    PERCENTILES
+        .iter()
+        .map(
+            |percentile| match get_percentile(values.to_vec(), *percentile) {
+                Some(value) => format!("p{:.0} {}", percentile, format(value)),
+                None => format!("p{:.0} -", percentile),
+            },
+        )
+        .collect::<Vec<String>>()
+        .join(" / ")
+}
+
+/// Get the format of a file, which is everything after the first dot of its name (e.g. `mp3` or
+/// `wav`).
+fn get_format(path: &Path) -> String {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|file_name| file_name.split_once('.'))
+        .map_or_else(|| "(none)".to_string(), |(_, format)| format.to_lowercase())
+}
+
+/// Get the recorded time (in hours) of a night, only segments listed in a session manifest have a
+/// known duration.
+fn get_recorded_hours(night: &Night) -> f32 {
+    night
+        .get_segments()
+        .iter()
+        .filter_map(|segment| segment.get_duration_in_seconds())
+        .fold(0.0, |hours, duration| hours + duration as f32 / 3600.0)
+}
+
+pub fn run_command_stats(options: StatsCommandOptions, config: InsomniaProject) {
+    // a recorder with lower priority encoders pauses them while the archive is scanned
+    let _activity_marker = mark_as_running(&config, Subsystem::Analysis);
+    let input_folder = options
+        .input_folder
+        .unwrap_or_else(|| config.data_directory.clone());
+    let nights = match ArchiveReader::open(Path::new(&input_folder))
+        .and_then(|archive_reader| archive_reader.get_nights())
+    {
+        Ok(nights) => nights,
+        Err(error) => {
+            error!(
+                "Could not read the recordings in {}. The error was: {}",
+                input_folder, error
+            );
+            return;
+        }
+    };
+    let (first_night, last_night) = match (nights.first(), nights.last()) {
+        (Some(first_night), Some(last_night)) => (first_night, last_night),
+        _ => {
+            error!("No recorded segments found in {}", input_folder);
+            return;
+        }
+    };
+
+    let mut segment_count = 0;
+    let mut silent_segments = 0;
+    let mut storage: BTreeMap<String, StorageUsage> = BTreeMap::new();
+    let mut total_events: Option<u32> = None;
+    let mut channel_events: BTreeMap<String, u32> = BTreeMap::new();
+    let mut hours_per_night = vec![];
+    let mut events_per_night = vec![];
+    let mut noise_per_night = vec![];
+    for night in &nights {
+        let segments = night.get_segments();
+        segment_count += segments.len();
+        silent_segments += segments
+            .iter()
+            .filter(|segment| segment.is_silent())
+            .count();
+        for file in segments.iter().flat_map(|segment| segment.get_files()) {
+            let usage = storage.entry(get_format(file)).or_default();
+            usage.files += 1;
+            usage.size_in_bytes += file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        }
+
+        let mut night_events: Option<u32> = None;
+        for segment in segments {
+            if let Some(events) = segment.get_events() {
+                night_events = Some(night_events.unwrap_or(0) + events);
+            }
+            for (channel, events) in segment.get_channel_events() {
+                *channel_events.entry(channel.clone()).or_default() += events;
+            }
+        }
+        if let Some(night_events) = night_events {
+            total_events = Some(total_events.unwrap_or(0) + night_events);
+            events_per_night.push(night_events as f32);
+        }
+        hours_per_night.push(get_recorded_hours(night));
+
+        // the noise of a night is the median RMS level of its segments which are not silent
+        let night_noise = get_median(
+            segments
+                .iter()
+                .filter(|segment| !segment.is_silent())
+                .filter_map(|segment| segment.get_rms_level_in_dbfs())
+                .collect(),
+        );
+        if let Some(night_noise) = night_noise {
+            noise_per_night.push(night_noise);
+        }
+    }
+
+    println!(
+        "[*] Nights:\t\t\t{} ({} to {})",
+        nights.len(),
+        first_night.get_date(),
+        last_night.get_date()
+    );
+    println!(
+        "[*] Recorded:\t\t\t{:.1} h",
+        hours_per_night.iter().sum::<f32>()
+    );
+    println!(
+        "    [-] Per night:\t\t{}",
+        describe_percentiles(&hours_per_night, |hours| format!("{:.1} h", hours))
+    );
+    println!(
+        "[*] Segments:\t\t\t{} ({} silent)",
+        segment_count, silent_segments
+    );
+    println!(
+        "[*] Storage:\t\t\t{} MB",
+        storage
+            .values()
+            .map(|usage| usage.size_in_bytes)
+            .sum::<u64>()
+            / 1024
+            / 1024
+    );
+    for (format, usage) in &storage {
+        println!(
+            "    [-] {}:\t\t\t{} MB ({} files)",
+            format,
+            usage.size_in_bytes / 1024 / 1024,
+            usage.files
+        );
+    }
+    match total_events {
+        Some(total_events) => {
+            println!("[*] Events:\t\t\t{}", total_events);
+            println!(
+                "    [-] Per night:\t\t{}",
+                describe_percentiles(&events_per_night, |events| format!("{:.0}", events))
+            );
+            for (channel, events) in &channel_events {
+                println!("    [-] {}:\t\t\t{}", channel, events);
+            }
+        }
+        None => println!("[*] Events:\t\t\tnot counted"),
+    }
+    match get_median(noise_per_night.clone()) {
+        Some(median_noise) => {
+            println!("[*] Noise (median):\t\t{:.1} dBFS", median_noise);
+            println!(
+                "    [-] Per night:\t\t{}",
+                describe_percentiles(&noise_per_night, |noise| format!("{:.1} dBFS", noise))
+            );
+        }
+        None => println!("[*] Noise (median):\t\tnot measured"),
+    }
+}
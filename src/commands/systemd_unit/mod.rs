@@ -0,0 +1,187 @@
+use std::env::{current_dir, current_exe, var};
+use std::fs::{canonicalize, write};
+use std::path::Path;
+
+use clap::Clap;
+use tracing::error;
+
+use crate::InsomniaProject;
+
+/// The restart policies of systemd which can be selected for the recorder.
+const RESTART_POLICIES: [&str; 6] = [
+    "no",
+    "always",
+    "on-success",
+    "on-failure",
+    "on-abnormal",
+    "on-watchdog",
+];
+
+/// The time the post-processing of the last segment may take after the recorder was asked to stop.
+const POST_PROCESSING_TIMEOUT_IN_SECONDS: u64 = 300;
+
+/// Create a systemd service unit which starts the recorder for the project on boot.
+#[derive(Clap)]
+pub struct SystemdUnitCommandOptions {
+    /// The file the unit is written to (e.g. `/etc/systemd/system/schlaflosigkeit.service`), it is
+    /// printed if none is specified.
+    #[clap(long)]
+    output: Option<String>,
+
+    /// The user the recorder runs as (the current user is used if none is specified).
+    #[clap(long)]
+    user: Option<String>,
+
+    /// The restart policy of systemd (no, always, on-success, on-failure, on-abnormal or
+    /// on-watchdog).
+    #[clap(long, default_value = "on-failure")]
+    restart: String,
+
+    /// The interval (in seconds) in which the recorder has to ping the watchdog of systemd, 0
+    /// disables the watchdog.
+    #[clap(long, default_value = "60")]
+    watchdog: u64,
+}
+
+/// Quote a single argument of a command line for systemd, which also expands `%` specifiers.
+fn quote_argument(argument: &str) -> String {
+    let escaped = argument.replace('%', "%%");
+    if escaped
+        .chars()
+        .any(|character| character.is_whitespace() || "\"'\\;$".contains(character))
+    {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+/// Get the name of the user this process runs as.
+fn get_current_user() -> Option<String> {
+    if let Some(user) = var("USER").ok().or_else(|| var("LOGNAME").ok()) {
+        return Some(user);
+    }
+    #[cfg(unix)]
+    unsafe {
+        let passwd = libc::getpwuid(libc::geteuid());
+        if !passwd.is_null() && !(*passwd).pw_name.is_null() {
+            return Some(
+                std::ffi::CStr::from_ptr((*passwd).pw_name)
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+    }
+    None
+}
+
+/// Get the absolute path of a file as a string.
+fn get_absolute_path(path: &Path) -> Result<String, String> {
+    canonicalize(path)
+        .map_err(|error| format!("could not resolve {}: {}", path.display(), error))
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+pub fn run_command_systemd_unit(
+    options: SystemdUnitCommandOptions,
+    project_file: &str,
+    config: InsomniaProject,
+) {
+    if !RESTART_POLICIES.contains(&options.restart.as_str()) {
+        error!(
+            "Unknown restart policy '{}', it has to be one of {}. Terminating.",
+            options.restart,
+            RESTART_POLICIES.join(", ")
+        );
+        return;
+    }
+    let user = match options.user.or_else(get_current_user) {
+        Some(user) => user,
+        None => {
+            error!("The current user could not be determined, please select one with --user. Terminating.");
+            return;
+        }
+    };
+
+    // the unit must not depend on the directory it was created in
+    let paths = current_exe()
+        .map_err(|error| format!("could not determine the executable: {}", error))
+        .and_then(|executable| get_absolute_path(&executable))
+        .and_then(|executable| {
+            get_absolute_path(Path::new(project_file)).map(|project| (executable, project))
+        })
+        .and_then(|(executable, project)| {
+            current_dir()
+                .map_err(|error| format!("could not determine the current directory: {}", error))
+                .map(|working_directory| {
+                    (
+                        executable,
+                        project,
+                        working_directory.to_string_lossy().to_string(),
+                    )
+                })
+        });
+    let (executable, project, working_directory) = match paths {
+        Ok(paths) => paths,
+        Err(error) => {
+            error!("The unit could not be created: {}. Terminating.", error);
+            return;
+        }
+    };
+
+    // the recorder finishes the current segment when it is stopped, so systemd has to wait for it
+    let stop_timeout_in_seconds = u64::from(config.defaults.get_segment_duration().get_seconds())
+        + u64::from(config.defaults.overlap_in_seconds)
+        + POST_PROCESSING_TIMEOUT_IN_SECONDS;
+    let mut unit = vec![
+        "[Unit]".to_string(),
+        "Description=Audio recorder of schlaflosigkeit".to_string(),
+        "Wants=sound.target time-sync.target".to_string(),
+        "After=sound.target time-sync.target".to_string(),
+        String::new(),
+        "[Service]".to_string(),
+        "Type=notify".to_string(),
+        format!("User={}", user),
+        "SupplementaryGroups=audio".to_string(),
+        format!("WorkingDirectory={}", working_directory.replace('%', "%%")),
+        format!(
+            "ExecStart={} {} record",
+            quote_argument(&executable),
+            quote_argument(&project)
+        ),
+        format!("Restart={}", options.restart),
+        "RestartSec=10".to_string(),
+        format!("TimeoutStopSec={}", stop_timeout_in_seconds),
+    ];
+    if options.watchdog > 0 {
+        unit.push(format!("WatchdogSec={}", options.watchdog));
+    }
+    unit.extend(vec![
+        String::new(),
+        "[Install]".to_string(),
+        "WantedBy=multi-user.target".to_string(),
+    ]);
+    let unit = unit.join("\n") + "\n";
+
+    match options.output {
+        Some(output) => {
+            if let Err(error) = write(&output, unit) {
+                error!(
+                    "Could not write the unit to {}. The error was: {}",
+                    output, error
+                );
+                return;
+            }
+            let unit_name = Path::new(&output)
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or(output.clone());
+            println!("[*] The unit was written to {}", output);
+            println!(
+                "    [-] Activate it with:\tsystemctl daemon-reload && systemctl enable --now {}",
+                unit_name
+            );
+        }
+        None => print!("{}", unit),
+    }
+}
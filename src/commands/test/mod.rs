@@ -0,0 +1,162 @@
+use std::env::temp_dir;
+use std::fs::{create_dir_all, remove_file};
+use std::path::{Path, PathBuf};
+
+use clap::Clap;
+use tracing::error;
+
+use crate::annotation::WaveMetaReader;
+use crate::backend::record_audio_with_backend;
+use crate::wave::{read_format, read_samples};
+use crate::{InsomniaProject, RecordingDeviceConfiguration};
+
+/// The share of the requested duration a test recording has to reach.
+const MINIMUM_DURATION_RATIO: f64 = 0.8;
+
+/// Record a short clip from every configured input and check that it has the configured format and
+/// contains audio, so a misconfigured setup is noticed before a whole night is recorded.
+#[derive(Clap)]
+pub struct TestCommandOptions {
+    /// The name of the input device which should be tested (all inputs if none is specified).
+    #[clap(index = 1)]
+    input: Option<String>,
+
+    /// The duration (in seconds) of the clip which is recorded from every input.
+    #[clap(long, default_value = "5")]
+    duration: u32,
+
+    /// Keep the recorded clips instead of removing them after they were checked.
+    #[clap(long)]
+    keep: bool,
+}
+
+/// Check the header and the content of a test recording. The problems which were found are
+/// returned.
+fn check_recording(
+    path: &Path,
+    input_device: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+) -> Vec<String> {
+    let mut problems = vec![];
+    let format = match read_format(path) {
+        Ok(format) => format,
+        Err(error) => return vec![format!("the header could not be read ({})", error)],
+    };
+    let expected_channels = if input_device.mono { 1 } else { 2 };
+    if format.channels != expected_channels {
+        problems.push(format!(
+            "{} channel(s) were recorded instead of {}",
+            format.channels, expected_channels
+        ));
+    }
+    if format.samples_per_second != input_device.sample_rate {
+        problems.push(format!(
+            "the sample rate is {} Hz instead of {} Hz",
+            format.samples_per_second, input_device.sample_rate
+        ));
+    }
+    if format.sample_format != Some(input_device.format) {
+        problems.push(format!(
+            "the samples have {} bits instead of the format {}",
+            format.bits_per_sample, input_device.format
+        ));
+    }
+
+    let duration = WaveMetaReader::from_file(&path.to_string_lossy())
+        .map(|meta_reader| meta_reader.get_duration())
+        .unwrap_or_default();
+    if duration < f64::from(duration_in_seconds) * MINIMUM_DURATION_RATIO {
+        problems.push(format!(
+            "only {:.1} of {} seconds were recorded",
+            duration, duration_in_seconds
+        ));
+    }
+
+    // a device which only delivers zeros is muted or not connected
+    match read_samples(path) {
+        Ok((_, samples)) if samples.iter().all(|sample| *sample == 0.0) => {
+            problems.push("the recording only contains silence (all samples are zero)".to_string())
+        }
+        Ok(_) => {}
+        Err(error) => problems.push(format!("the samples could not be read ({})", error)),
+    }
+    problems
+}
+
+pub fn run_command_test(options: TestCommandOptions, config: InsomniaProject) {
+    let mut input_names: Vec<&String> = match &options.input {
+        Some(input_name) if !config.input.contains_key(input_name) => {
+            error!("The input {} is not configured. Terminating.", input_name);
+            return;
+        }
+        Some(input_name) => vec![input_name],
+        None => config.input.keys().collect(),
+    };
+    input_names.sort();
+
+    let working_folder = temp_dir().join("schlaflosigkeit-test");
+    if let Err(error) = create_dir_all(&working_folder) {
+        error!(
+            "Could not create the folder {} for the test recordings. Terminating. The error was: {}",
+            working_folder.display(),
+            error
+        );
+        return;
+    }
+
+    let mut passed_inputs = 0;
+    for input_name in &input_names {
+        let input_device = &config.input[*input_name];
+        let configured_backend = input_device.get_backend(config.backend);
+        let backend = configured_backend.resolve();
+        println!("[*] Testing {}", input_name);
+        println!(
+            "    [-] Backend:\t\t{}",
+            backend.unwrap_or(configured_backend)
+        );
+
+        let problems = match backend {
+            None => vec![format!(
+                "the backend {} is not available in this build",
+                configured_backend
+            )],
+            Some(backend) => match record_audio_with_backend(
+                backend,
+                input_device,
+                options.duration,
+                working_folder.to_string_lossy().to_string(),
+                config.backpressure,
+                None,
+                &config.capture_watchdog,
+            ) {
+                None => vec!["nothing could be recorded".to_string()],
+                Some(recorded_segment) => {
+                    let recording = PathBuf::from(format!("{}.wav", recorded_segment.file_prefix));
+                    let problems = check_recording(&recording, input_device, options.duration);
+                    if options.keep {
+                        println!("    [-] Recording:\t\t{}", recording.display());
+                    } else {
+                        let _ = remove_file(&recording);
+                    }
+                    problems
+                }
+            },
+        };
+
+        if problems.is_empty() {
+            passed_inputs += 1;
+            println!("    [-] Result:\t\t\tpass");
+        } else {
+            println!("    [-] Result:\t\t\tfail");
+            for problem in problems {
+                println!("        [-] {}", problem);
+            }
+        }
+    }
+
+    println!(
+        "[*] Summary:\t\t\t{} of {} input(s) passed",
+        passed_inputs,
+        input_names.len()
+    );
+}
@@ -0,0 +1,73 @@
+use std::io::{self, IsTerminal};
+
+use clap::Clap;
+use tracing::error;
+
+use crate::commands::delete::confirm;
+use crate::update::{
+    check_for_update, get_executable, install_update, stage_update, CURRENT_VERSION,
+};
+use crate::InsomniaProject;
+
+/// Replace the binary with the latest release on GitHub if it is newer. The download is verified
+/// with its checksum and signature before it is installed, a release which can only be verified by
+/// its checksum (no public key is configured) has to be confirmed.
+#[derive(Clap)]
+pub struct UpdateCommandOptions {
+    /// Only check if a newer release is available without installing it.
+    #[clap(long)]
+    check: bool,
+
+    /// Do not ask for a confirmation before installing a release which is only verified by its
+    /// checksum.
+    #[clap(long)]
+    yes: bool,
+}
+
+pub fn run_command_update(options: UpdateCommandOptions, config: InsomniaProject) {
+    let update_configuration = config.update.unwrap_or_default();
+    println!("[*] Installed version:\t\t{}", CURRENT_VERSION);
+    let release = match check_for_update(&update_configuration) {
+        Ok(Some(release)) => release,
+        Ok(None) => {
+            println!("[*] The installed version is up to date");
+            return;
+        }
+        Err(error) => {
+            error!("Could not check for updates. The error was: {}", error);
+            return;
+        }
+    };
+    println!("[*] Available version:\t\t{}", release.version);
+    if options.check {
+        return;
+    }
+
+    // the checksum is downloaded from the same release, so it does not prove who built the binary
+    if update_configuration.public_key.is_none() && !options.yes {
+        println!("[!] No public key is configured, the release is only verified by its checksum");
+        if !io::stdin().is_terminal() {
+            error!("The installation can not be confirmed without a terminal, please use --yes or configure a public_key. Terminating.");
+            return;
+        }
+        if !confirm("Install the unsigned release anyway?") {
+            println!("[*] Nothing was installed");
+            return;
+        }
+    }
+
+    let result = get_executable().and_then(|executable| {
+        stage_update(&release, &executable, &update_configuration)
+            .and_then(|staged_binary| install_update(&staged_binary, &executable))
+    });
+    match result {
+        Ok(()) => println!(
+            "[*] Updated to the version {}, restart the recorder to use it",
+            release.version
+        ),
+        Err(error) => error!(
+            "Could not update to the version {}. The error was: {}",
+            release.version, error
+        ),
+    }
+}
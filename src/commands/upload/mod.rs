@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::Duration;
+
+use clap::Clap;
+use tracing::{error, info, info_span, warn};
+
+use crate::archive::layout::Archive;
+use crate::shutdown::sleep_unless_shutdown;
+use crate::upload::{get_pending_uploads, get_relative_path, upload_file, UploadLedger};
+use crate::InsomniaProject;
+
+/// Upload the encoded files of the archive which were not uploaded yet (with rsync).
+#[derive(Clap)]
+pub struct UploadCommandOptions {
+    /// Keep running and upload new encoded files as soon as they are finished.
+    #[clap(long)]
+    watch: bool,
+
+    /// The number of files which are uploaded at the same time (the configured concurrency is used
+    /// if none is specified).
+    #[clap(long)]
+    jobs: Option<usize>,
+
+    /// The bandwidth (in kbit/s) all uploads together may use (overrides the configured limit).
+    #[clap(long)]
+    bandwidth_limit: Option<u32>,
+}
+
+impl UploadCommandOptions {
+    /// Get the options for watching the encoded folder with the configured settings.
+    pub fn watching() -> UploadCommandOptions {
+        UploadCommandOptions {
+            watch: true,
+            jobs: None,
+            bandwidth_limit: None,
+        }
+    }
+}
+
+/// Upload the files with a fixed number of workers, the bandwidth limit is shared between them.
+/// The number of failed uploads is returned.
+fn upload_files(
+    files: Vec<PathBuf>,
+    encoded_folder: &Path,
+    destination: &str,
+    jobs: usize,
+    bandwidth_limit_in_kbits: Option<u32>,
+    ledger: &Arc<UploadLedger>,
+) -> usize {
+    let total = files.len();
+    let jobs = jobs.min(total).max(1);
+    let bandwidth_limit_per_job =
+        bandwidth_limit_in_kbits.map(|bandwidth_limit| (bandwidth_limit / jobs as u32).max(1));
+
+    let queue = Arc::new(Mutex::new(files.into_iter().collect::<VecDeque<_>>()));
+    let failed = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..jobs)
+        .map(|_| {
+            let queue = queue.clone();
+            let failed = failed.clone();
+            let ledger = ledger.clone();
+            let encoded_folder = encoded_folder.to_path_buf();
+            let destination = destination.to_string();
+            spawn(move || loop {
+                let file = match queue.lock().unwrap().pop_front() {
+                    Some(file) => file,
+                    None => return,
+                };
+                let relative_path = get_relative_path(&file, &encoded_folder).unwrap_or_default();
+                let _upload_entered = info_span!("upload", file = %relative_path).entered();
+                match upload_file(
+                    &file,
+                    &encoded_folder,
+                    &destination,
+                    bandwidth_limit_per_job,
+                ) {
+                    Ok(()) => {
+                        info!("Uploaded {}", relative_path);
+                        if let Err(error) = ledger.add(&relative_path) {
+                            error!(
+                                "Could not add {} to the list of uploaded files. The error was: {}",
+                                relative_path, error
+                            );
+                        }
+                    }
+                    Err(error) => {
+                        failed.fetch_add(1, Ordering::SeqCst);
+                        warn!("Could not upload {}: {}", relative_path, error);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    failed.load(Ordering::SeqCst)
+}
+
+pub fn run_command_upload(options: UploadCommandOptions, config: InsomniaProject) {
+    let upload_configuration = match &config.upload {
+        Some(upload_configuration) => upload_configuration,
+        None => {
+            error!("No upload destination is configured. Terminating.");
+            return;
+        }
+    };
+    let archive = match Archive::open(Path::new(&config.data_directory)) {
+        Ok(archive) => archive,
+        Err(error) => {
+            error!(
+                "Could not open the data directory. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    let ledger = match UploadLedger::open(&archive.get_state_folder()) {
+        Ok(ledger) => Arc::new(ledger),
+        Err(error) => {
+            error!(
+                "Could not read the list of uploaded files. Terminating. The error was: {}",
+                error
+            );
+            return;
+        }
+    };
+    let jobs = options
+        .jobs
+        .unwrap_or(upload_configuration.concurrency)
+        .max(1);
+    let bandwidth_limit_in_kbits = options
+        .bandwidth_limit
+        .or(upload_configuration.bandwidth_limit_in_kbits);
+    let poll_interval = Duration::from_secs(upload_configuration.poll_interval_in_seconds.max(1));
+    let encoded_folder = archive.get_encoded_folder();
+    if options.watch {
+        info!(
+            "Watching {} for new encoded files",
+            encoded_folder.display()
+        );
+    }
+
+    // without watching, the pending files are uploaded once
+    loop {
+        match get_pending_uploads(&encoded_folder, &ledger) {
+            Ok(files) if files.is_empty() => {
+                if !options.watch {
+                    println!("[*] No files have to be uploaded");
+                }
+            }
+            Ok(files) => {
+                let total = files.len();
+                info!(
+                    "Uploading {} file(s) to {}",
+                    total, upload_configuration.destination
+                );
+                let failed = upload_files(
+                    files,
+                    &encoded_folder,
+                    &upload_configuration.destination,
+                    jobs,
+                    bandwidth_limit_in_kbits,
+                    &ledger,
+                );
+                if !options.watch {
+                    println!(
+                        "[*] Uploaded {} of {} file(s), {} failed",
+                        total - failed,
+                        total,
+                        failed
+                    );
+                }
+            }
+            Err(error) => error!(
+                "Could not read the encoded folder. The error was: {}",
+                error
+            ),
+        }
+        if !options.watch || !sleep_unless_shutdown(poll_interval) {
+            return;
+        }
+    }
+}
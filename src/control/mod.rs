@@ -0,0 +1,228 @@
+use core::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::archive::layout::STATE_FOLDER_NAME;
+use crate::shutdown::{is_shutdown_requested, request_shutdown, request_split};
+
+/// The name of the control socket in the state folder of the archive, if no other path is
+/// configured.
+pub const CONTROL_SOCKET_NAME: &str = "control.sock";
+
+/// The time a client of the control socket has for sending its command.
+#[cfg(unix)]
+const CLIENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Set while the recording is paused by a request on the control socket.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// The settings of the control socket, on which other programs (like the `ctl` command) can ask
+/// the running recorder to pause, resume, split the current segment or shut down.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ControlConfiguration {
+    #[serde(default = "ControlConfiguration::default_enabled")]
+    pub enabled: bool,
+
+    /// The path of the Unix socket, by default `control.sock` in the state folder of the data
+    /// directory.
+    #[serde(default = "ControlConfiguration::default_socket")]
+    pub socket: Option<String>,
+}
+
+impl ControlConfiguration {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_socket() -> Option<String> {
+        None
+    }
+
+    /// Get the path of the control socket of the recorder which uses the supplied data directory.
+    pub fn get_socket_path(&self, data_directory: &str) -> PathBuf {
+        match &self.socket {
+            Some(socket) => PathBuf::from(socket),
+            None => Path::new(data_directory)
+                .join(STATE_FOLDER_NAME)
+                .join(CONTROL_SOCKET_NAME),
+        }
+    }
+}
+
+impl Default for ControlConfiguration {
+    fn default() -> Self {
+        ControlConfiguration {
+            enabled: ControlConfiguration::default_enabled(),
+            socket: ControlConfiguration::default_socket(),
+        }
+    }
+}
+
+/// A request to the running recorder. The commands are sent as a line of text (e.g. `pause`), the
+/// recorder replies with `ok` or with `error: ` followed by the reason.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlCommand {
+    /// Finish the current segment and do not record until the recording is resumed.
+    Pause,
+
+    /// Continue a paused recording with a new segment.
+    Resume,
+
+    /// Finish the current segment now and start the next one.
+    Split,
+
+    /// Finish the current segment and its post-processing and stop, like on a SIGTERM.
+    Shutdown,
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pause" => Ok(ControlCommand::Pause),
+            "resume" => Ok(ControlCommand::Resume),
+            "split" => Ok(ControlCommand::Split),
+            "shutdown" => Ok(ControlCommand::Shutdown),
+            _ => Err(format!("unknown command '{}'", value)),
+        }
+    }
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ControlCommand::Pause => write!(f, "pause"),
+            ControlCommand::Resume => write!(f, "resume"),
+            ControlCommand::Split => write!(f, "split"),
+            ControlCommand::Shutdown => write!(f, "shutdown"),
+        }
+    }
+}
+
+/// Check if the recording was paused on the control socket.
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::SeqCst)
+}
+
+/// Execute a command which was received on the control socket.
+fn execute(command: ControlCommand) -> Result<(), String> {
+    if is_shutdown_requested() {
+        return Err("the recorder is shutting down".to_string());
+    }
+    match command {
+        ControlCommand::Pause => {
+            if !PAUSED.swap(true, Ordering::SeqCst) {
+                info!("Pausing the recording after the current segment was finished");
+                request_split();
+            }
+        }
+        ControlCommand::Resume => {
+            if PAUSED.swap(false, Ordering::SeqCst) {
+                info!("Resuming the recording");
+            }
+        }
+        ControlCommand::Split => {
+            if is_paused() {
+                return Err("the recording is paused".to_string());
+            }
+            info!("Splitting the current segment");
+            request_split();
+        }
+        ControlCommand::Shutdown => {
+            info!("Stopping the recording after a request on the control socket");
+            request_shutdown();
+        }
+    }
+    Ok(())
+}
+
+/// The control socket of the running recorder. The socket file is removed when it is dropped.
+pub struct ControlServer {
+    socket_path: PathBuf,
+}
+
+impl ControlServer {
+    /// Listen on the socket and execute the commands of the clients in the background. A stale
+    /// socket file of a previous run is replaced, so the data directory has to be locked before.
+    #[cfg(unix)]
+    pub fn start(socket_path: &Path) -> io::Result<ControlServer> {
+        use std::fs::remove_file;
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixListener;
+        use std::thread::spawn;
+        use tracing::{debug, warn};
+
+        let _ = remove_file(socket_path);
+        let listener = UnixListener::bind(socket_path)?;
+        spawn(move || {
+            for client in listener.incoming() {
+                let mut client = match client {
+                    Ok(client) => client,
+                    Err(error) => {
+                        warn!("Could not accept a client of the control socket: {}", error);
+                        continue;
+                    }
+                };
+                let _ = client.set_read_timeout(Some(CLIENT_TIMEOUT));
+                let mut line = String::new();
+                if let Err(error) = BufReader::new(&client).read_line(&mut line) {
+                    debug!("Could not read the command of a client: {}", error);
+                    continue;
+                }
+                let reply = match line.trim().parse().and_then(execute) {
+                    Ok(()) => "ok".to_string(),
+                    Err(error) => format!("error: {}", error),
+                };
+                let _ = writeln!(client, "{}", reply);
+            }
+        });
+        info!("Listening for commands on {}", socket_path.display());
+        Ok(ControlServer {
+            socket_path: socket_path.to_path_buf(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn start(_: &Path) -> io::Result<ControlServer> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the control socket is only supported on Unix",
+        ))
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+/// Send a command to the recorder listening on the socket and return its reply.
+#[cfg(unix)]
+pub fn send_control_command(socket_path: &Path, command: ControlCommand) -> io::Result<String> {
+    use std::io::{Read, Write};
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket_path)?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply.trim().to_string())
+}
+
+#[cfg(not(unix))]
+pub fn send_control_command(_: &Path, _: ControlCommand) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "the control socket is only supported on Unix",
+    ))
+}
@@ -0,0 +1,126 @@
+use core::fmt;
+use std::path::Path;
+
+/// The number of frames per second of the timestamps of a cue sheet (the sectors of an audio CD).
+const FRAMES_PER_SECOND: u32 = 75;
+
+/// The maximum number of tracks a cue sheet (and an audio CD) can hold.
+pub const MAXIMUM_TRACKS: usize = 99;
+
+/// The minimum length of a track on an audio CD.
+pub const MINIMUM_TRACK_LENGTH_IN_SECONDS: f64 = 4.0;
+
+/// A track of a cue sheet which starts at an offset of the audio file.
+#[derive(Debug, Clone)]
+pub struct CueTrack {
+    pub title: String,
+    pub offset_in_seconds: f64,
+}
+
+/// A cue sheet which splits a single audio file into tracks.
+#[derive(Debug, Clone)]
+pub struct CueSheet {
+    title: String,
+    performer: Option<String>,
+    file_name: String,
+    tracks: Vec<CueTrack>,
+}
+
+/// Format an offset as the `MM:SS:FF` timestamp of a cue sheet.
+fn format_timestamp(offset_in_seconds: f64) -> String {
+    let frames = (offset_in_seconds.max(0.0) * f64::from(FRAMES_PER_SECOND)).round() as u64;
+    let seconds = frames / u64::from(FRAMES_PER_SECOND);
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 60,
+        seconds % 60,
+        frames % u64::from(FRAMES_PER_SECOND)
+    )
+}
+
+/// Quote a string of a cue sheet, which must not contain double quotes.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "'"))
+}
+
+/// Get the type of the audio file as expected by the `FILE` command of a cue sheet.
+fn get_file_type(file_name: &str) -> &'static str {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("mp3") => "MP3",
+        Some("aif") | Some("aiff") => "AIFF",
+        _ => "WAVE",
+    }
+}
+
+impl CueSheet {
+    pub fn new(title: &str, file_name: &str) -> CueSheet {
+        CueSheet {
+            title: title.to_string(),
+            performer: None,
+            file_name: file_name.to_string(),
+            tracks: vec![],
+        }
+    }
+
+    pub fn set_performer(&mut self, performer: &str) {
+        self.performer = Some(performer.to_string());
+    }
+
+    /// Add the tracks to the cue sheet. The tracks are ordered by their offset, the first track
+    /// always starts at the beginning of the file and tracks which would be shorter than a track
+    /// of an audio CD are skipped. The number of tracks which did not fit into the cue sheet is
+    /// returned.
+    pub fn add_tracks(&mut self, mut tracks: Vec<CueTrack>) -> usize {
+        tracks
+            .sort_by(|first, second| first.offset_in_seconds.total_cmp(&second.offset_in_seconds));
+        let mut skipped_tracks = 0;
+        for mut track in tracks {
+            let is_too_close = self.tracks.last().is_some_and(|last_track| {
+                track.offset_in_seconds - last_track.offset_in_seconds
+                    < MINIMUM_TRACK_LENGTH_IN_SECONDS
+            });
+            if is_too_close || self.tracks.len() >= MAXIMUM_TRACKS {
+                skipped_tracks += 1;
+                continue;
+            }
+            if self.tracks.is_empty() {
+                track.offset_in_seconds = 0.0;
+            }
+            self.tracks.push(track);
+        }
+        skipped_tracks
+    }
+
+    pub fn get_tracks(&self) -> &[CueTrack] {
+        &self.tracks
+    }
+}
+
+impl fmt::Display for CueSheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(performer) = &self.performer {
+            writeln!(f, "PERFORMER {}", quote(performer))?;
+        }
+        writeln!(f, "TITLE {}", quote(&self.title))?;
+        writeln!(
+            f,
+            "FILE {} {}",
+            quote(&self.file_name),
+            get_file_type(&self.file_name)
+        )?;
+        for (index, track) in self.tracks.iter().enumerate() {
+            writeln!(f, "  TRACK {:02} AUDIO", index + 1)?;
+            writeln!(f, "    TITLE {}", quote(&track.title))?;
+            writeln!(
+                f,
+                "    INDEX 01 {}",
+                format_timestamp(track.offset_in_seconds)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs::{read_to_string, remove_file, write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+/// The file in the state folder which stores the process id of a recorder in the background.
+pub const PID_FILE_NAME: &str = "recorder.pid";
+
+/// The file in the state folder the recorder in the background logs to.
+pub const LOG_FILE_NAME: &str = "recorder.log";
+
+/// The file with the process id of the daemon, it is removed when it is dropped.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(error) = remove_file(&self.path) {
+            warn!(
+                "Could not remove the PID file {}. The error was: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+}
+
+/// The process the caller continues in after the fork.
+pub enum Fork {
+    /// The original process, the daemon has the contained process id.
+    Parent(u32),
+
+    /// The detached process.
+    Daemon(PidFile),
+}
+
+/// Get the process id stored in a PID file if this process is still running. This check is only
+/// supported on Linux.
+pub fn get_running_process(pid_file: &Path) -> Option<u32> {
+    let process_id = read_to_string(pid_file).ok()?.trim().parse::<u32>().ok()?;
+    if Path::new("/proc").join(process_id.to_string()).exists() {
+        Some(process_id)
+    } else {
+        None
+    }
+}
+
+/// Detach the process from the terminal: it is forked, the child starts a new session and its
+/// standard streams are redirected to `/dev/null`. The process id of the child is stored in the
+/// PID file by the parent. This has to happen before any thread is started and is only supported
+/// on Unix.
+pub fn daemonize(pid_file: &Path) -> io::Result<Fork> {
+    if let Some(process_id) = get_running_process(pid_file) {
+        // the daemon replaced itself with an updated binary, it is detached already
+        if process_id == std::process::id() {
+            return Ok(Fork::Daemon(PidFile {
+                path: pid_file.to_path_buf(),
+            }));
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "the recorder is already running in the background (PID {})",
+                process_id
+            ),
+        ));
+    }
+
+    // the PID file is created before the fork, so the daemon is not started if it can not be
+    // written
+    write(pid_file, "")?;
+    if let Some(child) = detach()? {
+        write(pid_file, format!("{}\n", child))?;
+        return Ok(Fork::Parent(child));
+    }
+    Ok(Fork::Daemon(PidFile {
+        path: pid_file.to_path_buf(),
+    }))
+}
+
+/// Fork the process and detach the child. The process id of the child is returned in the parent.
+#[cfg(unix)]
+fn detach() -> io::Result<Option<u32>> {
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            child => return Ok(Some(child as u32)),
+        }
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let null = libc::open(b"/dev/null\0".as_ptr() as *const libc::c_char, libc::O_RDWR);
+        if null == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        for stream in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO].iter() {
+            libc::dup2(null, *stream);
+        }
+        if null > libc::STDERR_FILENO {
+            libc::close(null);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(not(unix))]
+fn detach() -> io::Result<Option<u32>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "running in the background is only supported on Unix",
+    ))
+}
@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{spawn, JoinHandle};
+use std::time::Duration;
+
+use chrono::Local;
+use lazy_static::lazy_static;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table};
+use ratatui::Frame;
+
+use crate::shutdown::{is_shutdown_requested, request_shutdown};
+use crate::status::{InputState, RecorderStatus, StatusBoard};
+
+/// The number of log messages which are kept for the dashboard.
+const MAXIMUM_LOG_LINES: usize = 200;
+
+/// The number of log messages which are printed after the dashboard was closed.
+const FINAL_LOG_LINES: usize = 10;
+
+/// The time between two redraws of the dashboard.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+lazy_static! {
+    static ref LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+}
+
+/// Keep a log message for the dashboard, which shows the latest ones instead of a scrolling log.
+pub fn add_log_line(line: String) {
+    let mut log_lines = LOG_LINES.lock().unwrap();
+    if log_lines.len() >= MAXIMUM_LOG_LINES {
+        log_lines.pop_front();
+    }
+    log_lines.push_back(line);
+}
+
+/// A full-screen view of the state of the recorder, which is redrawn in the background.
+pub struct Dashboard {
+    is_stopped: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Dashboard {
+    /// Take over the terminal and show the state of the recorder until the dashboard is stopped.
+    /// Pressing `q` (or Ctrl-C) requests a graceful shutdown of the recorder.
+    pub fn start(status_board: StatusBoard) -> Dashboard {
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let thread_is_stopped = is_stopped.clone();
+        let thread = spawn(move || {
+            let mut terminal = ratatui::init();
+            while !thread_is_stopped.load(Ordering::SeqCst) {
+                let status = status_board.get();
+                let log_lines: Vec<String> = LOG_LINES.lock().unwrap().iter().cloned().collect();
+                let _ = terminal.draw(|frame| draw(frame, &status, &log_lines));
+
+                // the terminal is in raw mode, so Ctrl-C is a key instead of a signal
+                if !event::poll(REFRESH_INTERVAL).unwrap_or(false) {
+                    continue;
+                }
+                if let Ok(Event::Key(key)) = event::read() {
+                    let is_ctrl_c = key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL);
+                    if key.kind == KeyEventKind::Press
+                        && (key.code == KeyCode::Char('q') || is_ctrl_c)
+                    {
+                        request_shutdown();
+                    }
+                }
+            }
+            ratatui::restore();
+        });
+        Dashboard { is_stopped, thread }
+    }
+
+    /// Give the terminal back and print the last log messages, so it is visible how the recorder
+    /// finished.
+    pub fn stop(self) {
+        self.is_stopped.store(true, Ordering::SeqCst);
+        let _ = self.thread.join();
+        let log_lines = LOG_LINES.lock().unwrap();
+        for line in log_lines
+            .iter()
+            .skip(log_lines.len().saturating_sub(FINAL_LOG_LINES))
+        {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Format a duration in seconds as `H:MM:SS`.
+fn format_duration(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn draw(frame: &mut Frame, status: &RecorderStatus, log_lines: &[String]) {
+    let [summary_area, inputs_area, interesting_area, log_area, help_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(status.inputs.len() as u16 + 3),
+        Constraint::Length(status.interesting_segments.len().max(1) as u16 + 2),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let now = Local::now();
+
+    let free_space = match status.free_space_in_bytes {
+        Some(free_space) => format!("{} MB", free_space / 1024 / 1024),
+        None => "unknown".to_string(),
+    };
+    let summary = Paragraph::new(Line::from(format!(
+        "Recording since {} ({})   Encoding queue: {}   Free space: {}",
+        status.started_at.format("%Y-%m-%d %H:%M"),
+        format_duration((now - status.started_at).num_seconds()),
+        status.encoding_queue_depth,
+        free_space
+    )))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("schlaflosigkeit"),
+    );
+    frame.render_widget(summary, summary_area);
+
+    let rows = status.inputs.iter().map(|(input_name, input_status)| {
+        let segment = match input_status.segment_started_at {
+            Some(segment_started_at) if input_status.state == InputState::Recording => format!(
+                "{} / {}",
+                format_duration((now - segment_started_at).num_seconds()),
+                format_duration(i64::from(input_status.segment_duration_in_seconds))
+            ),
+            _ => "-".to_string(),
+        };
+        let (peak, rms) = match &input_status.levels {
+            Some(levels) => (
+                format!("{:.1} dBFS", levels.peak_in_dbfs),
+                format!("{:.1} dBFS", levels.rms_in_dbfs),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let interestingness = input_status
+            .interestingness
+            .map_or_else(|| "-".to_string(), |score| score.to_string());
+        let style = match input_status.state {
+            InputState::Recording => Style::default().fg(Color::Green),
+            InputState::Failed | InputState::GaveUp => Style::default().fg(Color::Red),
+            InputState::Waiting => Style::default(),
+        };
+        Row::new(vec![
+            input_name.clone(),
+            input_status.state.to_string(),
+            segment,
+            input_status.recorded_segments.to_string(),
+            peak,
+            rms,
+            interestingness,
+            input_status.last_error.clone().unwrap_or_default(),
+        ])
+        .style(style)
+    });
+    let inputs = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(10),
+            Constraint::Length(18),
+            Constraint::Length(9),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(6),
+            Constraint::Min(10),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Input",
+            "State",
+            "Segment",
+            "Segments",
+            "Peak",
+            "RMS",
+            "Score",
+            "Last error",
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::default().borders(Borders::ALL).title("Inputs"));
+    frame.render_widget(inputs, inputs_area);
+
+    // the segments which should be listened to first
+    let interesting = if status.interesting_segments.is_empty() {
+        Paragraph::new("No segment was post-processed yet")
+    } else {
+        Paragraph::new(
+            status
+                .interesting_segments
+                .iter()
+                .map(|segment| {
+                    Line::from(format!(
+                        "{:>3}  {:<16} {}",
+                        segment.interestingness, segment.input, segment.file
+                    ))
+                })
+                .collect::<Vec<Line>>(),
+        )
+    }
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Most interesting segments"),
+    );
+    frame.render_widget(interesting, interesting_area);
+
+    // only the latest log messages which fit into the panel are shown
+    let visible_lines = log_area.height.saturating_sub(2) as usize;
+    let log = Paragraph::new(
+        log_lines
+            .iter()
+            .skip(log_lines.len().saturating_sub(visible_lines))
+            .map(|line| Line::from(line.as_str()))
+            .collect::<Vec<Line>>(),
+    )
+    .block(Block::default().borders(Borders::ALL).title("Log"));
+    frame.render_widget(log, log_area);
+
+    let help = if is_shutdown_requested() {
+        "Stopping, waiting for the post-processing of the last segments..."
+    } else {
+        "Press q to stop the recording"
+    };
+    frame.render_widget(Paragraph::new(help), help_area);
+}
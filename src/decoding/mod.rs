@@ -0,0 +1,222 @@
+use core::fmt;
+use std::error;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use tracing::warn;
+
+use crate::annotation::ReadError;
+use crate::wave::{open_samples, SampleReader};
+
+/// The maximum number of channels a decoded frame can hold.
+pub const MAXIMUM_CHANNELS: usize = 8;
+
+/// The samples of all channels at a single point in time, normalized to the range of -1.0 to 1.0.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    samples: [f32; MAXIMUM_CHANNELS],
+    channels: usize,
+}
+
+impl Frame {
+    pub fn get_samples(&self) -> &[f32] {
+        &self.samples[..self.channels]
+    }
+
+    /// Get the average of all channels.
+    pub fn get_mono(&self) -> f32 {
+        self.get_samples().iter().sum::<f32>() / self.channels as f32
+    }
+}
+
+/// The format of the decoded frames.
+#[derive(Debug, Clone, Copy)]
+pub struct PcmFormat {
+    pub channels: u16,
+    pub samples_per_second: u32,
+}
+
+/// An error which occurred while opening a file for decoding.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The wave file could not be read.
+    Read(ReadError),
+
+    /// The decoder (ffprobe or ffmpeg) could not be started.
+    Decoder(io::Error),
+
+    /// The file does not contain an audio stream which can be decoded.
+    NoAudioStream,
+
+    /// The file has more channels than a frame can hold.
+    TooManyChannels(u16),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Read(error) => write!(f, "{}", error),
+            DecodeError::Decoder(error) => write!(f, "could not run the decoder: {}", error),
+            DecodeError::NoAudioStream => write!(f, "no audio stream found"),
+            DecodeError::TooManyChannels(channels) => write!(
+                f,
+                "{} channels are not supported (at most {})",
+                channels, MAXIMUM_CHANNELS
+            ),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+enum Source {
+    Wave(SampleReader),
+    Ffmpeg {
+        child: Child,
+        reader: BufReader<ChildStdout>,
+    },
+}
+
+/// The frames of a decoded file. The iteration ends at the end of the file or at the first read
+/// error, a failed decoder is logged when it is dropped.
+pub struct PcmDecoder {
+    format: PcmFormat,
+    source: Source,
+}
+
+impl PcmDecoder {
+    pub fn get_format(&self) -> PcmFormat {
+        self.format
+    }
+
+    fn next_sample(&mut self) -> Option<f32> {
+        match &mut self.source {
+            Source::Wave(sample_reader) => sample_reader.next(),
+            Source::Ffmpeg { reader, .. } => {
+                let mut bytes = [0u8; 4];
+                reader.read_exact(&mut bytes).ok()?;
+                Some(f32::from_le_bytes(bytes))
+            }
+        }
+    }
+}
+
+impl Iterator for PcmDecoder {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let mut frame = Frame {
+            samples: [0.0; MAXIMUM_CHANNELS],
+            channels: usize::from(self.format.channels),
+        };
+        for channel in 0..frame.channels {
+            frame.samples[channel] = self.next_sample()?;
+        }
+        Some(frame)
+    }
+}
+
+impl Drop for PcmDecoder {
+    fn drop(&mut self) {
+        if let Source::Ffmpeg { child, .. } = &mut self.source {
+            // the decoder is still running if not all frames were read
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill();
+            }
+            match child.wait() {
+                Ok(status) if !status.success() && status.code().is_some() => {
+                    warn!("The decoder failed with {}", status)
+                }
+                Err(error) => warn!("Could not wait for the decoder. The error was: {}", error),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Get the format of the first audio stream of a file with ffprobe.
+fn probe_format(path: &Path) -> Result<PcmFormat, DecodeError> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("a:0")
+        .arg("-show_entries")
+        .arg("stream=channels,sample_rate")
+        .arg("-of")
+        .arg("default=noprint_wrappers=1")
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .map_err(DecodeError::Decoder)?;
+    let mut channels = None;
+    let mut samples_per_second = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        match line.split_once('=') {
+            Some(("channels", value)) => channels = value.trim().parse().ok(),
+            Some(("sample_rate", value)) => samples_per_second = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+    match (channels, samples_per_second) {
+        (Some(channels), Some(samples_per_second)) if channels > 0 => Ok(PcmFormat {
+            channels,
+            samples_per_second,
+        }),
+        _ => Err(DecodeError::NoAudioStream),
+    }
+}
+
+/// Open an audio file for decoding it frame by frame. Wave files are read directly, all other
+/// formats (e.g. FLAC, MP3, Ogg or Opus) are decoded with ffmpeg.
+pub fn decode_to_pcm(path: &Path) -> Result<PcmDecoder, DecodeError> {
+    let is_wave_file = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"));
+    let (format, source) = if is_wave_file {
+        let (wave_format, sample_reader) = open_samples(path).map_err(DecodeError::Read)?;
+        if usize::from(wave_format.channels) > MAXIMUM_CHANNELS {
+            return Err(DecodeError::TooManyChannels(wave_format.channels));
+        }
+        (
+            PcmFormat {
+                channels: wave_format.channels,
+                samples_per_second: wave_format.samples_per_second,
+            },
+            Source::Wave(sample_reader),
+        )
+    } else {
+        let format = probe_format(path)?;
+        if usize::from(format.channels) > MAXIMUM_CHANNELS {
+            return Err(DecodeError::TooManyChannels(format.channels));
+        }
+        let mut child = Command::new("ffmpeg")
+            .arg("-v")
+            .arg("error")
+            .arg("-i")
+            .arg(path)
+            .arg("-map")
+            .arg("0:a:0")
+            .arg("-f")
+            .arg("f32le")
+            .arg("-acodec")
+            .arg("pcm_f32le")
+            .arg("-")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(DecodeError::Decoder)?;
+        let reader = BufReader::new(child.stdout.take().ok_or_else(|| {
+            DecodeError::Decoder(io::Error::new(
+                io::ErrorKind::Other,
+                "the output of ffmpeg is not available",
+            ))
+        })?);
+        (format, Source::Ffmpeg { child, reader })
+    };
+    Ok(PcmDecoder { format, source })
+}
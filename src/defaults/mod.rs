@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use toml::value::Table;
+
+use crate::wave::SampleFormat;
+use crate::RecordingDeviceConfiguration;
+
+/// The sample rate (in Hz) the devices are recorded with.
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// The format of the samples the devices are recorded with.
+pub const SAMPLE_FORMAT: SampleFormat = SampleFormat::S16;
+
+/// The devices are recorded in stereo unless they are configured to be recorded in mono.
+pub const MONO: bool = false;
+
+/// The number of minutes which are recorded in a single file.
+pub const SEGMENT_DURATION_IN_MINUTES: u8 = 1;
+
+/// The number of seconds every recording continues into the next segment.
+pub const SEGMENT_OVERLAP_IN_SECONDS: u32 = 0;
+
+/// The duration of the segments, which is written like `30s`, `90s` or `1m 30s`. A plain number
+/// is a number of minutes (like the `duration_in_minutes` setting).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentDuration(u32);
+
+impl SegmentDuration {
+    pub fn from_minutes(minutes: u8) -> SegmentDuration {
+        SegmentDuration(60 * u32::from(minutes))
+    }
+
+    pub fn get_seconds(&self) -> u32 {
+        self.0
+    }
+}
+
+impl FromStr for SegmentDuration {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(minutes) = value.trim().parse::<u32>() {
+            return Ok(SegmentDuration(minutes.saturating_mul(60)));
+        }
+        let duration = humantime::parse_duration(value)
+            .map_err(|error| format!("'{}' is not a valid duration ({})", value, error))?;
+        if duration.subsec_nanos() != 0 {
+            return Err(format!("'{}' is not a whole number of seconds", value));
+        }
+        u32::try_from(duration.as_secs())
+            .map(SegmentDuration)
+            .map_err(|_| format!("'{}' is too long", value))
+    }
+}
+
+impl fmt::Display for SegmentDuration {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            humantime::format_duration(std::time::Duration::from_secs(u64::from(self.0)))
+        )
+    }
+}
+
+impl Serialize for SegmentDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SegmentDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The audio settings which are used for every input device (and the record command) which does
+/// not set them explicitly. The defaults of the project file override the built-in constants.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct AudioDefaults {
+    /// The sample rate (in Hz) the devices are recorded with.
+    #[serde(default = "AudioDefaults::default_sample_rate")]
+    pub sample_rate: u32,
+
+    /// The format of the samples the devices are recorded with.
+    #[serde(default = "AudioDefaults::default_format")]
+    pub format: SampleFormat,
+
+    /// Record the devices in mono instead of stereo.
+    #[serde(default = "AudioDefaults::default_mono")]
+    pub mono: bool,
+
+    /// The number of minutes which are recorded in a single file.
+    #[serde(default = "AudioDefaults::default_duration_in_minutes")]
+    pub duration_in_minutes: u8,
+
+    /// The duration of a single file with a unit (e.g. `30s` or `90s`), which allows durations
+    /// shorter than a minute or not in full minutes. It overrides the number of minutes.
+    #[serde(default = "AudioDefaults::default_duration")]
+    pub duration: Option<SegmentDuration>,
+
+    /// The number of seconds every recording continues into the next segment, so the seam between
+    /// two segments is covered by both of them. The next segment is started while the previous
+    /// recording is still running, so the inputs have to allow being opened several times at once.
+    #[serde(default = "AudioDefaults::default_overlap_in_seconds")]
+    pub overlap_in_seconds: u32,
+}
+
+impl AudioDefaults {
+    fn default_sample_rate() -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn default_format() -> SampleFormat {
+        SAMPLE_FORMAT
+    }
+
+    fn default_mono() -> bool {
+        MONO
+    }
+
+    fn default_duration_in_minutes() -> u8 {
+        SEGMENT_DURATION_IN_MINUTES
+    }
+
+    fn default_duration() -> Option<SegmentDuration> {
+        None
+    }
+
+    fn default_overlap_in_seconds() -> u32 {
+        SEGMENT_OVERLAP_IN_SECONDS
+    }
+
+    /// Get the duration of the segments, the duration with a unit takes precedence over the
+    /// number of minutes.
+    pub fn get_segment_duration(&self) -> SegmentDuration {
+        self.duration
+            .unwrap_or_else(|| SegmentDuration::from_minutes(self.duration_in_minutes))
+    }
+
+    /// Use these defaults for all settings of an input device.
+    pub fn apply_to(&self, device: &mut RecordingDeviceConfiguration) {
+        device.sample_rate = self.sample_rate;
+        device.format = self.format;
+        device.mono = self.mono;
+    }
+
+    /// Use these defaults for all settings which are not set explicitly in the tables of the input
+    /// devices in the project file. Devices without a table only use the defaults.
+    pub(crate) fn apply_to_inputs(
+        &self,
+        inputs: &mut HashMap<String, RecordingDeviceConfiguration>,
+        input_tables: Option<&Table>,
+    ) {
+        for (name, device) in inputs.iter_mut() {
+            let input_table = input_tables
+                .and_then(|input_tables| input_tables.get(name))
+                .and_then(|input_table| input_table.as_table());
+            let is_set = |key: &str| input_table.is_some_and(|table| table.contains_key(key));
+            if !is_set("sample_rate") {
+                device.sample_rate = self.sample_rate;
+            }
+            if !is_set("format") {
+                device.format = self.format;
+            }
+            if !is_set("mono") {
+                device.mono = self.mono;
+            }
+        }
+    }
+}
+
+impl Default for AudioDefaults {
+    fn default() -> Self {
+        AudioDefaults {
+            sample_rate: AudioDefaults::default_sample_rate(),
+            format: AudioDefaults::default_format(),
+            mono: AudioDefaults::default_mono(),
+            duration_in_minutes: AudioDefaults::default_duration_in_minutes(),
+            duration: AudioDefaults::default_duration(),
+            overlap_in_seconds: AudioDefaults::default_overlap_in_seconds(),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::analysis::{find_events, EnvelopeBuilder, ENVELOPE_VALUES_PER_SECOND};
+use crate::annotation::ReadError;
+use crate::wave::open_samples;
+
+/// The number of frames per second the cough detector splits the samples into.
+const COUGH_FRAMES_PER_SECOND: usize = 100;
+
+/// The percentile of the frame energies which is used as the noise floor.
+const NOISE_FLOOR_PERCENTILE: f32 = 0.1;
+
+/// The level (in dBFS) a cough has to reach at least, so nothing is found in near silence.
+const MINIMUM_COUGH_LEVEL_IN_DBFS: f32 = -50.0;
+
+/// The shortest and the longest burst (in frames) which is counted as a cough.
+const MINIMUM_COUGH_FRAMES: usize = 10;
+const MAXIMUM_COUGH_FRAMES: usize = 80;
+
+/// The latest frame of a burst its loudest frame may be, a cough starts explosively.
+const MAXIMUM_COUGH_ONSET_FRAMES: usize = 8;
+
+/// The number of quiet frames within a burst which do not end it.
+const MAXIMUM_COUGH_GAP_FRAMES: usize = 3;
+
+/// A detector finds a certain kind of sound in a recording.
+pub trait Detector {
+    /// The name of the sounds the detector finds (e.g. `cough`).
+    fn get_name(&self) -> &'static str;
+
+    /// Start a search for the sounds in mono samples with the supplied sample rate.
+    fn start(&self, samples_per_second: u32) -> Box<dyn Detection + '_>;
+}
+
+/// A running search of a detector. The samples are added one after another, so a recording never
+/// has to be loaded as a whole.
+pub trait Detection {
+    fn add_sample(&mut self, sample: f32);
+
+    /// Finish the search and return the offsets (in seconds) the sounds start at.
+    fn finish(self: Box<Self>) -> Vec<f32>;
+}
+
+/// Finds the loud passages (like snoring, talking or coughing) whose RMS energy exceeds a
+/// threshold, these are the events which are counted while recording.
+pub struct LoudnessDetector {
+    pub threshold: f32,
+}
+
+/// The search of a loudness detector, which only keeps the energy envelope of the samples.
+struct LoudnessDetection {
+    threshold: f32,
+    envelope_builder: EnvelopeBuilder,
+}
+
+impl Detector for LoudnessDetector {
+    fn get_name(&self) -> &'static str {
+        "event"
+    }
+
+    fn start(&self, samples_per_second: u32) -> Box<dyn Detection + '_> {
+        Box::new(LoudnessDetection {
+            threshold: self.threshold,
+            envelope_builder: EnvelopeBuilder::new(samples_per_second),
+        })
+    }
+}
+
+impl Detection for LoudnessDetection {
+    fn add_sample(&mut self, sample: f32) {
+        self.envelope_builder.add_sample(sample);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<f32> {
+        find_events(&self.envelope_builder.finish(), self.threshold)
+            .into_iter()
+            .map(|index| index as f32 / ENVELOPE_VALUES_PER_SECOND as f32)
+            .collect()
+    }
+}
+
+/// The settings of the cough detector which runs on every segment while recording.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CoughDetectionConfiguration {
+    #[serde(default = "CoughDetectionConfiguration::default_enabled")]
+    pub enabled: bool,
+
+    /// The level (in dB) a cough has to exceed the noise floor of the recording by.
+    #[serde(default = "CoughDetectionConfiguration::default_threshold_in_db")]
+    pub threshold_in_db: f32,
+
+    /// The frequency (in Hz) the sound of a cough has to be centered above at least, which tells
+    /// the broadband bursts of coughs apart from the low rumble of snoring.
+    #[serde(default = "CoughDetectionConfiguration::default_minimum_frequency_in_hz")]
+    pub minimum_frequency_in_hz: f32,
+}
+
+impl CoughDetectionConfiguration {
+    fn default_enabled() -> bool {
+        false
+    }
+
+    fn default_threshold_in_db() -> f32 {
+        20.0
+    }
+
+    fn default_minimum_frequency_in_hz() -> f32 {
+        1000.0
+    }
+}
+
+impl Default for CoughDetectionConfiguration {
+    fn default() -> Self {
+        CoughDetectionConfiguration {
+            enabled: CoughDetectionConfiguration::default_enabled(),
+            threshold_in_db: CoughDetectionConfiguration::default_threshold_in_db(),
+            minimum_frequency_in_hz: CoughDetectionConfiguration::default_minimum_frequency_in_hz(),
+        }
+    }
+}
+
+/// Finds coughs, which are short bursts of broadband sound that start explosively. Snoring and
+/// talking are longer and concentrated at lower frequencies, so they are not counted.
+#[derive(Debug, Clone)]
+pub struct CoughDetector {
+    threshold_in_db: f32,
+    minimum_frequency_in_hz: f32,
+}
+
+impl CoughDetector {
+    pub fn new(config: &CoughDetectionConfiguration) -> CoughDetector {
+        CoughDetector {
+            threshold_in_db: config.threshold_in_db,
+            minimum_frequency_in_hz: config.minimum_frequency_in_hz,
+        }
+    }
+
+    /// Check if a burst of frames (with their energies and the energies of their first
+    /// differences) sounds like a cough.
+    fn is_cough(
+        &self,
+        energies: &[f32],
+        difference_energies: &[f32],
+        samples_per_second: u32,
+    ) -> bool {
+        if !(MINIMUM_COUGH_FRAMES..=MAXIMUM_COUGH_FRAMES).contains(&energies.len()) {
+            return false;
+        }
+        let loudest_frame = energies
+            .iter()
+            .enumerate()
+            .fold(0, |loudest, (index, energy)| {
+                if *energy > energies[loudest] {
+                    index
+                } else {
+                    loudest
+                }
+            });
+        if loudest_frame > MAXIMUM_COUGH_ONSET_FRAMES {
+            return false;
+        }
+
+        // the energy of the first difference of a sine with the frequency f is 4 sin²(πf/fs) times
+        // its energy, which gives the frequency the sound of the burst is centered at
+        let ratio = difference_energies.iter().sum::<f32>()
+            / energies.iter().sum::<f32>().max(f32::EPSILON);
+        let frequency =
+            samples_per_second as f32 / std::f32::consts::PI * (ratio.sqrt() / 2.0).min(1.0).asin();
+        frequency >= self.minimum_frequency_in_hz
+    }
+
+    /// Find the coughs in the energies of the frames (and the energies of their first
+    /// differences) of a recording.
+    fn find_coughs(
+        &self,
+        energies: &[f32],
+        difference_energies: &[f32],
+        samples_per_second: u32,
+    ) -> Vec<f32> {
+        if energies.is_empty() {
+            return vec![];
+        }
+
+        // a frame is loud if it exceeds the noise floor of the recording by the threshold
+        let mut sorted_energies = energies.to_vec();
+        sorted_energies.sort_by(|first, second| first.total_cmp(second));
+        let noise_floor =
+            sorted_energies[((sorted_energies.len() - 1) as f32 * NOISE_FLOOR_PERCENTILE) as usize];
+        let loud_energy = (noise_floor * 10f32.powf(self.threshold_in_db / 10.0))
+            .max(10f32.powf(MINIMUM_COUGH_LEVEL_IN_DBFS / 10.0));
+
+        // the loud frames are grouped into bursts, short dips do not end a burst. the end of the
+        // samples ends the last burst.
+        let mut coughs = vec![];
+        let mut burst: Option<(usize, usize)> = None;
+        for index in 0..=energies.len() {
+            let is_loud = energies
+                .get(index)
+                .is_some_and(|energy| *energy >= loud_energy);
+            burst = match burst {
+                Some((start, _)) if is_loud => Some((start, index)),
+                Some((start, end))
+                    if index == energies.len() || index - end > MAXIMUM_COUGH_GAP_FRAMES =>
+                {
+                    if self.is_cough(
+                        &energies[start..=end],
+                        &difference_energies[start..=end],
+                        samples_per_second,
+                    ) {
+                        coughs.push(start as f32 / COUGH_FRAMES_PER_SECOND as f32);
+                    }
+                    None
+                }
+                None if is_loud => Some((index, index)),
+                burst => burst,
+            };
+        }
+        coughs
+    }
+}
+
+/// The search of a cough detector, which only keeps the energies of the frames of the samples.
+struct CoughDetection<'a> {
+    detector: &'a CoughDetector,
+    samples_per_second: u32,
+    samples_per_frame: usize,
+    samples_in_frame: usize,
+    energy: f32,
+    difference_energy: f32,
+    previous_sample: f32,
+    energies: Vec<f32>,
+    difference_energies: Vec<f32>,
+}
+
+impl Detector for CoughDetector {
+    fn get_name(&self) -> &'static str {
+        "cough"
+    }
+
+    fn start(&self, samples_per_second: u32) -> Box<dyn Detection + '_> {
+        Box::new(CoughDetection {
+            detector: self,
+            samples_per_second,
+            samples_per_frame: (samples_per_second as usize / COUGH_FRAMES_PER_SECOND).max(2),
+            samples_in_frame: 0,
+            energy: 0.0,
+            difference_energy: 0.0,
+            previous_sample: 0.0,
+            energies: vec![],
+            difference_energies: vec![],
+        })
+    }
+}
+
+impl Detection for CoughDetection<'_> {
+    fn add_sample(&mut self, sample: f32) {
+        // the first differences are only taken within a frame
+        self.energy += sample * sample;
+        if self.samples_in_frame > 0 {
+            let difference = sample - self.previous_sample;
+            self.difference_energy += difference * difference;
+        }
+        self.previous_sample = sample;
+        self.samples_in_frame += 1;
+
+        if self.samples_in_frame == self.samples_per_frame {
+            self.energies
+                .push(self.energy / self.samples_per_frame as f32);
+            self.difference_energies
+                .push(self.difference_energy / (self.samples_per_frame - 1) as f32);
+            self.energy = 0.0;
+            self.difference_energy = 0.0;
+            self.samples_in_frame = 0;
+        }
+    }
+
+    fn finish(self: Box<Self>) -> Vec<f32> {
+        self.detector.find_coughs(
+            &self.energies,
+            &self.difference_energies,
+            self.samples_per_second,
+        )
+    }
+}
+
+/// The sounds a detector found in a recording.
+pub struct ChannelDetections {
+    /// The offsets of the sounds in the mix of all channels.
+    pub mixed: Vec<f32>,
+
+    /// The offsets of the sounds in every single channel, empty if they were not searched
+    /// separately or for mono recordings.
+    pub per_channel: Vec<Vec<f32>>,
+}
+
+/// Stream the samples of a recording through a detector. The channels are mixed to mono and are
+/// searched separately as well if asked for.
+fn detect_in_frames(
+    detector: &dyn Detector,
+    path: &Path,
+    separate_channels: bool,
+) -> Result<ChannelDetections, ReadError> {
+    let (format, mut samples) = open_samples(path)?;
+    let channels = usize::from(format.channels.max(1));
+    let mut mixed = detector.start(format.samples_per_second);
+    let mut per_channel: Vec<Box<dyn Detection>> = if separate_channels && channels > 1 {
+        (0..channels)
+            .map(|_| detector.start(format.samples_per_second))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut frame = vec![0.0f32; channels];
+    loop {
+        // an incomplete frame at the end of the recording is ignored
+        let mut samples_in_frame = 0;
+        for (target, sample) in frame.iter_mut().zip(samples.by_ref().take(channels)) {
+            *target = sample;
+            samples_in_frame += 1;
+        }
+        if samples_in_frame < channels {
+            break;
+        }
+
+        mixed.add_sample(frame.iter().sum::<f32>() / channels as f32);
+        for (detection, sample) in per_channel.iter_mut().zip(&frame) {
+            detection.add_sample(*sample);
+        }
+    }
+    Ok(ChannelDetections {
+        mixed: mixed.finish(),
+        per_channel: per_channel
+            .into_iter()
+            .map(|detection| detection.finish())
+            .collect(),
+    })
+}
+
+/// Run a detector on a recorded (not yet encoded) segment, the channels are mixed to mono first.
+/// `None` is returned if the recording could not be read.
+pub fn detect_in_recording(detector: &dyn Detector, path: &Path) -> Option<Vec<f32>> {
+    detect_in_channels(detector, path, false).map(|detections| detections.mixed)
+}
+
+/// Run a detector on the mix of all channels of a recorded (not yet encoded) segment and on every
+/// single channel if `separate_channels` is set. `None` is returned if the recording could not be
+/// read.
+pub fn detect_in_channels(
+    detector: &dyn Detector,
+    path: &Path,
+    separate_channels: bool,
+) -> Option<ChannelDetections> {
+    match detect_in_frames(detector, path, separate_channels) {
+        Ok(detections) => Some(detections),
+        Err(error) => {
+            error!(
+                "Could not find the {}s in {}. The error was: {}",
+                detector.get_name(),
+                path.display(),
+                error
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use core::fmt;
+use std::fs::write;
+use std::io;
+use std::path::Path;
+
+use mp3lame_encoder::{
+    max_required_buffer_size, Bitrate, Builder, Encoder, FlushNoGap, InterleavedPcm, MonoPcm,
+};
+
+use crate::annotation::ReadError;
+use crate::encoding::EncodingConfiguration;
+use crate::wave::read_samples;
+
+/// The number of samples (per channel) which are passed to the encoder at once.
+const FRAMES_PER_CHUNK: usize = 8192;
+
+#[derive(Debug)]
+pub enum EncodeError {
+    /// The recording could not be read.
+    Read(ReadError),
+
+    /// The recording or the configuration can not be encoded by LAME.
+    Unsupported(String),
+
+    /// LAME could not be initialized with the format of the recording.
+    Build(String),
+
+    /// LAME failed while encoding the samples.
+    Encode(String),
+
+    /// The encoded file could not be written.
+    Io(io::Error),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodeError::Read(ref err) => write!(f, "Read error: {}", err),
+            EncodeError::Unsupported(ref message) => write!(f, "Unsupported: {}", message),
+            EncodeError::Build(ref message) => write!(f, "Encoder error: {}", message),
+            EncodeError::Encode(ref message) => write!(f, "Encoding error: {}", message),
+            EncodeError::Io(ref err) => write!(f, "IO error: {}", err),
+        }
+    }
+}
+
+fn get_bitrate(bitrate: u32) -> Result<Bitrate, EncodeError> {
+    match bitrate {
+        8 => Ok(Bitrate::Kbps8),
+        16 => Ok(Bitrate::Kbps16),
+        24 => Ok(Bitrate::Kbps24),
+        32 => Ok(Bitrate::Kbps32),
+        40 => Ok(Bitrate::Kbps40),
+        48 => Ok(Bitrate::Kbps48),
+        64 => Ok(Bitrate::Kbps64),
+        80 => Ok(Bitrate::Kbps80),
+        96 => Ok(Bitrate::Kbps96),
+        112 => Ok(Bitrate::Kbps112),
+        128 => Ok(Bitrate::Kbps128),
+        160 => Ok(Bitrate::Kbps160),
+        192 => Ok(Bitrate::Kbps192),
+        224 => Ok(Bitrate::Kbps224),
+        256 => Ok(Bitrate::Kbps256),
+        320 => Ok(Bitrate::Kbps320),
+        _ => Err(EncodeError::Unsupported(format!(
+            "the mp3 bitrate {} kbit/s is not supported by the native encoder",
+            bitrate
+        ))),
+    }
+}
+
+fn create_encoder(
+    channels: u16,
+    samples_per_second: u32,
+    encoding: &EncodingConfiguration,
+) -> Result<Encoder, EncodeError> {
+    if encoding.mp3_quality.is_some() {
+        return Err(EncodeError::Unsupported(
+            "the variable bitrate quality is only supported by ffmpeg".to_string(),
+        ));
+    }
+    let channels = match channels {
+        1 | 2 => channels as u8,
+        _ => {
+            return Err(EncodeError::Unsupported(format!(
+                "{} channels can not be encoded to mp3",
+                channels
+            )))
+        }
+    };
+
+    let mut builder = Builder::new()
+        .ok_or_else(|| EncodeError::Build("could not allocate the encoder".to_string()))?;
+    builder
+        .set_num_channels(channels)
+        .map_err(|error| EncodeError::Build(format!("{:?}", error)))?;
+    builder
+        .set_sample_rate(samples_per_second)
+        .map_err(|error| EncodeError::Build(format!("{:?}", error)))?;
+    if let Some(bitrate) = encoding.mp3_bitrate {
+        builder
+            .set_brate(get_bitrate(bitrate)?)
+            .map_err(|error| EncodeError::Build(format!("{:?}", error)))?;
+    }
+    builder
+        .build()
+        .map_err(|error| EncodeError::Build(format!("{:?}", error)))
+}
+
+/// Convert a sample (normalized to -1.0 to 1.0) with the supplied linear gain to 16 bit.
+fn to_i16(sample: f32, gain: f32) -> i16 {
+    (sample * gain * 32767.0).round().clamp(-32768.0, 32767.0) as i16
+}
+
+/// Encode a size as a synchsafe integer (seven bits per byte) like ID3v2 requires it.
+fn to_synchsafe(size: usize) -> [u8; 4] {
+    [
+        ((size >> 21) & 0x7F) as u8,
+        ((size >> 14) & 0x7F) as u8,
+        ((size >> 7) & 0x7F) as u8,
+        (size & 0x7F) as u8,
+    ]
+}
+
+/// Create an ID3v2.4 tag with a single (UTF-8) comment frame.
+fn create_comment_tag(comment: &str) -> Vec<u8> {
+    let mut frame_content = vec![0x03];
+    frame_content.extend_from_slice(b"eng\0");
+    frame_content.extend_from_slice(comment.as_bytes());
+
+    let mut frame = b"COMM".to_vec();
+    frame.extend_from_slice(&to_synchsafe(frame_content.len()));
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(&frame_content);
+
+    let mut tag = b"ID3\x04\x00\x00".to_vec();
+    tag.extend_from_slice(&to_synchsafe(frame.len()));
+    tag.extend_from_slice(&frame);
+    tag
+}
+
+/// Encode a recording to mp3 with LAME instead of ffmpeg. The optional gain (in dB) is applied
+/// before encoding, samples exceeding the full scale are clipped. The label is stored as a
+/// comment in an ID3 tag.
+pub fn encode_mp3(
+    input: &Path,
+    output: &Path,
+    encoding: &EncodingConfiguration,
+    gain_in_db: Option<f32>,
+    label: Option<&str>,
+) -> Result<(), EncodeError> {
+    let (format, samples) = read_samples(input).map_err(EncodeError::Read)?;
+    let mut encoder = create_encoder(format.channels, format.samples_per_second, encoding)?;
+
+    let gain = 10f32.powf(gain_in_db.unwrap_or(0.0) / 20.0);
+    let samples: Vec<i16> = samples.iter().map(|sample| to_i16(*sample, gain)).collect();
+
+    let channels = usize::from(format.channels);
+    let mut mp3_data = label.map(create_comment_tag).unwrap_or_default();
+    for chunk in samples.chunks(FRAMES_PER_CHUNK * channels) {
+        mp3_data.reserve(max_required_buffer_size(chunk.len() / channels));
+        let encoded_size = if channels == 1 {
+            encoder.encode(MonoPcm(chunk), mp3_data.spare_capacity_mut())
+        } else {
+            encoder.encode(InterleavedPcm(chunk), mp3_data.spare_capacity_mut())
+        }
+        .map_err(|error| EncodeError::Encode(format!("{:?}", error)))?;
+        // SAFETY: the encoder initialized exactly `encoded_size` bytes of the spare capacity
+        unsafe { mp3_data.set_len(mp3_data.len() + encoded_size) };
+    }
+
+    mp3_data.reserve(max_required_buffer_size(0));
+    let encoded_size = encoder
+        .flush::<FlushNoGap>(mp3_data.spare_capacity_mut())
+        .map_err(|error| EncodeError::Encode(format!("{:?}", error)))?;
+    // SAFETY: the encoder initialized exactly `encoded_size` bytes of the spare capacity
+    unsafe { mp3_data.set_len(mp3_data.len() + encoded_size) };
+
+    write(output, mp3_data).map_err(EncodeError::Io)
+}
@@ -0,0 +1,727 @@
+use core::fmt;
+use std::fs::{create_dir_all, read, remove_file, rename};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use crate::analysis::get_true_peak_in_db;
+use crate::annotation::WaveMetaReader;
+use crate::get_partial_file_path;
+use crate::wave::open_samples;
+
+#[cfg(feature = "lame")]
+pub mod lame;
+pub mod queue;
+
+/// The format the recordings are encoded to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Mp3,
+
+    /// Lossless compression, roughly half the size of the recording.
+    Flac,
+
+    /// Ogg Vorbis as a lossy alternative to mp3.
+    Ogg,
+}
+
+impl OutputFormat {
+    pub fn get_extension(self) -> &'static str {
+        match self {
+            OutputFormat::Mp3 => "mp3",
+            OutputFormat::Flac => "flac",
+            OutputFormat::Ogg => "ogg",
+        }
+    }
+
+    fn get_codec(self) -> &'static str {
+        match self {
+            OutputFormat::Mp3 => "libmp3lame",
+            OutputFormat::Flac => "flac",
+            OutputFormat::Ogg => "libvorbis",
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OutputFormat::Mp3 => write!(f, "mp3"),
+            OutputFormat::Flac => write!(f, "flac"),
+            OutputFormat::Ogg => write!(f, "ogg"),
+        }
+    }
+}
+
+/// Defines what happens to a recording after it was encoded successfully (and should not be kept).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RemovalMode {
+    /// Delete the recording.
+    #[default]
+    Delete,
+
+    /// Move the recording to the trash folder of the archive, so it can be restored.
+    Trash,
+}
+
+impl fmt::Display for RemovalMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RemovalMode::Delete => write!(f, "delete"),
+            RemovalMode::Trash => write!(f, "trash"),
+        }
+    }
+}
+
+/// The settings of the encoders which are used for converting the recordings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EncodingConfiguration {
+    /// The constant bitrate (in kbit/s) of the mp3 encoder.
+    #[serde(default = "EncodingConfiguration::default_mp3_bitrate")]
+    pub mp3_bitrate: Option<u32>,
+
+    /// The variable bitrate quality of the mp3 encoder between 0 (best) and 9 (smallest).
+    #[serde(default = "EncodingConfiguration::default_mp3_quality")]
+    pub mp3_quality: Option<u8>,
+
+    /// The quality of the Ogg Vorbis encoder between -1.0 (smallest) and 10.0 (best).
+    #[serde(default = "EncodingConfiguration::default_vorbis_quality")]
+    pub vorbis_quality: f32,
+
+    /// The sample rate (in Hz) the recordings are resampled to while encoding. The sample rate of
+    /// the recording is kept if none is set.
+    #[serde(default = "EncodingConfiguration::default_sample_rate")]
+    pub sample_rate: Option<u32>,
+
+    /// Keep the recording after it was encoded successfully.
+    #[serde(default = "EncodingConfiguration::default_keep_wav")]
+    pub keep_wav: bool,
+
+    /// What happens to a recording which is not kept after it was encoded.
+    #[serde(default = "EncodingConfiguration::default_removal")]
+    pub removal: RemovalMode,
+
+    /// The number of recordings which are encoded at the same time.
+    #[serde(default = "EncodingConfiguration::default_workers")]
+    pub workers: usize,
+
+    /// The maximum number of recordings which wait for being encoded. Further recordings are not
+    /// encoded and stay in the folder of the raw recordings.
+    #[serde(default = "EncodingConfiguration::default_maximum_queue_length")]
+    pub maximum_queue_length: usize,
+}
+
+/// The maximum difference (in seconds) between the durations of the recording and the encoded
+/// file, the encoders add a few milliseconds of padding at the start and the end.
+const MAXIMUM_DURATION_DIFFERENCE_IN_SECONDS: f64 = 1.0;
+
+/// The sample rates (in Hz) the recordings can be resampled to, all of them are supported by
+/// every output format.
+const SAMPLE_RATES: [u32; 9] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+
+/// The bitrates (in kbit/s) which are supported by the mp3 encoder.
+const MP3_BITRATES: [u32; 18] = [
+    8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 192, 224, 256, 320,
+];
+
+impl EncodingConfiguration {
+    fn default_mp3_bitrate() -> Option<u32> {
+        None
+    }
+
+    fn default_mp3_quality() -> Option<u8> {
+        None
+    }
+
+    fn default_vorbis_quality() -> f32 {
+        4.0
+    }
+
+    fn default_sample_rate() -> Option<u32> {
+        None
+    }
+
+    fn default_keep_wav() -> bool {
+        false
+    }
+
+    fn default_removal() -> RemovalMode {
+        RemovalMode::default()
+    }
+
+    fn default_workers() -> usize {
+        1
+    }
+
+    fn default_maximum_queue_length() -> usize {
+        8
+    }
+
+    /// Check if all settings are within the range supported by the encoders.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.mp3_bitrate.is_some() && self.mp3_quality.is_some() {
+            return Err(
+                "either a constant mp3 bitrate or a variable bitrate quality can be used"
+                    .to_string(),
+            );
+        }
+        if let Some(bitrate) = self.mp3_bitrate {
+            if !MP3_BITRATES.contains(&bitrate) {
+                return Err(format!(
+                    "the mp3 bitrate {} kbit/s is not supported",
+                    bitrate
+                ));
+            }
+        }
+        if let Some(quality) = self.mp3_quality {
+            if quality > 9 {
+                return Err(format!(
+                    "the mp3 quality {} is not between 0 and 9",
+                    quality
+                ));
+            }
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            if !SAMPLE_RATES.contains(&sample_rate) {
+                return Err(format!(
+                    "the sample rate {} Hz is not supported",
+                    sample_rate
+                ));
+            }
+        }
+        if self.workers == 0 {
+            return Err("at least one encoding worker is required".to_string());
+        }
+        if self.maximum_queue_length == 0 {
+            return Err("the maximum queue length has to be at least 1".to_string());
+        }
+        if !(-1.0..=10.0).contains(&self.vorbis_quality) {
+            return Err(format!(
+                "the vorbis quality {} is not between -1.0 and 10.0",
+                self.vorbis_quality
+            ));
+        }
+        Ok(())
+    }
+
+    /// Describe how the recordings are encoded with these settings (e.g. `mp3 (128 kbit/s)`), the
+    /// description is stored in the session manifests.
+    pub fn describe(&self, output_format: OutputFormat) -> String {
+        let mut settings = vec![];
+        match output_format {
+            OutputFormat::Mp3 => {
+                if let Some(bitrate) = self.mp3_bitrate {
+                    settings.push(format!("{} kbit/s", bitrate));
+                }
+                if let Some(quality) = self.mp3_quality {
+                    settings.push(format!("quality {}", quality));
+                }
+            }
+            OutputFormat::Ogg => settings.push(format!("quality {}", self.vorbis_quality)),
+            OutputFormat::Flac => {}
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            settings.push(format!("{} Hz", sample_rate));
+        }
+        if settings.is_empty() {
+            output_format.to_string()
+        } else {
+            format!("{} ({})", output_format, settings.join(", "))
+        }
+    }
+}
+
+impl Default for EncodingConfiguration {
+    fn default() -> Self {
+        EncodingConfiguration {
+            mp3_bitrate: EncodingConfiguration::default_mp3_bitrate(),
+            mp3_quality: EncodingConfiguration::default_mp3_quality(),
+            vorbis_quality: EncodingConfiguration::default_vorbis_quality(),
+            sample_rate: EncodingConfiguration::default_sample_rate(),
+            keep_wav: EncodingConfiguration::default_keep_wav(),
+            removal: EncodingConfiguration::default_removal(),
+            workers: EncodingConfiguration::default_workers(),
+            maximum_queue_length: EncodingConfiguration::default_maximum_queue_length(),
+        }
+    }
+}
+
+/// The configuration of the peak normalization which is applied before encoding the recordings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NormalizationConfiguration {
+    #[serde(default = "NormalizationConfiguration::default_enabled")]
+    pub enabled: bool,
+
+    /// The maximum true peak level (in dBTP) of the encoded file.
+    #[serde(default = "NormalizationConfiguration::default_true_peak_ceiling")]
+    pub true_peak_ceiling: f32,
+
+    /// The maximum gain (in dB) which is applied to a recording.
+    #[serde(default = "NormalizationConfiguration::default_maximum_gain")]
+    pub maximum_gain: f32,
+}
+
+impl NormalizationConfiguration {
+    fn default_enabled() -> bool {
+        false
+    }
+
+    fn default_true_peak_ceiling() -> f32 {
+        -1.0
+    }
+
+    fn default_maximum_gain() -> f32 {
+        30.0
+    }
+}
+
+impl NormalizationConfiguration {
+    /// Determine the gain (in dB) which brings the true peak of the recording to the configured
+    /// ceiling. `None` is returned if the normalization is disabled.
+    pub fn get_gain(&self, recording: &Path) -> Option<f32> {
+        if !self.enabled {
+            return None;
+        }
+        let (format, samples) = match open_samples(recording) {
+            Ok(result) => result,
+            Err(error) => {
+                warn!(
+                    "Could not determine the peak level of {}: {}",
+                    recording.display(),
+                    error
+                );
+                return None;
+            }
+        };
+
+        // a completely silent recording can not be normalized
+        let true_peak = get_true_peak_in_db(samples, format.channels);
+        if !true_peak.is_finite() {
+            return None;
+        }
+        let gain = (self.true_peak_ceiling - true_peak).min(self.maximum_gain);
+        debug!(
+            "The true peak of {} is {:.2} dBTP, applying a gain of {:.2} dB",
+            recording.display(),
+            true_peak,
+            gain
+        );
+        Some(gain)
+    }
+}
+
+impl Default for NormalizationConfiguration {
+    fn default() -> Self {
+        NormalizationConfiguration {
+            enabled: NormalizationConfiguration::default_enabled(),
+            true_peak_ceiling: NormalizationConfiguration::default_true_peak_ceiling(),
+            maximum_gain: NormalizationConfiguration::default_maximum_gain(),
+        }
+    }
+}
+
+/// Get the path of a file in the output folder with the same name as the recording but another
+/// extension.
+fn get_output_path(file_prefix: &str, output_folder: &Path, extension: &str) -> String {
+    let file_name = Path::new(file_prefix)
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    output_folder
+        .join(format!("{}.{}", file_name, extension))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Get the duration of a mp3 file by counting its frames. A truncated last frame is not counted.
+fn get_mp3_duration(path: &Path) -> Option<f64> {
+    const MPEG1_BITRATES: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const MPEG2_BITRATES: [u32; 15] =
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+    let content = read(path).ok()?;
+
+    // skip the ID3v2 tag, its size is stored in four bytes with seven bits each
+    let mut offset = 0;
+    if content.len() >= 10 && &content[0..3] == b"ID3" {
+        offset = 10
+            + content[6..10]
+                .iter()
+                .fold(0usize, |size, byte| (size << 7) | usize::from(byte & 0x7F));
+    }
+
+    let mut samples = 0u64;
+    let mut samples_per_second = 0;
+    while offset + 4 <= content.len() {
+        let header = &content[offset..offset + 4];
+        if header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+            break;
+        }
+        // only layer III frames of MPEG 1 (3), MPEG 2 (2) and MPEG 2.5 (0) are expected
+        let version = (header[1] >> 3) & 0x03;
+        let layer = (header[1] >> 1) & 0x03;
+        let bitrate_index = usize::from(header[2] >> 4);
+        let sample_rate_index = usize::from((header[2] >> 2) & 0x03);
+        if version == 1 || layer != 1 || bitrate_index == 0 || bitrate_index == 15 {
+            break;
+        }
+        if sample_rate_index == 3 {
+            break;
+        }
+
+        let (bitrate, sample_rate, samples_per_frame, coefficient) = match version {
+            3 => (
+                MPEG1_BITRATES[bitrate_index],
+                SAMPLE_RATES[sample_rate_index],
+                1152,
+                144,
+            ),
+            2 => (
+                MPEG2_BITRATES[bitrate_index],
+                SAMPLE_RATES[sample_rate_index] / 2,
+                576,
+                72,
+            ),
+            _ => (
+                MPEG2_BITRATES[bitrate_index],
+                SAMPLE_RATES[sample_rate_index] / 4,
+                576,
+                72,
+            ),
+        };
+        let padding = usize::from((header[2] >> 1) & 0x01);
+        let frame_length = (coefficient * bitrate * 1000 / sample_rate) as usize + padding;
+        if offset + frame_length > content.len() {
+            break;
+        }
+
+        samples += samples_per_frame;
+        samples_per_second = sample_rate;
+        offset += frame_length;
+    }
+
+    if samples_per_second == 0 {
+        return None;
+    }
+    Some(samples as f64 / f64::from(samples_per_second))
+}
+
+/// Get the duration of an encoded file with ffprobe.
+fn probe_duration(path: &Path) -> Option<f64> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration")
+        .arg("-of")
+        .arg("default=noprint_wrappers=1:nokey=1")
+        .arg(path)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Check if the encoded file has (nearly) the same duration as the recording, so a truncated or
+/// corrupt file does not lead to the removal of the recording.
+fn is_encoded_file_complete(input: &Path, output_path: &Path, output_format: OutputFormat) -> bool {
+    let recording_duration = match WaveMetaReader::from_file(&input.to_string_lossy()) {
+        Ok(meta_reader) => meta_reader.get_duration(),
+        Err(error) => {
+            warn!(
+                "Could not determine the duration of {}: {}",
+                input.display(),
+                error
+            );
+            return false;
+        }
+    };
+
+    // mp3 files are checked without external tools, since they might be encoded without ffmpeg
+    let encoded_duration = match output_format {
+        OutputFormat::Mp3 => get_mp3_duration(output_path),
+        OutputFormat::Flac | OutputFormat::Ogg => probe_duration(output_path),
+    };
+    match encoded_duration {
+        Some(encoded_duration)
+            if (encoded_duration - recording_duration).abs()
+                <= MAXIMUM_DURATION_DIFFERENCE_IN_SECONDS =>
+        {
+            true
+        }
+        Some(encoded_duration) => {
+            error!(
+                "The duration of {} ({:.1} s) does not match the one of {} ({:.1} s)",
+                output_path.display(),
+                encoded_duration,
+                input.display(),
+                recording_duration
+            );
+            false
+        }
+        None => {
+            error!(
+                "Could not determine the duration of {}",
+                output_path.display()
+            );
+            false
+        }
+    }
+}
+
+/// Convert a recording with ffmpeg, apply the gain (in dB) of the options with a limiter and
+/// store the label as a comment.
+fn convert_with_ffmpeg(input: &Path, output_path: &Path, options: &ConvertOptions) -> bool {
+    let encoding = &options.encoding;
+    let output_format = options.output_format;
+    let mut convert_command = Command::new("ffmpeg");
+
+    // a partial file left behind by an interrupted encoding is overwritten without asking, ffmpeg
+    // must never wait for input on the terminal
+    convert_command
+        .arg("-nostdin")
+        .arg("-y")
+        .arg("-i")
+        .arg(input);
+
+    // the limiter catches the overshoots of the interpolation and ensures the ceiling is kept
+    if let Some(gain) = options.gain_in_db {
+        let limit = 10f32.powf(options.normalization.true_peak_ceiling / 20.0);
+        convert_command.arg("-af").arg(format!(
+            "volume={:.2}dB,alimiter=limit={:.4}:level=0",
+            gain, limit
+        ));
+    }
+
+    if let Some(sample_rate) = encoding.sample_rate {
+        convert_command.arg("-ar").arg(format!("{}", sample_rate));
+    }
+
+    if let Some(label) = &options.label {
+        convert_command
+            .arg("-metadata")
+            .arg(format!("comment={}", label));
+    }
+
+    convert_command.arg("-c:a").arg(output_format.get_codec());
+    match output_format {
+        OutputFormat::Mp3 => {
+            if let Some(bitrate) = encoding.mp3_bitrate {
+                convert_command.arg("-b:a").arg(format!("{}k", bitrate));
+            }
+            if let Some(quality) = encoding.mp3_quality {
+                convert_command.arg("-q:a").arg(format!("{}", quality));
+            }
+        }
+        OutputFormat::Ogg => {
+            convert_command
+                .arg("-q:a")
+                .arg(format!("{}", encoding.vorbis_quality));
+        }
+        OutputFormat::Flac => {}
+    }
+
+    // the format can not be guessed from the extension of the partial file
+    let convert_status = convert_command
+        .arg("-f")
+        .arg(output_format.get_extension())
+        .arg(output_path)
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status();
+    convert_status.is_ok() && convert_status.unwrap().success()
+}
+
+/// Remove a recording which is not needed anymore. It is moved to the trash folder if one is
+/// supplied, failures are only logged.
+pub fn remove_recording(recording: &Path, trash_folder: Option<&Path>) {
+    let result = match trash_folder {
+        Some(trash_folder) => {
+            let trash_path = trash_folder.join(recording.file_name().unwrap_or_default());
+            create_dir_all(trash_folder).and_then(|_| rename(recording, trash_path))
+        }
+        None => remove_file(recording),
+    };
+    if let Err(error) = result {
+        warn!(
+            "Could not remove {}. The error was: {}",
+            recording.display(),
+            error
+        );
+    }
+}
+
+/// The options for encoding a recording.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    /// The folder the encoded file is stored in.
+    pub output_folder: PathBuf,
+
+    pub output_format: OutputFormat,
+
+    pub encoding: EncodingConfiguration,
+
+    /// The settings of the limiter which is used if a gain is applied.
+    pub normalization: NormalizationConfiguration,
+
+    /// The gain (in dB) which is applied before encoding (see
+    /// [`NormalizationConfiguration::get_gain`]).
+    pub gain_in_db: Option<f32>,
+
+    /// The label of the session which is stored as a comment in the encoded file.
+    pub label: Option<String>,
+
+    /// The folder the recording is moved to after it was encoded, it is deleted if none is set.
+    pub trash_folder: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum ConvertError {
+    /// The recording does not exist.
+    MissingInput(PathBuf),
+
+    /// The encoder failed, the recording is kept.
+    Encoder(PathBuf),
+
+    /// The encoded file is not complete, the recording is kept.
+    Incomplete(PathBuf),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConvertError::MissingInput(ref path) => {
+                write!(f, "the recording {} does not exist", path.display())
+            }
+            ConvertError::Encoder(ref path) => write!(f, "could not convert {}", path.display()),
+            ConvertError::Incomplete(ref path) => {
+                write!(f, "the encoded file {} is incomplete", path.display())
+            }
+        }
+    }
+}
+
+/// Convert a recording to the output format of the options (stored in the output folder with the
+/// same name as the recording) and remove the recording afterwards (unless it should be kept).
+/// The path of the encoded file is returned.
+pub fn convert_audio(input: &Path, options: &ConvertOptions) -> Result<PathBuf, ConvertError> {
+    if !input.is_file() {
+        return Err(ConvertError::MissingInput(input.to_path_buf()));
+    }
+    let file_name = input
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let output_path = options.output_folder.join(format!(
+        "{}.{}",
+        file_name,
+        options.output_format.get_extension()
+    ));
+    info!(
+        "Converting {} to {}",
+        input.display(),
+        output_path.display()
+    );
+
+    // the encoder writes to a partial file which replaces the output once it is complete, so a
+    // failed encoding never removes an encoded file which was stored before
+    let partial_path = get_partial_file_path(&output_path);
+
+    // mp3 files are encoded in-process if the encoder was built in, so ffmpeg is not required
+    // (resampling is left to ffmpeg though)
+    #[cfg(feature = "lame")]
+    let was_successful =
+        if options.output_format == OutputFormat::Mp3 && options.encoding.sample_rate.is_none() {
+            match lame::encode_mp3(
+                input,
+                &partial_path,
+                &options.encoding,
+                options.gain_in_db,
+                options.label.as_deref(),
+            ) {
+                Ok(()) => true,
+                Err(error) => {
+                    error!(
+                        "Could not encode {}. The error was: {}",
+                        input.display(),
+                        error
+                    );
+                    false
+                }
+            }
+        } else {
+            convert_with_ffmpeg(input, &partial_path, options)
+        };
+    #[cfg(not(feature = "lame"))]
+    let was_successful = convert_with_ffmpeg(input, &partial_path, options);
+
+    // the recording is only removed if the encoded file is complete
+    if !was_successful {
+        let _ = remove_file(&partial_path);
+        error!("Could not convert {}", input.display());
+        return Err(ConvertError::Encoder(input.to_path_buf()));
+    }
+    if !is_encoded_file_complete(input, &partial_path, options.output_format) {
+        let _ = remove_file(&partial_path);
+        return Err(ConvertError::Incomplete(output_path));
+    }
+    if let Err(error) = rename(&partial_path, &output_path) {
+        error!(
+            "Could not store the encoded file {}. The error was: {}",
+            output_path.display(),
+            error
+        );
+        let _ = remove_file(&partial_path);
+        return Err(ConvertError::Encoder(input.to_path_buf()));
+    }
+
+    // if the conversion was successful, we can remove the old record of the audio file
+    if options.encoding.keep_wav {
+        debug!("File conversion successful, keeping {}", input.display());
+        return Ok(output_path);
+    }
+    debug!("File conversion successful, removing {}", input.display());
+    remove_recording(input, options.trash_folder.as_deref());
+    Ok(output_path)
+}
+
+/// Create a small 8 kHz mono Opus preview of a recording (stored in the output folder) which can
+/// be used for fast seeking and streaming. The original recording is not touched.
+pub fn create_preview_file(file_prefix: String, output_folder: &Path) {
+    let output_path = get_output_path(&file_prefix, output_folder, "preview.opus");
+    info!("Creating preview {}", output_path);
+    let preview_status = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(format!("{}.wav", file_prefix))
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg("8000")
+        .arg("-c:a")
+        .arg("libopus")
+        .arg("-b:a")
+        .arg("12k")
+        .arg(&output_path)
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status();
+
+    // the preview is optional, so a failure is not fatal but should be visible
+    if preview_status.is_err() || !preview_status.unwrap().success() {
+        error!("Could not create the preview file for {}.wav", file_prefix);
+    }
+}
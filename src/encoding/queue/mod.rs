@@ -0,0 +1,186 @@
+use core::fmt;
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use tracing::{debug, error, info, info_span, Span};
+
+/// A post-processing job (e.g. encoding a recording) which is executed by a worker.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A condition which pauses the workers before they start the next job, e.g. while a subsystem
+/// with a higher priority is running.
+pub type PauseCondition = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// The interval in which a paused worker checks if it can continue.
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The interval in which the queue is checked while waiting for the remaining jobs.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The error which is returned if a job could not be queued since the queue is full.
+#[derive(Debug)]
+pub struct QueueFullError {
+    capacity: usize,
+}
+
+impl fmt::Display for QueueFullError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the queue is full ({} jobs are waiting)", self.capacity)
+    }
+}
+
+/// A queue which executes the post-processing of the recordings with a fixed number of workers,
+/// so the encoders can not pile up and starve the recorder. The queue can be shared between the
+/// recording threads by cloning it.
+#[derive(Clone)]
+pub struct EncodingQueue {
+    sender: SyncSender<Job>,
+    depth: Arc<AtomicUsize>,
+    active_jobs: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl EncodingQueue {
+    /// Create a queue for at most `capacity` waiting jobs and start the workers.
+    pub fn new(workers: usize, capacity: usize) -> EncodingQueue {
+        EncodingQueue::with_pause_condition(workers, capacity, Arc::new(|| false))
+    }
+
+    /// Create a queue whose workers do not start the next job while the pause condition is met.
+    pub fn with_pause_condition(
+        workers: usize,
+        capacity: usize,
+        pause_condition: PauseCondition,
+    ) -> EncodingQueue {
+        let (sender, receiver) = sync_channel::<Job>(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let depth = Arc::new(AtomicUsize::new(0));
+        let active_jobs = Arc::new(AtomicUsize::new(0));
+        for worker in 0..workers {
+            let receiver = receiver.clone();
+            let depth = depth.clone();
+            let active_jobs = active_jobs.clone();
+            let pause_condition = pause_condition.clone();
+            spawn(move || run_worker(worker, &receiver, &depth, &active_jobs, &pause_condition));
+        }
+        EncodingQueue {
+            sender,
+            depth,
+            active_jobs,
+            capacity,
+        }
+    }
+
+    /// Get the number of jobs which are waiting for a worker.
+    pub fn get_depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+
+    /// Wait until all queued jobs were executed.
+    pub fn wait_until_idle(&self) {
+        while self.get_depth() > 0 || self.active_jobs.load(Ordering::SeqCst) > 0 {
+            sleep(IDLE_CHECK_INTERVAL);
+        }
+    }
+
+    /// Add a job to the queue without waiting. The number of waiting jobs (including the new one)
+    /// is returned.
+    pub fn try_submit<F>(&self, job: F) -> Result<usize, QueueFullError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // the span of the job only covers its execution, its parent is the span which queued it
+        // (e.g. the recorded device)
+        let parent_span = Span::current();
+        let job = move || {
+            let _job_entered = info_span!(parent: &parent_span, "encode_job").entered();
+            job()
+        };
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst) + 1;
+        match self.sender.try_send(Box::new(job)) {
+            Ok(()) => Ok(depth),
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.depth.fetch_sub(1, Ordering::SeqCst);
+                Err(QueueFullError {
+                    capacity: self.capacity,
+                })
+            }
+        }
+    }
+}
+
+/// Marks a job as active while it exists, so the job is no longer counted when it ended in any way
+/// (including a panic).
+struct ActiveJob<'a> {
+    active_jobs: &'a AtomicUsize,
+}
+
+impl<'a> ActiveJob<'a> {
+    fn start(active_jobs: &'a AtomicUsize) -> ActiveJob<'a> {
+        active_jobs.fetch_add(1, Ordering::SeqCst);
+        ActiveJob { active_jobs }
+    }
+}
+
+impl Drop for ActiveJob<'_> {
+    fn drop(&mut self) {
+        self.active_jobs.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Get the message of a panic, if it has one.
+fn get_panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown reason"
+    }
+}
+
+fn run_worker(
+    worker: usize,
+    receiver: &Mutex<Receiver<Job>>,
+    depth: &AtomicUsize,
+    active_jobs: &AtomicUsize,
+    pause_condition: &PauseCondition,
+) {
+    loop {
+        // the lock is only held while waiting for the next job, not while executing it
+        let job = match receiver.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let _active_job = ActiveJob::start(active_jobs);
+        let remaining = depth.fetch_sub(1, Ordering::SeqCst) - 1;
+        debug!(
+            "Worker {} started a job, {} job(s) are waiting",
+            worker, remaining
+        );
+
+        // the job is postponed (and keeps its place) until the pause is over
+        if pause_condition() {
+            info!("Worker {} is paused", worker);
+            while pause_condition() {
+                sleep(PAUSE_CHECK_INTERVAL);
+            }
+            info!("Worker {} continues", worker);
+        }
+
+        // a failing job must not take the worker with it, otherwise the pool would shrink and
+        // waiting for the remaining jobs would never end
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(job)) {
+            error!(
+                "A job of worker {} failed unexpectedly: {}",
+                worker,
+                get_panic_message(payload.as_ref())
+            );
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// The interval in which a running hook is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The external commands which are executed when something happened in the recorder.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfiguration {
+    /// The command which is executed for every finished segment (after it was post-processed),
+    /// e.g. `my-script {path} {device} {start_iso}`.
+    #[serde(default = "HookConfiguration::default_on_segment_finished")]
+    pub on_segment_finished: Option<String>,
+
+    /// The command which is executed when an input is near-silent for a long time while another
+    /// input still records sound. `{start_iso}` and `{duration}` describe the silent time and
+    /// `{path}` is its latest recording.
+    #[serde(default = "HookConfiguration::default_on_microphone_detached")]
+    pub on_microphone_detached: Option<String>,
+
+    /// The command which is executed when an input is given up after its recording failed too
+    /// often. `{start_iso}` and `{duration}` describe the time it was failing and `{path}` is the
+    /// folder its recordings are stored in.
+    #[serde(default = "HookConfiguration::default_on_input_failed")]
+    pub on_input_failed: Option<String>,
+
+    /// The time after which a hook which is still running gets killed.
+    #[serde(default = "HookConfiguration::default_timeout_in_seconds")]
+    pub timeout_in_seconds: u64,
+}
+
+impl HookConfiguration {
+    fn default_on_segment_finished() -> Option<String> {
+        None
+    }
+
+    fn default_on_microphone_detached() -> Option<String> {
+        None
+    }
+
+    fn default_on_input_failed() -> Option<String> {
+        None
+    }
+
+    fn default_timeout_in_seconds() -> u64 {
+        60
+    }
+}
+
+impl Default for HookConfiguration {
+    fn default() -> Self {
+        HookConfiguration {
+            on_segment_finished: HookConfiguration::default_on_segment_finished(),
+            on_microphone_detached: HookConfiguration::default_on_microphone_detached(),
+            on_input_failed: HookConfiguration::default_on_input_failed(),
+            timeout_in_seconds: HookConfiguration::default_timeout_in_seconds(),
+        }
+    }
+}
+
+/// The values a placeholder of a hook can be replaced with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    Path,
+    Device,
+    StartIso,
+    Duration,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Placeholder> {
+        match name {
+            "path" => Some(Placeholder::Path),
+            "device" => Some(Placeholder::Device),
+            "start_iso" => Some(Placeholder::StartIso),
+            "duration" => Some(Placeholder::Duration),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// The information about a finished segment which is passed to the hook.
+pub struct FinishedSegment<'a> {
+    /// The encoded file of the segment or the raw recording if it was not encoded.
+    pub path: &'a Path,
+    pub device: &'a str,
+    pub started_at: DateTime<Local>,
+    pub duration_in_seconds: u32,
+}
+
+/// A command with placeholders (`{path}`, `{device}`, `{start_iso}` and `{duration}`) in its
+/// arguments. The arguments are split at whitespace and passed to the program directly, so the
+/// values are never interpreted by a shell. Literal braces are written as `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandTemplate {
+    arguments: Vec<Vec<TemplatePart>>,
+}
+
+impl CommandTemplate {
+    pub fn parse(template: &str) -> Result<CommandTemplate, String> {
+        let mut arguments = vec![];
+        for argument in template.split_whitespace() {
+            let mut parts = vec![];
+            let mut literal = String::new();
+            let mut characters = argument.chars().peekable();
+            while let Some(character) = characters.next() {
+                match character {
+                    '{' if characters.peek() == Some(&'{') => {
+                        characters.next();
+                        literal.push('{');
+                    }
+                    '}' if characters.peek() == Some(&'}') => {
+                        characters.next();
+                        literal.push('}');
+                    }
+                    '{' => {
+                        let mut name = String::new();
+                        let mut is_closed = false;
+                        for character in characters.by_ref() {
+                            if character == '}' {
+                                is_closed = true;
+                                break;
+                            }
+                            name.push(character);
+                        }
+                        if !is_closed {
+                            return Err(format!("unclosed '{{' in '{}'", argument));
+                        }
+                        let placeholder = Placeholder::parse(&name)
+                            .ok_or_else(|| format!("unknown placeholder '{{{}}}'", name))?;
+                        if !literal.is_empty() {
+                            parts.push(TemplatePart::Literal(literal.split_off(0)));
+                        }
+                        parts.push(TemplatePart::Placeholder(placeholder));
+                    }
+                    '}' => return Err(format!("unmatched '}}' in '{}'", argument)),
+                    _ => literal.push(character),
+                }
+            }
+            if !literal.is_empty() {
+                parts.push(TemplatePart::Literal(literal));
+            }
+            arguments.push(parts);
+        }
+
+        // the program itself is not templated, so a segment can never choose what is executed
+        match arguments.first().map(|program| program.as_slice()) {
+            None => Err("the command is empty".to_string()),
+            Some([TemplatePart::Literal(_)]) => Ok(CommandTemplate { arguments }),
+            Some(_) => Err("the program must not contain placeholders".to_string()),
+        }
+    }
+
+    /// Get the program and its arguments with the placeholders replaced by the supplied values.
+    pub fn render(&self, segment: &FinishedSegment) -> Vec<String> {
+        self.arguments
+            .iter()
+            .map(|parts| {
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        TemplatePart::Literal(literal) => literal.clone(),
+                        TemplatePart::Placeholder(Placeholder::Path) => {
+                            segment.path.to_string_lossy().to_string()
+                        }
+                        TemplatePart::Placeholder(Placeholder::Device) => {
+                            segment.device.to_string()
+                        }
+                        TemplatePart::Placeholder(Placeholder::StartIso) => {
+                            segment.started_at.to_rfc3339()
+                        }
+                        TemplatePart::Placeholder(Placeholder::Duration) => {
+                            segment.duration_in_seconds.to_string()
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Execute the command for a segment and wait until it exits. It is killed if it runs longer
+    /// than the timeout.
+    pub fn run(&self, segment: &FinishedSegment, timeout: Duration) -> Result<(), String> {
+        let arguments = self.render(segment);
+        let mut child = Command::new(&arguments[0])
+            .args(&arguments[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("could not run {}: {}", arguments[0], error))?;
+
+        let start = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => return Ok(()),
+                Ok(Some(status)) => return Err(format!("{} failed with {}", arguments[0], status)),
+                Ok(None) if start.elapsed() >= timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!(
+                        "{} was killed after {} seconds",
+                        arguments[0],
+                        timeout.as_secs()
+                    ));
+                }
+                Ok(None) => sleep(POLL_INTERVAL),
+                Err(error) => {
+                    return Err(format!("could not wait for {}: {}", arguments[0], error))
+                }
+            }
+        }
+    }
+}
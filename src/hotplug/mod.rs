@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::shutdown::sleep_unless_shutdown;
+use crate::{get_available_devices, DeviceInfo, RecordingDeviceConfiguration};
+
+/// The settings for recovering inputs whose USB audio device disappeared while recording.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HotplugConfiguration {
+    /// Wait for a disappeared card to reappear (with any card number) and continue recording it.
+    #[serde(default = "HotplugConfiguration::default_enabled")]
+    pub enabled: bool,
+
+    /// The time between two checks if a disappeared card is available again.
+    #[serde(default = "HotplugConfiguration::default_retry_interval_in_seconds")]
+    pub retry_interval_in_seconds: u32,
+}
+
+impl HotplugConfiguration {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_retry_interval_in_seconds() -> u32 {
+        5
+    }
+}
+
+impl Default for HotplugConfiguration {
+    fn default() -> Self {
+        HotplugConfiguration {
+            enabled: HotplugConfiguration::default_enabled(),
+            retry_interval_in_seconds: HotplugConfiguration::default_retry_interval_in_seconds(),
+        }
+    }
+}
+
+/// The name of the card and the number of the device an input records, which (unlike the number
+/// of the card) stay the same when a USB device is plugged in again.
+#[derive(Debug, Clone, PartialEq)]
+struct CardIdentity {
+    card_name: String,
+    device: u8,
+}
+
+/// Keeps track of the card numbers of the inputs which are recorded with `arecord`, so an input
+/// whose card disappeared can be recorded again once the card was re-enumerated.
+pub struct HotplugWatch {
+    retry_interval: Duration,
+    identities: HashMap<String, CardIdentity>,
+    card_and_device: Mutex<HashMap<String, (u8, u8)>>,
+}
+
+impl HotplugWatch {
+    /// Remember the names of the cards the inputs currently record. `None` is returned if the
+    /// recovery is disabled.
+    pub fn new<'a, I>(
+        config: &HotplugConfiguration,
+        inputs: I,
+        available_devices: &[DeviceInfo],
+    ) -> Option<HotplugWatch>
+    where
+        I: IntoIterator<Item = (&'a String, &'a RecordingDeviceConfiguration)>,
+    {
+        if !config.enabled {
+            return None;
+        }
+        let mut identities = HashMap::new();
+        let mut card_and_device = HashMap::new();
+        for (input_name, input_device) in inputs {
+            let device_info = available_devices.iter().find(|device_info| {
+                device_info.card == input_device.card && device_info.device == input_device.device
+            });
+            if let Some(device_info) = device_info {
+                identities.insert(
+                    input_name.clone(),
+                    CardIdentity {
+                        card_name: device_info.card_name.clone(),
+                        device: device_info.device,
+                    },
+                );
+                card_and_device
+                    .insert(input_name.clone(), (input_device.card, input_device.device));
+            }
+        }
+        Some(HotplugWatch {
+            retry_interval: Duration::from_secs(u64::from(config.retry_interval_in_seconds.max(1))),
+            identities,
+            card_and_device: Mutex::new(card_and_device),
+        })
+    }
+
+    /// Get the card and device number an input is currently recorded from, `None` if the input is
+    /// not watched.
+    pub fn get_card_and_device(&self, input_name: &str) -> Option<(u8, u8)> {
+        self.card_and_device
+            .lock()
+            .unwrap()
+            .get(input_name)
+            .copied()
+    }
+
+    /// Wait until the card of an input which failed to record is available again (matched by its
+    /// name, since the card number changes when the device is re-enumerated). The card is looked
+    /// for in the retry interval until the deadline passes or a shutdown is requested.
+    ///
+    /// The new card and device number are returned once the card reappeared. `None` is returned if
+    /// the card never disappeared (so the recording failed for another reason), if it did not
+    /// reappear in time or if the input is not watched.
+    pub fn wait_for_device(&self, input_name: &str, deadline: Instant) -> Option<(u8, u8)> {
+        let identity = self.identities.get(input_name)?;
+        let known_card_and_device = self.get_card_and_device(input_name)?;
+        let mut was_missing = false;
+        loop {
+            let current_card_and_device = get_available_devices()
+                .ok()
+                .and_then(|available_devices| find_card(identity, &available_devices));
+            match current_card_and_device {
+                Some(card_and_device)
+                    if card_and_device == known_card_and_device && !was_missing =>
+                {
+                    return None;
+                }
+                Some((card, device)) => {
+                    info!(
+                        "The card {} of {} is available again as card {} and device {}",
+                        identity.card_name, input_name, card, device
+                    );
+                    self.card_and_device
+                        .lock()
+                        .unwrap()
+                        .insert(input_name.to_string(), (card, device));
+                    return Some((card, device));
+                }
+                None if !was_missing => {
+                    warn!(
+                        "The card {} of {} disappeared, waiting for it to be plugged in again",
+                        identity.card_name, input_name
+                    );
+                    was_missing = true;
+                }
+                None => {}
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !sleep_unless_shutdown(self.retry_interval.min(remaining)) {
+                return None;
+            }
+        }
+    }
+}
+
+/// Find the card and device number of a card by its name.
+fn find_card(identity: &CardIdentity, available_devices: &[DeviceInfo]) -> Option<(u8, u8)> {
+    available_devices
+        .iter()
+        .find(|device_info| {
+            device_info.card_name == identity.card_name && device_info.device == identity.device
+        })
+        .map(|device_info| (device_info.card, device_info.device))
+}
@@ -0,0 +1,105 @@
+use std::cmp::Ordering;
+
+use crate::analysis::Levels;
+use crate::baseline::get_median;
+use crate::manifest::{SegmentManifest, SessionManifest};
+
+/// The share of the score (out of 100) which is given for a loud or clipping recording.
+const LEVEL_WEIGHT: f32 = 25.0;
+
+/// The share of the score which is given for the events of the segment.
+const EVENT_WEIGHT: f32 = 35.0;
+
+/// The share of the score which is given for the passages with sound (e.g. speech or snoring).
+const ACTIVITY_WEIGHT: f32 = 20.0;
+
+/// The share of the score which is given for a level which differs from the other segments.
+const DEVIATION_WEIGHT: f32 = 20.0;
+
+/// Peaks below this level do not add anything to the score.
+const QUIET_PEAK_LEVEL_IN_DBFS: f32 = -40.0;
+
+/// The number of events per minute which gets the full share of the events.
+const EVENTS_PER_MINUTE_FOR_FULL_SCORE: f32 = 10.0;
+
+/// The difference (in dB) from the usual RMS level which gets the full share of the deviation.
+const DEVIATION_FOR_FULL_SCORE_IN_DB: f32 = 12.0;
+
+/// Compute how interesting a segment is for someone listening to the night, as a score between 0
+/// (nothing happened) and 100. The score combines:
+///
+/// - the peak level, a clipping recording gets the full share,
+/// - the number of events per minute,
+/// - the share of the segment which is not silent (there is no speech detection, so any sound
+///   above the silence threshold counts),
+/// - how much the RMS level differs from the usual level of the input (`usual_rms_in_dbfs`).
+///
+/// Figures which were not measured for the segment do not add to the score. Silent segments
+/// always get a score of 0.
+pub fn score_segment(segment: &SegmentManifest, usual_rms_in_dbfs: Option<f32>) -> u8 {
+    if segment.silent {
+        return 0;
+    }
+    let mut score = 0.0;
+    if let (Some(peak_in_dbfs), Some(rms_in_dbfs)) =
+        (segment.peak_level_in_dbfs, segment.rms_level_in_dbfs)
+    {
+        let levels = Levels {
+            peak_in_dbfs,
+            rms_in_dbfs,
+        };
+        score += if levels.is_clipping() {
+            LEVEL_WEIGHT
+        } else {
+            LEVEL_WEIGHT * (1.0 - peak_in_dbfs / QUIET_PEAK_LEVEL_IN_DBFS).clamp(0.0, 1.0)
+        };
+        if let Some(usual_rms_in_dbfs) = usual_rms_in_dbfs {
+            let deviation = (rms_in_dbfs - usual_rms_in_dbfs).abs();
+            score += DEVIATION_WEIGHT * (deviation / DEVIATION_FOR_FULL_SCORE_IN_DB).min(1.0);
+        }
+    }
+    if let Some(events) = segment.events {
+        let minutes = (segment.duration_in_seconds as f32 / 60.0).max(1.0);
+        let events_per_minute = events as f32 / minutes;
+        score += EVENT_WEIGHT * (events_per_minute / EVENTS_PER_MINUTE_FOR_FULL_SCORE).min(1.0);
+    }
+    if let Some(silence_percentage) = segment.silence_percentage {
+        score += ACTIVITY_WEIGHT * (1.0 - silence_percentage / 100.0).clamp(0.0, 1.0);
+    }
+    score.round().clamp(0.0, 100.0) as u8
+}
+
+/// Get the usual RMS level of an input, which is the median of the levels of all segments of the
+/// input which are not silent.
+pub fn get_usual_rms_level<'a, I>(segments: I, input: &str) -> Option<f32>
+where
+    I: IntoIterator<Item = &'a SegmentManifest>,
+{
+    get_median(
+        segments
+            .into_iter()
+            .filter(|segment| segment.input == input && !segment.silent)
+            .filter_map(|segment| segment.rms_level_in_dbfs)
+            .collect(),
+    )
+}
+
+/// Score a segment of a session against the other segments of its input and store the score in
+/// the manifest. The score is returned, `None` if the segment is not part of the session.
+pub fn score_session_segment(manifest: &mut SessionManifest, file: &str) -> Option<u8> {
+    let index = manifest
+        .segments
+        .iter()
+        .position(|segment| segment.file == file)?;
+    let usual_rms_in_dbfs =
+        get_usual_rms_level(&manifest.segments, &manifest.segments[index].input);
+    let score = score_segment(&manifest.segments[index], usual_rms_in_dbfs);
+    manifest.segments[index].interestingness = Some(score);
+    Some(score)
+}
+
+/// Order two scores so the most interesting segments come first and the ones without a score
+/// come last.
+pub fn compare_interestingness(a: Option<u8>, b: Option<u8>) -> Ordering {
+    b.cmp(&a)
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::fs::{remove_file, File};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::defaults;
+use crate::playback::{play_samples, OutputConfiguration};
+use crate::wave::read_mono_samples;
+
+/// The file in the state folder which stores the measured latencies of all inputs.
+pub const CALIBRATION_FILE_NAME: &str = "latency.json";
+
+const SAMPLES_PER_SECOND: u32 = defaults::SAMPLE_RATE;
+
+/// The chirp is short, so finding it in the recording stays cheap.
+const CHIRP_DURATION_IN_SECONDS: f32 = 0.1;
+const CHIRP_START_FREQUENCY: f32 = 500.0;
+const CHIRP_END_FREQUENCY: f32 = 8000.0;
+
+/// The time between starting the recording and playing the chirp, so the recording is already
+/// running when the chirp arrives.
+const LEAD_IN: Duration = Duration::from_secs(1);
+const RECORDING_DURATION_IN_SECONDS: u32 = 4;
+
+/// The normalized correlation the best match has to exceed to be accepted as the chirp.
+const MINIMUM_CORRELATION: f32 = 0.3;
+
+/// The result of measuring the round-trip latency between an output and an input device.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LatencyMeasurement {
+    /// The name of the output device the chirp was played on.
+    pub output: String,
+
+    /// The time (in milliseconds) between starting the playback and the chirp arriving in the
+    /// recording.
+    pub latency_in_ms: f64,
+
+    /// The normalized correlation (between 0.0 and 1.0) of the detected chirp.
+    pub correlation: f32,
+
+    pub measured_at: String,
+}
+
+/// Load the measured latencies (by the name of the input) from the state folder.
+pub fn load_calibration(state_folder: &Path) -> io::Result<BTreeMap<String, LatencyMeasurement>> {
+    let path = state_folder.join(CALIBRATION_FILE_NAME);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    serde_json::from_reader(File::open(path)?).map_err(io::Error::from)
+}
+
+/// Store the measured latencies (by the name of the input) in the state folder.
+pub fn store_calibration(
+    state_folder: &Path,
+    calibration: &BTreeMap<String, LatencyMeasurement>,
+) -> io::Result<()> {
+    let file = File::create(state_folder.join(CALIBRATION_FILE_NAME))?;
+    serde_json::to_writer_pretty(file, calibration).map_err(io::Error::from)
+}
+
+/// Generate a linear sine sweep with fade-in and fade-out (a Hann window).
+pub fn generate_chirp(samples_per_second: u32) -> Vec<f32> {
+    let length = (CHIRP_DURATION_IN_SECONDS * samples_per_second as f32) as usize;
+    let sweep_rate = (CHIRP_END_FREQUENCY - CHIRP_START_FREQUENCY) / CHIRP_DURATION_IN_SECONDS;
+    (0..length)
+        .map(|index| {
+            let time = index as f32 / samples_per_second as f32;
+            let phase = 2.0 * PI * (CHIRP_START_FREQUENCY * time + 0.5 * sweep_rate * time * time);
+            let window = 0.5 - 0.5 * (2.0 * PI * index as f32 / length as f32).cos();
+            0.8 * window * phase.sin()
+        })
+        .collect()
+}
+
+/// Find the position (in samples) where the signal starts in the recording. The normalized
+/// cross-correlation of the best match is returned as well.
+pub fn find_signal(recording: &[f32], signal: &[f32]) -> Option<(usize, f32)> {
+    if signal.is_empty() || recording.len() < signal.len() {
+        return None;
+    }
+    let signal_energy: f32 = signal.iter().map(|sample| sample * sample).sum();
+
+    // the energy of the recording in the current window is updated while moving the window
+    let mut window_energy: f32 = recording[..signal.len()]
+        .iter()
+        .map(|sample| sample * sample)
+        .sum();
+    let mut best_match: Option<(usize, f32)> = None;
+    for offset in 0..=recording.len() - signal.len() {
+        if offset > 0 {
+            let removed = recording[offset - 1];
+            let added = recording[offset + signal.len() - 1];
+            window_energy = (window_energy - removed * removed + added * added).max(0.0);
+        }
+        if window_energy <= f32::EPSILON {
+            continue;
+        }
+
+        let product: f32 = recording[offset..offset + signal.len()]
+            .iter()
+            .zip(signal)
+            .map(|(recorded, expected)| recorded * expected)
+            .sum();
+        let correlation = product / (signal_energy * window_energy).sqrt();
+        if best_match.map_or(true, |(_, best_correlation)| correlation > best_correlation) {
+            best_match = Some((offset, correlation));
+        }
+    }
+    best_match
+}
+
+/// Play a chirp on the output device while recording the input device and measure the time
+/// until the chirp arrives in the recording. The start-up times of `arecord` and the playback
+/// tool are not compensated, so the result is only accurate to a few milliseconds. The temporary
+/// files are stored in the supplied folder.
+pub fn measure_latency(
+    input_pcm: &str,
+    output: &OutputConfiguration,
+    working_folder: &Path,
+) -> Result<(f64, f32), String> {
+    let chirp = generate_chirp(SAMPLES_PER_SECOND);
+    let recording_path = working_folder.join("calibration_recording.wav");
+
+    let mut record_command = Command::new("arecord");
+    record_command
+        .arg(format!("-D{}", input_pcm))
+        .arg(format!("-d{}", RECORDING_DURATION_IN_SECONDS))
+        .arg(format!(
+            "-f{}",
+            defaults::SAMPLE_FORMAT.get_arecord_format()
+        ))
+        .arg(format!("-r{}", SAMPLES_PER_SECOND))
+        .arg("-c1")
+        .arg(&recording_path)
+        .stderr(Stdio::null())
+        .stdout(Stdio::null());
+    let recording_started_at = Instant::now();
+    let recording_thread = spawn(move || record_command.status());
+
+    sleep(LEAD_IN);
+    let playback_started_at = Instant::now();
+    let playback_result = play_samples(output, &chirp, SAMPLES_PER_SECOND, working_folder);
+    let recording_status = recording_thread.join().unwrap();
+
+    if let Err(error) = playback_result {
+        let _ = remove_file(&recording_path);
+        return Err(format!("could not play the chirp: {}", error));
+    }
+    if !recording_status.is_ok_and(|status| status.success()) {
+        let _ = remove_file(&recording_path);
+        return Err(format!("could not record from {}", input_pcm));
+    }
+
+    let samples = read_mono_samples(&recording_path);
+    let _ = remove_file(&recording_path);
+    let (_, samples) =
+        samples.map_err(|error| format!("could not read the recording: {}", error))?;
+    let (position, correlation) = match find_signal(&samples, &chirp) {
+        Some((position, correlation)) if correlation >= MINIMUM_CORRELATION => {
+            (position, correlation)
+        }
+        _ => {
+            return Err(
+                "the chirp could not be found in the recording, is the volume too low?".to_string(),
+            )
+        }
+    };
+    debug!(
+        "Found the chirp at sample {} with a correlation of {:.2}",
+        position, correlation
+    );
+
+    let arrival = position as f64 / f64::from(SAMPLES_PER_SECOND);
+    let playback_offset = (playback_started_at - recording_started_at).as_secs_f64();
+    Ok(((arrival - playback_offset) * 1000.0, correlation))
+}
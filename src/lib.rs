@@ -2,21 +2,125 @@ use core::fmt;
 use std::collections::HashMap;
 use std::env::current_dir;
 use std::error;
+use std::fs::File;
+use std::io;
+use std::io::Read;
 use std::process::{Command, Stdio};
 
-use chrono::Local;
-use log::{debug, error, info};
 use regex::bytes::Regex;
 use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
 
+use crate::activation::ActivationConfiguration;
+use crate::analysis::{compare_channels, ChannelComparison};
+use crate::backend::watchdog::CaptureWatchdogConfiguration;
+use crate::backend::{BackpressureStrategy, RecordingBackend};
+use crate::backup::BackupConfiguration;
+use crate::baseline::BaselineConfiguration;
+use crate::clock::ClockConfiguration;
+use crate::control::ControlConfiguration;
+use crate::defaults::AudioDefaults;
+use crate::detection::CoughDetectionConfiguration;
+use crate::encoding::{EncodingConfiguration, NormalizationConfiguration, OutputFormat};
+use crate::hooks::HookConfiguration;
+use crate::hotplug::HotplugConfiguration;
+use crate::naming::template::{
+    get_filename_template, get_next_sequence_number, FileNameValues, FilenameTemplate,
+};
+use crate::naming::EventNamingConfiguration;
+use crate::playback::OutputConfiguration;
+use crate::power::PowerConfiguration;
+use crate::priority::PriorityConfiguration;
+use crate::retention::RetentionConfiguration;
+use crate::retry::RetryConfiguration;
+use crate::scheduler::{
+    MaintenanceTaskConfiguration, ScheduleConfiguration, StartAlignment, StopConfiguration,
+};
+use crate::shutdown::{is_recording_stop_requested, wait_for_recording_process};
+use crate::silence::SilenceConfiguration;
+use crate::storage::{QuotaAction, StorageConfiguration};
+use crate::sync::SyncConfiguration;
+use crate::tee::TeeConfiguration;
+use crate::telemetry::TelemetryConfiguration;
+use crate::timezone::{to_timestamp_timezone, TimestampTimezone};
+use crate::update::UpdateConfiguration;
+use crate::upload::UploadConfiguration;
+use crate::wave::{read_samples, repair_header, SampleFormat};
 use lazy_static::lazy_static;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+pub mod activation;
+pub mod analysis;
 pub mod annotation;
+pub mod archive;
+pub mod backend;
+pub mod backup;
+pub mod baseline;
+pub mod changelog;
+pub mod clock;
 pub mod commands;
+pub mod control;
+#[cfg(feature = "analysis")]
+pub mod cuesheet;
+#[cfg(feature = "recorder")]
+pub mod daemon;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+#[cfg(feature = "analysis")]
+pub mod decoding;
+pub mod defaults;
+pub mod detection;
+pub mod encoding;
+pub mod hooks;
+pub mod hotplug;
+pub mod interest;
+#[cfg(feature = "recorder")]
+pub mod latency;
+pub mod lock;
+pub mod manifest;
+#[cfg(feature = "recorder")]
+pub mod metrics;
+pub mod mixer;
+pub mod naming;
+pub mod playback;
+pub mod power;
+pub mod priority;
+#[cfg(feature = "recorder")]
+pub mod recovery;
+pub mod retention;
+pub mod retry;
+pub mod scheduler;
+pub mod schemas;
+pub mod shutdown;
+pub mod silence;
+pub mod status;
+pub mod storage;
+pub mod sync;
+#[cfg(feature = "recorder")]
+pub mod systemd;
+pub mod tee;
+pub mod telemetry;
+pub mod timezone;
+pub mod update;
+pub mod upload;
+pub mod wave;
+
+pub use crate::encoding::{convert_audio, create_preview_file};
+
+/// The duration of the recordings which are used for comparing the channels of a device.
+const CHANNEL_PROBE_DURATION_IN_SECONDS: u32 = 3;
+
+/// The suffix of the recordings which are not finished yet.
+pub const PARTIAL_FILE_SUFFIX: &str = ".partial";
 
 lazy_static! {
     static ref CARD_AND_DEVICES_REGEX: Regex = Regex::new(r"card (\d*):.*device (\d*):").unwrap();
+    static ref PCM_NAME_REGEX: Regex =
+        Regex::new(r"^(?:plug)?hw:(?:CARD=)?([^,]+)(?:,(?:DEV=)?(\d+))?$").unwrap();
+    static ref DEVICE_INFO_REGEX: Regex =
+        Regex::new(r"card (\d+): (\S+) \[[^\]]*\], device (\d+): [^\[]*\[([^\]]*)\]").unwrap();
+    static ref SAMPLE_RATE_RANGE_REGEX: Regex =
+        Regex::new(r"RATE:\s*[\[(]?(\d+)(?:\s+(\d+))?").unwrap();
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -30,9 +134,77 @@ pub struct RecordingDeviceConfiguration {
 
     #[serde(default = "RecordingDeviceConfiguration::default_mono")]
     pub mono: bool,
+
+    /// Switch to mono before recording if both channels of the device carry the same signal.
+    #[serde(default = "RecordingDeviceConfiguration::default_auto_mono")]
+    pub auto_mono: bool,
+
+    #[serde(default = "RecordingDeviceConfiguration::default_source")]
+    pub source: Option<String>,
+
+    #[serde(default = "RecordingDeviceConfiguration::default_backend")]
+    pub backend: Option<RecordingBackend>,
+
+    #[serde(default = "RecordingDeviceConfiguration::default_pcm")]
+    pub pcm: Option<String>,
+
+    #[serde(default = "RecordingDeviceConfiguration::default_format")]
+    pub format: SampleFormat,
+
+    /// The sample rate (in Hz) the device is recorded with.
+    #[serde(default = "RecordingDeviceConfiguration::default_sample_rate")]
+    pub sample_rate: u32,
+
+    #[serde(default = "RecordingDeviceConfiguration::default_output_format")]
+    pub output_format: Option<OutputFormat>,
+
+    /// A cron-like expression for the minutes a segment of this device is started in (e.g.
+    /// `* 22-23,0-6 * * 1-5`), the device records every segment if none is set.
+    #[serde(default = "RecordingDeviceConfiguration::default_schedule")]
+    pub schedule: Option<String>,
+
+    /// The names of the channels of the device (e.g. `["throat", "room"]`) which are used instead
+    /// of their positions.
+    #[serde(default = "RecordingDeviceConfiguration::default_channel_labels")]
+    pub channel_labels: Vec<String>,
+
+    /// The capture volume (e.g. `80%` or `-3dB`) which is set with the ALSA mixer before the
+    /// recording starts. The volume is not changed if none is set.
+    #[serde(default = "RecordingDeviceConfiguration::default_capture_volume")]
+    pub capture_volume: Option<String>,
+
+    /// The mixer control of the card the capture volume is set on.
+    #[serde(default = "RecordingDeviceConfiguration::default_mixer_control")]
+    pub mixer_control: String,
+
+    /// The name of the input in the project file, which is set by the record command for naming
+    /// the recordings.
+    #[serde(skip)]
+    pub name: Option<String>,
 }
 
 impl RecordingDeviceConfiguration {
+    /// Create the configuration for recording a card and device with the default settings.
+    pub fn new(card: u8, device: u8) -> RecordingDeviceConfiguration {
+        RecordingDeviceConfiguration {
+            card,
+            device,
+            mono: RecordingDeviceConfiguration::default_mono(),
+            auto_mono: RecordingDeviceConfiguration::default_auto_mono(),
+            source: RecordingDeviceConfiguration::default_source(),
+            backend: RecordingDeviceConfiguration::default_backend(),
+            pcm: RecordingDeviceConfiguration::default_pcm(),
+            format: RecordingDeviceConfiguration::default_format(),
+            sample_rate: RecordingDeviceConfiguration::default_sample_rate(),
+            output_format: RecordingDeviceConfiguration::default_output_format(),
+            schedule: RecordingDeviceConfiguration::default_schedule(),
+            channel_labels: RecordingDeviceConfiguration::default_channel_labels(),
+            capture_volume: RecordingDeviceConfiguration::default_capture_volume(),
+            mixer_control: RecordingDeviceConfiguration::default_mixer_control(),
+            name: None,
+        }
+    }
+
     fn default_device() -> u8 {
         0
     }
@@ -42,8 +214,83 @@ impl RecordingDeviceConfiguration {
     }
 
     fn default_mono() -> bool {
+        defaults::MONO
+    }
+
+    fn default_auto_mono() -> bool {
         false
     }
+
+    fn default_source() -> Option<String> {
+        None
+    }
+
+    fn default_backend() -> Option<RecordingBackend> {
+        None
+    }
+
+    fn default_format() -> SampleFormat {
+        defaults::SAMPLE_FORMAT
+    }
+
+    fn default_sample_rate() -> u32 {
+        defaults::SAMPLE_RATE
+    }
+
+    fn default_output_format() -> Option<OutputFormat> {
+        None
+    }
+
+    fn default_pcm() -> Option<String> {
+        None
+    }
+
+    fn default_schedule() -> Option<String> {
+        None
+    }
+
+    fn default_channel_labels() -> Vec<String> {
+        vec![]
+    }
+
+    fn default_capture_volume() -> Option<String> {
+        None
+    }
+
+    fn default_mixer_control() -> String {
+        "Capture".to_string()
+    }
+
+    /// Get the name of a channel (starting at 0) of a recording with the supplied number of
+    /// channels. Channels without a configured label are named by their position.
+    pub fn get_channel_label(&self, channel: usize, channels: usize) -> String {
+        match self.channel_labels.get(channel) {
+            Some(label) => label.clone(),
+            None if channels == 2 && channel == 0 => "left".to_string(),
+            None if channels == 2 && channel == 1 => "right".to_string(),
+            None => format!("channel {}", channel + 1),
+        }
+    }
+
+    /// Get the ALSA PCM the device is recorded from.
+    pub fn get_pcm(&self) -> String {
+        match &self.pcm {
+            Some(pcm) => pcm.clone(),
+            None => format!("hw:{},{}", self.card, self.device),
+        }
+    }
+
+    /// Get the backend which should be used for this device. The backend of the device overrides
+    /// the one of the project.
+    pub fn get_backend(&self, project_backend: RecordingBackend) -> RecordingBackend {
+        self.backend.unwrap_or(project_backend)
+    }
+
+    /// Get the format the recordings of this device are encoded to. The output format of the
+    /// device overrides the one of the project.
+    pub fn get_output_format(&self, project_output_format: OutputFormat) -> OutputFormat {
+        self.output_format.unwrap_or(project_output_format)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -52,11 +299,227 @@ pub struct InsomniaProject {
     #[serde(default = "InsomniaProject::default_data_directory")]
     pub data_directory: String,
 
+    /// A pattern (e.g. `%Y/%m/%d`) for the subfolders the files of the segments are stored in,
+    /// based on the time the segment was started.
+    #[serde(default = "InsomniaProject::default_subfolder_pattern")]
+    pub subfolder_pattern: Option<String>,
+
+    /// Store the files of each input in a folder named after its key in the `input` table.
+    #[serde(default = "InsomniaProject::default_per_input_folders")]
+    pub per_input_folders: bool,
+
+    /// The template the file names of the recordings are created from (e.g.
+    /// `{device_name}_{date}-{time}_{seq}`).
+    #[serde(default = "InsomniaProject::default_filename_template")]
+    pub filename_template: FilenameTemplate,
+
     #[serde(default = "InsomniaProject::default_input")]
     pub input: HashMap<String, RecordingDeviceConfiguration>,
+
+    #[serde(default = "InsomniaProject::default_create_previews")]
+    pub create_previews: bool,
+
+    #[serde(default = "InsomniaProject::default_backend")]
+    pub backend: RecordingBackend,
+
+    #[serde(default = "InsomniaProject::default_backpressure")]
+    pub backpressure: BackpressureStrategy,
+
+    #[serde(default = "InsomniaProject::default_output_format")]
+    pub output_format: OutputFormat,
+
+    #[serde(default = "InsomniaProject::default_encoding")]
+    pub encoding: EncodingConfiguration,
+
+    #[serde(default = "InsomniaProject::default_normalization")]
+    pub normalization: NormalizationConfiguration,
+
+    /// The free space the recorder keeps in the data directory.
+    #[serde(default = "InsomniaProject::default_storage")]
+    pub storage: StorageConfiguration,
+
+    /// The size (in GB) all recordings of the project may use, they are not limited if none is
+    /// set.
+    #[serde(default = "InsomniaProject::default_max_storage_gb")]
+    pub max_storage_gb: Option<f64>,
+
+    /// What the recorder does once the recordings exceed `max_storage_gb`.
+    #[serde(default = "InsomniaProject::default_quota_action")]
+    pub quota_action: QuotaAction,
+
+    /// How long the encoded recordings are kept in the data directory.
+    #[serde(default = "InsomniaProject::default_retention")]
+    pub retention: RetentionConfiguration,
+
+    #[serde(default = "InsomniaProject::default_sync")]
+    pub sync: Option<SyncConfiguration>,
+
+    #[serde(default = "InsomniaProject::default_event_naming")]
+    pub event_naming: EventNamingConfiguration,
+
+    /// The detection of coughs in every segment while recording.
+    #[serde(default = "InsomniaProject::default_cough_detection")]
+    pub cough_detection: CoughDetectionConfiguration,
+
+    /// What happens to the segments in which nothing happened.
+    #[serde(default = "InsomniaProject::default_silence")]
+    pub silence: SilenceConfiguration,
+
+    /// How inputs whose USB audio device disappeared while recording are recovered.
+    #[serde(default = "InsomniaProject::default_hotplug")]
+    pub hotplug: HotplugConfiguration,
+
+    /// The watchdog which restarts the capture of a device whose recording stopped growing.
+    #[serde(default = "InsomniaProject::default_capture_watchdog")]
+    pub capture_watchdog: CaptureWatchdogConfiguration,
+
+    /// How inputs whose recording failed are retried.
+    #[serde(default = "InsomniaProject::default_retry")]
+    pub retry: RetryConfiguration,
+
+    /// Record a file per event instead of whole segments (requires the cpal backend).
+    #[serde(default = "InsomniaProject::default_activation")]
+    pub activation: ActivationConfiguration,
+
+    /// Publish the captured audio for external real-time analyzers (requires the cpal backend).
+    #[serde(default = "InsomniaProject::default_tee")]
+    pub tee: Option<TeeConfiguration>,
+
+    /// The socket on which the running recorder can be paused, resumed, split or stopped.
+    #[serde(default = "InsomniaProject::default_control")]
+    pub control: ControlConfiguration,
+
+    /// The daily time window in which the record command records, it records continuously if none
+    /// is set.
+    #[serde(default = "InsomniaProject::default_schedule")]
+    pub schedule: Option<ScheduleConfiguration>,
+
+    /// The conditions after which the record command stops on its own.
+    #[serde(default = "InsomniaProject::default_stop")]
+    pub stop: StopConfiguration,
+
+    /// The destination the upload command uploads the encoded files to.
+    #[serde(default = "InsomniaProject::default_upload")]
+    pub upload: Option<UploadConfiguration>,
+
+    /// The second location the metadata of the archive is mirrored to after every session.
+    #[serde(default = "InsomniaProject::default_backup")]
+    pub backup: Option<BackupConfiguration>,
+
+    /// The output device which is used for playing audio (e.g. by the calibrate command).
+    #[serde(default = "InsomniaProject::default_output")]
+    pub output: Option<OutputConfiguration>,
+
+    /// The OpenTelemetry collector the spans of the recorder are exported to.
+    #[serde(default = "InsomniaProject::default_telemetry")]
+    pub telemetry: Option<TelemetryConfiguration>,
+
+    /// The external commands which are executed for events of the recorder.
+    #[serde(default = "InsomniaProject::default_hooks")]
+    pub hooks: HookConfiguration,
+
+    /// The source of the updates, the recorder in the background only updates itself if this is
+    /// set.
+    #[serde(default = "InsomniaProject::default_update")]
+    pub update: Option<UpdateConfiguration>,
+
+    #[serde(default = "InsomniaProject::default_maintenance")]
+    pub maintenance: Vec<MaintenanceTaskConfiguration>,
+
+    #[serde(default = "InsomniaProject::default_power")]
+    pub power: PowerConfiguration,
+
+    /// The comparison of each night against the nights before it in the report.
+    #[serde(default = "InsomniaProject::default_baseline")]
+    pub baseline: BaselineConfiguration,
+
+    /// The priorities of the subsystems which run besides the capture.
+    #[serde(default = "InsomniaProject::default_priority")]
+    pub priority: PriorityConfiguration,
+
+    #[serde(default = "InsomniaProject::default_clock")]
+    pub clock: ClockConfiguration,
+
+    /// The audio settings which are used for the input devices that do not set them explicitly.
+    #[serde(default = "InsomniaProject::default_defaults")]
+    pub defaults: AudioDefaults,
+
+    /// Patterns (regular expressions) for the names of devices which should never be recorded
+    /// from, like the capture devices of HDMI ports or webcams.
+    #[serde(default = "InsomniaProject::default_device_blacklist")]
+    pub device_blacklist: Vec<String>,
+
+    /// A label for the recorded sessions (e.g. the conditions of an experiment), which allows
+    /// analyzing the sessions as cohorts.
+    #[serde(default = "InsomniaProject::default_label")]
+    pub label: Option<String>,
+
+    /// The wall-clock boundary the record command waits for before it starts recording.
+    #[serde(default = "InsomniaProject::default_start_alignment")]
+    pub start_alignment: StartAlignment,
+
+    /// The time zone (`utc`, `local` or a name like `Europe/Berlin`) the file names, the subfolders
+    /// and the manifests of the recordings use.
+    #[serde(default = "InsomniaProject::default_timestamp_timezone")]
+    pub timestamp_timezone: TimestampTimezone,
+
+    /// The JSON file the record command keeps its current state in, by default `status.json` in
+    /// the state folder of the data directory.
+    #[serde(default = "InsomniaProject::default_status_file")]
+    pub status_file: Option<String>,
+}
+
+/// The errors which can occur while loading a project file.
+#[derive(Debug)]
+pub enum ProjectFileError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ProjectFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProjectFileError::Io(ref err) => {
+                write!(f, "Could not read the project file. The error was: {}", err)
+            }
+            ProjectFileError::Parse(ref err) => {
+                write!(
+                    f,
+                    "Could not parse the project file. The error was: {}",
+                    err
+                )
+            }
+        }
+    }
 }
 
 impl InsomniaProject {
+    /// Read and parse the project file at the supplied path.
+    pub fn from_file(path: &str) -> Result<InsomniaProject, ProjectFileError> {
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .map_err(ProjectFileError::Io)?;
+        let mut project: InsomniaProject =
+            toml::from_str(content.as_str()).map_err(ProjectFileError::Parse)?;
+
+        // the defaults only apply to the settings which are not set explicitly for a device
+        let value: toml::Value =
+            toml::from_str(content.as_str()).map_err(ProjectFileError::Parse)?;
+        let input_tables = value.get("input").and_then(|input| input.as_table());
+        project
+            .defaults
+            .apply_to_inputs(&mut project.input, input_tables);
+        Ok(project)
+    }
+
+    /// Create the configuration for recording a card and device with the defaults of the project.
+    pub fn new_input_device(&self, card: u8, device: u8) -> RecordingDeviceConfiguration {
+        let mut input_device = RecordingDeviceConfiguration::new(card, device);
+        self.defaults.apply_to(&mut input_device);
+        input_device
+    }
+
     fn default_data_directory() -> String {
         match current_dir() {
             Ok(current_dir) => match current_dir.to_str() {
@@ -67,18 +530,194 @@ impl InsomniaProject {
         }
     }
 
+    fn default_subfolder_pattern() -> Option<String> {
+        None
+    }
+
+    fn default_per_input_folders() -> bool {
+        false
+    }
+
+    fn default_filename_template() -> FilenameTemplate {
+        FilenameTemplate::default()
+    }
+
     fn default_input() -> HashMap<String, RecordingDeviceConfiguration> {
         let mut default_device = HashMap::new();
         default_device.insert(
             "default_device".to_string(),
-            RecordingDeviceConfiguration {
-                card: 0,
-                device: 0,
-                mono: false,
-            },
+            RecordingDeviceConfiguration::new(0, 0),
         );
         default_device
     }
+
+    fn default_create_previews() -> bool {
+        false
+    }
+
+    fn default_backend() -> RecordingBackend {
+        RecordingBackend::Auto
+    }
+
+    fn default_backpressure() -> BackpressureStrategy {
+        BackpressureStrategy::Drop
+    }
+
+    fn default_output_format() -> OutputFormat {
+        OutputFormat::default()
+    }
+
+    fn default_encoding() -> EncodingConfiguration {
+        EncodingConfiguration::default()
+    }
+
+    fn default_normalization() -> NormalizationConfiguration {
+        NormalizationConfiguration::default()
+    }
+
+    fn default_sync() -> Option<SyncConfiguration> {
+        None
+    }
+
+    fn default_cough_detection() -> CoughDetectionConfiguration {
+        CoughDetectionConfiguration::default()
+    }
+
+    fn default_event_naming() -> EventNamingConfiguration {
+        EventNamingConfiguration::default()
+    }
+
+    fn default_silence() -> SilenceConfiguration {
+        SilenceConfiguration::default()
+    }
+
+    fn default_capture_watchdog() -> CaptureWatchdogConfiguration {
+        CaptureWatchdogConfiguration::default()
+    }
+
+    fn default_hotplug() -> HotplugConfiguration {
+        HotplugConfiguration::default()
+    }
+
+    fn default_retry() -> RetryConfiguration {
+        RetryConfiguration::default()
+    }
+
+    fn default_activation() -> ActivationConfiguration {
+        ActivationConfiguration::default()
+    }
+
+    fn default_tee() -> Option<TeeConfiguration> {
+        None
+    }
+
+    fn default_control() -> ControlConfiguration {
+        ControlConfiguration::default()
+    }
+
+    fn default_schedule() -> Option<ScheduleConfiguration> {
+        None
+    }
+
+    fn default_stop() -> StopConfiguration {
+        StopConfiguration::default()
+    }
+
+    fn default_upload() -> Option<UploadConfiguration> {
+        None
+    }
+
+    fn default_backup() -> Option<BackupConfiguration> {
+        None
+    }
+
+    fn default_output() -> Option<OutputConfiguration> {
+        None
+    }
+
+    fn default_telemetry() -> Option<TelemetryConfiguration> {
+        None
+    }
+
+    fn default_update() -> Option<UpdateConfiguration> {
+        None
+    }
+
+    fn default_maintenance() -> Vec<MaintenanceTaskConfiguration> {
+        vec![]
+    }
+
+    fn default_power() -> PowerConfiguration {
+        PowerConfiguration::default()
+    }
+
+    fn default_storage() -> StorageConfiguration {
+        StorageConfiguration::default()
+    }
+
+    fn default_max_storage_gb() -> Option<f64> {
+        None
+    }
+
+    fn default_quota_action() -> QuotaAction {
+        QuotaAction::default()
+    }
+
+    fn default_retention() -> RetentionConfiguration {
+        RetentionConfiguration::default()
+    }
+
+    fn default_hooks() -> HookConfiguration {
+        HookConfiguration::default()
+    }
+
+    fn default_baseline() -> BaselineConfiguration {
+        BaselineConfiguration::default()
+    }
+
+    fn default_priority() -> PriorityConfiguration {
+        PriorityConfiguration::default()
+    }
+
+    fn default_clock() -> ClockConfiguration {
+        ClockConfiguration::default()
+    }
+
+    fn default_defaults() -> AudioDefaults {
+        AudioDefaults::default()
+    }
+
+    fn default_label() -> Option<String> {
+        None
+    }
+
+    fn default_status_file() -> Option<String> {
+        None
+    }
+
+    fn default_start_alignment() -> StartAlignment {
+        StartAlignment::default()
+    }
+
+    fn default_timestamp_timezone() -> TimestampTimezone {
+        TimestampTimezone::default()
+    }
+
+    fn default_device_blacklist() -> Vec<String> {
+        vec![
+            "HDMI".to_string(),
+            "(?i)webcam".to_string(),
+            "(?i)camera".to_string(),
+        ]
+    }
+
+    /// Compile the patterns of the device blacklist.
+    pub fn get_device_blacklist(&self) -> Result<Vec<regex::Regex>, regex::Error> {
+        self.device_blacklist
+            .iter()
+            .map(|pattern| regex::Regex::new(pattern))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -96,6 +735,138 @@ impl error::Error for AudioDeviceError {
     }
 }
 
+/// Information about a capture device of an audio card.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub card: u8,
+    pub device: u8,
+
+    /// The short name of the card (e.g. `USBMic`) which can be used in ALSA PCM names.
+    pub card_name: String,
+    pub device_name: String,
+
+    /// A human-readable description of the device.
+    pub description: String,
+}
+
+impl DeviceInfo {
+    /// Check if any name or the description of the device matches a pattern of the blacklist.
+    pub fn is_blacklisted(&self, blacklist: &[regex::Regex]) -> bool {
+        blacklist.iter().any(|pattern| {
+            pattern.is_match(&self.card_name)
+                || pattern.is_match(&self.device_name)
+                || pattern.is_match(&self.description)
+        })
+    }
+}
+
+fn get_arecord_output(argument: &str) -> Result<String, AudioDeviceError> {
+    let maybe_list_devices_output = Command::new("arecord").args([argument]).output();
+
+    //
+    if maybe_list_devices_output.is_err() {
+        error!("Could not get list of audio devices!");
+        return Err(AudioDeviceError);
+    }
+
+    //
+    let list_devices_output = maybe_list_devices_output.unwrap();
+    Ok(String::from_utf8_lossy(&list_devices_output.stdout).to_string())
+}
+
+fn get_device_list_output() -> Result<String, AudioDeviceError> {
+    get_arecord_output("-l")
+}
+
+/// Get the descriptions of all hardware PCMs (e.g. `hw:CARD=USBMic,DEV=0`) from the output of
+/// `arecord -L`.
+fn get_pcm_descriptions() -> HashMap<String, String> {
+    let mut descriptions = HashMap::new();
+    let pcm_list_output = match get_arecord_output("-L") {
+        Ok(output) => output,
+        Err(_) => return descriptions,
+    };
+
+    // the name of a PCM is followed by its (indented) description
+    let mut current_pcm_name: Option<String> = None;
+    for line in pcm_list_output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            current_pcm_name = Some(line.trim().to_string());
+        } else if let Some(pcm_name) = current_pcm_name.take() {
+            descriptions.insert(pcm_name, line.trim().to_string());
+        }
+    }
+    descriptions
+}
+
+/// Get a list of all capture devices of all audio cards.
+///
+/// # Errors
+/// An `AudioDeviceError` is returned if the devices could not be queried or if no capture device
+/// was found at all.
+pub fn get_available_devices() -> Result<Vec<DeviceInfo>, AudioDeviceError> {
+    let actual_text_output = get_device_list_output()?;
+    let device_list = parse_device_list(&actual_text_output, &get_pcm_descriptions());
+
+    // if we do not have found any audio devices, also exit with an error
+    if device_list.is_empty() {
+        return Err(AudioDeviceError);
+    }
+
+    Ok(device_list)
+}
+
+/// Parse the devices listed by `arecord -l` (or `aplay -l`), the descriptions of the PCMs are
+/// used for the devices if available.
+pub(crate) fn parse_device_list(
+    text_output: &str,
+    pcm_descriptions: &HashMap<String, String>,
+) -> Vec<DeviceInfo> {
+    let mut device_list = vec![];
+    for cap in DEVICE_INFO_REGEX.captures_iter(text_output.as_bytes()) {
+        let card: u8 = String::from_utf8_lossy(&cap[1]).parse().unwrap();
+        let card_name = String::from_utf8_lossy(&cap[2]).to_string();
+        let device: u8 = String::from_utf8_lossy(&cap[3]).parse().unwrap();
+        let device_name = String::from_utf8_lossy(&cap[4]).to_string();
+        let description = pcm_descriptions
+            .get(&format!("hw:CARD={},DEV={}", card_name, device))
+            .cloned()
+            .unwrap_or_else(|| format!("{}, {}", card_name, device_name));
+        debug!(
+            "Found audio card {} with device {} ({})",
+            card, device, description
+        );
+        device_list.push(DeviceInfo {
+            card,
+            device,
+            card_name,
+            device_name,
+            description,
+        });
+    }
+    device_list
+}
+
+/// Resolve an ALSA PCM name like `hw:CARD=USBMic,DEV=0` or `plughw:1,0` to the card and device
+/// number it currently refers to.
+pub fn resolve_pcm_name(pcm: &str, available_devices: &[DeviceInfo]) -> Option<(u8, u8)> {
+    let cap = PCM_NAME_REGEX.captures(pcm.as_bytes())?;
+    let card = String::from_utf8_lossy(&cap[1]).to_string();
+    let device: u8 = match cap.get(2) {
+        Some(device) => String::from_utf8_lossy(device.as_bytes()).parse().ok()?,
+        None => 0,
+    };
+
+    // the card can either be selected by its number or by its name
+    available_devices
+        .iter()
+        .find(|device_info| {
+            device_info.device == device
+                && (device_info.card_name == card || device_info.card.to_string() == card)
+        })
+        .map(|device_info| (device_info.card, device_info.device))
+}
+
 /// Get a list of valid audio cards and their devices.
 ///
 /// # Errors
@@ -118,18 +889,9 @@ impl error::Error for AudioDeviceError {
 ///   println!("Found audio device: {:?}", device);
 /// }
 /// ```
+#[deprecated(note = "only one device per card is returned, use get_available_devices instead")]
 pub fn get_available_cards() -> Result<HashMap<u8, (u8, u8)>, AudioDeviceError> {
-    let maybe_list_devices_output = Command::new("arecord").args(&["-l"]).output();
-
-    //
-    if maybe_list_devices_output.is_err() {
-        error!("Could not get list of audio devices!");
-        return Err(AudioDeviceError);
-    }
-
-    //
-    let list_devices_output = maybe_list_devices_output.unwrap();
-    let actual_text_output = String::from_utf8_lossy(&list_devices_output.stdout).to_string();
+    let actual_text_output = get_device_list_output()?;
     let mut device_list = HashMap::new();
 
     //
@@ -148,6 +910,41 @@ pub fn get_available_cards() -> Result<HashMap<u8, (u8, u8)>, AudioDeviceError>
     Ok(device_list)
 }
 
+/// Get the path of a new recording of a device based on the current time, it is named after the
+/// filename template of the project (in the time zone of the timestamps).
+pub(crate) fn get_output_file_path(
+    configuration: &RecordingDeviceConfiguration,
+    output_folder: &str,
+) -> PathBuf {
+    // devices which are not configured in the project file are named after their card and device
+    let device_name = configuration
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("c{:02}d{:02}", configuration.card, configuration.device));
+    let file_prefix = get_filename_template().format(&FileNameValues {
+        started_at: to_timestamp_timezone(clock::now()).naive_local(),
+        device_name: &device_name,
+        card: configuration.card,
+        device: configuration.device,
+        seq: get_next_sequence_number(configuration.card, configuration.device),
+    });
+    Path::new(output_folder).join(format!("{}.wav", file_prefix))
+}
+
+/// Get the path a recording is written to until it is finished. Recordings which keep this suffix
+/// were interrupted and are recovered when the next session starts.
+pub(crate) fn get_partial_file_path(output_file: &Path) -> PathBuf {
+    let mut partial_file = output_file.as_os_str().to_os_string();
+    partial_file.push(PARTIAL_FILE_SUFFIX);
+    PathBuf::from(partial_file)
+}
+
+/// Give a finished recording its final name.
+pub(crate) fn finish_partial_file(output_file: &Path) -> io::Result<()> {
+    std::fs::rename(get_partial_file_path(output_file), output_file)
+}
+
+/// Record a single audio file and return the path of the recording without the file extension.
 pub fn record_audio(
     card: u8,
     device: u8,
@@ -155,62 +952,142 @@ pub fn record_audio(
     record_mono: bool,
     output_folder: String,
 ) -> Option<String> {
-    let file_prefix = Local::now()
-        .naive_local()
-        .format("%Y%m%d_%H%M%S_%f")
-        .to_string();
+    let mut configuration = RecordingDeviceConfiguration::new(card, device);
+    configuration.mono = record_mono;
+    record_audio_from_pcm(&configuration, duration_in_seconds, output_folder)
+}
 
-    let output_file_pattern = format!("{}_c{:02}d{:02}.wav", file_prefix, card, device);
-    let output_file = Path::new(&output_folder).join(Path::new(&output_file_pattern));
+/// Get the command which records the supplied duration from the ALSA PCM of a device into a file.
+pub(crate) fn get_arecord_command(
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_file: &Path,
+) -> Command {
     let mut record_command = Command::new("arecord");
     record_command
-        .arg(format!("-Dhw:{},{}", card, device))
+        .arg(format!("-D{}", configuration.get_pcm()))
         .arg(format!("-d{}", duration_in_seconds))
-        .arg("-fS16_LE")
-        .arg("-r44100")
+        .arg(format!("-f{}", configuration.format.get_arecord_format()))
+        .arg(format!("-r{}", configuration.sample_rate))
+        .arg("-twav")
         .arg(output_file.to_str().unwrap())
         .stderr(Stdio::null())
         .stdout(Stdio::null());
 
     // ensure the right flag (mono or stereo) is set
-    if record_mono {
+    if configuration.mono {
         record_command.arg("-c1");
     } else {
         record_command.arg("-c2");
     }
+    record_command
+}
 
-    // now we can start the program and check its return status
-    let record_status = record_command.status();
-    if record_status.is_ok() && record_status.unwrap().success() {
-        return Some(file_prefix);
+/// Record a single audio file from the ALSA PCM of a device (e.g. `plughw:CARD=USBMic,DEV=0`)
+/// with its channels, sample format and sample rate and return the path of the recording without
+/// the file extension. The card and device are used for naming the file.
+pub fn record_audio_from_pcm(
+    configuration: &RecordingDeviceConfiguration,
+    duration_in_seconds: u32,
+    output_folder: String,
+) -> Option<String> {
+    let output_file = get_output_file_path(configuration, &output_folder);
+    let partial_file = get_partial_file_path(&output_file);
+
+    // now we can start the program and check its return status, a recording which was interrupted
+    // by a shutdown or a split is kept (arecord finalizes the header if it gets a SIGINT)
+    let record_status = get_arecord_command(configuration, duration_in_seconds, &partial_file)
+        .spawn()
+        .and_then(|mut child| wait_for_recording_process(&mut child));
+    let is_finished = match record_status {
+        Ok(exit_status) => {
+            exit_status.success()
+                || (is_recording_stop_requested() && repair_header(&partial_file).is_ok())
+        }
+        Err(_) => false,
+    };
+    if is_finished {
+        if let Err(error) = finish_partial_file(&output_file) {
+            error!(
+                "Could not rename the finished recording {}: {}",
+                partial_file.display(),
+                error
+            );
+            return None;
+        }
+        return Some(output_file.with_extension("").to_str().unwrap().to_string());
     }
 
     None
 }
 
-pub fn convert_audio_file(file_prefix: String) {
-    info!("Converting {}.wav to {}.mp3", file_prefix, file_prefix);
-    let convert_status = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(format!("{}.wav", file_prefix))
-        .arg(format!("{}.mp3", file_prefix))
-        .stderr(Stdio::null())
-        .stdout(Stdio::null())
-        .status();
+/// Record a few seconds in stereo from a device (with `arecord`) and compare both channels. The
+/// probe is stored in the working folder and removed afterwards.
+pub fn probe_channels(
+    device: &RecordingDeviceConfiguration,
+    working_folder: &Path,
+) -> Result<ChannelComparison, String> {
+    let mut stereo_device = device.clone();
+    stereo_device.mono = false;
+    let file_prefix = record_audio_from_pcm(
+        &stereo_device,
+        CHANNEL_PROBE_DURATION_IN_SECONDS,
+        working_folder.to_string_lossy().to_string(),
+    )
+    .ok_or_else(|| format!("could not record in stereo from {}", device.get_pcm()))?;
+    let probe_path = PathBuf::from(format!("{}.wav", file_prefix));
+    let samples = read_samples(&probe_path);
+    let _ = std::fs::remove_file(&probe_path);
 
-    // if the conversion was successful, we can remove the old record of the audio file
-    if convert_status.is_ok() && convert_status.unwrap().success() {
-        debug!(
-            "File conversion successful, removing old {}.wav file",
-            file_prefix
-        );
-        let _remove_status = Command::new("rm")
-            .arg("-rf")
-            .arg(format!("{}.wav", file_prefix))
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .spawn();
+    let (format, samples) =
+        samples.map_err(|error| format!("could not read the probe: {}", error))?;
+    if format.channels != 2 {
+        return Err(format!(
+            "the probe has {} channel(s) instead of two",
+            format.channels
+        ));
     }
+    compare_channels(&samples, format.samples_per_second)
+        .ok_or_else(|| "one of the channels is completely silent".to_string())
+}
+
+/// Check if a device can be recorded in mono by recording a short probe.
+pub fn is_mono_supported(device: &RecordingDeviceConfiguration, working_folder: &Path) -> bool {
+    let mut mono_device = device.clone();
+    mono_device.mono = true;
+    match record_audio_from_pcm(
+        &mono_device,
+        1,
+        working_folder.to_string_lossy().to_string(),
+    ) {
+        Some(file_prefix) => {
+            let _ = std::fs::remove_file(format!("{}.wav", file_prefix));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Get the lowest and the highest sample rate (in Hz) a device supports, as listed in the hardware
+/// parameters dumped by `arecord`. `None` is returned if the device could not be opened.
+pub fn get_supported_sample_rates(device: &RecordingDeviceConfiguration) -> Option<(u32, u32)> {
+    let output = Command::new("arecord")
+        .arg(format!("-D{}", device.get_pcm()))
+        .arg("--dump-hw-params")
+        .arg("-d1")
+        .arg("-twav")
+        .arg("/dev/null")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .ok()?;
+    let captures = SAMPLE_RATE_RANGE_REGEX.captures(&output.stderr)?;
+    let minimum: u32 = String::from_utf8_lossy(&captures[1]).parse().ok()?;
+    let maximum = match captures.get(2) {
+        Some(maximum) => String::from_utf8_lossy(maximum.as_bytes()).parse().ok()?,
+        None => minimum,
+    };
+    Some((minimum, maximum))
 }
 
 pub fn is_recording_tool_available() -> bool {
@@ -0,0 +1,97 @@
+use std::fs::{read_to_string, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::archive::layout::Archive;
+use crate::shutdown::sleep_unless_shutdown;
+
+/// The file in the state folder which is locked while a subcommand modifies the archive.
+pub const LOCK_FILE_NAME: &str = "archive.lock";
+
+/// The time between two attempts to lock the archive while waiting for it.
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The exclusive access of a subcommand to the archive of a project. Only the subcommands which
+/// modify the archive (`record`, `run`, `encode`, `prune` and `delete`) lock it, so only one of them
+/// runs at a time. The subcommands which only read it (e.g. `analyze`, `report`, `stats` or
+/// `upload`) can run at any time. The lock is released when it is dropped or the process ends.
+pub struct ArchiveLock {
+    _file: File,
+}
+
+/// Try to lock the file without blocking, `false` is returned if another process holds the lock.
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.kind() {
+        io::ErrorKind::WouldBlock => Ok(false),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Get the subcommand and the process id which hold the lock, as written to the lock file.
+fn get_holder(path: &Path) -> String {
+    match read_to_string(path) {
+        Ok(content) => match content.trim().split_once(' ') {
+            Some((process_id, command)) => format!("{} (PID {})", command, process_id),
+            None => "another subcommand".to_string(),
+        },
+        Err(_) => "another subcommand".to_string(),
+    }
+}
+
+impl ArchiveLock {
+    /// Lock the archive for a subcommand. If another subcommand holds the lock, an error is
+    /// returned unless the caller wants to wait until it is released.
+    pub fn acquire(archive: &Archive, command: &str, wait: bool) -> io::Result<ArchiveLock> {
+        let path = archive.get_state_folder().join(LOCK_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let mut is_waiting = false;
+        while !try_lock(&file)? {
+            let holder = get_holder(&path);
+            if !wait {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!(
+                        "the data directory is used by {}, use --wait to start once it finished",
+                        holder
+                    ),
+                ));
+            }
+            if !is_waiting {
+                info!("Waiting for {} to finish using the data directory", holder);
+                is_waiting = true;
+            }
+            if !sleep_unless_shutdown(WAIT_INTERVAL) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "the wait for the data directory was interrupted",
+                ));
+            }
+        }
+
+        // the holder is only written after the lock was acquired, so it is never overwritten by a
+        // waiting subcommand
+        file.set_len(0)?;
+        writeln!(file, "{} {}", std::process::id(), command)?;
+        Ok(ArchiveLock { _file: file })
+    }
+}
@@ -1,13 +1,47 @@
-use chrono::Local;
 use clap::{crate_authors, crate_description, crate_version, Clap};
-use log::{error, LevelFilter};
+use std::path::Path;
+use tracing::error;
 
+use schlaflosigkeit::changelog::check_archive_upgrade;
+#[cfg(feature = "analysis")]
+use schlaflosigkeit::commands::analyze::{run_command_analyze, AnalyzeCommandOptions};
+#[cfg(feature = "analysis")]
 use schlaflosigkeit::commands::annotate::{run_command_annotate, AnnotateCommandOptions};
+#[cfg(feature = "recorder")]
+use schlaflosigkeit::commands::calibrate::{run_command_calibrate, CalibrateCommandOptions};
 use schlaflosigkeit::commands::config::{run_command_config, ConfigCommandOptions};
+#[cfg(feature = "recorder")]
+use schlaflosigkeit::commands::ctl::{run_command_ctl, CtlCommandOptions};
+#[cfg(feature = "analysis")]
+use schlaflosigkeit::commands::cuesheet::{run_command_cuesheet, CuesheetCommandOptions};
+use schlaflosigkeit::commands::delete::{run_command_delete, DeleteCommandOptions};
+use schlaflosigkeit::commands::doctor::{run_command_doctor, DoctorCommandOptions};
+use schlaflosigkeit::commands::encode::{run_command_encode, EncodeCommandOptions};
+use schlaflosigkeit::commands::info::{run_command_info, InfoCommandOptions};
+#[cfg(feature = "recorder")]
+use schlaflosigkeit::commands::monitor::{run_command_monitor, MonitorCommandOptions};
+use schlaflosigkeit::commands::prune::{run_command_prune, PruneCommandOptions};
+#[cfg(feature = "recorder")]
 use schlaflosigkeit::commands::record::{run_command_record, RecordCommandOptions};
+#[cfg(feature = "analysis")]
+use schlaflosigkeit::commands::report::{run_command_report, ReportCommandOptions};
+#[cfg(feature = "recorder")]
+use schlaflosigkeit::commands::run::{run_command_run, RunCommandOptions};
+use schlaflosigkeit::commands::stats::{run_command_stats, StatsCommandOptions};
+#[cfg(feature = "recorder")]
+use schlaflosigkeit::commands::systemd_unit::{
+    run_command_systemd_unit, SystemdUnitCommandOptions,
+};
+#[cfg(feature = "recorder")]
+use schlaflosigkeit::commands::test::{run_command_test, TestCommandOptions};
+use schlaflosigkeit::commands::update::{run_command_update, UpdateCommandOptions};
+use schlaflosigkeit::commands::upload::{run_command_upload, UploadCommandOptions};
+#[cfg(feature = "recorder")]
+use schlaflosigkeit::daemon::{daemonize, Fork};
+use schlaflosigkeit::naming::template::set_filename_template;
+use schlaflosigkeit::telemetry::{initialize_telemetry, LogOutput};
+use schlaflosigkeit::timezone::set_timestamp_timezone;
 use schlaflosigkeit::InsomniaProject;
-use std::fs::File;
-use std::io::Read;
 
 #[derive(Clap)]
 #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
@@ -20,6 +54,11 @@ struct Opts {
     /// sub-command.
     #[clap(index = 1)]
     project: String,
+
+    /// Continue if this version interprets the data of an existing data directory differently than
+    /// the version which used it before.
+    #[clap(long)]
+    acknowledge_upgrade: bool,
 }
 
 #[derive(Clap)]
@@ -27,64 +66,209 @@ enum SubCommand {
     #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
     Config(ConfigCommandOptions),
 
+    #[cfg(feature = "recorder")]
     #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
     Record(RecordCommandOptions),
 
+    #[cfg(feature = "recorder")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Ctl(CtlCommandOptions),
+
+    #[cfg(feature = "analysis")]
     #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
     Annotate(AnnotateCommandOptions),
+
+    #[cfg(feature = "analysis")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Analyze(AnalyzeCommandOptions),
+
+    #[cfg(feature = "analysis")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Report(ReportCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Stats(StatsCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Doctor(DoctorCommandOptions),
+
+    #[cfg(feature = "recorder")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Calibrate(CalibrateCommandOptions),
+
+    #[cfg(feature = "recorder")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Monitor(MonitorCommandOptions),
+
+    #[cfg(feature = "recorder")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Test(TestCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Encode(EncodeCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Upload(UploadCommandOptions),
+
+    #[cfg(feature = "analysis")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Cuesheet(CuesheetCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Prune(PruneCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Delete(DeleteCommandOptions),
+
+    #[cfg(feature = "recorder")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    SystemdUnit(SystemdUnitCommandOptions),
+
+    #[cfg(feature = "recorder")]
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Run(RunCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Update(UpdateCommandOptions),
+
+    #[clap(version = crate_version!(), author = crate_authors!(), about = crate_description!())]
+    Info(InfoCommandOptions),
 }
 
-fn initialize_logging() {
-    // configure the logging framework and set the corresponding log level
-    let logging_framework = fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{}[{}][{}] {}",
-                Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
-                record.target(),
-                record.level(),
-                message
-            ))
-        })
-        .level(LevelFilter::Debug)
-        .chain(std::io::stdout())
-        .apply();
-
-    // ensure the logging framework was successfully initialized
-    if logging_framework.is_err() {
-        panic!("Could not initialize the logging framework. Terminating!");
+impl SubCommand {
+    /// Check if the subcommand reads or modifies the data directory.
+    fn touches_archive(&self) -> bool {
+        match self {
+            SubCommand::Config(_)
+            | SubCommand::Doctor(_)
+            | SubCommand::Info(_)
+            | SubCommand::Update(_) => false,
+            #[cfg(feature = "recorder")]
+            SubCommand::Ctl(_) => false,
+            _ => true,
+        }
     }
 }
 
 fn main() {
-    initialize_logging();
-
     // parse the options provided by the user
     let opts: Opts = Opts::parse();
 
-    // try to read the configuration file
-    let configuration: InsomniaProject = match File::open(opts.project) {
-        Ok(mut file) => {
-            let mut content = String::new();
-            let _ = file.read_to_string(&mut content);
-            match toml::from_str(content.as_str()) {
-                Ok(object) => object,
+    // try to read the configuration file (the errors are logged after the logging was initialized)
+    let configuration = InsomniaProject::from_file(&opts.project);
+
+    // the recorder can detach from the terminal, it logs to a file in that case
+    // the PID file of the recorder in the background is removed when it exits
+    #[cfg(feature = "recorder")]
+    let mut _pid_file = None;
+    let log_output = match (&opts.subcmd, &configuration) {
+        // the log messages must not end up between the labels if they are written to stdout
+        #[cfg(feature = "analysis")]
+        (SubCommand::Annotate(suboptions), _) if suboptions.writes_to_stdout() => LogOutput::Stderr,
+        #[cfg(feature = "analysis")]
+        (SubCommand::Cuesheet(suboptions), _) if suboptions.writes_to_stdout() => LogOutput::Stderr,
+        #[cfg(feature = "analysis")]
+        (SubCommand::Report(suboptions), _) if suboptions.writes_to_stdout() => LogOutput::Stderr,
+        (SubCommand::Info(_), _) => LogOutput::Stderr,
+        #[cfg(feature = "recorder")]
+        (SubCommand::Record(suboptions), Ok(configuration)) if suboptions.runs_as_daemon() => {
+            let fork =
+                suboptions
+                    .get_daemon_files(configuration)
+                    .and_then(|(pid_file, log_file)| {
+                        daemonize(&pid_file).map(|fork| (fork, pid_file, log_file))
+                    });
+            match fork {
+                Ok((Fork::Parent(process_id), pid_file, log_file)) => {
+                    println!("[*] Recording in the background (PID {})", process_id);
+                    println!("    [-] PID file:\t\t{}", pid_file.display());
+                    println!("    [-] Log file:\t\t{}", log_file.display());
+                    return;
+                }
+                Ok((Fork::Daemon(pid_file), _, log_file)) => {
+                    _pid_file = Some(pid_file);
+                    LogOutput::File(log_file)
+                }
                 Err(error) => {
-                    error!("Could not parse the project file. The error was: {}", error);
+                    let _telemetry_guard = initialize_telemetry(LogOutput::Stdout, None);
+                    error!(
+                        "Could not start the recorder in the background. The error was: {}",
+                        error
+                    );
                     return;
                 }
             }
         }
+        // containers collect the log from stdout
+        #[cfg(feature = "recorder")]
+        (SubCommand::Run(_), _) => LogOutput::Json,
+        // the dashboard shows the latest log messages itself
+        #[cfg(feature = "dashboard")]
+        (SubCommand::Record(suboptions), _) if suboptions.shows_dashboard() => LogOutput::Dashboard,
+        _ => LogOutput::Stdout,
+    };
+    let telemetry = configuration
+        .as_ref()
+        .ok()
+        .and_then(|configuration| configuration.telemetry.as_ref());
+    let _telemetry_guard = initialize_telemetry(log_output, telemetry);
+    let configuration = match configuration {
+        Ok(configuration) => configuration,
         Err(error) => {
-            error!("Could not read the project file. The error was: {}", error);
+            error!("{}", error);
             return;
         }
     };
 
+    // the recordings are named and recognized by the filename template and the time zone of the
+    // project
+    set_filename_template(configuration.filename_template.clone());
+    set_timestamp_timezone(configuration.timestamp_timezone.clone());
+
+    // the changes since the version which used the data directory before are shown first
+    if opts.subcmd.touches_archive()
+        && !check_archive_upgrade(
+            Path::new(&configuration.data_directory),
+            opts.acknowledge_upgrade,
+        )
+    {
+        return;
+    }
+
     // check which subcommand should be executed and call it
     match opts.subcmd {
+        #[cfg(feature = "analysis")]
+        SubCommand::Analyze(suboptions) => run_command_analyze(suboptions, configuration),
+        #[cfg(feature = "analysis")]
         SubCommand::Annotate(suboptions) => run_command_annotate(suboptions, configuration),
+        #[cfg(feature = "recorder")]
+        SubCommand::Calibrate(suboptions) => run_command_calibrate(suboptions, configuration),
         SubCommand::Config(suboptions) => run_command_config(suboptions, configuration),
+        #[cfg(feature = "recorder")]
+        SubCommand::Ctl(suboptions) => run_command_ctl(suboptions, configuration),
+        #[cfg(feature = "analysis")]
+        SubCommand::Cuesheet(suboptions) => run_command_cuesheet(suboptions, configuration),
+        SubCommand::Delete(suboptions) => run_command_delete(suboptions, configuration),
+        SubCommand::Doctor(suboptions) => run_command_doctor(suboptions, configuration),
+        SubCommand::Encode(suboptions) => run_command_encode(suboptions, configuration),
+        SubCommand::Info(suboptions) => run_command_info(suboptions),
+        #[cfg(feature = "recorder")]
+        SubCommand::Monitor(suboptions) => run_command_monitor(suboptions, configuration),
+        SubCommand::Prune(suboptions) => run_command_prune(suboptions, configuration),
+        #[cfg(feature = "recorder")]
         SubCommand::Record(suboptions) => run_command_record(suboptions, configuration),
+        #[cfg(feature = "analysis")]
+        SubCommand::Report(suboptions) => run_command_report(suboptions, configuration),
+        #[cfg(feature = "recorder")]
+        SubCommand::Run(suboptions) => run_command_run(suboptions, configuration),
+        SubCommand::Stats(suboptions) => run_command_stats(suboptions, configuration),
+        #[cfg(feature = "recorder")]
+        SubCommand::Test(suboptions) => run_command_test(suboptions, configuration),
+        SubCommand::Update(suboptions) => run_command_update(suboptions, configuration),
+        SubCommand::Upload(suboptions) => run_command_upload(suboptions, configuration),
+        #[cfg(feature = "recorder")]
+        SubCommand::SystemdUnit(suboptions) => {
+            run_command_systemd_unit(suboptions, &opts.project, configuration)
+        }
     }
 }
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::fs::{rename, File};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::available_parallelism;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::sync::SyncInformation;
+use crate::timezone::to_timestamp_timezone;
+
+/// The version of the manifest format which is written by this version of the tool.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The format which is used for all timestamps stored in a manifest.
+pub const MANIFEST_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A part of a recording in which no audio data could be stored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CaptureGap {
+    pub offset_in_seconds: f64,
+    pub duration_in_seconds: f64,
+}
+
+/// The information stored about a single recorded segment.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SegmentManifest {
+    pub file: String,
+    pub input: String,
+    pub started_at: String,
+    pub duration_in_seconds: u32,
+
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dropped_frames: u64,
+
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub spilled_frames: u64,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gaps: Vec<CaptureGap>,
+
+    /// The gain which was applied by the normalization before encoding the segment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied_gain_in_db: Option<f32>,
+
+    /// The highest sample level (in dBFS) of the recording.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_level_in_dbfs: Option<f32>,
+
+    /// The RMS level (in dBFS) of the whole recording.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rms_level_in_dbfs: Option<f32>,
+
+    /// The percentage (between 0.0 and 100.0) of the passages of the recording which are below
+    /// the silence threshold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub silence_percentage: Option<f32>,
+
+    /// The silence which was cut off at the start of the recording, so the remaining audio
+    /// starts this much later than `started_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leading_trim_in_seconds: Option<f64>,
+
+    /// The silence which was cut off at the end of the recording.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trailing_trim_in_seconds: Option<f64>,
+
+    /// The number of events the analysis found in the segment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<u32>,
+
+    /// The number of events per channel (by the label of the channel) for recordings with more
+    /// than one channel.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub channel_events: BTreeMap<String, u32>,
+
+    /// The offsets (in seconds, from the start of the stored recording) of the coughs which were
+    /// found in the segment, if the cough detection is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cough_offsets_in_seconds: Option<Vec<f32>>,
+
+    /// How interesting the segment is (between 0 and 100), the most interesting segments of a
+    /// night should be listened to first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interestingness: Option<u8>,
+
+    /// The average utilization (between 0.0 and 1.0) of all CPUs while the segment was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_utilization: Option<f32>,
+
+    /// The time the encoder needed to encode the segment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding_time_in_seconds: Option<f32>,
+
+    /// The number of segments which were waiting for being encoded when the segment was queued.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding_queue_depth: Option<usize>,
+
+    /// The source of the timestamps if another one than the system clock was configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_source: Option<String>,
+
+    /// The size (in seconds) of a backwards jump of the clock while the segment was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_jump_in_seconds: Option<f64>,
+
+    /// Set if the segment was left behind by an interrupted session and added when the next
+    /// session started.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub recovered: bool,
+
+    /// Set if the segment only covers the time from the start of the recorder until the first
+    /// boundary of the start alignment.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub partial: bool,
+
+    /// The time the recording continues after the end of the segment (into the next segment), so
+    /// events at the seam are not cut off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlap_in_seconds: Option<u32>,
+
+    /// Set if the segment was completely silent, its recording is not encoded then (or it was
+    /// discarded).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub silent: bool,
+
+    /// Set if the segment only contains an event of the sound-activated recording, the time
+    /// between the events was not recorded.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub activated: bool,
+
+    /// How the recording is stored after it was post-processed (e.g. `ogg (quality 4)`, or
+    /// `wav (low disk space)` if it was not encoded). Silent recordings are never encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+/// A change of the way the segments of an input are encoded during a session, e.g. since the
+/// recordings were kept unencoded while the disk was running out of space.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncodingChange {
+    pub input: String,
+
+    /// The start of the first segment which is encoded the new way.
+    pub started_at: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// The manifest of a recording session which lists all recorded segments.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionManifest {
+    pub version: u32,
+    pub started_at: String,
+
+    /// The result of the start synchronization with another machine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncInformation>,
+
+    /// The user-defined label of the session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// The number of CPU cores of the recording machine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_cores: Option<usize>,
+
+    /// The measured latencies (in milliseconds) between the output device and the inputs, used
+    /// for aligning the playback with the recordings.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub latencies_in_ms: BTreeMap<String, f64>,
+
+    /// The points at which the encoding of an input changed, in the order they happened.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encoding_changes: Vec<EncodingChange>,
+
+    pub segments: Vec<SegmentManifest>,
+}
+
+impl SessionManifest {
+    pub fn from_file(path: &Path) -> Result<SessionManifest, io::Error> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file).map_err(io::Error::from)
+    }
+}
+
+/// Keeps the manifest of the current session up-to-date on disk. It can be shared between the
+/// recording threads.
+pub struct ManifestWriter {
+    path: PathBuf,
+    manifest: Mutex<SessionManifest>,
+}
+
+impl ManifestWriter {
+    pub fn new(output_folder: &str, session_start: DateTime<Local>) -> ManifestWriter {
+        let session_start = to_timestamp_timezone(session_start);
+        let file_name = format!("{}_session.json", session_start.format("%Y%m%d_%H%M%S"));
+        ManifestWriter {
+            path: Path::new(output_folder).join(file_name),
+            manifest: Mutex::new(SessionManifest {
+                version: MANIFEST_VERSION,
+                started_at: session_start.format(MANIFEST_TIMESTAMP_FORMAT).to_string(),
+                sync: None,
+                label: None,
+                cpu_cores: available_parallelism().ok().map(|cores| cores.get()),
+                latencies_in_ms: BTreeMap::new(),
+                encoding_changes: vec![],
+                segments: vec![],
+            }),
+        }
+    }
+
+    /// Continue writing the manifest of an earlier session.
+    pub fn open(path: &Path) -> io::Result<ManifestWriter> {
+        Ok(ManifestWriter {
+            path: path.to_path_buf(),
+            manifest: Mutex::new(SessionManifest::from_file(path)?),
+        })
+    }
+
+    /// Check if a file is already listed in the manifest.
+    pub fn contains_segment(&self, file: &str) -> bool {
+        let manifest = self.manifest.lock().unwrap();
+        manifest.segments.iter().any(|segment| segment.file == file)
+    }
+
+    /// Get the user-defined label of the session.
+    pub fn get_label(&self) -> Option<String> {
+        self.manifest.lock().unwrap().label.clone()
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Update the information about the session and store the updated manifest.
+    pub fn update_session<F>(&self, update: F)
+    where
+        F: FnOnce(&mut SessionManifest),
+    {
+        let mut manifest = self.manifest.lock().unwrap();
+        update(&mut manifest);
+        if let Err(error) = self.store(&manifest) {
+            error!(
+                "Could not update the manifest {}. The error was: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+
+    /// Add a finished segment to the manifest and store the updated manifest.
+    pub fn add_segment(&self, segment: SegmentManifest) {
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.segments.push(segment);
+        if let Err(error) = self.store(&manifest) {
+            error!(
+                "Could not update the manifest {}. The error was: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+
+    /// Store how a segment is encoded. If the previous segment of the same input was encoded
+    /// differently, the change is recorded as well.
+    pub fn set_segment_encoding(&self, file: &str, encoding: String) {
+        let mut manifest = self.manifest.lock().unwrap();
+        let index = match manifest
+            .segments
+            .iter()
+            .position(|segment| segment.file == file)
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let segment = &manifest.segments[index];
+        let previous_encoding = manifest.segments[..index]
+            .iter()
+            .rev()
+            .find(|previous_segment| previous_segment.input == segment.input)
+            .and_then(|previous_segment| previous_segment.encoding.clone());
+        if let Some(previous_encoding) = previous_encoding {
+            if previous_encoding != encoding {
+                info!(
+                    "The segments of {} are encoded as {} instead of {} from now on",
+                    segment.input, encoding, previous_encoding
+                );
+                let encoding_change = EncodingChange {
+                    input: segment.input.clone(),
+                    started_at: segment.started_at.clone(),
+                    from: previous_encoding,
+                    to: encoding.clone(),
+                };
+                manifest.encoding_changes.push(encoding_change);
+            }
+        }
+        manifest.segments[index].encoding = Some(encoding);
+        if let Err(error) = self.store(&manifest) {
+            error!(
+                "Could not update the manifest {}. The error was: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+
+    /// Update the information of an already added segment and store the updated manifest.
+    pub fn update_segment<F>(&self, file: &str, update: F)
+    where
+        F: FnOnce(&mut SegmentManifest),
+    {
+        let mut manifest = self.manifest.lock().unwrap();
+        match manifest
+            .segments
+            .iter_mut()
+            .find(|segment| segment.file == file)
+        {
+            Some(segment) => update(segment),
+            None => return,
+        }
+        if let Err(error) = self.store(&manifest) {
+            error!(
+                "Could not update the manifest {}. The error was: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+
+    fn store(&self, manifest: &SessionManifest) -> io::Result<()> {
+        // write to a temporary file first, so readers never see a partially written manifest
+        let temporary_path = self.path.with_extension("json.tmp");
+        let mut file = File::create(&temporary_path)?;
+        serde_json::to_writer_pretty(&mut file, manifest)?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        rename(&temporary_path, &self.path)
+    }
+}
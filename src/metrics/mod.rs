@@ -0,0 +1,228 @@
+use std::fmt::Write as _;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::spawn;
+use std::time::Duration;
+
+use chrono::Local;
+use tracing::{debug, info, warn};
+
+use crate::shutdown::is_shutdown_requested;
+use crate::status::{InputState, RecorderStatus, StatusBoard};
+
+/// The time a client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serve the health and the metrics of the recorder over HTTP in the background:
+///
+/// - `/health` answers with `200 OK` while the recorder is running and with `503` while it drains
+///   after a shutdown was requested or if all inputs failed (or were given up).
+/// - `/metrics` lists the state of the recorder and its inputs in the Prometheus text format.
+pub fn start_metrics_server(address: &str, status_board: StatusBoard) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!("Serving the health and the metrics on {}", address);
+    spawn(move || {
+        for client in listener.incoming() {
+            match client {
+                Ok(client) => {
+                    if let Err(error) = answer_request(client, &status_board) {
+                        debug!("Could not answer a request for the metrics: {}", error);
+                    }
+                }
+                Err(error) => warn!("Could not accept a request for the metrics: {}", error),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Read the request line (the headers are ignored) and send the requested document.
+fn answer_request(mut client: TcpStream, status_board: &StatusBoard) -> io::Result<()> {
+    client.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(client.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status_line, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/health")) => {
+            let (is_healthy, state) = get_health(&status_board.get());
+            let status_line = if is_healthy {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            (status_line, "text/plain", format!("{}\n", state))
+        }
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            render_metrics(&status_board.get()),
+        ),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    };
+    write!(
+        client,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status_line,
+        content_type,
+        body.len(),
+        body
+    )?;
+    client.flush()
+}
+
+/// Check if the recorder is healthy and describe its state.
+fn get_health(status: &RecorderStatus) -> (bool, &'static str) {
+    if is_shutdown_requested() {
+        return (false, "draining");
+    }
+    let has_failed = !status.inputs.is_empty()
+        && status.inputs.values().all(|input_status| {
+            input_status.state == InputState::Failed || input_status.state == InputState::GaveUp
+        });
+    if has_failed {
+        (false, "failed")
+    } else {
+        (true, "ok")
+    }
+}
+
+/// Describe the state of the recorder in the Prometheus text format.
+fn render_metrics(status: &RecorderStatus) -> String {
+    let mut metrics = String::new();
+    let mut add_metric = |name: &str, help: &str, kind: &str, samples: Vec<(String, f64)>| {
+        let _ = writeln!(metrics, "# HELP schlaflosigkeit_{} {}", name, help);
+        let _ = writeln!(metrics, "# TYPE schlaflosigkeit_{} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(metrics, "schlaflosigkeit_{}{} {}", name, labels, value);
+        }
+    };
+    let input_label = |input_name: &str| format!("{{input=\"{}\"}}", input_name.replace('"', "'"));
+
+    add_metric(
+        "uptime_seconds",
+        "The time since the recorder was started.",
+        "gauge",
+        vec![(
+            String::new(),
+            (Local::now() - status.started_at).num_seconds() as f64,
+        )],
+    );
+    add_metric(
+        "draining",
+        "Set while the recorder finishes its work after a shutdown was requested.",
+        "gauge",
+        vec![(String::new(), f64::from(u8::from(is_shutdown_requested())))],
+    );
+    add_metric(
+        "encoding_queue_depth",
+        "The number of segments which are waiting for being post-processed.",
+        "gauge",
+        vec![(String::new(), status.encoding_queue_depth as f64)],
+    );
+    if let Some(free_space_in_bytes) = status.free_space_in_bytes {
+        add_metric(
+            "free_space_bytes",
+            "The free space of the data directory.",
+            "gauge",
+            vec![(String::new(), free_space_in_bytes as f64)],
+        );
+    }
+    add_metric(
+        "input_state",
+        "The current state of an input (waiting, recording, failed or gave_up).",
+        "gauge",
+        status
+            .inputs
+            .iter()
+            .flat_map(|(input_name, input_status)| {
+                [
+                    InputState::Waiting,
+                    InputState::Recording,
+                    InputState::Failed,
+                    InputState::GaveUp,
+                ]
+                .iter()
+                .map(|state| {
+                    (
+                        format!(
+                            "{{input=\"{}\",state=\"{}\"}}",
+                            input_name.replace('"', "'"),
+                            state
+                        ),
+                        f64::from(u8::from(input_status.state == *state)),
+                    )
+                })
+                .collect::<Vec<_>>()
+            })
+            .collect(),
+    );
+    add_metric(
+        "recorded_segments_total",
+        "The number of segments an input recorded since the recorder was started.",
+        "counter",
+        status
+            .inputs
+            .iter()
+            .map(|(input_name, input_status)| {
+                (
+                    input_label(input_name),
+                    f64::from(input_status.recorded_segments),
+                )
+            })
+            .collect(),
+    );
+    add_metric(
+        "peak_level_dbfs",
+        "The peak level of the latest post-processed segment of an input.",
+        "gauge",
+        status
+            .inputs
+            .iter()
+            .filter_map(|(input_name, input_status)| {
+                let levels = input_status.levels.as_ref()?;
+                Some((input_label(input_name), f64::from(levels.peak_in_dbfs)))
+            })
+            .collect(),
+    );
+    add_metric(
+        "rms_level_dbfs",
+        "The RMS level of the latest post-processed segment of an input.",
+        "gauge",
+        status
+            .inputs
+            .iter()
+            .filter_map(|(input_name, input_status)| {
+                let levels = input_status.levels.as_ref()?;
+                Some((input_label(input_name), f64::from(levels.rms_in_dbfs)))
+            })
+            .collect(),
+    );
+    add_metric(
+        "interestingness",
+        "How interesting (between 0 and 100) the latest post-processed segment of an input is.",
+        "gauge",
+        status
+            .inputs
+            .iter()
+            .filter_map(|(input_name, input_status)| {
+                let interestingness = input_status.interestingness?;
+                Some((input_label(input_name), f64::from(interestingness)))
+            })
+            .collect(),
+    );
+    metrics
+}
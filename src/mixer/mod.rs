@@ -0,0 +1,67 @@
+use core::fmt;
+use std::process::{Command, Stdio};
+
+/// The capture volume of an input as it is set with the ALSA mixer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureVolume {
+    /// A percentage (between 0 and 100) of the range of the mixer control.
+    Percent(u8),
+
+    /// A gain in dB, which the mixer control has to support.
+    Decibels(f32),
+}
+
+impl CaptureVolume {
+    /// Parse a capture volume like `80%` or `-3.5dB`.
+    pub fn parse(volume: &str) -> Result<CaptureVolume, String> {
+        let volume = volume.trim();
+        if let Some(percent) = volume.strip_suffix('%') {
+            return match percent.trim().parse::<u8>() {
+                Ok(percent) if percent <= 100 => Ok(CaptureVolume::Percent(percent)),
+                _ => Err(format!(
+                    "'{}' is not a percentage between 0 and 100",
+                    volume
+                )),
+            };
+        }
+        let decibels = volume
+            .strip_suffix("dB")
+            .or_else(|| volume.strip_suffix("db"))
+            .ok_or_else(|| format!("'{}' has to end with '%' or 'dB'", volume))?;
+        match decibels.trim().parse::<f32>() {
+            Ok(decibels) if decibels.is_finite() => Ok(CaptureVolume::Decibels(decibels)),
+            _ => Err(format!("'{}' is not a gain in dB", volume)),
+        }
+    }
+}
+
+impl fmt::Display for CaptureVolume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CaptureVolume::Percent(percent) => write!(f, "{}%", percent),
+            CaptureVolume::Decibels(decibels) => write!(f, "{}dB", decibels),
+        }
+    }
+}
+
+/// Set the capture volume of a mixer control of an ALSA card with `amixer`.
+pub fn set_capture_volume(card: u8, control: &str, volume: CaptureVolume) -> Result<(), String> {
+    let output = Command::new("amixer")
+        .arg("-q")
+        .arg("-c")
+        .arg(card.to_string())
+        .arg("sset")
+        .arg(control)
+        .arg(volume.to_string())
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| format!("could not run amixer: {}", error))?;
+    if !output.status.success() {
+        return Err(format!(
+            "amixer failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
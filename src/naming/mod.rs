@@ -0,0 +1,193 @@
+use core::fmt;
+use std::fs::{create_dir_all, read_dir, rename};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::detection::{detect_in_channels, LoudnessDetector};
+
+pub mod template;
+
+/// The name of the folder (below the folder of the recordings) for segments with events.
+pub const INTERESTING_FOLDER_NAME: &str = "interesting";
+
+/// How the files of a segment are marked once its events were counted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EventNamingMode {
+    /// Do not analyze the segments while recording.
+    #[default]
+    Off,
+
+    /// Add the event count as a suffix (e.g. `_e07`) to the file names.
+    Rename,
+
+    /// Keep the files and add symbolic links with the event count suffix to the `interesting`
+    /// folder.
+    Symlink,
+
+    /// Move the files with the event count suffix to the `interesting` folder.
+    Move,
+}
+
+impl fmt::Display for EventNamingMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EventNamingMode::Off => write!(f, "off"),
+            EventNamingMode::Rename => write!(f, "rename"),
+            EventNamingMode::Symlink => write!(f, "symlink"),
+            EventNamingMode::Move => write!(f, "move"),
+        }
+    }
+}
+
+/// The configuration for naming the segments based on the number of events they contain.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EventNamingConfiguration {
+    #[serde(default = "EventNamingConfiguration::default_mode")]
+    pub mode: EventNamingMode,
+
+    /// The RMS energy (between 0.0 and 1.0) a passage has to exceed to be counted as an event.
+    #[serde(default = "EventNamingConfiguration::default_threshold")]
+    pub threshold: f32,
+}
+
+impl EventNamingConfiguration {
+    fn default_mode() -> EventNamingMode {
+        EventNamingMode::Off
+    }
+
+    fn default_threshold() -> f32 {
+        0.1
+    }
+}
+
+impl Default for EventNamingConfiguration {
+    fn default() -> Self {
+        EventNamingConfiguration {
+            mode: EventNamingConfiguration::default_mode(),
+            threshold: EventNamingConfiguration::default_threshold(),
+        }
+    }
+}
+
+/// Get the event count suffix which is added to the names of the files of a segment.
+pub fn get_event_suffix(events: u32) -> String {
+    format!("_e{:02}", events)
+}
+
+/// The events which were counted in a segment.
+#[derive(Debug, Clone)]
+pub struct EventCounts {
+    /// The events of the mix of all channels.
+    pub total: u32,
+
+    /// The events of every single channel, empty for mono recordings.
+    pub per_channel: Vec<u32>,
+}
+
+/// Count the events of a recorded (not yet encoded) segment. The channels of a recording with
+/// more than one channel are counted separately as well.
+pub fn count_events_in_recording(file_prefix: &str, threshold: f32) -> Option<EventCounts> {
+    let path = format!("{}.wav", file_prefix);
+    let detections = detect_in_channels(&LoudnessDetector { threshold }, Path::new(&path), true)?;
+    Some(EventCounts {
+        total: detections.mixed.len() as u32,
+        per_channel: detections
+            .per_channel
+            .iter()
+            .map(|events| events.len() as u32)
+            .collect(),
+    })
+}
+
+/// Get all files in the supplied folders which belong to the segment (e.g. the recording, the
+/// encoded file and the preview).
+fn get_segment_files(folders: &[PathBuf], segment_name: &str) -> io::Result<Vec<PathBuf>> {
+    let file_name_prefix = format!("{}.", segment_name);
+    let mut files = vec![];
+    for folder in folders {
+        for entry in read_dir(folder)? {
+            let path = entry?.path();
+            let is_segment_file = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&file_name_prefix));
+            if is_segment_file && path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_: &Path, _: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "symbolic links are only supported on unix systems",
+    ))
+}
+
+/// Mark all files of a segment in the supplied folders with its event count as configured.
+/// Segments without any events are left untouched. The files which were moved are returned with
+/// their new paths.
+pub fn apply_event_naming(
+    folders: &[PathBuf],
+    segment_name: &str,
+    events: u32,
+    configuration: &EventNamingConfiguration,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moved_files = vec![];
+    if configuration.mode == EventNamingMode::Off || events == 0 {
+        return Ok(moved_files);
+    }
+
+    let suffix = get_event_suffix(events);
+    for file in get_segment_files(folders, segment_name)? {
+        let file_name = match file.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        // the suffix is added to the segment name, the extensions stay untouched
+        let new_file_name = match file_name.split_once('.') {
+            Some((segment_name, extensions)) => {
+                format!("{}{}.{}", segment_name, suffix, extensions)
+            }
+            None => format!("{}{}", file_name, suffix),
+        };
+        let folder = file.parent().unwrap_or_else(|| Path::new("."));
+        let interesting_folder = folder.join(INTERESTING_FOLDER_NAME);
+
+        match configuration.mode {
+            EventNamingMode::Rename => {
+                let new_file = folder.join(&new_file_name);
+                rename(&file, &new_file)?;
+                moved_files.push((file.clone(), new_file));
+            }
+            EventNamingMode::Symlink => {
+                create_dir_all(&interesting_folder)?;
+                let target = Path::new("..").join(file_name);
+                create_symlink(&target, &interesting_folder.join(&new_file_name))?
+            }
+            EventNamingMode::Move => {
+                create_dir_all(&interesting_folder)?;
+                let new_file = interesting_folder.join(&new_file_name);
+                rename(&file, &new_file)?;
+                moved_files.push((file.clone(), new_file));
+            }
+            EventNamingMode::Off => {}
+        }
+        debug!("Marked {} with {} events", file.display(), events);
+    }
+    Ok(moved_files)
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The template of the file names of the recordings, which is used if none is configured.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{date}_{time}_{fraction}_c{card}d{device}";
+
+lazy_static! {
+    static ref ACTIVE_TEMPLATE: RwLock<FilenameTemplate> = RwLock::new(FilenameTemplate::default());
+
+    /// The number of the next recording of each card and device.
+    static ref SEQUENCE_NUMBERS: Mutex<HashMap<(u8, u8), u32>> = Mutex::new(HashMap::new());
+}
+
+/// A placeholder in a filename template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    /// The date the recording was started at (e.g. `20240312`).
+    Date,
+
+    /// The time the recording was started at (e.g. `231500`).
+    Time,
+
+    /// The nanoseconds of the time the recording was started at.
+    Fraction,
+
+    /// The name of the input in the project file.
+    DeviceName,
+
+    /// The number of the ALSA card (with two digits).
+    Card,
+
+    /// The number of the ALSA device (with two digits).
+    Device,
+
+    /// The number of the recording of the device since the recorder was started (with four
+    /// digits).
+    Seq,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Placeholder> {
+        match name {
+            "date" => Some(Placeholder::Date),
+            "time" => Some(Placeholder::Time),
+            "fraction" => Some(Placeholder::Fraction),
+            "device_name" => Some(Placeholder::DeviceName),
+            "card" => Some(Placeholder::Card),
+            "device" => Some(Placeholder::Device),
+            "seq" => Some(Placeholder::Seq),
+            _ => None,
+        }
+    }
+
+    /// Get the pattern which matches the values of the placeholder in a file name.
+    fn get_pattern(&self) -> &'static str {
+        match *self {
+            Placeholder::Date => r"(?P<date>\d{8})",
+            Placeholder::Time => r"(?P<time>\d{6})",
+            Placeholder::Fraction => r"(?P<fraction>\d{9})",
+            Placeholder::DeviceName => r"(?P<device_name>[^/.]+?)",
+            Placeholder::Card => r"(?P<card>\d{2,3})",
+            Placeholder::Device => r"(?P<device>\d{2,3})",
+            Placeholder::Seq => r"(?P<seq>\d{4,})",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// The values a file name is created from.
+pub struct FileNameValues<'a> {
+    pub started_at: NaiveDateTime,
+    pub device_name: &'a str,
+    pub card: u8,
+    pub device: u8,
+    pub seq: u32,
+}
+
+/// The values which were read from a file name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedFileName {
+    pub started_at: NaiveDateTime,
+    pub device_name: Option<String>,
+    pub card: Option<u8>,
+    pub device: Option<u8>,
+    pub seq: Option<u32>,
+}
+
+/// The template the file names of the recordings are created from, e.g.
+/// `{device_name}_{date}-{time}_{seq}`. It has to contain the date and the time the recording was
+/// started at and either the name of the input or the card and the device, so the files of two
+/// inputs never share a name.
+#[derive(Debug, Clone)]
+pub struct FilenameTemplate {
+    template: String,
+    parts: Vec<TemplatePart>,
+    regex: Regex,
+}
+
+impl FilenameTemplate {
+    fn contains(&self, placeholder: Placeholder) -> bool {
+        self.parts.contains(&TemplatePart::Placeholder(placeholder))
+    }
+
+    /// Create the file name (without an extension) of a recording.
+    pub fn format(&self, values: &FileNameValues) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Placeholder(placeholder) => match placeholder {
+                    Placeholder::Date => values.started_at.format("%Y%m%d").to_string(),
+                    Placeholder::Time => values.started_at.format("%H%M%S").to_string(),
+                    Placeholder::Fraction => format!("{:09}", values.started_at.nanosecond()),
+                    Placeholder::DeviceName => get_safe_name(values.device_name),
+                    Placeholder::Card => format!("{:02}", values.card),
+                    Placeholder::Device => format!("{:02}", values.device),
+                    Placeholder::Seq => format!("{:04}", values.seq),
+                },
+            })
+            .collect()
+    }
+
+    /// Read the values from the name of a segment (the file name without its extensions). An event
+    /// count suffix (e.g. `_e07`) is ignored. `None` is returned if the name does not match the
+    /// template.
+    pub fn parse(&self, segment_name: &str) -> Option<ParsedFileName> {
+        let captures = self.regex.captures(segment_name)?;
+        let date = NaiveDate::parse_from_str(captures.name("date")?.as_str(), "%Y%m%d").ok()?;
+        let time = NaiveTime::parse_from_str(captures.name("time")?.as_str(), "%H%M%S").ok()?;
+        let mut started_at = date.and_time(time);
+        if let Some(fraction) = captures.name("fraction") {
+            started_at = started_at.with_nanosecond(fraction.as_str().parse().ok()?)?;
+        }
+        let get_number = |name: &str| {
+            captures
+                .name(name)
+                .and_then(|value| value.as_str().parse().ok())
+        };
+        Some(ParsedFileName {
+            started_at,
+            device_name: captures
+                .name("device_name")
+                .map(|device_name| device_name.as_str().to_string()),
+            card: get_number("card"),
+            device: get_number("device"),
+            seq: captures
+                .name("seq")
+                .and_then(|seq| seq.as_str().parse().ok()),
+        })
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while !rest.is_empty() {
+            let (text, placeholder) = match rest.find('{') {
+                Some(start) => {
+                    let end = rest[start..]
+                        .find('}')
+                        .map(|end| start + end)
+                        .ok_or_else(|| {
+                            format!("the placeholder at '{}' is not closed", &rest[start..])
+                        })?;
+                    let name = &rest[start + 1..end];
+                    let placeholder = Placeholder::from_name(name)
+                        .ok_or_else(|| format!("{{{}}} is not a known placeholder", name))?;
+                    let text = &rest[..start];
+                    rest = &rest[end + 1..];
+                    (text, Some(placeholder))
+                }
+                None => {
+                    let text = rest;
+                    rest = "";
+                    (text, None)
+                }
+            };
+            if let Some(character) = text.chars().find(|character| "/\\.}".contains(*character)) {
+                return Err(format!("the file names must not contain '{}'", character));
+            }
+            if !text.is_empty() {
+                parts.push(TemplatePart::Text(text.to_string()));
+            }
+            if let Some(placeholder) = placeholder {
+                parts.push(TemplatePart::Placeholder(placeholder));
+            }
+        }
+
+        let pattern: String = parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => regex::escape(text),
+                TemplatePart::Placeholder(placeholder) => placeholder.get_pattern().to_string(),
+            })
+            .collect();
+        let regex = Regex::new(&format!(r"^{}(?:_e\d{{2,}})?$", pattern))
+            .map_err(|error| error.to_string())?;
+        let filename_template = FilenameTemplate {
+            template: template.to_string(),
+            parts,
+            regex,
+        };
+
+        if !filename_template.contains(Placeholder::Date)
+            || !filename_template.contains(Placeholder::Time)
+        {
+            return Err("the template has to contain {date} and {time}".to_string());
+        }
+        let identifies_input = filename_template.contains(Placeholder::DeviceName)
+            || (filename_template.contains(Placeholder::Card)
+                && filename_template.contains(Placeholder::Device));
+        if !identifies_input {
+            return Err(
+                "the template has to contain either {device_name} or {card} and {device}"
+                    .to_string(),
+            );
+        }
+        Ok(filename_template)
+    }
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        DEFAULT_FILENAME_TEMPLATE.parse().unwrap()
+    }
+}
+
+impl fmt::Display for FilenameTemplate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.template)
+    }
+}
+
+impl Serialize for FilenameTemplate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FilenameTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Get a version of the name of an input which can be used in file and folder names, the
+/// separators of paths and extensions as well as whitespace are replaced by dashes.
+pub fn get_safe_name(name: &str) -> String {
+    name.chars()
+        .map(|character| match character {
+            '/' | '.' => '-',
+            character if character.is_whitespace() => '-',
+            character => character,
+        })
+        .collect()
+}
+
+/// Use the template of a project for naming new recordings and for reading the start time and the
+/// input from the names of the recordings.
+pub fn set_filename_template(template: FilenameTemplate) {
+    *ACTIVE_TEMPLATE.write().unwrap() = template;
+}
+
+/// Get the template the recordings are named with.
+pub fn get_filename_template() -> FilenameTemplate {
+    ACTIVE_TEMPLATE.read().unwrap().clone()
+}
+
+/// Get the sequence number of the next recording of a card and device, the recordings of each
+/// device are numbered from 1 since the recorder was started.
+pub(crate) fn get_next_sequence_number(card: u8, device: u8) -> u32 {
+    let mut sequence_numbers = SEQUENCE_NUMBERS.lock().unwrap();
+    let sequence_number = sequence_numbers.entry((card, device)).or_insert(0);
+    *sequence_number += 1;
+    *sequence_number
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::remove_file;
+use std::path::Path;
+use std::process::{id, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::wave::WaveWriter;
+use crate::{parse_device_list, resolve_pcm_name};
+
+/// The name of the output device which is used if no device is configured.
+const DEFAULT_DEVICE_NAME: &str = "default";
+
+/// Counts the played files, so concurrent playbacks of one process do not share a file.
+static PLAYBACK_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The backends which can be used for playing audio.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackBackend {
+    /// Play by calling the `aplay` tool of ALSA, the device is an ALSA PCM.
+    #[default]
+    Aplay,
+
+    /// Play on a PulseAudio or PipeWire sink using `paplay`, the device is the name of the sink.
+    Pulse,
+}
+
+impl fmt::Display for PlaybackBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlaybackBackend::Aplay => write!(f, "aplay"),
+            PlaybackBackend::Pulse => write!(f, "pulse"),
+        }
+    }
+}
+
+impl PlaybackBackend {
+    /// Get the tool which is called for playing audio.
+    pub fn get_tool(&self) -> &'static str {
+        match *self {
+            PlaybackBackend::Aplay => "aplay",
+            PlaybackBackend::Pulse => "paplay",
+        }
+    }
+}
+
+/// The output device which is used by everything playing audio (e.g. the calibration chirp).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfiguration {
+    /// The name of the output device, which is an ALSA PCM (e.g. `plughw:0,0` or
+    /// `default:CARD=Speaker`) or the name of a PulseAudio sink. The default device of the backend
+    /// is used if none is set.
+    #[serde(default = "OutputConfiguration::default_device")]
+    pub device: Option<String>,
+
+    /// The volume (between 0.0 and 1.0) the audio is played with.
+    #[serde(default = "OutputConfiguration::default_volume")]
+    pub volume: f32,
+
+    /// The backend which is used for playing audio.
+    #[serde(default = "OutputConfiguration::default_backend")]
+    pub backend: PlaybackBackend,
+}
+
+impl OutputConfiguration {
+    fn default_device() -> Option<String> {
+        None
+    }
+
+    fn default_volume() -> f32 {
+        1.0
+    }
+
+    fn default_backend() -> PlaybackBackend {
+        PlaybackBackend::default()
+    }
+
+    /// Get the name of the output device, `default` if none is configured.
+    pub fn get_device_name(&self) -> &str {
+        self.device.as_deref().unwrap_or(DEFAULT_DEVICE_NAME)
+    }
+
+    /// Check that the volume is valid, the tool of the backend is available and the configured
+    /// device is one of the available output devices.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.volume) {
+            return Err(format!(
+                "the volume {} is not between 0.0 and 1.0",
+                self.volume
+            ));
+        }
+        let tool = self.backend.get_tool();
+        if !is_tool_available(tool) {
+            return Err(format!("{} is not available", tool));
+        }
+        let device = match &self.device {
+            Some(device) => device,
+            None => return Ok(()),
+        };
+        // hardware PCMs can also be selected by the number of the card and device
+        if self.backend == PlaybackBackend::Aplay && resolve_playback_pcm(device)?.is_some() {
+            return Ok(());
+        }
+        let available_outputs = get_available_outputs(self.backend)?;
+        if !available_outputs.contains(device) {
+            return Err(format!(
+                "the output device {} could not be found (available: {})",
+                device,
+                available_outputs.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for OutputConfiguration {
+    fn default() -> Self {
+        OutputConfiguration {
+            device: OutputConfiguration::default_device(),
+            volume: OutputConfiguration::default_volume(),
+            backend: OutputConfiguration::default_backend(),
+        }
+    }
+}
+
+fn is_tool_available(tool: &str) -> bool {
+    match Command::new(tool)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(exit_status) => exit_status.success(),
+        Err(_) => false,
+    }
+}
+
+fn get_command_output(program: &str, arguments: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(arguments)
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|error| format!("could not run {}: {}", program, error))?;
+    if !output.status.success() {
+        return Err(format!("{} failed with {}", program, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Resolve a hardware PCM name like `plughw:0,0` or `hw:CARD=Speaker,DEV=0` to the card and
+/// device number of the playback device it refers to. `None` is returned for other PCM names or
+/// if there is no such playback device.
+fn resolve_playback_pcm(pcm: &str) -> Result<Option<(u8, u8)>, String> {
+    let playback_devices =
+        parse_device_list(&get_command_output("aplay", &["-l"])?, &HashMap::new());
+    Ok(resolve_pcm_name(pcm, &playback_devices))
+}
+
+/// Get the names of all output devices of a backend: the ALSA PCMs listed by `aplay -L` or the
+/// PulseAudio sinks listed by `pactl`.
+pub fn get_available_outputs(backend: PlaybackBackend) -> Result<Vec<String>, String> {
+    let outputs = match backend {
+        // the name of a PCM is followed by its (indented) description
+        PlaybackBackend::Aplay => get_command_output("aplay", &["-L"])?
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with(char::is_whitespace))
+            .map(|line| line.trim().to_string())
+            .collect(),
+        PlaybackBackend::Pulse => get_command_output("pactl", &["list", "short", "sinks"])?
+            .lines()
+            .filter_map(|line| line.split('\t').nth(1))
+            .map(|sink| sink.to_string())
+            .collect(),
+    };
+    Ok(outputs)
+}
+
+/// Play mono samples (between -1.0 and 1.0) on the output device with its volume. The samples are
+/// written to a temporary file in the supplied folder, the call returns after the playback
+/// finished.
+pub fn play_samples(
+    output: &OutputConfiguration,
+    samples: &[f32],
+    samples_per_second: u32,
+    working_folder: &Path,
+) -> Result<(), String> {
+    let path = working_folder.join(format!(
+        "playback_{}_{}.wav",
+        id(),
+        PLAYBACK_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let volume = output.volume.clamp(0.0, 1.0);
+    let samples: Vec<i16> = samples
+        .iter()
+        .map(|sample| (sample.clamp(-1.0, 1.0) * volume * 32767.0) as i16)
+        .collect();
+    let mut wave_writer = WaveWriter::create(&path, 1, samples_per_second)
+        .map_err(|error| format!("could not write the audio: {}", error))?;
+    wave_writer
+        .write_samples(&samples)
+        .and_then(|_| wave_writer.finalize())
+        .map_err(|error| format!("could not write the audio: {}", error))?;
+
+    let mut play_command = Command::new(output.backend.get_tool());
+    match output.backend {
+        PlaybackBackend::Aplay => {
+            play_command.arg("-q");
+            if let Some(device) = &output.device {
+                play_command.arg(format!("-D{}", device));
+            }
+        }
+        PlaybackBackend::Pulse => {
+            if let Some(device) = &output.device {
+                play_command.arg(format!("--device={}", device));
+            }
+        }
+    }
+    let status = play_command
+        .arg(&path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let _ = remove_file(&path);
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!(
+            "could not play on {} ({} failed with {})",
+            output.get_device_name(),
+            output.backend.get_tool(),
+            status
+        )),
+        Err(error) => Err(format!(
+            "could not run {}: {}",
+            output.backend.get_tool(),
+            error
+        )),
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs::read_to_string;
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::SegmentManifest;
+
+/// The power figures of the recording machine which are used to estimate the consumed energy.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PowerConfiguration {
+    /// The power (in W) the machine draws while it is idle, including the attached microphones.
+    #[serde(default = "PowerConfiguration::default_base_power_in_watts")]
+    pub base_power_in_watts: f32,
+
+    /// The additional power (in W) the machine draws if all CPU cores are fully utilized.
+    #[serde(default = "PowerConfiguration::default_cpu_power_in_watts")]
+    pub cpu_power_in_watts: f32,
+}
+
+impl PowerConfiguration {
+    fn default_base_power_in_watts() -> f32 {
+        2.7
+    }
+
+    fn default_cpu_power_in_watts() -> f32 {
+        3.7
+    }
+}
+
+impl Default for PowerConfiguration {
+    fn default() -> Self {
+        PowerConfiguration {
+            base_power_in_watts: PowerConfiguration::default_base_power_in_watts(),
+            cpu_power_in_watts: PowerConfiguration::default_cpu_power_in_watts(),
+        }
+    }
+}
+
+/// A snapshot of the time all CPUs of the system spent busy and in total (in clock ticks).
+#[derive(Debug, Clone, Copy)]
+pub struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    /// Read the current CPU times from `/proc/stat`. This is only supported on Linux.
+    pub fn read() -> Option<CpuTimes> {
+        let content = read_to_string("/proc/stat").ok()?;
+        let values: Vec<u64> = content
+            .lines()
+            .next()?
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        if values.len() < 4 {
+            return None;
+        }
+
+        // the idle and the iowait time are not counted as busy
+        let total = values.iter().sum();
+        let idle = values[3] + values.get(4).copied().unwrap_or(0);
+        Some(CpuTimes {
+            busy: total - idle,
+            total,
+        })
+    }
+
+    /// Get the average utilization (between 0.0 and 1.0) of all CPUs since an earlier snapshot.
+    pub fn get_utilization_since(&self, earlier: &CpuTimes) -> Option<f32> {
+        let total = self.total.checked_sub(earlier.total)?;
+        let busy = self.busy.checked_sub(earlier.busy)?;
+        if total == 0 {
+            return None;
+        }
+        Some(busy as f32 / total as f32)
+    }
+}
+
+/// The estimated energy which was consumed while recording.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnergyEstimate {
+    pub capture_in_wh: f32,
+    pub encoding_in_wh: f32,
+}
+
+impl EnergyEstimate {
+    pub fn get_total_in_wh(&self) -> f32 {
+        self.capture_in_wh + self.encoding_in_wh
+    }
+}
+
+/// Estimate the energy which was consumed for recording the supplied segments. The machine draws
+/// its base power for the whole recorded time and additional power based on the measured CPU
+/// utilization. The part of the CPU power which was used by the encoder is attributed to encoding,
+/// everything else to the capture.
+pub fn estimate_energy(
+    segments: &[&SegmentManifest],
+    recorded_time_in_seconds: f32,
+    cpu_cores: usize,
+    configuration: &PowerConfiguration,
+) -> EnergyEstimate {
+    // the utilization is measured for the whole system, so it is weighted by the segment duration
+    let (weighted_utilization, measured_time) = segments
+        .iter()
+        .filter_map(|segment| {
+            segment.cpu_utilization.map(|utilization| {
+                let duration = segment.duration_in_seconds as f32;
+                (utilization * duration, duration)
+            })
+        })
+        .fold((0.0, 0.0), |(sum, time), (utilization, duration)| {
+            (sum + utilization, time + duration)
+        });
+    let average_utilization = if measured_time > 0.0 {
+        weighted_utilization / measured_time
+    } else {
+        0.0
+    };
+    let recorded_time_in_hours = recorded_time_in_seconds / 3600.0;
+    let total_in_wh = recorded_time_in_hours
+        * (configuration.base_power_in_watts
+            + average_utilization * configuration.cpu_power_in_watts);
+
+    // the encoder uses a single core while it runs
+    let encoding_time_in_hours: f32 = segments
+        .iter()
+        .filter_map(|segment| segment.encoding_time_in_seconds)
+        .sum::<f32>()
+        / 3600.0;
+    let encoding_in_wh =
+        encoding_time_in_hours * configuration.cpu_power_in_watts / cpu_cores.max(1) as f32;
+
+    EnergyEstimate {
+        capture_in_wh: (total_in_wh - encoding_in_wh).max(0.0),
+        encoding_in_wh,
+    }
+}
@@ -0,0 +1,165 @@
+use core::fmt;
+use std::fs::{read_to_string, remove_file, write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::archive::layout::Archive;
+use crate::InsomniaProject;
+
+/// The suffix of the files in the state folder which mark a running subsystem.
+const ACTIVITY_FILE_SUFFIX: &str = ".running";
+
+/// The priority of a subsystem which competes with the others for the CPU. The capture always has
+/// the highest priority.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PriorityClass {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl fmt::Display for PriorityClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PriorityClass::Low => write!(f, "low"),
+            PriorityClass::Normal => write!(f, "normal"),
+            PriorityClass::High => write!(f, "high"),
+        }
+    }
+}
+
+/// The subsystems which run besides the capture and might compete for the CPU.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subsystem {
+    /// The encoding (and post-processing) workers of the record command.
+    Encoding,
+
+    /// The analyze and report commands.
+    Analysis,
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Subsystem::Encoding => write!(f, "encoding"),
+            Subsystem::Analysis => write!(f, "analysis"),
+        }
+    }
+}
+
+/// The priorities of the subsystems. A subsystem pauses (between two jobs) while a subsystem with
+/// a higher priority is running.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PriorityConfiguration {
+    #[serde(default = "PriorityConfiguration::default_encoding")]
+    pub encoding: PriorityClass,
+
+    #[serde(default = "PriorityConfiguration::default_analysis")]
+    pub analysis: PriorityClass,
+}
+
+impl PriorityConfiguration {
+    fn default_encoding() -> PriorityClass {
+        PriorityClass::default()
+    }
+
+    fn default_analysis() -> PriorityClass {
+        PriorityClass::default()
+    }
+
+    /// Get the priority class of a subsystem.
+    pub fn get_priority(&self, subsystem: Subsystem) -> PriorityClass {
+        match subsystem {
+            Subsystem::Encoding => self.encoding,
+            Subsystem::Analysis => self.analysis,
+        }
+    }
+
+    /// Get the subsystems which have a higher priority than the supplied one.
+    pub fn get_preceding_subsystems(&self, subsystem: Subsystem) -> Vec<Subsystem> {
+        let priority = self.get_priority(subsystem);
+        [Subsystem::Encoding, Subsystem::Analysis]
+            .iter()
+            .copied()
+            .filter(|other| self.get_priority(*other) > priority)
+            .collect()
+    }
+}
+
+impl Default for PriorityConfiguration {
+    fn default() -> Self {
+        PriorityConfiguration {
+            encoding: PriorityConfiguration::default_encoding(),
+            analysis: PriorityConfiguration::default_analysis(),
+        }
+    }
+}
+
+fn get_activity_file_path(state_folder: &Path, subsystem: Subsystem) -> PathBuf {
+    state_folder.join(format!("{}{}", subsystem, ACTIVITY_FILE_SUFFIX))
+}
+
+/// Marks a subsystem as running (across processes) by a file with the process id in the state
+/// folder. The file is removed when the marker is dropped.
+pub struct ActivityMarker {
+    path: PathBuf,
+}
+
+impl ActivityMarker {
+    pub fn create(state_folder: &Path, subsystem: Subsystem) -> io::Result<ActivityMarker> {
+        let path = get_activity_file_path(state_folder, subsystem);
+        write(&path, format!("{}\n", std::process::id()))?;
+        Ok(ActivityMarker { path })
+    }
+}
+
+impl Drop for ActivityMarker {
+    fn drop(&mut self) {
+        if let Err(error) = remove_file(&self.path) {
+            warn!(
+                "Could not remove {}. The error was: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+}
+
+/// Check if a subsystem is running. The marker of a process which does not exist anymore (e.g.
+/// after a crash) is ignored, this check is only supported on Linux.
+pub fn is_running(state_folder: &Path, subsystem: Subsystem) -> bool {
+    let content = match read_to_string(get_activity_file_path(state_folder, subsystem)) {
+        Ok(content) => content,
+        Err(_) => return false,
+    };
+    match content.trim().parse::<u32>() {
+        Ok(process_id) if Path::new("/proc").is_dir() => {
+            Path::new("/proc").join(process_id.to_string()).exists()
+        }
+        _ => true,
+    }
+}
+
+/// Mark a subsystem as running in the state folder of the data directory of a project, so a
+/// recorder can pause its subsystems with a lower priority. `None` is returned (after a warning) if
+/// the marker could not be created.
+pub fn mark_as_running(config: &InsomniaProject, subsystem: Subsystem) -> Option<ActivityMarker> {
+    let marker = Archive::open(Path::new(&config.data_directory))
+        .and_then(|archive| ActivityMarker::create(&archive.get_state_folder(), subsystem));
+    match marker {
+        Ok(marker) => Some(marker),
+        Err(error) => {
+            warn!(
+                "Could not mark the {} as running. The error was: {}",
+                subsystem, error
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::fs::{read_dir, rename};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use tracing::{info, warn};
+
+use crate::annotation::WaveMetaReader;
+use crate::archive::layout::Archive;
+use crate::archive::{collect_files, ArchiveReader, SESSION_MANIFEST_SUFFIX};
+use crate::encoding::OutputFormat;
+use crate::manifest::{ManifestWriter, SegmentManifest, MANIFEST_TIMESTAMP_FORMAT};
+use crate::timezone::{from_timestamp_timezone, to_timestamp_timezone};
+use crate::wave::{read_broadcast_extension, repair_header};
+use crate::{RecordingDeviceConfiguration, PARTIAL_FILE_SUFFIX};
+
+/// The input which is stored in the manifest if the device of a recording is not configured.
+const UNKNOWN_INPUT_NAME: &str = "unknown";
+
+/// A recording of an interrupted session which was not encoded yet.
+#[derive(Debug, Clone)]
+pub struct RecoveredRecording {
+    /// The path of the recording without the file extension.
+    pub file_prefix: String,
+
+    /// The name of the input the recording was made with (if it is known).
+    pub input: Option<String>,
+}
+
+/// The recordings an interrupted session left behind and the manifest they were added to.
+pub struct Recovery {
+    pub manifest_writer: ManifestWriter,
+    pub recordings: Vec<RecoveredRecording>,
+}
+
+/// Repair the headers of the recordings which were not finished and give them their final name.
+fn finish_partial_recordings(raw_folder: &Path) -> io::Result<()> {
+    let mut files = vec![];
+    collect_files(raw_folder, &mut files)?;
+    for path in files {
+        let output_file = match path
+            .to_str()
+            .and_then(|path| path.strip_suffix(PARTIAL_FILE_SUFFIX))
+        {
+            Some(output_file) => PathBuf::from(output_file),
+            None => continue,
+        };
+        if let Err(error) = repair_header(&path) {
+            warn!(
+                "Could not repair the interrupted recording {}, it is left untouched. The error was: {}",
+                path.display(),
+                error
+            );
+            continue;
+        }
+        info!(
+            "Recovered the interrupted recording {}",
+            output_file.display()
+        );
+        rename(&path, &output_file)?;
+    }
+    Ok(())
+}
+
+/// Get the manifest of the session which was started last.
+fn get_latest_manifest_path(state_folder: &Path) -> io::Result<Option<PathBuf>> {
+    let mut manifests = vec![];
+    for entry in read_dir(state_folder)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(SESSION_MANIFEST_SUFFIX) {
+            manifests.push(path);
+        }
+    }
+
+    // the names of the manifests start with the time the session was started
+    Ok(manifests.into_iter().max())
+}
+
+/// Get the time a start time of a file name (in the time zone of the timestamps) refers to.
+fn to_local_time(time: NaiveDateTime) -> DateTime<Local> {
+    from_timestamp_timezone(time)
+        .map(|time| time.with_timezone(&Local))
+        .unwrap_or_else(Local::now)
+}
+
+/// Get the name of the configured input which records from a card and device.
+fn get_input_name(
+    inputs: &HashMap<String, RecordingDeviceConfiguration>,
+    card: Option<u8>,
+    device: Option<u8>,
+) -> Option<String> {
+    inputs
+        .iter()
+        .find(|(_, input_device)| {
+            Some(input_device.card) == card && Some(input_device.device) == device
+        })
+        .map(|(input_name, _)| input_name.clone())
+}
+
+/// Recover the recordings an interrupted session (e.g. by a crash or a power loss) left behind.
+/// Recordings which were not finished get a repaired header and their final name, recordings
+/// which are not listed in any manifest are added to the manifest of the latest session with
+/// their original timestamps. All recordings which were not encoded yet are returned, `None` is
+/// returned if there is nothing to recover.
+pub fn recover_recordings(
+    archive: &Archive,
+    inputs: &HashMap<String, RecordingDeviceConfiguration>,
+) -> io::Result<Option<Recovery>> {
+    finish_partial_recordings(&archive.get_raw_folder())?;
+
+    let encoded_extensions: Vec<&str> = [OutputFormat::Mp3, OutputFormat::Flac, OutputFormat::Ogg]
+        .iter()
+        .map(|format| format.get_extension())
+        .collect();
+    let mut unencoded_segments = vec![];
+    for segment in ArchiveReader::open(archive.get_root())?.get_segments()? {
+        let recording = match segment.get_file_with_extension("wav") {
+            Some(recording) => recording.to_path_buf(),
+            None => continue,
+        };
+        // silent segments are not encoded on purpose
+        if segment.is_silent()
+            || encoded_extensions
+                .iter()
+                .any(|extension| segment.get_file_with_extension(extension).is_some())
+        {
+            continue;
+        }
+        unencoded_segments.push((segment, recording));
+    }
+    if unencoded_segments.is_empty() {
+        return Ok(None);
+    }
+
+    // the recordings which are not listed anywhere belong to the latest session
+    let manifest_writer = match get_latest_manifest_path(&archive.get_state_folder())? {
+        Some(path) => ManifestWriter::open(&path)?,
+        None => ManifestWriter::new(
+            &archive.get_state_folder().to_string_lossy(),
+            to_local_time(unencoded_segments[0].0.get_started_at()),
+        ),
+    };
+
+    let mut recordings = vec![];
+    for (segment, recording) in unencoded_segments {
+        let file_name = recording
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let input = segment
+            .get_input()
+            .map(|input| input.to_string())
+            .or_else(|| {
+                segment
+                    .get_device_name()
+                    .filter(|device_name| inputs.contains_key(*device_name))
+                    .map(|device_name| device_name.to_string())
+            })
+            .or_else(|| get_input_name(inputs, segment.get_card(), segment.get_device()));
+
+        if segment.get_input().is_none() && !manifest_writer.contains_segment(&file_name) {
+            // the header is not finalized if the recording tool was killed
+            if let Err(error) = repair_header(&recording) {
+                warn!(
+                    "Could not repair the header of {}. The error was: {}",
+                    recording.display(),
+                    error
+                );
+            }
+            let duration_in_seconds = WaveMetaReader::from_file(&recording.to_string_lossy())
+                .map(|meta_reader| meta_reader.get_duration().round() as u32)
+                .unwrap_or_default();
+            let started_at = read_broadcast_extension(&recording)
+                .ok()
+                .flatten()
+                .map(|extension| extension.origination_time)
+                .unwrap_or_else(|| segment.get_started_at());
+            info!(
+                "Adding the recording {} of an interrupted session to {}",
+                file_name,
+                manifest_writer.get_path().display()
+            );
+            manifest_writer.add_segment(SegmentManifest {
+                file: file_name,
+                input: input
+                    .clone()
+                    .unwrap_or_else(|| UNKNOWN_INPUT_NAME.to_string()),
+                started_at: to_timestamp_timezone(to_local_time(started_at))
+                    .format(MANIFEST_TIMESTAMP_FORMAT)
+                    .to_string(),
+                duration_in_seconds,
+                recovered: true,
+                ..Default::default()
+            });
+        }
+
+        recordings.push(RecoveredRecording {
+            file_prefix: recording.with_extension("").to_string_lossy().to_string(),
+            input,
+        });
+    }
+    Ok(Some(Recovery {
+        manifest_writer,
+        recordings,
+    }))
+}
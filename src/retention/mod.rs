@@ -0,0 +1,131 @@
+use std::fs::remove_file;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::archive::collect_files;
+use crate::archive::layout::Archive;
+
+/// How long (and how much of) the encoded recordings are kept in the data directory.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionConfiguration {
+    /// The number of days the encoded recordings are kept, they are kept forever if none is set.
+    #[serde(default = "RetentionConfiguration::default_keep_days")]
+    pub keep_days: Option<u32>,
+
+    /// The size (in GB) the encoded recordings, their previews and the trash may use together,
+    /// the size is not limited if none is set.
+    #[serde(default = "RetentionConfiguration::default_keep_gigabytes")]
+    pub keep_gigabytes: Option<f64>,
+}
+
+impl RetentionConfiguration {
+    fn default_keep_days() -> Option<u32> {
+        None
+    }
+
+    fn default_keep_gigabytes() -> Option<f64> {
+        None
+    }
+
+    /// Check if any limit is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.keep_days.is_some() || self.keep_gigabytes.is_some()
+    }
+}
+
+impl Default for RetentionConfiguration {
+    fn default() -> Self {
+        RetentionConfiguration {
+            keep_days: RetentionConfiguration::default_keep_days(),
+            keep_gigabytes: RetentionConfiguration::default_keep_gigabytes(),
+        }
+    }
+}
+
+/// A file which is subject to the retention policy.
+struct RetainedFile {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+fn get_retained_files(folder: &Path) -> io::Result<Vec<RetainedFile>> {
+    let mut files = vec![];
+    if folder.is_dir() {
+        collect_files(folder, &mut files)?;
+    }
+    Ok(files
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = path.metadata().ok()?;
+            Some(RetainedFile {
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                size: metadata.len(),
+                path,
+            })
+        })
+        .collect())
+}
+
+/// The files which were removed by the retention policy.
+#[derive(Debug, Default)]
+pub struct PruneSummary {
+    pub files: Vec<PathBuf>,
+    pub size_in_bytes: u64,
+}
+
+/// Remove the files which exceed the retention policy, the trash is emptied before the oldest
+/// encoded recordings (and their previews) are removed. The recordings which were not encoded yet
+/// are never removed. With `dry_run`, the files are only listed.
+pub fn prune_archive(
+    archive: &Archive,
+    retention: &RetentionConfiguration,
+    dry_run: bool,
+) -> io::Result<PruneSummary> {
+    let mut summary = PruneSummary::default();
+    if !retention.is_enabled() {
+        return Ok(summary);
+    }
+
+    let mut trashed_files = get_retained_files(&archive.get_trash_folder())?;
+    trashed_files.sort_by_key(|file| file.modified);
+    let mut encoded_files = get_retained_files(&archive.get_encoded_folder())?;
+    encoded_files.extend(get_retained_files(&archive.get_previews_folder())?);
+    encoded_files.sort_by_key(|file| file.modified);
+    let files: Vec<RetainedFile> = trashed_files.into_iter().chain(encoded_files).collect();
+
+    let oldest_kept = retention.keep_days.and_then(|keep_days| {
+        SystemTime::now().checked_sub(Duration::from_secs(u64::from(keep_days) * 24 * 3600))
+    });
+    let size_limit = retention
+        .keep_gigabytes
+        .map(|keep_gigabytes| (keep_gigabytes.max(0.0) * 1024.0 * 1024.0 * 1024.0) as u64);
+    let mut total_size: u64 = files.iter().map(|file| file.size).sum();
+
+    for file in files {
+        let is_expired = oldest_kept.is_some_and(|oldest_kept| file.modified < oldest_kept);
+        let exceeds_size_limit = size_limit.is_some_and(|size_limit| total_size > size_limit);
+        if !is_expired && !exceeds_size_limit {
+            continue;
+        }
+        if !dry_run {
+            if let Err(error) = remove_file(&file.path) {
+                warn!(
+                    "Could not remove {}. The error was: {}",
+                    file.path.display(),
+                    error
+                );
+                continue;
+            }
+        }
+        total_size -= file.size;
+        summary.size_in_bytes += file.size;
+        summary.files.push(file.path);
+    }
+    Ok(summary)
+}
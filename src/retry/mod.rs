@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// The settings for retrying the recording of an input which failed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RetryConfiguration {
+    /// The number of consecutive failures after which an input is given up, 0 retries forever.
+    #[serde(default = "RetryConfiguration::default_maximum_attempts")]
+    pub maximum_attempts: u32,
+
+    /// The delay after the first failure, it is doubled with every further failure.
+    #[serde(default = "RetryConfiguration::default_initial_delay_in_seconds")]
+    pub initial_delay_in_seconds: u32,
+
+    /// The longest delay between two attempts.
+    #[serde(default = "RetryConfiguration::default_maximum_delay_in_seconds")]
+    pub maximum_delay_in_seconds: u32,
+}
+
+impl RetryConfiguration {
+    fn default_maximum_attempts() -> u32 {
+        10
+    }
+
+    fn default_initial_delay_in_seconds() -> u32 {
+        1
+    }
+
+    fn default_maximum_delay_in_seconds() -> u32 {
+        300
+    }
+
+    /// Get the delay before the next attempt after the supplied number of consecutive failures.
+    pub fn get_delay(&self, failures: u32) -> Duration {
+        let factor = 2u64.saturating_pow(failures.saturating_sub(1));
+        let delay_in_seconds = u64::from(self.initial_delay_in_seconds.max(1))
+            .saturating_mul(factor)
+            .min(u64::from(self.maximum_delay_in_seconds.max(1)));
+        Duration::from_secs(delay_in_seconds)
+    }
+}
+
+impl Default for RetryConfiguration {
+    fn default() -> Self {
+        RetryConfiguration {
+            maximum_attempts: RetryConfiguration::default_maximum_attempts(),
+            initial_delay_in_seconds: RetryConfiguration::default_initial_delay_in_seconds(),
+            maximum_delay_in_seconds: RetryConfiguration::default_maximum_delay_in_seconds(),
+        }
+    }
+}
+
+/// What happens after the recording of an input failed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureFailure {
+    /// The recording is tried again after the delay.
+    Retry { failures: u32, delay: Duration },
+
+    /// The input failed too often and is not recorded anymore.
+    GiveUp {
+        failures: u32,
+        failing_since: DateTime<Local>,
+    },
+}
+
+/// The consecutive failures of a single input.
+struct InputFailures {
+    failures: u32,
+    failing_since: DateTime<Local>,
+    has_given_up: bool,
+}
+
+/// Counts the consecutive failures of the inputs, so failing inputs are retried with an
+/// increasing delay (instead of immediately) and given up eventually. It can be shared between
+/// the recording threads.
+pub struct CaptureBackoff {
+    config: RetryConfiguration,
+    inputs: Mutex<HashMap<String, InputFailures>>,
+}
+
+impl CaptureBackoff {
+    pub fn new(config: &RetryConfiguration) -> CaptureBackoff {
+        CaptureBackoff {
+            config: config.clone(),
+            inputs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a failed recording of an input and decide if it is tried again.
+    pub fn add_failure(&self, input_name: &str) -> CaptureFailure {
+        let mut inputs = self.inputs.lock().unwrap();
+        let input = inputs
+            .entry(input_name.to_string())
+            .or_insert_with(|| InputFailures {
+                failures: 0,
+                failing_since: Local::now(),
+                has_given_up: false,
+            });
+        input.failures += 1;
+        if self.config.maximum_attempts > 0 && input.failures >= self.config.maximum_attempts {
+            input.has_given_up = true;
+            return CaptureFailure::GiveUp {
+                failures: input.failures,
+                failing_since: input.failing_since,
+            };
+        }
+        CaptureFailure::Retry {
+            failures: input.failures,
+            delay: self.config.get_delay(input.failures),
+        }
+    }
+
+    /// Forget the failures of an input which recorded successfully.
+    pub fn add_success(&self, input_name: &str) {
+        self.inputs.lock().unwrap().remove(input_name);
+    }
+
+    /// Check if an input failed too often and is not recorded anymore.
+    pub fn has_given_up(&self, input_name: &str) -> bool {
+        self.inputs
+            .lock()
+            .unwrap()
+            .get(input_name)
+            .is_some_and(|input| input.has_given_up)
+    }
+}
@@ -0,0 +1,523 @@
+use core::fmt;
+use std::error;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::spawn;
+
+use chrono::{Datelike, Duration as OldDuration, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+/// An error which occurred while parsing a cron expression.
+#[derive(Debug)]
+pub struct CronParseError {
+    expression: String,
+    reason: String,
+}
+
+impl fmt::Display for CronParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid cron expression '{}': {}",
+            self.expression, self.reason
+        )
+    }
+}
+
+impl error::Error for CronParseError {}
+
+/// A single field of a cron expression, represented by a bit for each allowed value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CronField {
+    allowed_values: u64,
+    is_wildcard: bool,
+}
+
+impl CronField {
+    fn parse(field: &str, minimum: u32, maximum: u32) -> Result<CronField, String> {
+        let mut allowed_values = 0u64;
+        for part in field.split(',') {
+            // every part can have a step size (e.g. `*/15` or `0-30/5`)
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (
+                    range,
+                    step.parse::<u32>()
+                        .map_err(|_| format!("invalid step '{}'", step))?,
+                ),
+                None => (part, 1),
+            };
+            if step == 0 {
+                return Err("the step size must not be zero".to_string());
+            }
+
+            let parse_value = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|value| *value >= minimum && *value <= maximum)
+                    .ok_or(format!(
+                        "'{}' is not between {} and {}",
+                        value, minimum, maximum
+                    ))
+            };
+            let (start, end) = if range == "*" {
+                (minimum, maximum)
+            } else if let Some((start, end)) = range.split_once('-') {
+                (parse_value(start)?, parse_value(end)?)
+            } else {
+                let value = parse_value(range)?;
+                if part.contains('/') {
+                    (value, maximum)
+                } else {
+                    (value, value)
+                }
+            };
+            if start > end {
+                return Err(format!("the range '{}' is empty", range));
+            }
+
+            for value in (start..=end).step_by(step as usize) {
+                allowed_values |= 1 << value;
+            }
+        }
+        Ok(CronField {
+            allowed_values,
+            is_wildcard: field == "*",
+        })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.allowed_values & (1 << value) != 0
+    }
+}
+
+/// A cron-like expression with the five fields minute, hour, day of month, month and day of week
+/// (0 or 7 is Sunday). Every field supports `*`, single values, ranges, lists and steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpression {
+    minute: CronField,
+    hour: CronField,
+    day_of_month: CronField,
+    month: CronField,
+    day_of_week: CronField,
+}
+
+impl CronExpression {
+    pub fn parse(expression: &str) -> Result<CronExpression, CronParseError> {
+        let to_error = |reason: String| CronParseError {
+            expression: expression.to_string(),
+            reason,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(to_error(format!(
+                "expected 5 fields but found {}",
+                fields.len()
+            )));
+        }
+
+        // Sunday can be written as 0 or 7, internally only 0 is used
+        let mut day_of_week = CronField::parse(fields[4], 0, 7).map_err(to_error)?;
+        if day_of_week.matches(7) {
+            day_of_week.allowed_values |= 1;
+        }
+
+        Ok(CronExpression {
+            minute: CronField::parse(fields[0], 0, 59).map_err(to_error)?,
+            hour: CronField::parse(fields[1], 0, 23).map_err(to_error)?,
+            day_of_month: CronField::parse(fields[2], 1, 31).map_err(to_error)?,
+            month: CronField::parse(fields[3], 1, 12).map_err(to_error)?,
+            day_of_week,
+        })
+    }
+
+    /// Check if the expression matches the minute of the supplied time.
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        if !self.minute.matches(time.minute())
+            || !self.hour.matches(time.hour())
+            || !self.month.matches(time.month())
+        {
+            return false;
+        }
+
+        // like cron, a day matches if either of both day fields matches if both are restricted
+        let day_of_month_matches = self.day_of_month.matches(time.day());
+        let day_of_week_matches = self
+            .day_of_week
+            .matches(time.weekday().num_days_from_sunday());
+        match (self.day_of_month.is_wildcard, self.day_of_week.is_wildcard) {
+            (true, true) => true,
+            (true, false) => day_of_week_matches,
+            (false, true) => day_of_month_matches,
+            (false, false) => day_of_month_matches || day_of_week_matches,
+        }
+    }
+
+    /// Check if the expression matches any minute after `from` up to and including `to`.
+    pub fn matches_between(&self, from: &NaiveDateTime, to: &NaiveDateTime) -> bool {
+        let mut minute = from.with_second(0).and_then(|time| time.with_nanosecond(0));
+        while let Some(current_minute) = minute {
+            let next_minute = current_minute + OldDuration::minutes(1);
+            if next_minute > *to {
+                return false;
+            }
+            if self.matches(&next_minute) {
+                return true;
+            }
+            minute = Some(next_minute);
+        }
+        false
+    }
+}
+
+/// The daily time window in which the recorder records (e.g. from 22:30 to 07:00), so the record
+/// command does not have to be started and stopped every night.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfiguration {
+    /// The time (`HH:MM`) the recording is started.
+    pub start: String,
+
+    /// The time (`HH:MM`) the recording is stopped. A time before the start refers to the next
+    /// day.
+    pub stop: String,
+}
+
+impl ScheduleConfiguration {
+    /// Parse the start and stop time of the window.
+    pub fn get_window(&self) -> Result<RecordingWindow, String> {
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("'{}' is not a valid time (HH:MM)", time))
+        };
+        let start = parse_time(&self.start)?;
+        let stop = parse_time(&self.stop)?;
+        if start == stop {
+            return Err("the start and the stop of the schedule are the same".to_string());
+        }
+        Ok(RecordingWindow { start, stop })
+    }
+}
+
+/// The parsed daily recording window of a [`ScheduleConfiguration`].
+#[derive(Debug, Clone, Copy)]
+pub struct RecordingWindow {
+    start: NaiveTime,
+    stop: NaiveTime,
+}
+
+impl RecordingWindow {
+    /// Check if the recorder should record at the supplied time.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.stop {
+            self.start <= time && time < self.stop
+        } else {
+            // the window spans midnight
+            time >= self.start || time < self.stop
+        }
+    }
+
+    /// Get the next time the window starts (or `now` if it is already open).
+    pub fn get_next_start(&self, now: NaiveDateTime) -> NaiveDateTime {
+        if self.contains(now.time()) {
+            return now;
+        }
+        let start = now.date().and_time(self.start);
+        if start > now {
+            start
+        } else {
+            start + OldDuration::days(1)
+        }
+    }
+
+    /// Get the time between the start and the stop of the window.
+    pub fn get_duration(&self) -> OldDuration {
+        let duration = self.stop - self.start;
+        if duration < OldDuration::zero() {
+            duration + OldDuration::days(1)
+        } else {
+            duration
+        }
+    }
+
+    /// Get the time the window which is open at `now` closes.
+    pub fn get_stop_after(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let stop = now.date().and_time(self.stop);
+        if stop > now {
+            stop
+        } else {
+            stop + OldDuration::days(1)
+        }
+    }
+}
+
+/// The wall-clock boundary the record command waits for before it starts recording.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub enum StartAlignment {
+    /// Start immediately (e.g. for debugging).
+    #[serde(rename = "none")]
+    None,
+
+    /// Start at the next full minute.
+    #[default]
+    #[serde(rename = "minute")]
+    Minute,
+
+    /// Start at the next multiple of five minutes (e.g. 23:05).
+    #[serde(rename = "5min")]
+    FiveMinutes,
+
+    /// Start at the next full hour.
+    #[serde(rename = "hour")]
+    Hour,
+}
+
+impl fmt::Display for StartAlignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StartAlignment::None => write!(f, "none"),
+            StartAlignment::Minute => write!(f, "minute"),
+            StartAlignment::FiveMinutes => write!(f, "5min"),
+            StartAlignment::Hour => write!(f, "hour"),
+        }
+    }
+}
+
+impl StartAlignment {
+    fn get_interval_in_seconds(&self) -> u32 {
+        match *self {
+            StartAlignment::None => 0,
+            StartAlignment::Minute => 60,
+            StartAlignment::FiveMinutes => 5 * 60,
+            StartAlignment::Hour => 60 * 60,
+        }
+    }
+
+    /// Get the time until the next boundary after the supplied time, which is zero without an
+    /// alignment.
+    pub fn get_time_until_boundary(&self, now: NaiveDateTime) -> OldDuration {
+        let interval_in_seconds = self.get_interval_in_seconds();
+        if interval_in_seconds == 0 {
+            return OldDuration::zero();
+        }
+        let elapsed_in_ms = i64::from(now.num_seconds_from_midnight() % interval_in_seconds) * 1000
+            + i64::from(now.nanosecond() / 1_000_000);
+        OldDuration::milliseconds(i64::from(interval_in_seconds) * 1000 - elapsed_in_ms)
+    }
+}
+
+/// The conditions after which the record command stops on its own instead of recording until it
+/// is stopped. The first condition which is met stops the recording.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StopConfiguration {
+    /// The number of segments after which the recording stops.
+    #[serde(default = "StopConfiguration::default_maximum_segments")]
+    pub maximum_segments: Option<u32>,
+
+    /// The time (e.g. `8h` or `7h 30m`) after the start of the record command at which the
+    /// recording stops.
+    #[serde(default = "StopConfiguration::default_total_duration")]
+    pub total_duration: Option<String>,
+
+    /// The time (`HH:MM`) at which the recording stops, the first time after the start of the
+    /// record command is used.
+    #[serde(default = "StopConfiguration::default_until")]
+    pub until: Option<String>,
+}
+
+impl StopConfiguration {
+    fn default_maximum_segments() -> Option<u32> {
+        None
+    }
+
+    fn default_total_duration() -> Option<String> {
+        None
+    }
+
+    fn default_until() -> Option<String> {
+        None
+    }
+
+    /// Check if any stop condition is configured.
+    pub fn is_set(&self) -> bool {
+        self.maximum_segments.is_some() || self.total_duration.is_some() || self.until.is_some()
+    }
+
+    /// Parse the conditions for a recording which starts at the supplied time.
+    pub fn get_condition(&self, started_at: NaiveDateTime) -> Result<StopCondition, String> {
+        if self.maximum_segments == Some(0) {
+            return Err("the maximum number of segments has to be at least 1".to_string());
+        }
+        let total_duration_deadline = match &self.total_duration {
+            Some(total_duration) => {
+                let total_duration = humantime::parse_duration(total_duration)
+                    .ok()
+                    .and_then(|total_duration| OldDuration::from_std(total_duration).ok())
+                    .filter(|total_duration| *total_duration > OldDuration::zero())
+                    .ok_or_else(|| format!("'{}' is not a valid duration", total_duration))?;
+                Some(started_at + total_duration)
+            }
+            None => None,
+        };
+        let until_deadline = match &self.until {
+            Some(until) => {
+                let until = NaiveTime::parse_from_str(until, "%H:%M")
+                    .map_err(|_| format!("'{}' is not a valid time (HH:MM)", until))?;
+                let deadline = started_at.date().and_time(until);
+                Some(if deadline > started_at {
+                    deadline
+                } else {
+                    deadline + OldDuration::days(1)
+                })
+            }
+            None => None,
+        };
+        let deadline = match (total_duration_deadline, until_deadline) {
+            (Some(first), Some(second)) => Some(first.min(second)),
+            (first, second) => first.or(second),
+        };
+        Ok(StopCondition {
+            maximum_segments: self.maximum_segments,
+            deadline,
+        })
+    }
+}
+
+impl Default for StopConfiguration {
+    fn default() -> Self {
+        StopConfiguration {
+            maximum_segments: StopConfiguration::default_maximum_segments(),
+            total_duration: StopConfiguration::default_total_duration(),
+            until: StopConfiguration::default_until(),
+        }
+    }
+}
+
+/// The parsed conditions of a [`StopConfiguration`] for a started recording.
+#[derive(Debug, Clone, Copy)]
+pub struct StopCondition {
+    maximum_segments: Option<u32>,
+    deadline: Option<NaiveDateTime>,
+}
+
+impl StopCondition {
+    /// Get the time at which the recording stops, `None` if it is not stopped at a certain time.
+    pub fn get_deadline(&self) -> Option<NaiveDateTime> {
+        self.deadline
+    }
+
+    /// Check if the recording has to stop before the next segment. The reason is returned if it
+    /// has to stop.
+    pub fn get_reason(&self, recorded_segments: u32, now: NaiveDateTime) -> Option<String> {
+        if let Some(maximum_segments) = self.maximum_segments {
+            if recorded_segments >= maximum_segments {
+                return Some(format!("{} segment(s) were recorded", recorded_segments));
+            }
+        }
+        match self.deadline {
+            // a remaining second is not worth a segment
+            Some(deadline) if deadline - now < OldDuration::seconds(1) => {
+                Some(format!("the stop time {} was reached", deadline))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A maintenance task which is executed by the scheduler at the configured times.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceTaskConfiguration {
+    /// A descriptive name which is used for logging.
+    pub name: String,
+
+    /// The cron-like expression when the task should run (e.g. `30 12 * * *`).
+    pub schedule: String,
+
+    /// The command which is executed by the shell.
+    pub command: String,
+}
+
+struct ScheduledTask {
+    configuration: MaintenanceTaskConfiguration,
+    schedule: CronExpression,
+    is_running: Arc<AtomicBool>,
+}
+
+/// A lightweight scheduler for maintenance tasks. It does not run on its own but is polled by the
+/// recording loop between two segments, so the tasks never start during the start of a segment.
+pub struct Scheduler {
+    tasks: Vec<ScheduledTask>,
+    last_check: NaiveDateTime,
+}
+
+impl Scheduler {
+    pub fn new(
+        tasks: &[MaintenanceTaskConfiguration],
+        now: NaiveDateTime,
+    ) -> Result<Scheduler, CronParseError> {
+        let mut scheduled_tasks = vec![];
+        for task in tasks {
+            scheduled_tasks.push(ScheduledTask {
+                configuration: task.clone(),
+                schedule: CronExpression::parse(&task.schedule)?,
+                is_running: Arc::new(AtomicBool::new(false)),
+            });
+        }
+        Ok(Scheduler {
+            tasks: scheduled_tasks,
+            last_check: now,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Start all tasks which were due since the last check in the background. A task which is
+    /// still running from its last start is skipped.
+    pub fn run_due_tasks(&mut self, now: NaiveDateTime) {
+        for task in &self.tasks {
+            if !task.schedule.matches_between(&self.last_check, &now) {
+                continue;
+            }
+            if task.is_running.swap(true, Ordering::SeqCst) {
+                warn!(
+                    "The maintenance task {} is still running, skipping it this time",
+                    task.configuration.name
+                );
+                continue;
+            }
+
+            let configuration = task.configuration.clone();
+            let is_running = task.is_running.clone();
+            spawn(move || {
+                info!("Running the maintenance task {}", configuration.name);
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(&configuration.command)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
+                match status {
+                    Ok(status) if status.success() => {
+                        info!("The maintenance task {} finished", configuration.name)
+                    }
+                    Ok(status) => error!(
+                        "The maintenance task {} failed with {}",
+                        configuration.name, status
+                    ),
+                    Err(error) => error!(
+                        "Could not run the maintenance task {}. The error was: {}",
+                        configuration.name, error
+                    ),
+                }
+                is_running.store(false, Ordering::SeqCst);
+            });
+        }
+        self.last_check = now;
+    }
+}
@@ -0,0 +1,307 @@
+use serde_json::{json, Value};
+
+use crate::manifest::MANIFEST_VERSION;
+use crate::status::STATUS_VERSION;
+
+/// The version of the JSON output of the report command which is written by this version of the
+/// tool.
+pub const REPORT_VERSION: u32 = 1;
+
+/// The JSON Schema dialect all schemas are written in.
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The schema of a JSON format the tool writes. Within a version, fields are only ever added (and
+/// are optional then), existing fields are neither removed nor changed. Readers should ignore the
+/// fields they do not know.
+pub struct Schema {
+    /// The name the schema is listed with.
+    pub name: &'static str,
+
+    /// The version of the format which is stored in its `version` field.
+    pub version: u32,
+
+    pub document: Value,
+}
+
+/// Get the schemas of all JSON formats the tool writes.
+pub fn get_schemas() -> Vec<Schema> {
+    vec![
+        Schema {
+            name: "session_manifest",
+            version: MANIFEST_VERSION,
+            document: get_session_manifest_schema(),
+        },
+        Schema {
+            name: "report",
+            version: REPORT_VERSION,
+            document: get_report_schema(),
+        },
+        Schema {
+            name: "status",
+            version: STATUS_VERSION,
+            document: get_status_schema(),
+        },
+    ]
+}
+
+fn get_session_manifest_schema() -> Value {
+    let count = json!({ "type": "integer", "minimum": 0 });
+    let seconds = json!({ "type": "number", "minimum": 0 });
+    let flag = json!({ "type": "boolean", "default": false });
+    let segment = json!({
+        "type": "object",
+        "required": ["file", "input", "started_at", "duration_in_seconds"],
+        "properties": {
+            "file": { "type": "string" },
+            "input": { "type": "string" },
+            "started_at": { "$ref": "#/$defs/timestamp" },
+            "duration_in_seconds": count,
+            "dropped_frames": count,
+            "spilled_frames": count,
+            "gaps": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["offset_in_seconds", "duration_in_seconds"],
+                    "properties": {
+                        "offset_in_seconds": seconds,
+                        "duration_in_seconds": seconds
+                    }
+                }
+            },
+            "applied_gain_in_db": { "type": "number" },
+            "peak_level_in_dbfs": { "type": "number", "maximum": 0 },
+            "rms_level_in_dbfs": { "type": "number", "maximum": 0 },
+            "silence_percentage": { "type": "number", "minimum": 0, "maximum": 100 },
+            "leading_trim_in_seconds": seconds,
+            "trailing_trim_in_seconds": seconds,
+            "events": count,
+            "channel_events": {
+                "type": "object",
+                "additionalProperties": count
+            },
+            "cough_offsets_in_seconds": {
+                "description": "The offsets of the coughs from the start of the stored recording.",
+                "type": "array",
+                "items": seconds
+            },
+            "interestingness": { "type": "integer", "minimum": 0, "maximum": 100 },
+            "cpu_utilization": { "type": "number", "minimum": 0, "maximum": 1 },
+            "encoding_time_in_seconds": seconds,
+            "encoding_queue_depth": count,
+            "timestamp_source": { "type": "string" },
+            "clock_jump_in_seconds": seconds,
+            "recovered": flag,
+            "partial": flag,
+            "overlap_in_seconds": count,
+            "silent": flag,
+            "activated": flag,
+            "encoding": {
+                "description": "How the recording is stored after it was post-processed.",
+                "type": "string"
+            }
+        }
+    });
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "$id": format!("urn:schlaflosigkeit:session_manifest:{}", MANIFEST_VERSION),
+        "title": "Session manifest",
+        "description": "The segments which were recorded in a recording session.",
+        "type": "object",
+        "required": ["version", "started_at", "segments"],
+        "properties": {
+            "version": { "const": MANIFEST_VERSION },
+            "started_at": { "$ref": "#/$defs/timestamp" },
+            "sync": {
+                "description": "The result of the start synchronization with another machine.",
+                "type": "object",
+                "required": ["role", "peer", "offset_in_ms", "round_trip_in_ms", "started_at"],
+                "properties": {
+                    "role": { "enum": ["leader", "follower"] },
+                    "peer": { "type": "string" },
+                    "offset_in_ms": { "type": "integer" },
+                    "round_trip_in_ms": { "type": "integer" },
+                    "started_at": { "type": "string" }
+                }
+            },
+            "label": { "type": "string" },
+            "cpu_cores": count,
+            "latencies_in_ms": {
+                "type": "object",
+                "additionalProperties": { "type": "number" }
+            },
+            "encoding_changes": {
+                "description": "The points at which the encoding of an input changed.",
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["input", "started_at", "from", "to"],
+                    "properties": {
+                        "input": { "type": "string" },
+                        "started_at": { "$ref": "#/$defs/timestamp" },
+                        "from": { "type": "string" },
+                        "to": { "type": "string" }
+                    }
+                }
+            },
+            "segments": {
+                "type": "array",
+                "items": { "$ref": "#/$defs/segment" }
+            }
+        },
+        "$defs": {
+            "timestamp": {
+                "description": "A local timestamp with milliseconds and the UTC offset.",
+                "type": "string",
+                "pattern": "^\\d{4}-\\d{2}-\\d{2}T\\d{2}:\\d{2}:\\d{2}\\.\\d{3}[+-]\\d{2}:\\d{2}$"
+            },
+            "segment": segment
+        }
+    })
+}
+
+fn get_report_schema() -> Value {
+    let count = json!({ "type": "integer", "minimum": 0 });
+    let energy = json!({ "type": "number", "minimum": 0 });
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "$id": format!("urn:schlaflosigkeit:report:{}", REPORT_VERSION),
+        "title": "Report",
+        "description": "The summary of the recorded nights (written by `report --json`).",
+        "type": "object",
+        "required": ["version", "nights"],
+        "properties": {
+            "version": { "const": REPORT_VERSION },
+            "nights": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["night", "labels", "segments", "recorded_in_seconds", "energy", "anomalies"],
+                    "properties": {
+                        "night": {
+                            "description": "The date the night starts at.",
+                            "type": "string",
+                            "format": "date"
+                        },
+                        "labels": { "type": "array", "items": { "type": "string" } },
+                        "segments": count,
+                        "recorded_in_seconds": { "type": "number", "minimum": 0 },
+                        "events": count,
+                        "channel_events": {
+                            "type": "object",
+                            "additionalProperties": count
+                        },
+                        "coughs": count,
+                        "cough_times": {
+                            "description": "The local times the coughs of the night started at.",
+                            "type": "array",
+                            "items": { "type": "string", "format": "date-time" }
+                        },
+                        "peak_level_in_dbfs": {
+                            "description": "The highest peak level of the segments of the night.",
+                            "type": "number",
+                            "maximum": 0
+                        },
+                        "rms_level_in_dbfs": {
+                            "description": "The median RMS level of the segments of the night.",
+                            "type": "number",
+                            "maximum": 0
+                        },
+                        "breathing_rates": {
+                            "description": "EXPERIMENTAL: The estimated breathing rate for every 5 minutes of the night (written by `report --breathing-rate`).",
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["started_at", "ended_at", "breaths_per_minute"],
+                                "properties": {
+                                    "started_at": { "type": "string", "format": "date-time" },
+                                    "ended_at": { "type": "string", "format": "date-time" },
+                                    "breaths_per_minute": {
+                                        "description": "The estimated breaths per minute, null if the window was not quiet enough.",
+                                        "type": ["number", "null"],
+                                        "minimum": 0
+                                    }
+                                }
+                            }
+                        },
+                        "energy": {
+                            "type": "object",
+                            "required": ["capture_in_wh", "encoding_in_wh", "total_in_wh"],
+                            "properties": {
+                                "capture_in_wh": energy,
+                                "encoding_in_wh": energy,
+                                "total_in_wh": energy
+                            }
+                        },
+                        "most_interesting": {
+                            "description": "The segments of the night which should be listened to first.",
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["file", "input", "interestingness"],
+                                "properties": {
+                                    "file": { "type": "string" },
+                                    "input": { "type": "string" },
+                                    "interestingness": { "type": "integer", "minimum": 0, "maximum": 100 }
+                                }
+                            }
+                        },
+                        "baseline_nights": {
+                            "description": "The number of preceding nights the night was compared to.",
+                            "type": "integer",
+                            "minimum": 0
+                        },
+                        "anomalies": { "type": "array", "items": { "type": "string" } }
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn get_status_schema() -> Value {
+    let count = json!({ "type": "integer", "minimum": 0 });
+    let timestamp = json!({
+        "description": "A local timestamp with milliseconds and the UTC offset.",
+        "type": "string",
+        "pattern": "^\\d{4}-\\d{2}-\\d{2}T\\d{2}:\\d{2}:\\d{2}\\.\\d{3}[+-]\\d{2}:\\d{2}$"
+    });
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "$id": format!("urn:schlaflosigkeit:status:{}", STATUS_VERSION),
+        "title": "Status",
+        "description": "The current state of the record command, which is replaced after every change.",
+        "type": "object",
+        "required": ["version", "updated_at", "started_at", "draining", "encoding_queue_depth", "inputs"],
+        "properties": {
+            "version": { "const": STATUS_VERSION },
+            "updated_at": timestamp,
+            "started_at": timestamp,
+            "stopped_at": timestamp,
+            "draining": {
+                "description": "Set while the recorder finishes its work after a shutdown was requested.",
+                "type": "boolean"
+            },
+            "paused": {
+                "description": "Set while the recording is paused on the control socket.",
+                "type": "boolean"
+            },
+            "encoding_queue_depth": count,
+            "free_space_in_bytes": count,
+            "inputs": {
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "required": ["state", "segment_duration_in_seconds", "recorded_segments"],
+                    "properties": {
+                        "state": { "enum": ["waiting", "recording", "failed", "gave_up"] },
+                        "segment_started_at": timestamp,
+                        "segment_duration_in_seconds": count,
+                        "recorded_segments": count,
+                        "last_error": { "type": "string" }
+                    }
+                }
+            }
+        }
+    })
+}
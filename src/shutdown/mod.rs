@@ -0,0 +1,115 @@
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Set by the signal handler if the process should stop after the current segment.
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Set if the recordings of the current segment should be finished now, so the next segment starts
+/// early.
+static SPLIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// The interval in which waiting threads check if a shutdown was requested.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[cfg(unix)]
+extern "C" fn handle_signal(_: libc::c_int) {
+    // only async-signal-safe operations are allowed here
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Request a graceful shutdown on SIGINT and SIGTERM instead of terminating the process
+/// immediately. This is only supported on Unix.
+pub fn install_signal_handlers() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Request a graceful shutdown like a SIGINT does, e.g. if the terminal does not send signals
+/// while a dashboard is shown.
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Check if a graceful shutdown was requested.
+pub fn is_shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Finish the recordings of the current segment now, like a shutdown does, but continue with the
+/// next segment afterwards.
+pub fn request_split() {
+    SPLIT_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Check if the current segment should be finished early.
+pub fn is_split_requested() -> bool {
+    SPLIT_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Forget the split request once all recordings of the segment were finished.
+pub fn clear_split_request() {
+    SPLIT_REQUESTED.store(false, Ordering::SeqCst);
+}
+
+/// Check if the running recordings should be finished now, since a shutdown or a split of the
+/// segment was requested.
+pub fn is_recording_stop_requested() -> bool {
+    is_shutdown_requested() || is_split_requested()
+}
+
+/// Sleep for the supplied duration or until the condition is met. Returns `false` if the sleep
+/// was interrupted.
+fn sleep_until(duration: Duration, condition: fn() -> bool) -> bool {
+    let end = Instant::now() + duration;
+    loop {
+        if condition() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= end {
+            return true;
+        }
+        sleep(POLL_INTERVAL.min(end - now));
+    }
+}
+
+/// Sleep for the supplied duration or until a shutdown is requested. Returns `false` if the sleep
+/// was interrupted by a shutdown request.
+pub fn sleep_unless_shutdown(duration: Duration) -> bool {
+    sleep_until(duration, is_shutdown_requested)
+}
+
+/// Sleep for the supplied duration or until the running recordings should be finished. Returns
+/// `false` if the sleep was interrupted by a shutdown or a split request.
+pub fn sleep_unless_recording_stopped(duration: Duration) -> bool {
+    sleep_until(duration, is_recording_stop_requested)
+}
+
+/// Wait for a recording process to exit. If a shutdown or a split is requested in the meantime, the
+/// process gets a SIGINT (like the processes in the foreground of a terminal on Ctrl-C), so it can
+/// finalize the recording.
+pub fn wait_for_recording_process(child: &mut Child) -> io::Result<ExitStatus> {
+    let mut was_interrupted = false;
+    loop {
+        if let Some(exit_status) = child.try_wait()? {
+            return Ok(exit_status);
+        }
+        if is_recording_stop_requested() && !was_interrupted {
+            was_interrupted = true;
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+            }
+            #[cfg(not(unix))]
+            child.kill()?;
+        }
+        sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,330 @@
+use core::fmt;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration as OldDuration, Local};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::analysis::{read_energy_envelope, RecordingEnvelope, ENVELOPE_VALUES_PER_SECOND};
+
+/// What happens to the segments which are completely silent.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SilenceMode {
+    /// Do not check the segments for silence.
+    #[default]
+    Off,
+
+    /// Keep the recordings of silent segments, but do not encode them.
+    SkipEncoding,
+
+    /// Remove the recordings of silent segments.
+    Discard,
+}
+
+impl fmt::Display for SilenceMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SilenceMode::Off => write!(f, "off"),
+            SilenceMode::SkipEncoding => write!(f, "skip_encoding"),
+            SilenceMode::Discard => write!(f, "discard"),
+        }
+    }
+}
+
+/// The configuration for skipping the segments in which nothing happened.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SilenceConfiguration {
+    #[serde(default = "SilenceConfiguration::default_mode")]
+    pub mode: SilenceMode,
+
+    /// The RMS energy (between 0.0 and 1.0) no passage of a segment may reach for the segment to
+    /// be considered silent.
+    #[serde(default = "SilenceConfiguration::default_threshold")]
+    pub threshold: f32,
+
+    /// The percentage of passages below the threshold from which a segment counts as near-silent
+    /// for detecting a microphone which fell off.
+    #[serde(default = "SilenceConfiguration::default_near_silent_percentage")]
+    pub near_silent_percentage: f32,
+
+    /// The time an input which recorded sound before has to stay near-silent (while another input
+    /// still records sound) until it is reported as detached. 0 disables the detection.
+    #[serde(default = "SilenceConfiguration::default_detached_after_in_minutes")]
+    pub detached_after_in_minutes: u32,
+
+    /// Cut off the silence at the start of the recorded segments before they are encoded.
+    #[serde(default)]
+    pub trim_leading_silence: bool,
+
+    /// Cut off the silence at the end of the recorded segments before they are encoded.
+    #[serde(default)]
+    pub trim_trailing_silence: bool,
+
+    /// The silence which is kept before the first and after the last sound of a trimmed segment.
+    #[serde(default = "SilenceConfiguration::default_trim_padding_in_seconds")]
+    pub trim_padding_in_seconds: f32,
+
+    /// The most silence which is cut off at either end of a segment.
+    #[serde(default = "SilenceConfiguration::default_maximum_trim_in_seconds")]
+    pub maximum_trim_in_seconds: u32,
+}
+
+impl SilenceConfiguration {
+    fn default_mode() -> SilenceMode {
+        SilenceMode::Off
+    }
+
+    fn default_threshold() -> f32 {
+        0.01
+    }
+
+    fn default_near_silent_percentage() -> f32 {
+        99.0
+    }
+
+    fn default_detached_after_in_minutes() -> u32 {
+        30
+    }
+
+    fn default_trim_padding_in_seconds() -> f32 {
+        2.0
+    }
+
+    fn default_maximum_trim_in_seconds() -> u32 {
+        300
+    }
+
+    /// Check if the silence at the start or the end of the segments is cut off.
+    pub fn is_trimming(&self) -> bool {
+        self.trim_leading_silence || self.trim_trailing_silence
+    }
+}
+
+impl Default for SilenceConfiguration {
+    fn default() -> Self {
+        SilenceConfiguration {
+            mode: SilenceConfiguration::default_mode(),
+            threshold: SilenceConfiguration::default_threshold(),
+            near_silent_percentage: SilenceConfiguration::default_near_silent_percentage(),
+            detached_after_in_minutes: SilenceConfiguration::default_detached_after_in_minutes(),
+            trim_leading_silence: false,
+            trim_trailing_silence: false,
+            trim_padding_in_seconds: SilenceConfiguration::default_trim_padding_in_seconds(),
+            maximum_trim_in_seconds: SilenceConfiguration::default_maximum_trim_in_seconds(),
+        }
+    }
+}
+
+/// Get the percentage (between 0.0 and 100.0) of the passages of a recorded (not yet encoded)
+/// segment whose energy stays below the threshold. A segment is silent if it is 100 percent.
+/// `None` is returned if the recording could not be read.
+pub fn measure_silence(path: &Path, threshold: f32) -> Option<f32> {
+    match read_energy_envelope(path) {
+        Ok(RecordingEnvelope {
+            values: envelope, ..
+        }) => {
+            if envelope.is_empty() {
+                return Some(100.0);
+            }
+            let silent_passages = envelope.iter().filter(|value| **value < threshold).count();
+            Some(silent_passages as f32 * 100.0 / envelope.len() as f32)
+        }
+        Err(error) => {
+            error!(
+                "Could not check {} for silence. The error was: {}",
+                path.display(),
+                error
+            );
+            None
+        }
+    }
+}
+
+/// The silent frames which are cut off at the start and the end of a recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceTrim {
+    pub leading_frames: u64,
+    pub trailing_frames: u64,
+    pub samples_per_second: u32,
+}
+
+impl SilenceTrim {
+    pub fn get_leading_in_seconds(&self) -> f64 {
+        self.leading_frames as f64 / f64::from(self.samples_per_second.max(1))
+    }
+
+    pub fn get_trailing_in_seconds(&self) -> f64 {
+        self.trailing_frames as f64 / f64::from(self.samples_per_second.max(1))
+    }
+}
+
+/// Find the silence at the start and the end of a recorded (not yet encoded) segment which can be
+/// cut off. The configured padding is kept around the first and the last passage above the
+/// threshold and at most `maximum_trim_in_seconds` are cut off at either end, so the trimmed
+/// recording can still be placed on the timeline of the night.
+///
+/// `None` is returned if nothing has to be cut off, if the whole recording is silent (which is
+/// handled by the silence mode) or if the recording could not be read.
+pub fn find_silence_trim(path: &Path, configuration: &SilenceConfiguration) -> Option<SilenceTrim> {
+    let envelope = match read_energy_envelope(path) {
+        Ok(envelope) => envelope,
+        Err(error) => {
+            error!(
+                "Could not check {} for silence to trim. The error was: {}",
+                path.display(),
+                error
+            );
+            return None;
+        }
+    };
+    let format = envelope.format;
+    let first_sound = envelope
+        .values
+        .iter()
+        .position(|value| *value >= configuration.threshold)?;
+    let last_sound = envelope
+        .values
+        .iter()
+        .rposition(|value| *value >= configuration.threshold)?;
+
+    let frames = envelope.frames;
+    let frames_per_value =
+        (format.samples_per_second as usize / ENVELOPE_VALUES_PER_SECOND).max(1) as u64;
+    let padding_frames = (configuration.trim_padding_in_seconds.max(0.0) as f64
+        * f64::from(format.samples_per_second)) as u64;
+    let maximum_frames =
+        u64::from(configuration.maximum_trim_in_seconds) * u64::from(format.samples_per_second);
+    let sound_start = first_sound as u64 * frames_per_value;
+    let sound_end = ((last_sound as u64 + 1) * frames_per_value).min(frames);
+
+    let trim = SilenceTrim {
+        leading_frames: if configuration.trim_leading_silence {
+            sound_start
+                .saturating_sub(padding_frames)
+                .min(maximum_frames)
+        } else {
+            0
+        },
+        trailing_frames: if configuration.trim_trailing_silence {
+            (frames - sound_end)
+                .saturating_sub(padding_frames)
+                .min(maximum_frames)
+        } else {
+            0
+        },
+        samples_per_second: format.samples_per_second,
+    };
+    if trim.leading_frames == 0 && trim.trailing_frames == 0 {
+        return None;
+    }
+    Some(trim)
+}
+
+/// How the activity of an input changed with its latest segment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MicrophoneActivity {
+    Unchanged,
+
+    /// The input is near-silent since the time, while another input still records sound. Its
+    /// microphone probably fell off or got covered.
+    Detached {
+        silent_since: DateTime<Local>,
+        silent_for_in_seconds: u32,
+    },
+
+    /// The input records sound again after it was reported as detached.
+    Recovered,
+}
+
+/// The activity of a single input as seen by the `MicrophoneWatch`.
+#[derive(Default)]
+struct InputActivity {
+    has_been_active: bool,
+
+    /// The end of the latest segment in which the input recorded sound.
+    active_until: Option<DateTime<Local>>,
+    silent_since: Option<DateTime<Local>>,
+    is_detached: bool,
+}
+
+/// Watches the silence percentages of the segments of all inputs to notice a microphone which
+/// suddenly stops recording anything. An input is only considered as detached if it recorded
+/// sound before and another input still records sound, so a quiet night is not reported.
+pub struct MicrophoneWatch {
+    near_silent_percentage: f32,
+    detached_after: OldDuration,
+    inputs: Mutex<HashMap<String, InputActivity>>,
+}
+
+impl MicrophoneWatch {
+    /// Get the watch for the configuration, `None` is returned if the detection is disabled.
+    pub fn new(configuration: &SilenceConfiguration) -> Option<MicrophoneWatch> {
+        if configuration.detached_after_in_minutes == 0 {
+            return None;
+        }
+        Some(MicrophoneWatch {
+            near_silent_percentage: configuration.near_silent_percentage,
+            detached_after: OldDuration::minutes(i64::from(
+                configuration.detached_after_in_minutes,
+            )),
+            inputs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Add the silence percentage of a segment of an input. The segments of an input have to be
+    /// added in the order they were recorded.
+    pub fn add_segment(
+        &self,
+        input: &str,
+        started_at: DateTime<Local>,
+        duration_in_seconds: u32,
+        silence_percentage: f32,
+    ) -> MicrophoneActivity {
+        let finished_at = started_at + OldDuration::seconds(i64::from(duration_in_seconds));
+        let mut inputs = self.inputs.lock().unwrap();
+
+        if silence_percentage < self.near_silent_percentage {
+            let activity = inputs.entry(input.to_string()).or_default();
+            let was_detached = activity.is_detached;
+            *activity = InputActivity {
+                has_been_active: true,
+                active_until: Some(finished_at),
+                silent_since: None,
+                is_detached: false,
+            };
+            return if was_detached {
+                MicrophoneActivity::Recovered
+            } else {
+                MicrophoneActivity::Unchanged
+            };
+        }
+
+        let silent_since = {
+            let activity = inputs.entry(input.to_string()).or_default();
+            if !activity.has_been_active || activity.is_detached {
+                return MicrophoneActivity::Unchanged;
+            }
+            *activity.silent_since.get_or_insert(started_at)
+        };
+        let is_other_input_active = inputs.iter().any(|(other_input, other_activity)| {
+            other_input != input
+                && other_activity
+                    .active_until
+                    .is_some_and(|active_until| active_until > silent_since)
+        });
+        if finished_at - silent_since < self.detached_after || !is_other_input_active {
+            return MicrophoneActivity::Unchanged;
+        }
+        if let Some(activity) = inputs.get_mut(input) {
+            activity.is_detached = true;
+        }
+        MicrophoneActivity::Detached {
+            silent_since,
+            silent_for_in_seconds: (finished_at - silent_since).num_seconds() as u32,
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use core::fmt;
+use std::collections::BTreeMap;
+use std::fs::{rename, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use tracing::error;
+
+use crate::analysis::Levels;
+use crate::interest::compare_interestingness;
+use crate::manifest::MANIFEST_TIMESTAMP_FORMAT;
+use crate::shutdown::is_shutdown_requested;
+
+/// The number of the most interesting segments which are kept for showing them.
+const MAXIMUM_INTERESTING_SEGMENTS: usize = 5;
+
+/// The version of the status file which is written by this version of the tool.
+pub const STATUS_VERSION: u32 = 1;
+
+/// The name of the status file in the state folder of the archive, if no other file is configured.
+pub const STATUS_FILE_NAME: &str = "status.json";
+
+/// What an input of the recorder is currently doing.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InputState {
+    /// The input is not recorded at the moment (e.g. outside of its schedule).
+    #[default]
+    Waiting,
+
+    /// A segment of the input is recorded.
+    Recording,
+
+    /// The last segment of the input could not be recorded.
+    Failed,
+
+    /// The recording of the input failed too often, it is not recorded anymore.
+    GaveUp,
+}
+
+impl fmt::Display for InputState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InputState::Waiting => write!(f, "waiting"),
+            InputState::Recording => write!(f, "recording"),
+            InputState::Failed => write!(f, "failed"),
+            InputState::GaveUp => write!(f, "gave_up"),
+        }
+    }
+}
+
+/// The state of a single input of the recorder.
+#[derive(Debug, Clone, Default)]
+pub struct InputStatus {
+    pub state: InputState,
+
+    /// The start of the segment which is currently recorded.
+    pub segment_started_at: Option<DateTime<Local>>,
+    pub segment_duration_in_seconds: u32,
+
+    /// The number of segments which were recorded since the recorder was started.
+    pub recorded_segments: u32,
+
+    /// The levels of the last recording which was post-processed.
+    pub levels: Option<Levels>,
+
+    /// How interesting the last recording which was post-processed is (between 0 and 100).
+    pub interestingness: Option<u8>,
+    pub last_error: Option<String>,
+}
+
+/// A post-processed segment together with its interestingness.
+#[derive(Debug, Clone)]
+pub struct ScoredSegment {
+    pub input: String,
+    pub file: String,
+    pub interestingness: u8,
+}
+
+/// The state of the running recorder.
+#[derive(Debug, Clone)]
+pub struct RecorderStatus {
+    pub started_at: DateTime<Local>,
+    pub inputs: BTreeMap<String, InputStatus>,
+    pub encoding_queue_depth: usize,
+
+    /// The free space of the data directory, as checked before the last segment.
+    pub free_space_in_bytes: Option<u64>,
+
+    /// The most interesting segments since the recorder was started, the most interesting first.
+    pub interesting_segments: Vec<ScoredSegment>,
+
+    /// The time the recorder stopped at, it is `None` while it is running.
+    pub stopped_at: Option<DateTime<Local>>,
+
+    /// Set while the recording is paused on the control socket.
+    pub paused: bool,
+}
+
+/// The state of an input as it is written to the status file.
+#[derive(Serialize)]
+struct InputStatusFile<'a> {
+    state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    segment_started_at: Option<String>,
+    segment_duration_in_seconds: u32,
+    recorded_segments: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_error: Option<&'a str>,
+}
+
+/// The state of the recorder as it is written to the status file.
+#[derive(Serialize)]
+struct StatusFile<'a> {
+    version: u32,
+    updated_at: String,
+    started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stopped_at: Option<String>,
+    draining: bool,
+    paused: bool,
+    encoding_queue_depth: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    free_space_in_bytes: Option<u64>,
+    inputs: BTreeMap<&'a str, InputStatusFile<'a>>,
+}
+
+impl<'a> From<&'a RecorderStatus> for StatusFile<'a> {
+    fn from(status: &'a RecorderStatus) -> Self {
+        let format_timestamp =
+            |timestamp: &DateTime<Local>| timestamp.format(MANIFEST_TIMESTAMP_FORMAT).to_string();
+        StatusFile {
+            version: STATUS_VERSION,
+            updated_at: format_timestamp(&Local::now()),
+            started_at: format_timestamp(&status.started_at),
+            stopped_at: status.stopped_at.as_ref().map(format_timestamp),
+            draining: is_shutdown_requested() && status.stopped_at.is_none(),
+            paused: status.paused,
+            encoding_queue_depth: status.encoding_queue_depth,
+            free_space_in_bytes: status.free_space_in_bytes,
+            inputs: status
+                .inputs
+                .iter()
+                .map(|(input_name, input_status)| {
+                    (
+                        input_name.as_str(),
+                        InputStatusFile {
+                            state: input_status.state.to_string(),
+                            segment_started_at: input_status
+                                .segment_started_at
+                                .as_ref()
+                                .map(format_timestamp),
+                            segment_duration_in_seconds: input_status.segment_duration_in_seconds,
+                            recorded_segments: input_status.recorded_segments,
+                            last_error: input_status.last_error.as_deref(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Write the state of the recorder to the status file.
+fn write_status_file(path: &Path, status: &RecorderStatus) -> io::Result<()> {
+    // write to a temporary file first, so readers never see a partially written status. the file
+    // changes often and is rewritten anyway, so it is not synced to the disk.
+    let temporary_path = path.with_extension("json.tmp");
+    let mut file = File::create(&temporary_path)?;
+    serde_json::to_writer_pretty(&mut file, &StatusFile::from(status))?;
+    file.write_all(b"\n")?;
+    rename(&temporary_path, path)
+}
+
+/// The state of the recorder which is shared by the recording threads and the ones displaying it.
+/// Every change is written to the status file as well (if one is set).
+#[derive(Clone)]
+pub struct StatusBoard {
+    status: Arc<Mutex<RecorderStatus>>,
+    status_file: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl StatusBoard {
+    pub fn new() -> StatusBoard {
+        StatusBoard {
+            status: Arc::new(Mutex::new(RecorderStatus {
+                started_at: Local::now(),
+                inputs: BTreeMap::new(),
+                encoding_queue_depth: 0,
+                free_space_in_bytes: None,
+                interesting_segments: vec![],
+                stopped_at: None,
+                paused: false,
+            })),
+            status_file: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Keep the state of the recorder in a JSON file, which external monitoring can poll.
+    pub fn set_status_file(&self, path: PathBuf) {
+        *self.status_file.lock().unwrap() = Some(path);
+    }
+
+    /// Write the state to the status file, this is called while the state is locked so the
+    /// changes are written in order.
+    fn store(&self, status: &RecorderStatus) {
+        if let Some(path) = self.status_file.lock().unwrap().as_ref() {
+            if let Err(error) = write_status_file(path, status) {
+                error!(
+                    "Could not update the status file {}. The error was: {}",
+                    path.display(),
+                    error
+                );
+            }
+        }
+    }
+
+    /// Start over with the inputs which are recorded.
+    pub fn set_inputs<'a, I>(&self, input_names: I)
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        let mut status = self.status.lock().unwrap();
+        status.started_at = Local::now();
+        status.inputs = input_names
+            .into_iter()
+            .map(|input_name| (input_name.clone(), InputStatus::default()))
+            .collect();
+        status.interesting_segments.clear();
+        status.stopped_at = None;
+        self.store(&status);
+    }
+
+    /// Get a copy of the current state.
+    pub fn get(&self) -> RecorderStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn update<F>(&self, update: F)
+    where
+        F: FnOnce(&mut RecorderStatus),
+    {
+        let mut status = self.status.lock().unwrap();
+        update(&mut status);
+        self.store(&status);
+    }
+
+    /// Update the state of a single input.
+    pub fn update_input<F>(&self, input_name: &str, update: F)
+    where
+        F: FnOnce(&mut InputStatus),
+    {
+        let mut status = self.status.lock().unwrap();
+        if let Some(input_status) = status.inputs.get_mut(input_name) {
+            update(input_status);
+            self.store(&status);
+        }
+    }
+
+    /// Show the score of a post-processed segment, only the most interesting segments are kept.
+    pub fn add_scored_segment(&self, input_name: &str, file: &str, interestingness: u8) {
+        let mut status = self.status.lock().unwrap();
+        if let Some(input_status) = status.inputs.get_mut(input_name) {
+            input_status.interestingness = Some(interestingness);
+        }
+        status.interesting_segments.push(ScoredSegment {
+            input: input_name.to_string(),
+            file: file.to_string(),
+            interestingness,
+        });
+        // the sort is stable, so the earlier segment wins if two are equally interesting
+        status.interesting_segments.sort_by(|a, b| {
+            compare_interestingness(Some(a.interestingness), Some(b.interestingness))
+        });
+        status
+            .interesting_segments
+            .truncate(MAXIMUM_INTERESTING_SEGMENTS);
+        self.store(&status);
+    }
+}
+
+impl Default for StatusBoard {
+    fn default() -> Self {
+        StatusBoard::new()
+    }
+}
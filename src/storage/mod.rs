@@ -0,0 +1,271 @@
+use core::fmt;
+use std::fs::remove_file;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::archive::layout::Archive;
+use crate::archive::{collect_files, ArchiveReader};
+
+/// The interval in which the size of the archive is determined again, the sizes of the new
+/// recordings are added to the last known size in between.
+const QUOTA_RESCAN_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// What the recorder does if the free space of the data directory drops below the threshold.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LowSpaceAction {
+    /// Stop recording.
+    #[default]
+    Stop,
+
+    /// Keep recording, but do not encode the recordings anymore.
+    SkipEncoding,
+
+    /// Remove the oldest files of the archive until enough space is free again.
+    Purge,
+}
+
+impl fmt::Display for LowSpaceAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LowSpaceAction::Stop => write!(f, "stop"),
+            LowSpaceAction::SkipEncoding => write!(f, "skip_encoding"),
+            LowSpaceAction::Purge => write!(f, "purge"),
+        }
+    }
+}
+
+/// What the recorder does if the recordings exceed the storage quota of the project.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaAction {
+    /// Stop recording.
+    #[default]
+    Stop,
+
+    /// Remove the oldest segments until the recordings fit into the quota again.
+    Rotate,
+}
+
+impl fmt::Display for QuotaAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QuotaAction::Stop => write!(f, "stop"),
+            QuotaAction::Rotate => write!(f, "rotate"),
+        }
+    }
+}
+
+/// The settings for monitoring the free space of the data directory while recording.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StorageConfiguration {
+    /// The free space (in MB) which is required before a segment is started.
+    #[serde(default = "StorageConfiguration::default_minimum_free_space_in_mb")]
+    pub minimum_free_space_in_mb: u64,
+
+    #[serde(default = "StorageConfiguration::default_action")]
+    pub action: LowSpaceAction,
+}
+
+impl StorageConfiguration {
+    fn default_minimum_free_space_in_mb() -> u64 {
+        500
+    }
+
+    fn default_action() -> LowSpaceAction {
+        LowSpaceAction::default()
+    }
+
+    pub fn get_minimum_free_space_in_bytes(&self) -> u64 {
+        self.minimum_free_space_in_mb * 1024 * 1024
+    }
+}
+
+impl Default for StorageConfiguration {
+    fn default() -> Self {
+        StorageConfiguration {
+            minimum_free_space_in_mb: StorageConfiguration::default_minimum_free_space_in_mb(),
+            action: StorageConfiguration::default_action(),
+        }
+    }
+}
+
+/// Get the space (in bytes) which is available to unprivileged users on the file system of the
+/// supplied folder. This is only supported on Unix.
+pub fn get_free_space_in_bytes(folder: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(folder.as_os_str().as_bytes()).ok()?;
+        let mut statistics: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut statistics) } != 0 {
+            return None;
+        }
+        #[allow(clippy::unnecessary_cast)]
+        Some(statistics.f_bavail as u64 * statistics.f_frsize as u64)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = folder;
+        None
+    }
+}
+
+/// Get the files which can be removed to free space, the oldest files first. The trash is emptied
+/// before the previews and the encoded files are removed, the recordings which were not encoded
+/// yet are never removed.
+fn get_purgeable_files(archive: &Archive) -> io::Result<Vec<PathBuf>> {
+    let mut purgeable_files = vec![];
+    for folder in [
+        archive.get_trash_folder(),
+        archive.get_previews_folder(),
+        archive.get_encoded_folder(),
+    ]
+    .iter()
+    {
+        if !folder.is_dir() {
+            continue;
+        }
+        let mut files = vec![];
+        collect_files(folder, &mut files)?;
+        let mut files: Vec<(SystemTime, PathBuf)> = files
+            .into_iter()
+            .map(|file| {
+                let modified = file
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (modified, file)
+            })
+            .collect();
+        files.sort();
+        purgeable_files.extend(files.into_iter().map(|(_, file)| file));
+    }
+    Ok(purgeable_files)
+}
+
+/// Remove the oldest files of the archive until the required space is free. The number of
+/// removed files is returned.
+pub fn purge_oldest_files(archive: &Archive, required_space_in_bytes: u64) -> io::Result<usize> {
+    let mut removed_files = 0;
+    for file in get_purgeable_files(archive)? {
+        match get_free_space_in_bytes(archive.get_root()) {
+            Some(free_space) if free_space >= required_space_in_bytes => break,
+            Some(_) => {}
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "the free space could not be determined",
+                ))
+            }
+        }
+        match remove_file(&file) {
+            Ok(()) => {
+                warn!("Removed {} to free space", file.display());
+                removed_files += 1;
+            }
+            Err(error) => warn!(
+                "Could not remove {} to free space. The error was: {}",
+                file.display(),
+                error
+            ),
+        }
+    }
+    Ok(removed_files)
+}
+
+/// Get the size of all files which belong to the recorded segments of the archive.
+pub fn get_archive_size_in_bytes(archive: &Archive) -> io::Result<u64> {
+    let segments = ArchiveReader::open(archive.get_root())?.get_segments()?;
+    Ok(segments
+        .iter()
+        .flat_map(|segment| segment.get_files())
+        .filter_map(|file| file.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum())
+}
+
+/// Keeps track of the size of the recordings while recording, so the archive does not have to be
+/// scanned before every segment.
+pub struct QuotaTracker {
+    limit_in_bytes: u64,
+    used_in_bytes: u64,
+    last_scan: Instant,
+}
+
+impl QuotaTracker {
+    /// Create a tracker for a quota (in GB) with the current size of the archive.
+    pub fn new(archive: &Archive, max_storage_gb: f64) -> io::Result<QuotaTracker> {
+        Ok(QuotaTracker {
+            limit_in_bytes: (max_storage_gb.max(0.0) * 1024.0 * 1024.0 * 1024.0) as u64,
+            used_in_bytes: get_archive_size_in_bytes(archive)?,
+            last_scan: Instant::now(),
+        })
+    }
+
+    pub fn get_limit_in_bytes(&self) -> u64 {
+        self.limit_in_bytes
+    }
+
+    pub fn get_used_in_bytes(&self) -> u64 {
+        self.used_in_bytes
+    }
+
+    /// Add the size of new recordings.
+    pub fn add(&mut self, size_in_bytes: u64) {
+        self.used_in_bytes += size_in_bytes;
+    }
+
+    /// Check if the recordings exceed the quota. Since the encoding shrinks the recordings after
+    /// they were added, the archive is scanned again before the quota is considered exceeded and
+    /// regularly in between.
+    pub fn is_exceeded(&mut self, archive: &Archive) -> io::Result<bool> {
+        let is_scan_outdated = self.last_scan.elapsed() >= QUOTA_RESCAN_INTERVAL;
+        if is_scan_outdated || self.used_in_bytes > self.limit_in_bytes {
+            self.used_in_bytes = get_archive_size_in_bytes(archive)?;
+            self.last_scan = Instant::now();
+        }
+        Ok(self.used_in_bytes > self.limit_in_bytes)
+    }
+
+    /// Remove the oldest segments until the recordings fit into the quota again. Segments which
+    /// were not encoded yet are only removed if `include_unencoded` is set (e.g. if the recordings
+    /// are not encoded at all), otherwise they are waiting for the encoder. The number of removed
+    /// segments is returned.
+    pub fn rotate(&mut self, archive: &Archive, include_unencoded: bool) -> io::Result<usize> {
+        let encoded_folder = archive.get_encoded_folder();
+        let mut removed_segments = 0;
+        for segment in ArchiveReader::open(archive.get_root())?.get_segments()? {
+            if self.used_in_bytes <= self.limit_in_bytes {
+                break;
+            }
+            let is_encoded = segment
+                .get_files()
+                .iter()
+                .any(|file| file.starts_with(&encoded_folder));
+            if !is_encoded && !include_unencoded {
+                continue;
+            }
+            for file in segment.get_files() {
+                let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                match remove_file(file) {
+                    Ok(()) => self.used_in_bytes = self.used_in_bytes.saturating_sub(size),
+                    Err(error) => warn!(
+                        "Could not remove {} to stay within the quota. The error was: {}",
+                        file.display(),
+                        error
+                    ),
+                }
+            }
+            removed_segments += 1;
+        }
+        Ok(removed_segments)
+    }
+}
@@ -0,0 +1,261 @@
+use core::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::manifest::MANIFEST_TIMESTAMP_FORMAT;
+
+/// The number of time requests used to measure the clock offset, the one with the shortest round
+/// trip is used.
+const TIME_REQUESTS: usize = 8;
+
+/// The minimal time between the handshake and the synchronized start.
+const START_DELAY_IN_MS: i64 = 10_000;
+
+/// The role of a machine during the start synchronization.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncRole {
+    /// Waits for the follower and decides when the recording starts.
+    Leader,
+
+    /// Connects to the leader and starts at the same wall-clock time.
+    Follower,
+}
+
+impl fmt::Display for SyncRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyncRole::Leader => write!(f, "leader"),
+            SyncRole::Follower => write!(f, "follower"),
+        }
+    }
+}
+
+/// The configuration for starting the recordings of two machines at the same time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConfiguration {
+    pub role: SyncRole,
+
+    /// The address the leader listens on or the follower connects to (e.g. `192.168.1.10:4711`).
+    pub address: String,
+
+    /// The time to wait for the other machine before recording is started without it.
+    #[serde(default = "SyncConfiguration::default_timeout_in_seconds")]
+    pub timeout_in_seconds: u64,
+}
+
+impl SyncConfiguration {
+    fn default_timeout_in_seconds() -> u64 {
+        300
+    }
+}
+
+/// The result of the start synchronization which is stored in the session manifest.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SyncInformation {
+    pub role: SyncRole,
+    pub peer: String,
+
+    /// The offset of the leader's clock relative to the clock of this machine.
+    pub offset_in_ms: i64,
+    pub round_trip_in_ms: i64,
+
+    /// The time (on the clock of this machine) the recording was started.
+    pub started_at: String,
+}
+
+fn now_in_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+fn wait_until(timestamp_in_ms: i64) {
+    let remaining = timestamp_in_ms - now_in_ms();
+    if remaining > 0 {
+        sleep(Duration::from_millis(remaining as u64));
+    }
+}
+
+fn format_timestamp(timestamp_in_ms: i64) -> String {
+    match DateTime::from_timestamp_millis(timestamp_in_ms) {
+        Some(timestamp) => timestamp
+            .with_timezone(&Local)
+            .format(MANIFEST_TIMESTAMP_FORMAT)
+            .to_string(),
+        None => timestamp_in_ms.to_string(),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the connection was closed",
+        ));
+    }
+    Ok(line.trim().to_string())
+}
+
+fn accept_follower(configuration: &SyncConfiguration) -> io::Result<TcpStream> {
+    let listener = TcpListener::bind(&configuration.address)?;
+    listener.set_nonblocking(true)?;
+    let deadline = Instant::now() + Duration::from_secs(configuration.timeout_in_seconds);
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                return Ok(stream);
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "no follower connected in time",
+                    ));
+                }
+                sleep(Duration::from_millis(100));
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Wait for the follower, tell it when to start and wait until that time.
+fn synchronize_as_leader(configuration: &SyncConfiguration) -> io::Result<SyncInformation> {
+    info!(
+        "Waiting for a follower to connect on {}",
+        configuration.address
+    );
+    let stream = accept_follower(configuration)?;
+    let peer = stream.peer_addr()?.to_string();
+    stream.set_read_timeout(Some(Duration::from_secs(configuration.timeout_in_seconds)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    // answer the time requests of the follower until it asks for the start time
+    let start_in_ms = loop {
+        match read_line(&mut reader)?.as_str() {
+            "TIME" => writeln!(writer, "{}", now_in_ms())?,
+            "START" => {
+                let start_in_ms = (now_in_ms() + START_DELAY_IN_MS) / 60_000 * 60_000 + 60_000;
+                writeln!(writer, "{}", start_in_ms)?;
+                break start_in_ms;
+            }
+            _ => return Err(invalid_data("unexpected request of the follower")),
+        }
+    };
+
+    // the follower reports the offset it measured, so both manifests contain it
+    let measurement = read_line(&mut reader)?;
+    let mut values = measurement.split_whitespace().skip(1);
+    let follower_offset: i64 = values
+        .next()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    let round_trip: i64 = values
+        .next()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+
+    info!(
+        "Follower {} connected, starting at {}",
+        peer,
+        format_timestamp(start_in_ms)
+    );
+    wait_until(start_in_ms);
+    Ok(SyncInformation {
+        role: SyncRole::Leader,
+        peer,
+        offset_in_ms: -follower_offset,
+        round_trip_in_ms: round_trip,
+        started_at: format_timestamp(now_in_ms()),
+    })
+}
+
+/// Measure the clock offset to the leader and start at the time the leader selected.
+fn synchronize_as_follower(configuration: &SyncConfiguration) -> io::Result<SyncInformation> {
+    info!("Connecting to the leader on {}", configuration.address);
+    let deadline = Instant::now() + Duration::from_secs(configuration.timeout_in_seconds);
+    let stream = loop {
+        match TcpStream::connect(&configuration.address) {
+            Ok(stream) => break stream,
+            Err(error) => {
+                if Instant::now() >= deadline {
+                    return Err(error);
+                }
+                sleep(Duration::from_secs(1));
+            }
+        }
+    };
+    let peer = stream.peer_addr()?.to_string();
+    stream.set_read_timeout(Some(Duration::from_secs(configuration.timeout_in_seconds)))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    // the request with the shortest round trip gives the most accurate offset
+    let mut best_measurement: Option<(i64, i64)> = None;
+    for _ in 0..TIME_REQUESTS {
+        let sent_at = now_in_ms();
+        writeln!(writer, "TIME")?;
+        let leader_time: i64 = read_line(&mut reader)?
+            .parse()
+            .map_err(|_| invalid_data("invalid time of the leader"))?;
+        let received_at = now_in_ms();
+        let round_trip = received_at - sent_at;
+        let offset = leader_time - (sent_at + received_at) / 2;
+        debug!(
+            "Measured an offset of {} ms with a round trip of {} ms",
+            offset, round_trip
+        );
+        if best_measurement.map_or(true, |(_, best_round_trip)| round_trip < best_round_trip) {
+            best_measurement = Some((offset, round_trip));
+        }
+    }
+    let (offset, round_trip) = best_measurement.unwrap_or((0, 0));
+
+    // ask the leader when to start and report the measured offset back
+    writeln!(writer, "START")?;
+    let start_in_ms: i64 = read_line(&mut reader)?
+        .parse()
+        .map_err(|_| invalid_data("invalid start time of the leader"))?;
+    writeln!(writer, "OFFSET {} {}", offset, round_trip)?;
+
+    let local_start_in_ms = start_in_ms - offset;
+    info!(
+        "The clock of the leader {} is {} ms ahead, starting at {}",
+        peer,
+        offset,
+        format_timestamp(local_start_in_ms)
+    );
+    if offset.abs() > 1000 {
+        warn!("The clocks of both machines differ by more than a second, check the time synchronization");
+    }
+    wait_until(local_start_in_ms);
+    Ok(SyncInformation {
+        role: SyncRole::Follower,
+        peer,
+        offset_in_ms: offset,
+        round_trip_in_ms: round_trip,
+        started_at: format_timestamp(now_in_ms()),
+    })
+}
+
+/// Agree with the other machine on a start time at a full minute and wait until it is reached.
+pub fn synchronize_start(configuration: &SyncConfiguration) -> io::Result<SyncInformation> {
+    match configuration.role {
+        SyncRole::Leader => synchronize_as_leader(configuration),
+        SyncRole::Follower => synchronize_as_follower(configuration),
+    }
+}
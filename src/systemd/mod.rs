@@ -0,0 +1,113 @@
+use std::env;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::{debug, warn};
+
+/// The time (in seconds since the epoch) until which the watchdog is pinged, it is moved forward
+/// by the recording loop as long as it makes progress.
+static WATCHDOG_DEADLINE: AtomicU64 = AtomicU64::new(0);
+
+fn get_seconds_since_epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn send_to_socket(socket_path: &str, state: &str) -> io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = UnixDatagram::unbound()?;
+    // a leading `@` marks a socket in the abstract namespace (which only exists on Linux)
+    #[cfg(target_os = "linux")]
+    if let Some(name) = socket_path.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::SocketAddr;
+
+        let address = SocketAddr::from_abstract_name(name)?;
+        return socket.send_to_addr(state.as_bytes(), &address).map(|_| ());
+    }
+    socket.send_to(state.as_bytes(), socket_path).map(|_| ())
+}
+
+#[cfg(not(unix))]
+fn send_to_socket(_: &str, _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "the notifications of systemd are only supported on Unix",
+    ))
+}
+
+/// Send a state change (e.g. `READY=1`) to systemd. Nothing is sent if the process was not
+/// started by systemd with `Type=notify`.
+pub fn notify(state: &str) {
+    let socket_path = match env::var("NOTIFY_SOCKET") {
+        Ok(socket_path) => socket_path,
+        Err(_) => return,
+    };
+    match send_to_socket(&socket_path, state) {
+        Ok(()) => debug!("Sent {} to systemd", state),
+        Err(error) => warn!(
+            "Could not send {} to systemd. The error was: {}",
+            state, error
+        ),
+    }
+}
+
+/// Get the interval in which systemd expects the watchdog pings, if the watchdog is enabled for
+/// this process.
+fn get_watchdog_interval() -> Option<Duration> {
+    // the watchdog might be meant for another process if the variables were inherited
+    if let Ok(process_id) = env::var("WATCHDOG_PID") {
+        if process_id.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|interval| interval.parse::<u64>().ok())
+        .filter(|interval| *interval > 0)
+        .map(Duration::from_micros)
+}
+
+/// Keep the watchdog alive for the supplied duration. If it is not extended again in time, the
+/// pings stop and systemd restarts the hung process.
+pub fn extend_watchdog(duration: Duration) {
+    WATCHDOG_DEADLINE.store(
+        get_seconds_since_epoch() + duration.as_secs(),
+        Ordering::SeqCst,
+    );
+}
+
+/// Start pinging the watchdog of systemd in the background (if it is enabled) until the deadline
+/// set with `extend_watchdog` passes.
+pub fn start_watchdog(initial_duration: Duration) {
+    let interval = match get_watchdog_interval() {
+        Some(interval) => interval,
+        None => return,
+    };
+    extend_watchdog(initial_duration);
+    debug!(
+        "Pinging the watchdog of systemd every {} ms",
+        interval.as_millis() / 2
+    );
+    spawn(move || {
+        let mut is_reported = false;
+        loop {
+            if get_seconds_since_epoch() <= WATCHDOG_DEADLINE.load(Ordering::SeqCst) {
+                notify("WATCHDOG=1");
+                is_reported = false;
+            } else if !is_reported {
+                warn!(
+                    "The recording does not make any progress anymore, stopping the watchdog pings"
+                );
+                is_reported = true;
+            }
+            sleep(interval / 2);
+        }
+    });
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+/// The bytes every block on the tee starts with.
+pub const TEE_MAGIC: &[u8; 4] = b"SLPC";
+
+/// The version of the framing format of the blocks on the tee.
+pub const TEE_VERSION: u16 = 1;
+
+/// The size of the fixed part of the header of a block (without the input name).
+const HEADER_SIZE: usize = 34;
+
+/// The configuration of the live tee, which publishes the captured samples on a Unix socket so
+/// external programs can analyze them while they are recorded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TeeConfiguration {
+    /// The path of the Unix socket the clients connect to.
+    pub socket: String,
+
+    /// The number of blocks which are queued for a client before newer blocks are dropped for it.
+    #[serde(default = "TeeConfiguration::default_maximum_queued_blocks")]
+    pub maximum_queued_blocks: usize,
+}
+
+impl TeeConfiguration {
+    fn default_maximum_queued_blocks() -> usize {
+        64
+    }
+}
+
+/// A block of captured samples as it is sent on the tee. All numbers are little-endian:
+///
+/// | Offset | Size | Field                                                         |
+/// |--------|------|---------------------------------------------------------------|
+/// | 0      | 4    | the magic bytes `SLPC`                                        |
+/// | 4      | 2    | the version of the format (currently 1)                       |
+/// | 6      | 2    | the number of channels                                        |
+/// | 8      | 4    | the sample rate in Hz                                         |
+/// | 12     | 8    | the sequence number of the block for its input                |
+/// | 20     | 8    | the time the block was captured (µs since the Unix epoch)     |
+/// | 28     | 4    | the number of frames                                          |
+/// | 32     | 2    | the length `n` of the input name in bytes                     |
+/// | 34     | n    | the name of the input (UTF-8)                                 |
+/// | 34 + n |      | the interleaved samples (signed 16 bit)                       |
+///
+/// The sequence numbers of an input have gaps if blocks were dropped for a client, since it did
+/// not read them fast enough.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeeBlock {
+    pub input: String,
+    pub channels: u16,
+    pub samples_per_second: u32,
+    pub sequence: u64,
+    pub captured_at_in_microseconds: u64,
+    pub samples: Vec<i16>,
+}
+
+impl TeeBlock {
+    /// Encode the block in the framing format of the tee.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let frames = self.samples.len() / usize::from(self.channels.max(1));
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.input.len() + self.samples.len() * 2);
+        bytes.extend_from_slice(TEE_MAGIC);
+        bytes.extend_from_slice(&TEE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.channels.to_le_bytes());
+        bytes.extend_from_slice(&self.samples_per_second.to_le_bytes());
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.extend_from_slice(&self.captured_at_in_microseconds.to_le_bytes());
+        bytes.extend_from_slice(&(frames as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.input.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(self.input.as_bytes());
+        for sample in &self.samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Read the next block from a connection to the tee. This is what a client of the tee uses,
+    /// e.g. with a `std::os::unix::net::UnixStream` connected to the configured socket.
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<TeeBlock> {
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != TEE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the block does not start with the magic bytes of the tee",
+            ));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != TEE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the version {} of the tee format is not supported", version),
+            ));
+        }
+        let read_u64 = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&header[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let channels = u16::from_le_bytes([header[6], header[7]]);
+        let samples_per_second = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        let sequence = read_u64(12);
+        let captured_at_in_microseconds = read_u64(20);
+        let frames = u32::from_le_bytes([header[28], header[29], header[30], header[31]]);
+        let input_length = u16::from_le_bytes([header[32], header[33]]);
+
+        let mut input = vec![0u8; usize::from(input_length)];
+        reader.read_exact(&mut input)?;
+        let input = String::from_utf8(input)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let mut sample_bytes = vec![0u8; frames as usize * usize::from(channels) * 2];
+        reader.read_exact(&mut sample_bytes)?;
+        let samples = sample_bytes
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+
+        Ok(TeeBlock {
+            input,
+            channels,
+            samples_per_second,
+            sequence,
+            captured_at_in_microseconds,
+            samples,
+        })
+    }
+}
+
+/// The clients of the tee and the next sequence number of every input.
+#[derive(Default)]
+struct TeeState {
+    clients: Vec<SyncSender<Arc<Vec<u8>>>>,
+    sequences: HashMap<String, u64>,
+}
+
+/// The socket file of the tee, it is removed when the server and all of its sources are dropped.
+struct TeeSocket {
+    path: PathBuf,
+}
+
+impl Drop for TeeSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The server side of the tee. The captures publish their samples on it and it forwards them to
+/// all connected clients. A client which does not keep up misses blocks, the recording is never
+/// slowed down by it.
+#[derive(Clone)]
+pub struct TeeServer {
+    state: Arc<Mutex<TeeState>>,
+    _socket: Arc<TeeSocket>,
+}
+
+impl TeeServer {
+    /// Listen on the configured socket and accept clients in the background. A stale socket
+    /// of a previous run is replaced, any other file at the path is left untouched and an error is
+    /// returned.
+    pub fn start(configuration: &TeeConfiguration) -> io::Result<TeeServer> {
+        let state = Arc::new(Mutex::new(TeeState::default()));
+        listen(
+            &configuration.socket,
+            configuration.maximum_queued_blocks,
+            state.clone(),
+        )?;
+        info!("Publishing the captured audio on {}", configuration.socket);
+        Ok(TeeServer {
+            state,
+            _socket: Arc::new(TeeSocket {
+                path: PathBuf::from(&configuration.socket),
+            }),
+        })
+    }
+
+    /// Get the handle the capture of an input publishes its samples with.
+    pub fn get_source(&self, input: &str) -> TeeSource {
+        TeeSource {
+            server: self.clone(),
+            input: input.to_string(),
+        }
+    }
+}
+
+/// The handle a capture publishes its samples on the tee with.
+#[derive(Clone)]
+pub struct TeeSource {
+    server: TeeServer,
+    input: String,
+}
+
+impl TeeSource {
+    /// Send the captured (interleaved) samples to all clients of the tee.
+    pub fn publish(&self, channels: u16, samples_per_second: u32, samples: &[i16]) {
+        let mut state = self.server.state.lock().unwrap();
+        if state.clients.is_empty() {
+            return;
+        }
+        let sequence = state.sequences.entry(self.input.clone()).or_insert(0);
+        let block = TeeBlock {
+            input: self.input.clone(),
+            channels,
+            samples_per_second,
+            sequence: *sequence,
+            captured_at_in_microseconds: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_micros() as u64)
+                .unwrap_or(0),
+            samples: samples.to_vec(),
+        };
+        *sequence += 1;
+
+        let bytes = Arc::new(block.to_bytes());
+        state
+            .clients
+            .retain(|client| match client.try_send(bytes.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
+
+/// Forward the queued blocks to a client until it disconnects.
+fn serve_client<W: Write + Send + 'static>(
+    mut client: W,
+    maximum_queued_blocks: usize,
+    state: &Mutex<TeeState>,
+) {
+    let (sender, receiver) = sync_channel::<Arc<Vec<u8>>>(maximum_queued_blocks.max(1));
+    state.lock().unwrap().clients.push(sender);
+    spawn(move || {
+        for bytes in receiver {
+            if let Err(error) = client.write_all(&bytes) {
+                debug!("A client of the tee disconnected: {}", error);
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+fn listen(
+    socket_path: &str,
+    maximum_queued_blocks: usize,
+    state: Arc<Mutex<TeeState>>,
+) -> io::Result<()> {
+    use std::fs::{remove_file, symlink_metadata};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // a bad path in the configuration must not delete a file which is not a socket
+    match symlink_metadata(socket_path) {
+        Ok(metadata) if metadata.file_type().is_socket() => remove_file(socket_path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", socket_path),
+            ))
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => return Err(error),
+    }
+    let listener = UnixListener::bind(socket_path)?;
+    spawn(move || {
+        for client in listener.incoming() {
+            match client {
+                Ok(client) => {
+                    debug!("A client connected to the tee");
+                    serve_client(client, maximum_queued_blocks, &state);
+                }
+                Err(error) => warn!("Could not accept a client of the tee: {}", error),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_: &str, _: usize, _: Arc<Mutex<TeeState>>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "the tee is only supported on Unix",
+    ))
+}
@@ -0,0 +1,258 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "otlp")]
+use tracing::error;
+use tracing::field::{Field, Visit};
+#[cfg(not(feature = "otlp"))]
+use tracing::warn;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+#[cfg(feature = "dashboard")]
+use crate::dashboard::add_log_line;
+
+/// The settings for exporting the spans of the recorder (the sessions, segments, devices, encoding
+/// jobs and uploads) to an OpenTelemetry collector.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TelemetryConfiguration {
+    /// The OTLP/HTTP endpoint the spans are sent to (e.g. `http://localhost:4318/v1/traces`).
+    pub otlp_endpoint: String,
+
+    /// The name the spans are reported with, which tells several recorders apart.
+    #[serde(default = "TelemetryConfiguration::default_service_name")]
+    pub service_name: String,
+}
+
+impl TelemetryConfiguration {
+    fn default_service_name() -> String {
+        "schlaflosigkeit".to_string()
+    }
+}
+
+/// The destination of the log messages.
+pub enum LogOutput {
+    Stdout,
+    Stderr,
+    File(PathBuf),
+    #[cfg(feature = "dashboard")]
+    Dashboard,
+    /// A JSON object per line on stdout, which log collectors of containers can parse.
+    Json,
+}
+
+/// Where the formatted log lines are written to.
+enum LogWriter {
+    Stdout,
+    Stderr,
+    File(Mutex<File>),
+    #[cfg(feature = "dashboard")]
+    Dashboard,
+}
+
+/// Collects the message of an event, the other fields are appended to it as `key=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push(format!("{}={}", field.name(), value));
+        }
+    }
+}
+
+impl MessageVisitor {
+    fn get_message(&self) -> String {
+        if self.fields.is_empty() {
+            return self.message.clone();
+        }
+        format!("{} {}", self.message, self.fields.join(" "))
+    }
+}
+
+/// Writes the events as the log lines of the recorder (`[date][time][target][LEVEL] message`) or
+/// as JSON objects, the spans are not shown in the lines.
+struct LogLayer {
+    is_json: bool,
+    writer: LogWriter,
+}
+
+impl<S> Layer<S> for LogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, context: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+
+        #[cfg(feature = "dashboard")]
+        if let LogWriter::Dashboard = self.writer {
+            add_log_line(visitor.get_message());
+            return;
+        }
+
+        let line = if self.is_json {
+            // the names of the spans (e.g. `session/segment/device`) tell the lines of the
+            // concurrently recorded inputs apart
+            let spans = context
+                .event_scope(event)
+                .map(|scope| {
+                    scope
+                        .from_root()
+                        .map(|span| span.name())
+                        .collect::<Vec<&str>>()
+                        .join("/")
+                })
+                .unwrap_or_default();
+            let mut line = serde_json::json!({
+                "timestamp": Local::now().to_rfc3339(),
+                "level": metadata.level().to_string(),
+                "target": metadata.target(),
+                "message": visitor.get_message(),
+            });
+            if !spans.is_empty() {
+                line["spans"] = serde_json::Value::from(spans);
+            }
+            line.to_string()
+        } else {
+            format!(
+                "{}[{}][{}] {}",
+                Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                metadata.target(),
+                metadata.level(),
+                visitor.get_message()
+            )
+        };
+        let _ = match &self.writer {
+            LogWriter::Stdout => writeln!(io::stdout().lock(), "{}", line),
+            LogWriter::Stderr => writeln!(io::stderr().lock(), "{}", line),
+            LogWriter::File(file) => writeln!(file.lock().unwrap(), "{}", line),
+            #[cfg(feature = "dashboard")]
+            LogWriter::Dashboard => Ok(()),
+        };
+    }
+}
+
+/// Flushes the exported spans when it is dropped at the end of the program.
+pub struct TelemetryGuard {
+    #[cfg(feature = "otlp")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            let _ = tracer_provider.shutdown();
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn create_tracer_provider(
+    config: &TelemetryConfiguration,
+) -> Result<opentelemetry_sdk::trace::SdkTracerProvider, String> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(config.otlp_endpoint.as_str())
+        .build()
+        .map_err(|error| error.to_string())?;
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build())
+}
+
+/// Initialize the log output and (if configured and built with the `otlp` feature) the export of
+/// the spans. The returned guard has to be kept until the program ends.
+pub fn initialize_telemetry(
+    log_output: LogOutput,
+    config: Option<&TelemetryConfiguration>,
+) -> TelemetryGuard {
+    let is_json = matches!(log_output, LogOutput::Json);
+    let writer = match log_output {
+        LogOutput::Stdout | LogOutput::Json => LogWriter::Stdout,
+        LogOutput::Stderr => LogWriter::Stderr,
+        #[cfg(feature = "dashboard")]
+        LogOutput::Dashboard => LogWriter::Dashboard,
+        LogOutput::File(path) => match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => LogWriter::File(Mutex::new(file)),
+            Err(error) => panic!(
+                "Could not open the log file {}: {}. Terminating!",
+                path.display(),
+                error
+            ),
+        },
+    };
+
+    // the exporter is created first, its errors are logged once the log output is ready
+    #[cfg(feature = "otlp")]
+    let (tracer_provider, exporter_error) = match config.map(create_tracer_provider) {
+        Some(Ok(tracer_provider)) => (Some(tracer_provider), None),
+        Some(Err(error)) => (None, Some(error)),
+        None => (None, None),
+    };
+    #[cfg(feature = "otlp")]
+    let otlp_layer = tracer_provider.as_ref().map(|tracer_provider| {
+        use opentelemetry::trace::TracerProvider;
+
+        tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("schlaflosigkeit"))
+    });
+    #[cfg(not(feature = "otlp"))]
+    let otlp_layer: Option<tracing_subscriber::layer::Identity> = None;
+
+    let subscriber = tracing_subscriber::registry()
+        .with(LevelFilter::DEBUG)
+        .with(LogLayer { is_json, writer })
+        .with(otlp_layer);
+    if subscriber.try_init().is_err() {
+        panic!("Could not initialize the logging framework. Terminating!");
+    }
+
+    #[cfg(feature = "otlp")]
+    if let Some(error) = exporter_error {
+        error!(
+            "Could not start the export of the spans. The error was: {}",
+            error
+        );
+    }
+    #[cfg(not(feature = "otlp"))]
+    if config.is_some() {
+        warn!("The spans can not be exported since the otlp feature is not enabled in this build");
+    }
+    TelemetryGuard {
+        #[cfg(feature = "otlp")]
+        tracer_provider,
+    }
+}
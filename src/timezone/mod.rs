@@ -0,0 +1,476 @@
+use core::fmt;
+use std::env;
+use std::fs::read;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use chrono::{
+    DateTime, Datelike, Duration as OldDuration, FixedOffset, Local, NaiveDate, NaiveDateTime,
+    TimeZone, Utc, Weekday,
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The folder the time zone database is read from if `TZDIR` is not set.
+const DEFAULT_ZONEINFO_FOLDER: &str = "/usr/share/zoneinfo";
+
+/// The offset (in seconds) of the daylight saving time to the standard time if a rule does not
+/// name it.
+const DEFAULT_DAYLIGHT_SAVING_IN_SECONDS: i32 = 3600;
+
+/// The time of the day (in seconds) a rule switches to or from daylight saving time if it does
+/// not name it.
+const DEFAULT_RULE_TIME_IN_SECONDS: i64 = 2 * 3600;
+
+lazy_static! {
+    static ref ACTIVE_TIMEZONE: RwLock<TimestampTimezone> = RwLock::new(TimestampTimezone::Local);
+}
+
+/// A day of the year a rule switches at, given as the n-th weekday (0 is Sunday) of a month. The
+/// fifth week is the last one of the month.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RuleDate {
+    month: u32,
+    week: u8,
+    weekday: u32,
+}
+
+impl RuleDate {
+    fn get_date(&self, year: i32) -> Option<NaiveDate> {
+        let weekday = (0..self.weekday).fold(Weekday::Sun, |weekday, _| weekday.succ());
+        (1..=self.week)
+            .rev()
+            .find_map(|week| NaiveDate::from_weekday_of_month_opt(year, self.month, weekday, week))
+    }
+}
+
+/// The daylight saving time of a POSIX time zone rule (e.g. `CET-1CEST,M3.5.0,M10.5.0/3`).
+#[derive(Debug, Clone, PartialEq)]
+struct DaylightSavingRule {
+    offset: i32,
+    start: (RuleDate, i64),
+    end: (RuleDate, i64),
+}
+
+/// The rule of a time zone for the times after the last transition listed in its file.
+#[derive(Debug, Clone, PartialEq)]
+struct PosixRule {
+    standard_offset: i32,
+    daylight_saving: Option<DaylightSavingRule>,
+}
+
+/// Reads the parts of a POSIX time zone rule.
+struct RuleReader<'a> {
+    rest: &'a str,
+}
+
+impl<'a> RuleReader<'a> {
+    fn skip(&mut self, character: char) -> bool {
+        match self.rest.strip_prefix(character) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn read_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let end = self
+            .rest
+            .find(|character| !predicate(character))
+            .unwrap_or(self.rest.len());
+        let (value, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        value
+    }
+
+    /// Read the name of a time (e.g. `CET` or `<+03>`).
+    fn read_name(&mut self) -> Option<&'a str> {
+        let name = if self.skip('<') {
+            let name = self.read_while(|character| character != '>');
+            if !self.skip('>') {
+                return None;
+            }
+            name
+        } else {
+            self.read_while(|character| character.is_ascii_alphabetic())
+        };
+        Some(name).filter(|name| !name.is_empty())
+    }
+
+    fn read_number(&mut self) -> Option<i64> {
+        self.read_while(|character| character.is_ascii_digit())
+            .parse()
+            .ok()
+    }
+
+    /// Read a signed time like `-1`, `5:30` or `+25:00:00` as seconds.
+    fn read_time(&mut self) -> Option<i64> {
+        let sign = if self.skip('-') {
+            -1
+        } else {
+            self.skip('+');
+            1
+        };
+        let mut seconds = self.read_number()? * 3600;
+        if self.skip(':') {
+            seconds += self.read_number()? * 60;
+            if self.skip(':') {
+                seconds += self.read_number()?;
+            }
+        }
+        Some(sign * seconds)
+    }
+
+    /// Read a switch like `M3.5.0/3`, the other formats of the days are not used by the rules in
+    /// the time zone database.
+    fn read_switch(&mut self) -> Option<(RuleDate, i64)> {
+        if !self.skip(',') || !self.skip('M') {
+            return None;
+        }
+        let month = self.read_number()?;
+        self.skip('.').then_some(())?;
+        let week = self.read_number()?;
+        self.skip('.').then_some(())?;
+        let weekday = self.read_number()?;
+        if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+            return None;
+        }
+        let time = if self.skip('/') {
+            self.read_time()?
+        } else {
+            DEFAULT_RULE_TIME_IN_SECONDS
+        };
+        Some((
+            RuleDate {
+                month: month as u32,
+                week: week as u8,
+                weekday: weekday as u32,
+            },
+            time,
+        ))
+    }
+}
+
+impl PosixRule {
+    fn parse(rule: &str) -> Option<PosixRule> {
+        let mut reader = RuleReader { rest: rule };
+        reader.read_name()?;
+
+        // the offsets of POSIX rules are the time to add to get UTC, so they are inverted
+        let standard_offset = -reader.read_time()? as i32;
+        if reader.rest.is_empty() {
+            return Some(PosixRule {
+                standard_offset,
+                daylight_saving: None,
+            });
+        }
+        reader.read_name()?;
+        let offset = if reader.rest.starts_with(',') {
+            standard_offset + DEFAULT_DAYLIGHT_SAVING_IN_SECONDS
+        } else {
+            -reader.read_time()? as i32
+        };
+        let start = reader.read_switch()?;
+        let end = reader.read_switch()?;
+        if !reader.rest.is_empty() {
+            return None;
+        }
+        Some(PosixRule {
+            standard_offset,
+            daylight_saving: Some(DaylightSavingRule { offset, start, end }),
+        })
+    }
+
+    /// Get the offset (in seconds) to UTC at a time (in seconds since the epoch).
+    fn get_offset(&self, timestamp: i64) -> i32 {
+        let daylight_saving = match &self.daylight_saving {
+            Some(daylight_saving) => daylight_saving,
+            None => return self.standard_offset,
+        };
+        let year = match DateTime::from_timestamp(timestamp + i64::from(self.standard_offset), 0) {
+            Some(time) => time.year(),
+            None => return self.standard_offset,
+        };
+
+        // the switches are given in the local time which was valid before them
+        let get_switch = |(date, time): (RuleDate, i64), offset: i32| {
+            date.get_date(year).map(|date| {
+                date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() + time - i64::from(offset)
+            })
+        };
+        let (start, end) = match (
+            get_switch(daylight_saving.start, self.standard_offset),
+            get_switch(daylight_saving.end, daylight_saving.offset),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return self.standard_offset,
+        };
+        let is_daylight_saving = if start < end {
+            start <= timestamp && timestamp < end
+        } else {
+            // the daylight saving time spans the turn of the year (southern hemisphere)
+            timestamp >= start || timestamp < end
+        };
+        if is_daylight_saving {
+            daylight_saving.offset
+        } else {
+            self.standard_offset
+        }
+    }
+}
+
+/// The offsets to UTC of a time zone, as read from its file in the time zone database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneInfo {
+    /// The offset before the first transition.
+    initial_offset: i32,
+
+    /// The times (in seconds since the epoch) the offset changes at and the new offsets.
+    transitions: Vec<(i64, i32)>,
+
+    /// The rule for the times after the last transition.
+    rule: Option<PosixRule>,
+}
+
+/// The counts of the header of a TZif file.
+struct TzifHeader {
+    version: u8,
+    utc_indicator_count: usize,
+    standard_indicator_count: usize,
+    leap_second_count: usize,
+    transition_count: usize,
+    type_count: usize,
+    character_count: usize,
+}
+
+impl TzifHeader {
+    const LENGTH: usize = 44;
+
+    fn parse(data: &[u8]) -> Option<TzifHeader> {
+        if data.len() < TzifHeader::LENGTH || &data[..4] != b"TZif" {
+            return None;
+        }
+        let count = |index: usize| {
+            let start = 20 + 4 * index;
+            u32::from_be_bytes([
+                data[start],
+                data[start + 1],
+                data[start + 2],
+                data[start + 3],
+            ]) as usize
+        };
+        Some(TzifHeader {
+            version: data[4],
+            utc_indicator_count: count(0),
+            standard_indicator_count: count(1),
+            leap_second_count: count(2),
+            transition_count: count(3),
+            type_count: count(4),
+            character_count: count(5),
+        })
+    }
+
+    /// Get the length of the data block which follows the header.
+    fn get_data_length(&self, time_size: usize) -> usize {
+        self.transition_count * (time_size + 1)
+            + self.type_count * 6
+            + self.character_count
+            + self.leap_second_count * (time_size + 4)
+            + self.standard_indicator_count
+            + self.utc_indicator_count
+    }
+}
+
+impl ZoneInfo {
+    /// Read the contents of a TZif file (see RFC 8536). The 64 bit data of version 2 and later
+    /// is preferred over the 32 bit data.
+    fn parse(data: &[u8]) -> Option<ZoneInfo> {
+        let mut header = TzifHeader::parse(data)?;
+        let mut start = TzifHeader::LENGTH;
+        let mut time_size = 4;
+        if header.version >= b'2' {
+            start += header.get_data_length(4);
+            header = TzifHeader::parse(data.get(start..)?)?;
+            start += TzifHeader::LENGTH;
+            time_size = 8;
+        }
+        let block = data.get(start..start + header.get_data_length(time_size))?;
+
+        let types_start = header.transition_count * (time_size + 1);
+        let offsets: Vec<i32> = block[types_start..types_start + header.type_count * 6]
+            .chunks_exact(6)
+            .map(|info| i32::from_be_bytes([info[0], info[1], info[2], info[3]]))
+            .collect();
+        let mut transitions = vec![];
+        for index in 0..header.transition_count {
+            let time_bytes = &block[index * time_size..(index + 1) * time_size];
+            let time = if time_size == 8 {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(time_bytes);
+                i64::from_be_bytes(bytes)
+            } else {
+                i64::from(i32::from_be_bytes([
+                    time_bytes[0],
+                    time_bytes[1],
+                    time_bytes[2],
+                    time_bytes[3],
+                ]))
+            };
+            let type_index = usize::from(block[header.transition_count * time_size + index]);
+            transitions.push((time, *offsets.get(type_index)?));
+        }
+
+        // the footer of version 2 and later contains the rule for the following times
+        let rule = if time_size == 8 {
+            let footer = String::from_utf8_lossy(&data[start + block.len()..]).to_string();
+            PosixRule::parse(footer.trim_matches('\n'))
+        } else {
+            None
+        };
+        Some(ZoneInfo {
+            initial_offset: *offsets.first()?,
+            transitions,
+            rule,
+        })
+    }
+
+    /// Load a time zone (e.g. `Europe/Berlin`) from the time zone database of the system.
+    pub fn load(name: &str) -> Result<ZoneInfo, String> {
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|part| part == "..") {
+            return Err(format!("'{}' is not the name of a time zone", name));
+        }
+        let folder = env::var_os("TZDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ZONEINFO_FOLDER));
+        let path = folder.join(name);
+        let data = read(&path).map_err(|error| {
+            format!(
+                "the time zone '{}' could not be read from {} ({})",
+                name,
+                path.display(),
+                error
+            )
+        })?;
+        ZoneInfo::parse(&data)
+            .ok_or_else(|| format!("{} is not a valid time zone file", path.display()))
+    }
+
+    /// Get the offset (in seconds) to UTC at a time (in seconds since the epoch).
+    pub fn get_offset(&self, timestamp: i64) -> i32 {
+        let index = self
+            .transitions
+            .partition_point(|(time, _)| *time <= timestamp);
+        match (index, &self.rule) {
+            (0, _) => self.initial_offset,
+            (index, Some(rule)) if index == self.transitions.len() => rule.get_offset(timestamp),
+            (index, _) => self.transitions[index - 1].1,
+        }
+    }
+}
+
+/// The time zone the file names, the subfolders and the manifests of the recordings use.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum TimestampTimezone {
+    /// The time zone of the system, whose daylight saving time repeats an hour every autumn.
+    #[default]
+    Local,
+
+    /// Coordinated Universal Time, which never jumps.
+    Utc,
+
+    /// A time zone of the time zone database (e.g. `Europe/Berlin`).
+    Named(String, ZoneInfo),
+}
+
+impl TimestampTimezone {
+    /// Get the offset to UTC at a time.
+    fn get_offset(&self, time: &DateTime<Utc>) -> FixedOffset {
+        let seconds = match self {
+            TimestampTimezone::Local => time.with_timezone(&Local).offset().local_minus_utc(),
+            TimestampTimezone::Utc => 0,
+            TimestampTimezone::Named(_, zone_info) => zone_info.get_offset(time.timestamp()),
+        };
+        FixedOffset::east_opt(seconds).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+impl FromStr for TimestampTimezone {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "local" => Ok(TimestampTimezone::Local),
+            "utc" | "UTC" => Ok(TimestampTimezone::Utc),
+            name => Ok(TimestampTimezone::Named(
+                name.to_string(),
+                ZoneInfo::load(name)?,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TimestampTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimestampTimezone::Local => write!(f, "local"),
+            TimestampTimezone::Utc => write!(f, "utc"),
+            TimestampTimezone::Named(name, _) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl Serialize for TimestampTimezone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimestampTimezone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Use the time zone of a project for naming new recordings and for reading the start time from
+/// the names of the recordings.
+pub fn set_timestamp_timezone(timezone: TimestampTimezone) {
+    *ACTIVE_TIMEZONE.write().unwrap() = timezone;
+}
+
+/// Convert a time to the time zone the recordings are named in.
+pub fn to_timestamp_timezone(time: DateTime<Local>) -> DateTime<FixedOffset> {
+    let time = time.with_timezone(&Utc);
+    time.with_timezone(&ACTIVE_TIMEZONE.read().unwrap().get_offset(&time))
+}
+
+/// Get the time a wall-clock time in the time zone the recordings are named in (e.g. the start
+/// time in a file name) refers to. If the time is ambiguous because the clocks were set back, the
+/// earlier time is returned. `None` is returned if the time was skipped by the time zone.
+pub fn from_timestamp_timezone(time: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+    let timezone = ACTIVE_TIMEZONE.read().unwrap();
+    if let TimestampTimezone::Local = *timezone {
+        return Local
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|time| time.fixed_offset());
+    }
+
+    // the offsets just before and after the time are tried, so both sides of a switch are found
+    let as_utc = time.and_utc();
+    let mut candidates: Vec<DateTime<FixedOffset>> = [-1, 1]
+        .iter()
+        .map(|days| timezone.get_offset(&(as_utc + OldDuration::days(*days))))
+        .chain(std::iter::once(timezone.get_offset(&as_utc)))
+        .filter_map(|offset| offset.from_local_datetime(&time).single())
+        .filter(|candidate| {
+            timezone.get_offset(&candidate.with_timezone(&Utc)) == *candidate.offset()
+        })
+        .collect();
+    candidates.sort();
+    candidates.into_iter().next()
+}
@@ -0,0 +1,425 @@
+use std::cmp::Ordering as VersionOrdering;
+use std::env::{args_os, consts, current_exe};
+use std::fs::{remove_file, rename};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+/// The version of the running binary.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The time a single request to GitHub may take.
+const REQUEST_TIMEOUT_IN_SECONDS: u64 = 600;
+
+/// The settings for updating the binary from the releases on GitHub.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateConfiguration {
+    /// The GitHub repository (`owner/name`) the releases are published in.
+    #[serde(default = "UpdateConfiguration::default_repository")]
+    pub repository: String,
+
+    /// The name of the release asset for this machine, the name is derived from the architecture
+    /// and the operating system if none is set.
+    #[serde(default = "UpdateConfiguration::default_asset")]
+    pub asset: Option<String>,
+
+    /// The minisign public key the release assets are signed with. Only the checksum is verified
+    /// if none is set, which has to be confirmed when updating and rules out automatic updates.
+    #[serde(default = "UpdateConfiguration::default_public_key")]
+    pub public_key: Option<String>,
+
+    /// Check for updates while recording in the background and install them between two segments.
+    #[serde(default = "UpdateConfiguration::default_automatic")]
+    pub automatic: bool,
+
+    /// The interval in which the recorder in the background checks for updates.
+    #[serde(default = "UpdateConfiguration::default_check_interval_in_hours")]
+    pub check_interval_in_hours: u64,
+}
+
+impl UpdateConfiguration {
+    fn default_repository() -> String {
+        "flying7eleven/insomnia-rs".to_string()
+    }
+
+    fn default_asset() -> Option<String> {
+        None
+    }
+
+    fn default_public_key() -> Option<String> {
+        None
+    }
+
+    fn default_automatic() -> bool {
+        false
+    }
+
+    fn default_check_interval_in_hours() -> u64 {
+        24
+    }
+
+    /// Check if the settings can be used. Releases which are installed without anyone watching
+    /// have to be signed, a checksum from the same release only shows that the download is complete.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.automatic && self.public_key.is_none() {
+            return Err(
+                "automatic updates require a public_key to verify the signature of the releases"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Get the name of the release asset for this machine.
+    pub fn get_asset(&self) -> String {
+        self.asset
+            .clone()
+            .unwrap_or_else(|| format!("schlaflosigkeit-{}-{}", consts::ARCH, consts::OS))
+    }
+}
+
+impl Default for UpdateConfiguration {
+    fn default() -> Self {
+        UpdateConfiguration {
+            repository: UpdateConfiguration::default_repository(),
+            asset: UpdateConfiguration::default_asset(),
+            public_key: UpdateConfiguration::default_public_key(),
+            automatic: UpdateConfiguration::default_automatic(),
+            check_interval_in_hours: UpdateConfiguration::default_check_interval_in_hours(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GitHubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Deserialize)]
+struct GitHubRelease {
+    tag_name: String,
+    assets: Vec<GitHubAsset>,
+}
+
+/// A release with a binary for this machine.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub version: String,
+    binary_url: String,
+    checksum_url: String,
+    signature_url: Option<String>,
+}
+
+/// Compare two versions (e.g. `v0.4.0` and `0.10.1`) by their numeric components.
+fn compare_versions(first: &str, second: &str) -> VersionOrdering {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split('.')
+            .map(|component| {
+                component
+                    .chars()
+                    .take_while(|character| character.is_ascii_digit())
+                    .collect::<String>()
+                    .parse()
+                    .unwrap_or(0)
+            })
+            .collect()
+    };
+    parse(first).cmp(&parse(second))
+}
+
+/// Run curl with the supplied arguments and get its output.
+fn run_curl(arguments: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .arg("--fail")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--location")
+        .arg("--max-time")
+        .arg(REQUEST_TIMEOUT_IN_SECONDS.to_string())
+        .args(arguments)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| format!("could not run curl: {}", error))?;
+    if !output.status.success() {
+        return Err(format!(
+            "curl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Get the latest release if it is newer than the running binary and has a binary (with a
+/// checksum) for this machine.
+pub fn check_for_update(config: &UpdateConfiguration) -> Result<Option<Release>, String> {
+    let url = format!(
+        "https://api.github.com/repos/{}/releases/latest",
+        config.repository
+    );
+    let response = run_curl(&["--header", "Accept: application/vnd.github+json", &url])?;
+    let release: GitHubRelease = serde_json::from_slice(&response)
+        .map_err(|error| format!("could not parse the release: {}", error))?;
+    if compare_versions(&release.tag_name, CURRENT_VERSION) != VersionOrdering::Greater {
+        return Ok(None);
+    }
+
+    let asset = config.get_asset();
+    let get_url = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|release_asset| release_asset.name == name)
+            .map(|release_asset| release_asset.browser_download_url.clone())
+    };
+    let binary_url = get_url(&asset).ok_or_else(|| {
+        format!(
+            "the release {} has no binary named {}",
+            release.tag_name, asset
+        )
+    })?;
+    let checksum_url = get_url(&format!("{}.sha256", asset)).ok_or_else(|| {
+        format!(
+            "the release {} has no checksum for {}",
+            release.tag_name, asset
+        )
+    })?;
+    let signature_url = get_url(&format!("{}.minisig", asset));
+    if config.public_key.is_some() && signature_url.is_none() {
+        return Err(format!(
+            "the release {} has no signature for {}",
+            release.tag_name, asset
+        ));
+    }
+    Ok(Some(Release {
+        version: release.tag_name.trim_start_matches('v').to_string(),
+        binary_url,
+        checksum_url,
+        signature_url,
+    }))
+}
+
+/// Get the SHA-256 checksum of a file with sha256sum.
+fn get_checksum(file: &Path) -> Result<String, String> {
+    let output = Command::new("sha256sum")
+        .arg(file)
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|error| format!("could not run sha256sum: {}", error))?;
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()
+        .filter(|_| output.status.success())
+        .map(|checksum| checksum.to_lowercase())
+        .ok_or_else(|| format!("could not compute the checksum of {}", file.display()))
+}
+
+/// Verify the downloaded binary against the checksum (and the signature, if a public key is
+/// configured) of the release.
+fn verify_binary(
+    binary: &Path,
+    release: &Release,
+    config: &UpdateConfiguration,
+) -> Result<(), String> {
+    let expected_checksum = String::from_utf8_lossy(&run_curl(&[&release.checksum_url])?)
+        .split_whitespace()
+        .next()
+        .map(|checksum| checksum.to_lowercase())
+        .ok_or("the checksum of the release is empty")?;
+    if get_checksum(binary)? != expected_checksum {
+        return Err("the checksum of the downloaded binary does not match".to_string());
+    }
+
+    let (public_key, signature_url) = match (&config.public_key, &release.signature_url) {
+        (Some(public_key), Some(signature_url)) => (public_key, signature_url),
+        _ => {
+            warn!("No public key is configured, the update is only verified by its checksum");
+            return Ok(());
+        }
+    };
+    let signature = binary.with_extension("minisig");
+    run_curl(&["--output", &signature.to_string_lossy(), signature_url])?;
+    let status = Command::new("minisign")
+        .arg("-V")
+        .arg("-q")
+        .arg("-P")
+        .arg(public_key)
+        .arg("-m")
+        .arg(binary)
+        .arg("-x")
+        .arg(&signature)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let _ = remove_file(&signature);
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => Err("the signature of the downloaded binary is invalid".to_string()),
+        Err(error) => Err(format!("could not run minisign: {}", error)),
+    }
+}
+
+/// Get the path the new binary is downloaded to. It is stored next to the executable, so it can
+/// be swapped atomically.
+fn get_staging_path(executable: &Path) -> PathBuf {
+    let mut staging_path = executable.as_os_str().to_owned();
+    staging_path.push(".new");
+    PathBuf::from(staging_path)
+}
+
+/// Download and verify the binary of a release next to the executable. The path of the staged
+/// binary is returned.
+pub fn stage_update(
+    release: &Release,
+    executable: &Path,
+    config: &UpdateConfiguration,
+) -> Result<PathBuf, String> {
+    let staging_path = get_staging_path(executable);
+    run_curl(&[
+        "--output",
+        &staging_path.to_string_lossy(),
+        &release.binary_url,
+    ])?;
+    if let Err(error) = verify_binary(&staging_path, release, config) {
+        let _ = remove_file(&staging_path);
+        return Err(error);
+    }
+    #[cfg(unix)]
+    {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        set_permissions(&staging_path, Permissions::from_mode(0o755))
+            .map_err(|error| format!("could not make the binary executable: {}", error))?;
+    }
+    Ok(staging_path)
+}
+
+/// Replace the executable with the staged binary. The running process keeps using the old one.
+pub fn install_update(staged_binary: &Path, executable: &Path) -> Result<(), String> {
+    rename(staged_binary, executable).map_err(|error| {
+        format!(
+            "could not replace {} with the new binary: {}",
+            executable.display(),
+            error
+        )
+    })
+}
+
+/// Get the path of the running executable, resolved before it is replaced by an update.
+pub fn get_executable() -> Result<PathBuf, String> {
+    current_exe()
+        .and_then(|executable| executable.canonicalize())
+        .map_err(|error| format!("could not determine the executable: {}", error))
+}
+
+/// Replace the running process with the (updated) executable and the same arguments. This only
+/// returns if the executable could not be started and is only supported on Unix.
+pub fn restart(executable: &Path) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let error = Command::new(executable).args(args_os().skip(1)).exec();
+        format!("could not restart {}: {}", executable.display(), error)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = args_os;
+        format!(
+            "{} can not be restarted on this platform",
+            executable.display()
+        )
+    }
+}
+
+/// Checks for updates in the background in the configured interval and stages them, so they can
+/// be installed between two segments.
+pub struct UpdateChecker {
+    config: UpdateConfiguration,
+    executable: PathBuf,
+    last_check: Option<Instant>,
+    is_checking: Arc<AtomicBool>,
+    staged_update: Arc<Mutex<Option<(String, PathBuf)>>>,
+}
+
+impl UpdateChecker {
+    pub fn new(config: UpdateConfiguration) -> Result<UpdateChecker, String> {
+        let executable = get_executable()?;
+
+        // a binary which was staged by an earlier process was not verified by this one
+        let _ = remove_file(get_staging_path(&executable));
+        Ok(UpdateChecker {
+            config,
+            executable,
+            last_check: None,
+            is_checking: Arc::new(AtomicBool::new(false)),
+            staged_update: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Start a check in the background if the last one is older than the interval.
+    pub fn poll(&mut self) {
+        let interval = Duration::from_secs(self.config.check_interval_in_hours.max(1) * 3600);
+        if self
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < interval)
+            || self.is_checking.swap(true, Ordering::SeqCst)
+        {
+            return;
+        }
+        self.last_check = Some(Instant::now());
+
+        let config = self.config.clone();
+        let executable = self.executable.clone();
+        let is_checking = self.is_checking.clone();
+        let staged_update = self.staged_update.clone();
+        spawn(move || {
+            match check_for_update(&config) {
+                Ok(Some(release)) => {
+                    info!("Downloading the version {}", release.version);
+                    match stage_update(&release, &executable, &config) {
+                        Ok(staged_binary) => {
+                            *staged_update.lock().unwrap() = Some((release.version, staged_binary))
+                        }
+                        Err(error) => error!(
+                            "Could not download the version {}. The error was: {}",
+                            release.version, error
+                        ),
+                    }
+                }
+                Ok(None) => info!("The version {} is up to date", CURRENT_VERSION),
+                Err(error) => warn!("Could not check for updates. The error was: {}", error),
+            }
+            is_checking.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Check if a verified update is ready to be installed.
+    pub fn has_staged_update(&self) -> bool {
+        self.staged_update.lock().unwrap().is_some()
+    }
+
+    /// Install the staged update and restart the process with it. This only returns if the update
+    /// failed, the recording continues with the running version then.
+    pub fn install_and_restart(&self) {
+        let (version, staged_binary) = match self.staged_update.lock().unwrap().take() {
+            Some(staged_update) => staged_update,
+            None => return,
+        };
+        if let Err(error) = install_update(&staged_binary, &self.executable) {
+            error!("Could not install the version {}: {}", version, error);
+            let _ = remove_file(&staged_binary);
+            return;
+        }
+        info!("Installed the version {}, restarting", version);
+        error!("{}", restart(&self.executable));
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+use std::fs::{read_to_string, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::archive::collect_files;
+use crate::encoding::OutputFormat;
+
+/// The file in the state folder which lists all encoded files which were uploaded already.
+pub const UPLOAD_LEDGER_FILE_NAME: &str = "uploaded.txt";
+
+/// Encoded files which were modified more recently than this might still be written.
+const MINIMUM_AGE: Duration = Duration::from_secs(60);
+
+/// The settings for uploading the encoded files to another machine with rsync.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UploadConfiguration {
+    /// The rsync destination the encoded files are uploaded to (e.g. `backup:recordings/`). The
+    /// subfolders of the encoded files are kept.
+    pub destination: String,
+
+    /// The number of files which are uploaded at the same time.
+    #[serde(default = "UploadConfiguration::default_concurrency")]
+    pub concurrency: usize,
+
+    /// The bandwidth (in kbit/s) all uploads together may use, the bandwidth is not limited if
+    /// none is set.
+    #[serde(default = "UploadConfiguration::default_bandwidth_limit_in_kbits")]
+    pub bandwidth_limit_in_kbits: Option<u32>,
+
+    /// The interval in which the archive is checked for new encoded files while watching it.
+    #[serde(default = "UploadConfiguration::default_poll_interval_in_seconds")]
+    pub poll_interval_in_seconds: u64,
+}
+
+impl UploadConfiguration {
+    fn default_concurrency() -> usize {
+        2
+    }
+
+    fn default_bandwidth_limit_in_kbits() -> Option<u32> {
+        None
+    }
+
+    fn default_poll_interval_in_seconds() -> u64 {
+        30
+    }
+}
+
+/// The list of encoded files (relative to the encoded folder) which were uploaded already. It is
+/// stored in the state folder, so the files are not uploaded again after a restart.
+pub struct UploadLedger {
+    path: PathBuf,
+    uploaded_files: Mutex<HashSet<String>>,
+}
+
+impl UploadLedger {
+    pub fn open(state_folder: &Path) -> io::Result<UploadLedger> {
+        let path = state_folder.join(UPLOAD_LEDGER_FILE_NAME);
+        let uploaded_files = match read_to_string(&path) {
+            Ok(content) => content.lines().map(|line| line.to_string()).collect(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(error) => return Err(error),
+        };
+        Ok(UploadLedger {
+            path,
+            uploaded_files: Mutex::new(uploaded_files),
+        })
+    }
+
+    pub fn contains(&self, relative_path: &str) -> bool {
+        self.uploaded_files.lock().unwrap().contains(relative_path)
+    }
+
+    /// Add an uploaded file to the ledger.
+    pub fn add(&self, relative_path: &str) -> io::Result<()> {
+        let mut uploaded_files = self.uploaded_files.lock().unwrap();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", relative_path)?;
+        uploaded_files.insert(relative_path.to_string());
+        Ok(())
+    }
+}
+
+/// Get the path of a file relative to a folder as it is stored in the ledger.
+pub fn get_relative_path(file: &Path, folder: &Path) -> Option<String> {
+    file.strip_prefix(folder)
+        .ok()
+        .and_then(|relative_path| relative_path.to_str())
+        .map(|relative_path| relative_path.to_string())
+}
+
+/// Get all finished encoded files in the encoded folder (and its subfolders) which were not
+/// uploaded yet, ordered by their path.
+pub fn get_pending_uploads(
+    encoded_folder: &Path,
+    ledger: &UploadLedger,
+) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    collect_files(encoded_folder, &mut files)?;
+
+    let extensions: Vec<&str> = [OutputFormat::Mp3, OutputFormat::Flac, OutputFormat::Ogg]
+        .iter()
+        .map(|format| format.get_extension())
+        .collect();
+    let mut pending_uploads: Vec<PathBuf> = files
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| extensions.contains(&extension))
+        })
+        .filter(|path| {
+            get_relative_path(path, encoded_folder)
+                .is_some_and(|relative_path| !ledger.contains(&relative_path))
+        })
+        .filter(|path| {
+            path.metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age >= MINIMUM_AGE)
+        })
+        .collect();
+    pending_uploads.sort();
+    Ok(pending_uploads)
+}
+
+/// Upload an encoded file with rsync. The path relative to the encoded folder is kept at the
+/// destination and the bandwidth (in kbit/s) of the upload is limited (if set).
+pub fn upload_file(
+    file: &Path,
+    encoded_folder: &Path,
+    destination: &str,
+    bandwidth_limit_in_kbits: Option<u32>,
+) -> Result<(), String> {
+    let relative_path = get_relative_path(file, encoded_folder)
+        .ok_or_else(|| format!("{} is not in the encoded folder", file.display()))?;
+
+    // the `/./` marks the part of the path which is created at the destination
+    let mut upload_command = Command::new("rsync");
+    upload_command
+        .arg("--archive")
+        .arg("--partial")
+        .arg("--relative");
+    if let Some(bandwidth_limit) = bandwidth_limit_in_kbits {
+        // rsync expects the limit in KiB/s
+        let bandwidth_limit_in_kib = (u64::from(bandwidth_limit) * 1000 / 8 / 1024).max(1);
+        upload_command.arg(format!("--bwlimit={}", bandwidth_limit_in_kib));
+    }
+    let status = upload_command
+        .arg(encoded_folder.join(".").join(&relative_path))
+        .arg(destination)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| format!("could not run rsync: {}", error))?;
+    if !status.success() {
+        return Err(format!("rsync failed with {}", status));
+    }
+    Ok(())
+}
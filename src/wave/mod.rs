@@ -0,0 +1,724 @@
+use std::fs::{read, rename, write, File, OpenOptions};
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Take, Write};
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+use crate::annotation::{ReadError, ReadErrorKind};
+
+/// The format tag of integer PCM samples in the format chunk.
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+
+/// The format tag of floating point samples in the format chunk.
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+
+/// The format tag which indicates that the actual format is stored in the extension.
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// The size of the fixed part of the broadcast extension chunk (without the coding history).
+const BROADCAST_EXTENSION_SIZE: usize = 602;
+
+/// The chunk which reserves the space of the broadcast extension in front of the samples, so the
+/// extension can be written once the recording is finished without moving the samples.
+const RESERVED_CHUNK_ID: &[u8; 4] = b"JUNK";
+
+/// The size of the header written by the `WaveWriter` (the RIFF header, the format chunk, the
+/// reserved chunk and the header of the data chunk).
+const WAVE_WRITER_HEADER_SIZE: u32 = 12 + 8 + 16 + 8 + BROADCAST_EXTENSION_SIZE as u32 + 8;
+
+/// The format of a single sample of a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleFormat {
+    /// Signed 16 bit integer samples.
+    #[default]
+    S16,
+
+    /// Signed 24 bit integer samples (packed into three bytes).
+    S24,
+
+    /// Signed 32 bit integer samples.
+    S32,
+
+    /// 32 bit floating point samples.
+    F32,
+}
+
+impl SampleFormat {
+    pub fn get_bits_per_sample(self) -> u16 {
+        match self {
+            SampleFormat::S16 => 16,
+            SampleFormat::S24 => 24,
+            SampleFormat::S32 | SampleFormat::F32 => 32,
+        }
+    }
+
+    /// Get the name of the format as used by the `-f` option of `arecord`.
+    pub fn get_arecord_format(self) -> &'static str {
+        match self {
+            SampleFormat::S16 => "S16_LE",
+            SampleFormat::S24 => "S24_3LE",
+            SampleFormat::S32 => "S32_LE",
+            SampleFormat::F32 => "FLOAT_LE",
+        }
+    }
+
+    /// Get the name of the format as used by the `--format` option of `parecord`.
+    pub fn get_pulse_format(self) -> &'static str {
+        match self {
+            SampleFormat::S16 => "s16le",
+            SampleFormat::S24 => "s24le",
+            SampleFormat::S32 => "s32le",
+            SampleFormat::F32 => "float32le",
+        }
+    }
+
+    /// Get the name of the format as used by the `--format` option of `pw-record`.
+    pub fn get_pipewire_format(self) -> &'static str {
+        match self {
+            SampleFormat::S16 => "s16",
+            SampleFormat::S24 => "s24",
+            SampleFormat::S32 => "s32",
+            SampleFormat::F32 => "f32",
+        }
+    }
+
+    fn from_format_chunk(format_tag: u16, bits_per_sample: u16) -> Option<SampleFormat> {
+        match (format_tag, bits_per_sample) {
+            (WAVE_FORMAT_PCM, 16) => Some(SampleFormat::S16),
+            (WAVE_FORMAT_PCM, 24) => Some(SampleFormat::S24),
+            (WAVE_FORMAT_PCM, 32) => Some(SampleFormat::S32),
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => Some(SampleFormat::F32),
+            _ => None,
+        }
+    }
+
+    /// Decode a single little-endian sample to a value between -1.0 and 1.0.
+    pub(crate) fn decode_sample(self, bytes: &[u8]) -> f32 {
+        match self {
+            SampleFormat::S16 => f32::from(i16::from_le_bytes([bytes[0], bytes[1]])) / 32768.0,
+            SampleFormat::S24 => {
+                // shift the three bytes into the upper part to get the sign extension for free
+                (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8_388_608.0
+            }
+            SampleFormat::S32 => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32
+                    / 2_147_483_648.0
+            }
+            SampleFormat::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+impl fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SampleFormat::S16 => write!(f, "s16"),
+            SampleFormat::S24 => write!(f, "s24"),
+            SampleFormat::S32 => write!(f, "s32"),
+            SampleFormat::F32 => write!(f, "f32"),
+        }
+    }
+}
+
+/// The format information of a wave file.
+#[derive(Debug, Clone, Copy)]
+pub struct WaveFormat {
+    pub channels: u16,
+    pub samples_per_second: u32,
+    pub bits_per_sample: u16,
+    pub sample_format: Option<SampleFormat>,
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buffer[offset], buffer[offset + 1]])
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buffer[offset],
+        buffer[offset + 1],
+        buffer[offset + 2],
+        buffer[offset + 3],
+    ])
+}
+
+/// Read all samples of a wave file with one of the supported sample formats. The samples of all
+/// channels are interleaved and normalized to the range of -1.0 to 1.0.
+pub fn read_samples(path: &Path) -> Result<(WaveFormat, Vec<f32>), ReadError> {
+    let content = read(path).map_err(ReadError::Io)?;
+
+    // ensure the file starts with a valid RIFF/WAVE header
+    if content.len() < 12 || &content[0..4] != b"RIFF" {
+        return Err(ReadError::Format(ReadErrorKind::NotARiffFile));
+    }
+    if &content[8..12] != b"WAVE" {
+        return Err(ReadError::Format(ReadErrorKind::NotAWaveFile));
+    }
+
+    // walk through all chunks until we found the format and the data chunk
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= content.len() {
+        let chunk_id = &content[offset..offset + 4];
+        let chunk_size = read_u32(&content, offset + 4) as usize;
+        let chunk_start = offset + 8;
+        let chunk_end = content.len().min(chunk_start + chunk_size);
+
+        if chunk_id == b"fmt " {
+            // the header of an interrupted recording might be cut off within the format chunk
+            let available_size = chunk_end - chunk_start;
+            if available_size < 16 {
+                return Err(ReadError::Format(ReadErrorKind::NoFormatChunk));
+            }
+
+            // the extensible format stores the actual format tag in the first bytes of the GUID
+            let mut format_tag = read_u16(&content, chunk_start);
+            if format_tag == WAVE_FORMAT_EXTENSIBLE && available_size >= 26 {
+                format_tag = read_u16(&content, chunk_start + 24);
+            }
+            let bits_per_sample = read_u16(&content, chunk_start + 14);
+            format = Some(WaveFormat {
+                channels: read_u16(&content, chunk_start + 2),
+                samples_per_second: read_u32(&content, chunk_start + 4),
+                bits_per_sample,
+                sample_format: SampleFormat::from_format_chunk(format_tag, bits_per_sample),
+            });
+        } else if chunk_id == b"data" {
+            let format = format.ok_or(ReadError::Format(ReadErrorKind::NoFormatChunk))?;
+            let sample_format = match format.sample_format {
+                Some(sample_format) if format.channels > 0 => sample_format,
+                _ => return Err(ReadError::Format(ReadErrorKind::UnsupportedSampleFormat)),
+            };
+
+            let samples = content[chunk_start..chunk_end]
+                .chunks_exact(usize::from(format.bits_per_sample / 8))
+                .map(|sample| sample_format.decode_sample(sample))
+                .collect();
+            return Ok((format, samples));
+        }
+
+        // chunks are always aligned to an even number of bytes
+        offset = chunk_start + chunk_size + chunk_size % 2;
+    }
+
+    Err(ReadError::Format(ReadErrorKind::NoDataChunk))
+}
+
+/// Read all samples of a wave file. The channels are mixed down to a single one and
+/// the samples are normalized to the range of -1.0 to 1.0.
+pub fn read_mono_samples(path: &Path) -> Result<(WaveFormat, Vec<f32>), ReadError> {
+    let (format, samples) = read_samples(path)?;
+    let channels = usize::from(format.channels);
+    let mono_samples = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((format, mono_samples))
+}
+
+/// A simple writer for 16 bit PCM wave files.
+pub struct WaveWriter {
+    file: BufWriter<File>,
+    data_block_size_in_byte: u32,
+}
+
+impl WaveWriter {
+    pub fn create(path: &Path, channels: u16, samples_per_second: u32) -> io::Result<WaveWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let bits_per_sample: u16 = 16;
+        let block_align = channels * bits_per_sample / 8;
+
+        // the sizes of the RIFF and data chunk are not known yet and will be set on finalization
+        file.write_all(b"RIFF")?;
+        file.write_all(&(WAVE_WRITER_HEADER_SIZE - 8).to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+
+        // write the format chunk describing the PCM data
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&samples_per_second.to_le_bytes())?;
+        file.write_all(&(samples_per_second * u32::from(block_align)).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&bits_per_sample.to_le_bytes())?;
+
+        // reserve the space for the broadcast extension, which has to be in front of the samples
+        file.write_all(RESERVED_CHUNK_ID)?;
+        file.write_all(&(BROADCAST_EXTENSION_SIZE as u32).to_le_bytes())?;
+        file.write_all(&[0u8; BROADCAST_EXTENSION_SIZE])?;
+
+        // the header of the data chunk, the samples will follow
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(WaveWriter {
+            file,
+            data_block_size_in_byte: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_block_size_in_byte += (samples.len() * 2) as u32;
+        Ok(())
+    }
+
+    /// Write the final chunk sizes into the header and close the file.
+    pub fn finalize(mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(
+            &(WAVE_WRITER_HEADER_SIZE - 8 + self.data_block_size_in_byte).to_le_bytes(),
+        )?;
+        self.file
+            .seek(SeekFrom::Start(u64::from(WAVE_WRITER_HEADER_SIZE - 4)))?;
+        self.file
+            .write_all(&self.data_block_size_in_byte.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// The metadata of the broadcast extension (`bext`) chunk of a Broadcast Wave file, which allows
+/// audio tools to place the recordings on a common timeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BroadcastExtension {
+    pub description: String,
+
+    /// The name of the originator (e.g. the host which recorded the file).
+    pub originator: String,
+
+    /// The reference of the originator (e.g. the name of the input device).
+    pub originator_reference: String,
+
+    /// The (local) time the recording was started.
+    pub origination_time: NaiveDateTime,
+
+    /// The first sample of the recording, counted in samples since midnight.
+    pub time_reference: u64,
+}
+
+/// Write a string into a fixed-size field, the remaining bytes are filled with zeros.
+fn write_fixed_string(buffer: &mut [u8], value: &str) {
+    for (target, character) in buffer.iter_mut().zip(value.chars()) {
+        *target = if character.is_ascii() {
+            character as u8
+        } else {
+            b'?'
+        };
+    }
+}
+
+/// Read a string from a fixed-size field which might be padded with zeros.
+fn read_fixed_string(buffer: &[u8]) -> String {
+    let end = buffer
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).trim().to_string()
+}
+
+impl BroadcastExtension {
+    /// Create the metadata for a recording with the supplied sample rate which was started at
+    /// the supplied time.
+    pub fn new(
+        description: &str,
+        originator: &str,
+        originator_reference: &str,
+        origination_time: NaiveDateTime,
+        samples_per_second: u32,
+    ) -> BroadcastExtension {
+        let seconds_since_midnight = u64::from(origination_time.num_seconds_from_midnight());
+        BroadcastExtension {
+            description: description.to_string(),
+            originator: originator.to_string(),
+            originator_reference: originator_reference.to_string(),
+            origination_time,
+            time_reference: seconds_since_midnight * u64::from(samples_per_second),
+        }
+    }
+
+    /// Get the content of the chunk (version 1 without a coding history).
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut content = vec![0u8; BROADCAST_EXTENSION_SIZE];
+        write_fixed_string(&mut content[0..256], &self.description);
+        write_fixed_string(&mut content[256..288], &self.originator);
+        write_fixed_string(&mut content[288..320], &self.originator_reference);
+        write_fixed_string(
+            &mut content[320..330],
+            &self.origination_time.format("%Y-%m-%d").to_string(),
+        );
+        write_fixed_string(
+            &mut content[330..338],
+            &self.origination_time.format("%H:%M:%S").to_string(),
+        );
+        content[338..346].copy_from_slice(&self.time_reference.to_le_bytes());
+        content[346..348].copy_from_slice(&1u16.to_le_bytes());
+        content
+    }
+
+    fn from_bytes(content: &[u8]) -> Option<BroadcastExtension> {
+        if content.len() < BROADCAST_EXTENSION_SIZE {
+            return None;
+        }
+
+        // some tools use other separators than the specified ones for the date and the time
+        let date = read_fixed_string(&content[320..330]).replace(&['_', ':', ' ', '/'][..], "-");
+        let time = read_fixed_string(&content[330..338]).replace(&['_', '-', ' ', '.'][..], ":");
+        let origination_time = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .ok()?
+            .and_time(NaiveTime::parse_from_str(&time, "%H:%M:%S").ok()?);
+
+        let mut time_reference = [0u8; 8];
+        time_reference.copy_from_slice(&content[338..346]);
+        Some(BroadcastExtension {
+            description: read_fixed_string(&content[0..256]),
+            originator: read_fixed_string(&content[256..288]),
+            originator_reference: read_fixed_string(&content[288..320]),
+            origination_time,
+            time_reference: u64::from_le_bytes(time_reference),
+        })
+    }
+}
+
+/// Find a chunk of a wave file and move the position of the file to its content. The size of the
+/// content (limited to the end of the file) is returned. Only the chunk headers are read while
+/// searching, so this is cheap even for long recordings.
+fn find_chunk(file: &mut File, chunk_id: &[u8; 4]) -> Result<Option<u64>, ReadError> {
+    let file_size = file.metadata().map_err(ReadError::Io)?.len();
+
+    // ensure the file starts with a valid RIFF/WAVE header
+    let mut header = [0u8; 12];
+    file.seek(SeekFrom::Start(0)).map_err(ReadError::Io)?;
+    if file.read_exact(&mut header).is_err() || &header[0..4] != b"RIFF" {
+        return Err(ReadError::Format(ReadErrorKind::NotARiffFile));
+    }
+    if &header[8..12] != b"WAVE" {
+        return Err(ReadError::Format(ReadErrorKind::NotAWaveFile));
+    }
+
+    let mut offset = 12;
+    while offset + 8 <= file_size {
+        let mut chunk_header = [0u8; 8];
+        file.seek(SeekFrom::Start(offset)).map_err(ReadError::Io)?;
+        file.read_exact(&mut chunk_header).map_err(ReadError::Io)?;
+        let chunk_size = u64::from(read_u32(&chunk_header, 4));
+
+        if &chunk_header[0..4] == chunk_id {
+            return Ok(Some(chunk_size.min(file_size - offset - 8)));
+        }
+
+        // chunks are always aligned to an even number of bytes
+        offset += 8 + chunk_size + chunk_size % 2;
+    }
+    Ok(None)
+}
+
+/// Find a chunk of a wave file and read its content.
+fn read_chunk(file: &mut File, chunk_id: &[u8; 4]) -> Result<Option<Vec<u8>>, ReadError> {
+    match find_chunk(file, chunk_id)? {
+        Some(size) => {
+            let mut content = vec![0u8; size as usize];
+            file.read_exact(&mut content).map_err(ReadError::Io)?;
+            Ok(Some(content))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reads the samples of a wave file one after another without loading the whole file. The
+/// samples of all channels are interleaved and normalized to the range of -1.0 to 1.0.
+pub struct SampleReader {
+    reader: BufReader<Take<File>>,
+    sample_format: SampleFormat,
+    bytes_per_sample: usize,
+}
+
+impl Iterator for SampleReader {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let mut bytes = [0u8; 4];
+        self.reader
+            .read_exact(&mut bytes[..self.bytes_per_sample])
+            .ok()?;
+        Some(self.sample_format.decode_sample(&bytes))
+    }
+}
+
+/// Open a wave file with one of the supported sample formats for reading its samples.
+pub fn open_samples(path: &Path) -> Result<(WaveFormat, SampleReader), ReadError> {
+    let format = read_format(path)?;
+    let sample_format = match format.sample_format {
+        Some(sample_format) if format.channels > 0 => sample_format,
+        _ => return Err(ReadError::Format(ReadErrorKind::UnsupportedSampleFormat)),
+    };
+    let mut file = File::open(path).map_err(ReadError::Io)?;
+    let data_size =
+        find_chunk(&mut file, b"data")?.ok_or(ReadError::Format(ReadErrorKind::NoDataChunk))?;
+    Ok((
+        format,
+        SampleReader {
+            reader: BufReader::new(file.take(data_size)),
+            sample_format,
+            bytes_per_sample: usize::from(format.bits_per_sample / 8),
+        },
+    ))
+}
+
+/// Read the format of a wave file without reading its samples.
+pub fn read_format(path: &Path) -> Result<WaveFormat, ReadError> {
+    let mut file = File::open(path).map_err(ReadError::Io)?;
+    let content = match read_chunk(&mut file, b"fmt ")? {
+        Some(content) if content.len() >= 16 => content,
+        _ => return Err(ReadError::Format(ReadErrorKind::NoFormatChunk)),
+    };
+    let mut format_tag = read_u16(&content, 0);
+    if format_tag == WAVE_FORMAT_EXTENSIBLE && content.len() >= 26 {
+        format_tag = read_u16(&content, 24);
+    }
+    let bits_per_sample = read_u16(&content, 14);
+    Ok(WaveFormat {
+        channels: read_u16(&content, 2),
+        samples_per_second: read_u32(&content, 4),
+        bits_per_sample,
+        sample_format: SampleFormat::from_format_chunk(format_tag, bits_per_sample),
+    })
+}
+
+/// Get the size (in bytes) of the samples of a wave file without reading them.
+pub fn read_data_size(path: &Path) -> Result<u64, ReadError> {
+    let mut file = File::open(path).map_err(ReadError::Io)?;
+    find_chunk(&mut file, b"data")?.ok_or(ReadError::Format(ReadErrorKind::NoDataChunk))
+}
+
+/// Read the broadcast extension of a wave file, `None` is returned if the file does not have one.
+pub fn read_broadcast_extension(path: &Path) -> Result<Option<BroadcastExtension>, ReadError> {
+    let mut file = File::open(path).map_err(ReadError::Io)?;
+    Ok(
+        read_chunk(&mut file, b"bext")?
+            .and_then(|content| BroadcastExtension::from_bytes(&content)),
+    )
+}
+
+/// Write a broadcast extension chunk in front of the samples of a finished wave file. The space
+/// reserved by the `WaveWriter` (or a previous broadcast extension) is overwritten in place. Files
+/// without it (like the recordings of `arecord`) are rewritten once with the chunk inserted before
+/// the data chunk, as some tools only read the chunks in front of the samples.
+pub fn write_broadcast_extension(
+    path: &Path,
+    extension: &BroadcastExtension,
+) -> Result<(), ReadError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(ReadError::Io)?;
+    find_chunk(&mut file, b"data")?.ok_or(ReadError::Format(ReadErrorKind::NoDataChunk))?;
+    let data_start = file.stream_position().map_err(ReadError::Io)?;
+    let content = extension.to_bytes();
+
+    // look for a chunk in front of the samples which is large enough to be replaced
+    let mut offset = 12;
+    while offset + 8 < data_start {
+        let mut chunk_header = [0u8; 8];
+        file.seek(SeekFrom::Start(offset)).map_err(ReadError::Io)?;
+        file.read_exact(&mut chunk_header).map_err(ReadError::Io)?;
+        let chunk_size = u64::from(read_u32(&chunk_header, 4));
+        let chunk_id = &chunk_header[0..4];
+        if (chunk_id == RESERVED_CHUNK_ID || chunk_id == b"bext")
+            && chunk_size >= content.len() as u64
+        {
+            // the size of the chunk is kept, the remaining space is filled with zeros
+            let mut chunk_content = vec![0u8; chunk_size as usize];
+            chunk_content[..content.len()].copy_from_slice(&content);
+            file.seek(SeekFrom::Start(offset)).map_err(ReadError::Io)?;
+            file.write_all(b"bext").map_err(ReadError::Io)?;
+            file.seek(SeekFrom::Current(4)).map_err(ReadError::Io)?;
+            file.write_all(&chunk_content).map_err(ReadError::Io)?;
+            return file.flush().map_err(ReadError::Io);
+        }
+        offset += 8 + chunk_size + chunk_size % 2;
+    }
+
+    // the file is copied with the chunk inserted before the data chunk and then replaces it, so
+    // an interrupted rewrite does not damage the recording
+    let extended_path = path.with_extension("wav.extending");
+    let mut extended_file = BufWriter::new(File::create(&extended_path).map_err(ReadError::Io)?);
+    file.seek(SeekFrom::Start(0)).map_err(ReadError::Io)?;
+    let mut reader = BufReader::new(file);
+    io::copy(&mut (&mut reader).take(data_start - 8), &mut extended_file).map_err(ReadError::Io)?;
+    extended_file.write_all(b"bext").map_err(ReadError::Io)?;
+    extended_file
+        .write_all(&(content.len() as u32).to_le_bytes())
+        .map_err(ReadError::Io)?;
+    extended_file.write_all(&content).map_err(ReadError::Io)?;
+    io::copy(&mut reader, &mut extended_file).map_err(ReadError::Io)?;
+
+    // the size of the RIFF chunk covers everything after its header
+    let extended_size = extended_file.stream_position().map_err(ReadError::Io)?;
+    extended_file
+        .seek(SeekFrom::Start(4))
+        .map_err(ReadError::Io)?;
+    extended_file
+        .write_all(&((extended_size - 8) as u32).to_le_bytes())
+        .map_err(ReadError::Io)?;
+    extended_file.flush().map_err(ReadError::Io)?;
+    drop(extended_file);
+    rename(&extended_path, path).map_err(ReadError::Io)
+}
+
+/// Repair the chunk sizes in the header of a recording which was interrupted before its header
+/// was finalized (e.g. by a crash or a power loss). Everything after the header of the data chunk
+/// is treated as samples, an incomplete frame at the end is cut off. Returns if the header had to
+/// be changed.
+pub fn repair_header(path: &Path) -> Result<bool, ReadError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(ReadError::Io)?;
+    let block_align = match read_chunk(&mut file, b"fmt ")? {
+        Some(content) if content.len() >= 16 => u64::from(read_u16(&content, 12).max(1)),
+        _ => return Err(ReadError::Format(ReadErrorKind::NoFormatChunk)),
+    };
+    let file_size = file.metadata().map_err(ReadError::Io)?.len();
+
+    // find the data chunk, the chunks after it can not be found if its size is not valid
+    let mut offset = 12;
+    let (data_start, data_size) = loop {
+        if offset + 8 > file_size {
+            return Err(ReadError::Format(ReadErrorKind::NoDataChunk));
+        }
+        let mut chunk_header = [0u8; 8];
+        file.seek(SeekFrom::Start(offset)).map_err(ReadError::Io)?;
+        file.read_exact(&mut chunk_header).map_err(ReadError::Io)?;
+        let chunk_size = u64::from(read_u32(&chunk_header, 4));
+        if &chunk_header[0..4] == b"data" {
+            break (offset + 8, chunk_size);
+        }
+        offset += 8 + chunk_size + chunk_size % 2;
+    };
+
+    // a data chunk which ends within the file is complete (interrupted recordings either have
+    // no size or a placeholder exceeding the file)
+    let available_size = file_size - data_start;
+    if available_size == 0 || (data_size > 0 && data_size <= available_size) {
+        return Ok(false);
+    }
+    let repaired_size = available_size - available_size % block_align;
+    let repaired_file_size = data_start + repaired_size;
+    file.set_len(repaired_file_size).map_err(ReadError::Io)?;
+    file.seek(SeekFrom::Start(4)).map_err(ReadError::Io)?;
+    file.write_all(&((repaired_file_size - 8) as u32).to_le_bytes())
+        .map_err(ReadError::Io)?;
+    file.seek(SeekFrom::Start(data_start - 4))
+        .map_err(ReadError::Io)?;
+    file.write_all(&(repaired_size as u32).to_le_bytes())
+        .map_err(ReadError::Io)?;
+    file.flush().map_err(ReadError::Io)?;
+    Ok(true)
+}
+
+/// Append the samples of a wave file to a recording with the same format whose data chunk is its
+/// last chunk (e.g. a recording of `arecord` whose capture was restarted).
+pub fn append_recording(path: &Path, other_path: &Path) -> Result<(), ReadError> {
+    let mut other_file = File::open(other_path).map_err(ReadError::Io)?;
+    let other_data_size = find_chunk(&mut other_file, b"data")?
+        .ok_or(ReadError::Format(ReadErrorKind::NoDataChunk))?;
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(ReadError::Io)?;
+    let data_size =
+        find_chunk(&mut file, b"data")?.ok_or(ReadError::Format(ReadErrorKind::NoDataChunk))?;
+    let data_start = file.stream_position().map_err(ReadError::Io)?;
+    let data_end = data_start + data_size;
+    file.set_len(data_end).map_err(ReadError::Io)?;
+    file.seek(SeekFrom::End(0)).map_err(ReadError::Io)?;
+    io::copy(&mut other_file.take(other_data_size), &mut file).map_err(ReadError::Io)?;
+
+    // the sizes of the data and the RIFF chunk cover the appended samples
+    let appended_data_size = file.stream_position().map_err(ReadError::Io)? - data_start;
+    file.seek(SeekFrom::Start(4)).map_err(ReadError::Io)?;
+    file.write_all(&((data_start + appended_data_size - 8) as u32).to_le_bytes())
+        .map_err(ReadError::Io)?;
+    file.seek(SeekFrom::Start(data_start - 4))
+        .map_err(ReadError::Io)?;
+    file.write_all(&(appended_data_size as u32).to_le_bytes())
+        .map_err(ReadError::Io)?;
+    file.flush().map_err(ReadError::Io)
+}
+
+/// Cut off frames at the start and the end of a wave file. All other chunks are kept, the time
+/// reference of a broadcast extension is moved to the new first sample. The file is rewritten
+/// next to the recording and then replaces it, so an interrupted trim does not damage it.
+pub fn trim_recording(
+    path: &Path,
+    leading_frames: u64,
+    trailing_frames: u64,
+) -> Result<(), ReadError> {
+    let content = read(path).map_err(ReadError::Io)?;
+    if content.len() < 12 || &content[0..4] != b"RIFF" {
+        return Err(ReadError::Format(ReadErrorKind::NotARiffFile));
+    }
+    if &content[8..12] != b"WAVE" {
+        return Err(ReadError::Format(ReadErrorKind::NotAWaveFile));
+    }
+
+    let mut block_align = None;
+    let mut has_data = false;
+    let mut trimmed = content[0..12].to_vec();
+    let mut offset = 12;
+    while offset + 8 <= content.len() {
+        let chunk_id = &content[offset..offset + 4];
+        let chunk_size = read_u32(&content, offset + 4) as usize;
+        let chunk_start = offset + 8;
+        let chunk_end = content.len().min(chunk_start + chunk_size);
+        let mut chunk = content[chunk_start..chunk_end].to_vec();
+
+        if chunk_id == b"fmt " && chunk.len() >= 16 {
+            block_align = Some(u64::from(read_u16(&chunk, 12).max(1)));
+        } else if chunk_id == b"data" {
+            let block_align = block_align.ok_or(ReadError::Format(ReadErrorKind::NoFormatChunk))?;
+            let frames = chunk.len() as u64 / block_align;
+            let first_frame = leading_frames.min(frames);
+            let last_frame = frames.saturating_sub(trailing_frames).max(first_frame);
+            chunk = chunk
+                [(first_frame * block_align) as usize..(last_frame * block_align) as usize]
+                .to_vec();
+            has_data = true;
+        } else if chunk_id == b"bext" && chunk.len() >= BROADCAST_EXTENSION_SIZE {
+            let mut time_reference = [0u8; 8];
+            time_reference.copy_from_slice(&chunk[338..346]);
+            let time_reference = u64::from_le_bytes(time_reference) + leading_frames;
+            chunk[338..346].copy_from_slice(&time_reference.to_le_bytes());
+        }
+
+        trimmed.extend_from_slice(chunk_id);
+        trimmed.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        trimmed.extend_from_slice(&chunk);
+        // chunks are always aligned to an even number of bytes
+        if chunk.len() % 2 == 1 {
+            trimmed.push(0);
+        }
+        offset = chunk_start + chunk_size + chunk_size % 2;
+    }
+    if !has_data {
+        return Err(ReadError::Format(ReadErrorKind::NoDataChunk));
+    }
+
+    // the size of the RIFF chunk covers everything after its header
+    let riff_size = (trimmed.len() - 8) as u32;
+    trimmed[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    let trimmed_path = path.with_extension("wav.trimming");
+    write(&trimmed_path, &trimmed).map_err(ReadError::Io)?;
+    rename(&trimmed_path, path).map_err(ReadError::Io)
+}